@@ -17,7 +17,7 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
-use clap::{Arg, ArgAction, Command};
+use clap::{Arg, ArgAction, ArgGroup, Command};
 
 pub fn build_cli() -> Command {
     Command::new("dog")
@@ -97,6 +97,13 @@ pub fn build_cli() -> Command {
                 .value_name("ADDR")
                 .action(ArgAction::Append),
         )
+        .arg(
+            Arg::new("resolvers")
+                .long("resolvers")
+                .help("Read more named resolvers out of a file")
+                .value_name("FILE")
+                .action(ArgAction::Set),
+        )
         .arg(
             Arg::new("class")
                 .long("class")
@@ -104,6 +111,21 @@ pub fn build_cli() -> Command {
                 .value_name("CLASS")
                 .action(ArgAction::Append),
         )
+        .arg(
+            Arg::new("no-search")
+                .long("no-search")
+                .help("Do not append the search domains from resolv.conf to names")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("file")
+                .short('f')
+                .long("file")
+                .help("Read queries from a file, one per line, or '-' for standard input")
+                .value_name("PATH")
+                .action(ArgAction::Set)
+                .conflicts_with("trace"),
+        )
         .next_help_heading("Sending options")
         .arg(
             Arg::new("edns")
@@ -119,6 +141,77 @@ pub fn build_cli() -> Command {
                 .value_name("NUMBER")
                 .action(ArgAction::Set),
         )
+        .arg(
+            Arg::new("concurrency")
+                .long("concurrency")
+                .help("Send at most this many queries at once")
+                .value_name("NUMBER")
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("qps")
+                .long("qps")
+                .help("Send at most this many queries each second")
+                .value_name("NUMBER")
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("timeout")
+                .long("timeout")
+                .help("How long to wait for a response (such as 2s or 500ms)")
+                .value_name("DURATION")
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("tries")
+                .long("tries")
+                .help("How many times to send a query that times out or fails")
+                .value_name("NUMBER")
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("retry-tcp")
+                .long("retry-tcp")
+                .help("Re-send queries over TCP if they fail over UDP")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("tsig")
+                .long("tsig")
+                .help("Sign requests with a TSIG key (NAME:ALGORITHM:SECRET or a key file)")
+                .value_name("KEY")
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("trace")
+                .long("trace")
+                .help("Trace the delegation chain down from the root servers")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["tls", "https", "http3", "quic", "odoh"]),
+        )
+        .arg(
+            Arg::new("root-hints")
+                .long("root-hints")
+                .help("Read the root servers to trace from out of a file")
+                .value_name("FILE")
+                .requires("trace")
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("validate")
+                .long("validate")
+                .help("Validate DNSSEC signatures up to a trust anchor")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("trace"),
+        )
+        .arg(
+            Arg::new("trust-anchor")
+                .long("trust-anchor")
+                .help("Read the trust anchor to validate against out of a file")
+                .value_name("FILE")
+                .requires("validate")
+                .action(ArgAction::Set),
+        )
         .arg(
             Arg::new("Z")
                 .short('Z')
@@ -155,6 +248,73 @@ pub fn build_cli() -> Command {
                 .help("Use the DNS-over-HTTPS protocol")
                 .action(ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("quic")
+                .short('Q')
+                .long("quic")
+                .help("Use the DNS-over-QUIC protocol")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("doh-method")
+                .long("doh-method")
+                .help("HTTP method to send DNS-over-HTTPS requests with")
+                .value_name("METHOD")
+                .value_parser(["GET", "POST"])
+                .ignore_case(true)
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("http3")
+                .long("http3")
+                .help("Send DNS-over-HTTPS requests over HTTP/3")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["udp", "tcp", "tls", "quic"]),
+        )
+        .arg(
+            Arg::new("odoh")
+                .long("odoh")
+                .help("Send DNS-over-HTTPS requests through an Oblivious DoH relay")
+                .value_name("RELAY")
+                .action(ArgAction::Set)
+                .conflicts_with_all(["udp", "tcp", "tls", "quic"]),
+        )
+        .arg(
+            Arg::new("mdns")
+                .long("mdns")
+                .help("Send queries to multicast DNS responders on the local link")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("llmnr")
+                .long("llmnr")
+                .help("Send single-label names to LLMNR responders on the local link")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["mdns", "browse"]),
+        )
+        .arg(
+            Arg::new("browse")
+                .long("browse")
+                .help("Browse the local link for instances of a DNS-SD service")
+                .value_name("SERVICE")
+                .action(ArgAction::Append),
+        )
+        .arg(
+            Arg::new("listen")
+                .long("listen")
+                .help("How long to collect multicast responses for (such as 2s or 500ms)")
+                .value_name("DURATION")
+                .requires("multicast")
+                .action(ArgAction::Set),
+        )
+        .group(
+            ArgGroup::new("multicast")
+                .args(["mdns", "llmnr", "browse"])
+                .multiple(true)
+                .conflicts_with_all([
+                    "tcp", "tls", "https", "http3", "quic", "odoh", "trace", "validate",
+                ]),
+        )
         .next_help_heading("Output options")
         .arg(
             Arg::new("color")
@@ -212,6 +372,12 @@ pub fn build_cli() -> Command {
                 .help("List known DNS record types")
                 .action(ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("list-resolvers")
+                .long("list-resolvers")
+                .help("List resolvers that can be picked by name")
+                .action(ArgAction::SetTrue),
+        )
         .arg(
             Arg::new("verbose")
                 .short('v')
@@ -47,6 +47,27 @@ pub struct Colours {
     /// The style for the answer section.
     pub answer: Style,
 
+    /// The style for the authority section.
+    pub authority: Style,
+
+    /// The style for the additional section.
+    pub additional: Style,
+
+    /// The style for records removed in a zone transfer.
+    pub removed: Style,
+
+    /// The style for records added in a zone transfer.
+    pub added: Style,
+
+    /// The style for a response code that indicates success.
+    pub rcode_success: Style,
+
+    /// The style for a response code that indicates an error.
+    pub rcode_error: Style,
+
+    /// The style for the OPT pseudo-record.
+    pub opt: Style,
+
     /// The style for A records.
     pub a: Style,
     /// The style for AAAA records.
@@ -81,6 +102,21 @@ impl Colours {
                 .fg_color(Some(Color::Ansi(AnsiColor::Blue)))
                 .bold(),
             answer: Style::default(),
+            authority: Style::new().fg_color(Some(Color::Ansi(AnsiColor::Cyan))),
+            additional: Style::new().fg_color(Some(Color::Ansi(AnsiColor::Green))),
+            removed: Style::new()
+                .fg_color(Some(Color::Ansi(AnsiColor::Red)))
+                .bold(),
+            added: Style::new()
+                .fg_color(Some(Color::Ansi(AnsiColor::Green)))
+                .bold(),
+            rcode_success: Style::new()
+                .fg_color(Some(Color::Ansi(AnsiColor::Green)))
+                .bold(),
+            rcode_error: Style::new()
+                .fg_color(Some(Color::Ansi(AnsiColor::Red)))
+                .bold(),
+            opt: Style::new().fg_color(Some(Color::Ansi(AnsiColor::BrightBlack))),
             a: Style::new()
                 .fg_color(Some(Color::Ansi(AnsiColor::Green)))
                 .bold(),
@@ -0,0 +1,724 @@
+/*
+ * dog - A command-line DNS client
+ * Copyright (c) 2026 l1a and contributors
+ * Original code Copyright (c) Benjamin Sago
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Encrypting DNS requests with version 2 of the DNSCrypt protocol.
+//!
+//! A DNSCrypt resolver publishes certificates as TXT records under its
+//! provider name, each one holding a short-term public key signed by the
+//! provider’s long-term key, which comes from the stamp. Once a
+//! certificate has been checked, each request gets encrypted with a
+//! fresh key pair of dog’s own and the resolver’s short-term key, then
+//! sent over UDP or TCP like a plain request would be. The protocol is
+//! described at <https://dnscrypt.info/protocol>.
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::future::Future;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crypto_box::aead::Aead;
+use crypto_box::{ChaChaBox, PublicKey, SalsaBox, SecretKey};
+use hickory_resolver::net::NetError;
+use hickory_resolver::proto::op::{DnsResponse, Message, Query};
+use hickory_resolver::proto::rr::{Name, RData, Record, RecordType};
+use log::*;
+use ring::rand::{SecureRandom, SystemRandom};
+use ring::signature::{UnparsedPublicKey, ED25519};
+use tokio::sync::OnceCell;
+
+/// The bytes every certificate starts with.
+const CERT_MAGIC: &[u8; 4] = b"DNSC";
+
+/// The bytes every response starts with.
+const RESOLVER_MAGIC: [u8; 8] = [0x72, 0x36, 0x66, 0x6e, 0x76, 0x57, 0x6a, 0x38];
+
+/// The length of a certificate without any extensions.
+const CERT_LENGTH: usize = 124;
+
+/// The length of the half of the nonce that each side picks.
+const HALF_NONCE_LENGTH: usize = 12;
+
+/// The smallest a query sent over UDP can be once it’s padded. Resolvers
+/// don’t send back responses that are larger than the query, so that
+/// they can’t be used to amplify attacks.
+const MIN_UDP_QUERY_LENGTH: usize = 256;
+
+/// The size of the blocks queries get padded out to.
+const PADDING_BLOCK: usize = 64;
+
+/// A DNSCrypt **provider**, as given in a stamp.
+#[derive(PartialEq, Eq, Hash, Debug, Clone)]
+pub struct Provider {
+    /// The provider’s long-term public key, which certificates are signed
+    /// with.
+    pub public_key: [u8; 32],
+
+    /// The provider name, which certificates are published under.
+    pub name: String,
+}
+
+/// A DNSCrypt **resolver**, which holds on to its certificate once it’s
+/// been fetched.
+#[derive(Debug)]
+pub struct Resolver {
+    /// The provider to fetch certificates for.
+    provider: Provider,
+
+    /// The certificate to encrypt requests with, once it’s been fetched.
+    certificate: OnceCell<Certificate>,
+}
+
+impl Resolver {
+    /// Creates a new resolver for the given provider.
+    pub fn new(provider: Provider) -> Self {
+        Self {
+            provider,
+            certificate: OnceCell::new(),
+        }
+    }
+
+    /// The provider that certificates get fetched for.
+    pub fn provider(&self) -> &Provider {
+        &self.provider
+    }
+
+    /// The certificate that requests are encrypted with, if it’s been
+    /// fetched already.
+    pub fn fetched_certificate(&self) -> Option<&Certificate> {
+        self.certificate.get()
+    }
+
+    /// Returns the certificate to encrypt requests with, fetching it first
+    /// by sending the given function a request for the provider’s TXT
+    /// records if it hasn’t been already.
+    pub async fn certificate<F, Fut>(&self, fetch: F) -> Result<&Certificate, NetError>
+    where
+        F: FnOnce(Message) -> Fut,
+        Fut: Future<Output = Result<DnsResponse, NetError>>,
+    {
+        self.certificate
+            .get_or_try_init(|| async {
+                let mut request = Message::query();
+                request.metadata.recursion_desired = true;
+                let name = Name::from_utf8(&self.provider.name)?;
+                request.add_query(Query::query(name, RecordType::TXT));
+
+                let response = fetch(request).await?;
+                let certificate = Certificate::choose(&self.provider, &response.answers, now())
+                    .map_err(|e| NetError::from(e.to_string()))?;
+                debug!("Using DNSCrypt certificate {certificate:?}");
+                Ok(certificate)
+            })
+            .await
+    }
+}
+
+/// A **certificate** that a resolver has published, which has been checked
+/// against the provider’s key.
+#[derive(Debug, Clone)]
+pub struct Certificate {
+    /// The construction to encrypt requests with.
+    construction: Construction,
+
+    /// The resolver’s short-term public key.
+    resolver_key: [u8; 32],
+
+    /// The bytes that requests encrypted with this certificate start with.
+    client_magic: [u8; 8],
+
+    /// The certificate’s serial number, which is higher for newer ones.
+    pub serial: u32,
+
+    /// The time, in seconds since the Unix epoch, after which the
+    /// certificate can’t be used.
+    pub valid_until: u32,
+}
+
+/// The **construction**, which is what the DNSCrypt protocol calls the
+/// pair of algorithms used to encrypt messages.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Copy, Clone)]
+pub enum Construction {
+    /// X25519 with XSalsa20-Poly1305.
+    XSalsa20Poly1305,
+
+    /// X25519 with XChaCha20-Poly1305.
+    XChaCha20Poly1305,
+}
+
+impl fmt::Display for Construction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::XSalsa20Poly1305 => write!(f, "XSalsa20-Poly1305"),
+            Self::XChaCha20Poly1305 => write!(f, "XChaCha20-Poly1305"),
+        }
+    }
+}
+
+impl Certificate {
+    /// Picks the certificate to use out of the TXT records in a response:
+    /// the one with the highest serial number, out of the ones that are
+    /// signed by the provider and valid at the given time.
+    fn choose(provider: &Provider, records: &[Record], now: u32) -> Result<Self, DnsCryptError> {
+        let mut best: Option<Self> = None;
+        let mut last_error = DnsCryptError::NoCertificate;
+
+        for record in records {
+            let RData::TXT(txt) = &record.data else {
+                continue;
+            };
+
+            let bytes = txt.txt_data.concat();
+            match Self::parse(&bytes, provider, now) {
+                Ok(certificate) => {
+                    let newer = best.as_ref().is_none_or(|b| {
+                        (certificate.serial, certificate.construction) > (b.serial, b.construction)
+                    });
+                    if newer {
+                        best = Some(certificate);
+                    }
+                }
+                Err(e) => {
+                    debug!("Skipping DNSCrypt certificate: {e}");
+                    last_error = e;
+                }
+            }
+        }
+
+        best.ok_or(last_error)
+    }
+
+    /// Parses a certificate, checking its signature against the provider’s
+    /// key, and that it’s valid at the given time.
+    fn parse(bytes: &[u8], provider: &Provider, now: u32) -> Result<Self, DnsCryptError> {
+        if bytes.len() < CERT_LENGTH || &bytes[..4] != CERT_MAGIC {
+            return Err(DnsCryptError::InvalidCertificate);
+        }
+
+        let construction = match u16::from_be_bytes([bytes[4], bytes[5]]) {
+            0x0001 => Construction::XSalsa20Poly1305,
+            0x0002 => Construction::XChaCha20Poly1305,
+            other => return Err(DnsCryptError::UnsupportedConstruction(other)),
+        };
+
+        let (signature, signed) = bytes[8..].split_at(64);
+        UnparsedPublicKey::new(&ED25519, &provider.public_key)
+            .verify(signed, signature)
+            .map_err(|_| DnsCryptError::BadSignature)?;
+
+        // The signed part holds the resolver’s key, the client magic, then
+        // the serial number and validity period as four-byte numbers
+        let number = |at: usize| {
+            u32::from_be_bytes([signed[at], signed[at + 1], signed[at + 2], signed[at + 3]])
+        };
+        let valid_from = number(44);
+        let valid_until = number(48);
+        if now < valid_from || now > valid_until {
+            return Err(DnsCryptError::Expired);
+        }
+
+        Ok(Self {
+            construction,
+            resolver_key: <[u8; 32]>::try_from(&signed[..32]).expect("Slice is 32 bytes"),
+            client_magic: <[u8; 8]>::try_from(&signed[32..40]).expect("Slice is 8 bytes"),
+            serial: number(40),
+            valid_until,
+        })
+    }
+
+    /// The construction that requests are encrypted with.
+    pub fn construction(&self) -> Construction {
+        self.construction
+    }
+
+    /// Encrypts a DNS message with a fresh key pair, padding it for the
+    /// given transport. Returns the packet to send, and what’s needed to
+    /// decrypt the response.
+    pub fn encrypt(&self, message: &[u8], tcp: bool) -> Result<(Vec<u8>, Sealed), DnsCryptError> {
+        let random = SystemRandom::new();
+        let mut secret = [0; 32];
+        let mut client_nonce = [0; HALF_NONCE_LENGTH];
+        random
+            .fill(&mut secret)
+            .and_then(|()| random.fill(&mut client_nonce))
+            .map_err(|_| DnsCryptError::Random)?;
+
+        Ok(self.encrypt_with(message, tcp, &SecretKey::from(secret), client_nonce))
+    }
+
+    /// Encrypts a DNS message with the given key and nonce.
+    fn encrypt_with(
+        &self,
+        message: &[u8],
+        tcp: bool,
+        secret: &SecretKey,
+        client_nonce: [u8; HALF_NONCE_LENGTH],
+    ) -> (Vec<u8>, Sealed) {
+        let cipher = Cipher::new(
+            self.construction,
+            &PublicKey::from(self.resolver_key),
+            secret,
+        );
+        let min_length = if tcp { 0 } else { MIN_UDP_QUERY_LENGTH };
+        let nonce = nonce_for(&client_nonce, &[0; HALF_NONCE_LENGTH]);
+        let ciphertext = cipher.encrypt(&nonce, &pad(message, min_length));
+
+        let mut packet = Vec::with_capacity(8 + 32 + HALF_NONCE_LENGTH + ciphertext.len());
+        packet.extend_from_slice(&self.client_magic);
+        packet.extend_from_slice(secret.public_key().as_bytes());
+        packet.extend_from_slice(&client_nonce);
+        packet.extend_from_slice(&ciphertext);
+
+        (
+            packet,
+            Sealed {
+                cipher,
+                client_nonce,
+            },
+        )
+    }
+}
+
+/// What’s needed to decrypt the response to a request that’s been
+/// **sealed**.
+pub struct Sealed {
+    /// The cipher the request was encrypted with.
+    cipher: Cipher,
+
+    /// The half of the nonce that dog picked, which the response has to
+    /// repeat.
+    client_nonce: [u8; HALF_NONCE_LENGTH],
+}
+
+impl fmt::Debug for Sealed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sealed")
+            .field("client_nonce", &self.client_nonce)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Sealed {
+    /// Decrypts the response to the request, returning the DNS message
+    /// inside it.
+    pub fn decrypt(&self, packet: &[u8]) -> Result<Vec<u8>, DnsCryptError> {
+        let header_length = RESOLVER_MAGIC.len() + HALF_NONCE_LENGTH * 2;
+        if packet.len() < header_length || packet[..8] != RESOLVER_MAGIC {
+            return Err(DnsCryptError::InvalidResponse);
+        }
+
+        let (client_nonce, resolver_nonce) = packet[8..header_length].split_at(HALF_NONCE_LENGTH);
+        if client_nonce != self.client_nonce {
+            return Err(DnsCryptError::InvalidResponse);
+        }
+
+        let nonce = nonce_for(client_nonce, resolver_nonce);
+        let plaintext = self.cipher.decrypt(&nonce, &packet[header_length..])?;
+        unpad(&plaintext).ok_or(DnsCryptError::InvalidResponse)
+    }
+}
+
+/// The **cipher** for one of the constructions, set up with the key
+/// shared between dog and the resolver.
+enum Cipher {
+    /// X25519 with XSalsa20-Poly1305.
+    Salsa(SalsaBox),
+
+    /// X25519 with XChaCha20-Poly1305.
+    ChaCha(ChaChaBox),
+}
+
+impl Cipher {
+    /// Sets up the cipher for the given construction, with the key shared
+    /// between the given public and secret keys.
+    fn new(construction: Construction, public_key: &PublicKey, secret: &SecretKey) -> Self {
+        match construction {
+            Construction::XSalsa20Poly1305 => Self::Salsa(SalsaBox::new(public_key, secret)),
+            Construction::XChaCha20Poly1305 => Self::ChaCha(ChaChaBox::new(public_key, secret)),
+        }
+    }
+
+    /// Encrypts a message, putting the tag before the ciphertext.
+    fn encrypt(&self, nonce: &[u8; 24], plaintext: &[u8]) -> Vec<u8> {
+        let result = match self {
+            Self::Salsa(cipher) => cipher.encrypt(nonce.into(), plaintext),
+            Self::ChaCha(cipher) => cipher.encrypt(nonce.into(), plaintext),
+        };
+        result.expect("Message too long to encrypt")
+    }
+
+    /// Decrypts a message, checking the tag before the ciphertext.
+    fn decrypt(&self, nonce: &[u8; 24], ciphertext: &[u8]) -> Result<Vec<u8>, DnsCryptError> {
+        let result = match self {
+            Self::Salsa(cipher) => cipher.decrypt(nonce.into(), ciphertext),
+            Self::ChaCha(cipher) => cipher.decrypt(nonce.into(), ciphertext),
+        };
+        result.map_err(|_| DnsCryptError::Decryption)
+    }
+}
+
+/// Puts the two halves of a nonce together.
+fn nonce_for(client_nonce: &[u8], resolver_nonce: &[u8]) -> [u8; 24] {
+    let mut nonce = [0; 24];
+    nonce[..HALF_NONCE_LENGTH].copy_from_slice(client_nonce);
+    nonce[HALF_NONCE_LENGTH..].copy_from_slice(resolver_nonce);
+    nonce
+}
+
+/// Pads a message with a 0x80 byte and then zeroes, out to a multiple of
+/// the block size that’s at least the given length.
+fn pad(message: &[u8], min_length: usize) -> Vec<u8> {
+    let length = (message.len() + 1)
+        .next_multiple_of(PADDING_BLOCK)
+        .max(min_length);
+
+    let mut padded = Vec::with_capacity(length);
+    padded.extend_from_slice(message);
+    padded.push(0x80);
+    padded.resize(length, 0);
+    padded
+}
+
+/// Removes the padding from a message, returning `None` if there isn’t
+/// any.
+fn unpad(padded: &[u8]) -> Option<Vec<u8>> {
+    let end = padded.iter().rposition(|b| *b != 0)?;
+    (padded[end] == 0x80).then(|| padded[..end].to_vec())
+}
+
+/// The current time, in seconds since the Unix epoch, which is what the
+/// validity period of a certificate is given in.
+pub fn now() -> u32 {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    u32::try_from(seconds).unwrap_or(u32::MAX)
+}
+
+/// An error that stops a request being encrypted, or its response being
+/// decrypted.
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum DnsCryptError {
+    /// The resolver didn’t send back any certificates.
+    NoCertificate,
+
+    /// A certificate is too short, or doesn’t start with the right bytes.
+    InvalidCertificate,
+
+    /// A certificate uses a construction that dog doesn’t support.
+    UnsupportedConstruction(u16),
+
+    /// A certificate isn’t signed by the provider’s key.
+    BadSignature,
+
+    /// A certificate isn’t valid at the current time.
+    Expired,
+
+    /// Random bytes for the key or nonce couldn’t be generated.
+    Random,
+
+    /// The response doesn’t start with the right bytes, or doesn’t repeat
+    /// the nonce of the request.
+    InvalidResponse,
+
+    /// The response couldn’t be decrypted.
+    Decryption,
+}
+
+impl fmt::Display for DnsCryptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoCertificate => write!(f, "No DNSCrypt certificate from resolver"),
+            Self::InvalidCertificate => write!(f, "Invalid DNSCrypt certificate"),
+            Self::UnsupportedConstruction(version) => {
+                write!(f, "Unsupported DNSCrypt construction {version:#06x}")
+            }
+            Self::BadSignature => write!(f, "DNSCrypt certificate has a bad signature"),
+            Self::Expired => write!(f, "DNSCrypt certificate is not currently valid"),
+            Self::Random => write!(f, "Failed to generate random bytes"),
+            Self::InvalidResponse => write!(f, "Invalid DNSCrypt response"),
+            Self::Decryption => write!(f, "Failed to decrypt DNSCrypt response"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::transport::Transport;
+    use hickory_resolver::config::ConnectionConfig;
+    use hickory_resolver::proto::op::OpCode;
+    use hickory_resolver::proto::rr::rdata::TXT;
+    use pretty_assertions::assert_eq;
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+    use std::net::SocketAddr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tokio::net::UdpSocket;
+
+    /// The seed of the provider’s signing key.
+    const PROVIDER_SEED: [u8; 32] = [1; 32];
+
+    /// The resolver’s short-term secret key.
+    const RESOLVER_SECRET: [u8; 32] = [2; 32];
+
+    /// The bytes that requests to the stand-in resolver start with.
+    const CLIENT_MAGIC: [u8; 8] = *b"dogmagic";
+
+    fn signing_key() -> Ed25519KeyPair {
+        Ed25519KeyPair::from_seed_unchecked(&PROVIDER_SEED).unwrap()
+    }
+
+    fn provider() -> Provider {
+        Provider {
+            public_key: <[u8; 32]>::try_from(signing_key().public_key().as_ref()).unwrap(),
+            name: String::from("2.dnscrypt-cert.dns.example"),
+        }
+    }
+
+    /// Builds a certificate for the resolver’s key, signed with the given
+    /// key.
+    fn certificate(
+        signing_key: &Ed25519KeyPair,
+        version: u16,
+        serial: u32,
+        valid: (u32, u32),
+    ) -> Vec<u8> {
+        let resolver_key = SecretKey::from(RESOLVER_SECRET).public_key();
+
+        let mut signed = Vec::new();
+        signed.extend_from_slice(resolver_key.as_bytes());
+        signed.extend_from_slice(&CLIENT_MAGIC);
+        signed.extend_from_slice(&serial.to_be_bytes());
+        signed.extend_from_slice(&valid.0.to_be_bytes());
+        signed.extend_from_slice(&valid.1.to_be_bytes());
+
+        let mut certificate = CERT_MAGIC.to_vec();
+        certificate.extend_from_slice(&version.to_be_bytes());
+        certificate.extend_from_slice(&[0, 0]);
+        certificate.extend_from_slice(signing_key.sign(&signed).as_ref());
+        certificate.extend_from_slice(&signed);
+        certificate
+    }
+
+    fn records(certificates: &[Vec<u8>]) -> Vec<Record> {
+        certificates
+            .iter()
+            .map(|certificate| {
+                let txt = TXT::from_bytes(vec![certificate]);
+                Record::from_rdata(
+                    Name::from_utf8(provider().name).unwrap(),
+                    3600,
+                    RData::TXT(txt),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn choose_highest_serial() {
+        let records = records(&[
+            certificate(&signing_key(), 0x0001, 10, (100, 200)),
+            certificate(&signing_key(), 0x0002, 12, (100, 200)),
+            certificate(&signing_key(), 0x0002, 11, (100, 200)),
+        ]);
+        let certificate = Certificate::choose(&provider(), &records, 150).unwrap();
+
+        assert_eq!(certificate.serial, 12);
+        assert_eq!(certificate.construction(), Construction::XChaCha20Poly1305);
+        assert_eq!(certificate.client_magic, CLIENT_MAGIC);
+        assert_eq!(certificate.valid_until, 200);
+    }
+
+    #[test]
+    fn skip_expired() {
+        let records = records(&[
+            certificate(&signing_key(), 0x0002, 12, (100, 120)),
+            certificate(&signing_key(), 0x0001, 10, (100, 200)),
+        ]);
+        let certificate = Certificate::choose(&provider(), &records, 150).unwrap();
+
+        assert_eq!(certificate.serial, 10);
+    }
+
+    #[test]
+    fn only_expired() {
+        let records = records(&[certificate(&signing_key(), 0x0002, 12, (100, 120))]);
+
+        assert_eq!(
+            Certificate::choose(&provider(), &records, 150).unwrap_err(),
+            DnsCryptError::Expired
+        );
+    }
+
+    #[test]
+    fn bad_signature() {
+        let other_key = Ed25519KeyPair::from_seed_unchecked(&[3; 32]).unwrap();
+        let records = records(&[certificate(&other_key, 0x0002, 12, (100, 200))]);
+
+        assert_eq!(
+            Certificate::choose(&provider(), &records, 150).unwrap_err(),
+            DnsCryptError::BadSignature
+        );
+    }
+
+    #[test]
+    fn unsupported_construction() {
+        let records = records(&[certificate(&signing_key(), 0x0003, 12, (100, 200))]);
+
+        assert_eq!(
+            Certificate::choose(&provider(), &records, 150).unwrap_err(),
+            DnsCryptError::UnsupportedConstruction(0x0003)
+        );
+    }
+
+    #[test]
+    fn no_certificates() {
+        assert_eq!(
+            Certificate::choose(&provider(), &[], 150).unwrap_err(),
+            DnsCryptError::NoCertificate
+        );
+    }
+
+    #[test]
+    fn padding() {
+        assert_eq!(pad(&[1; 63], 0).len(), 64);
+        assert_eq!(pad(&[1; 64], 0).len(), 128);
+        assert_eq!(pad(&[1; 10], MIN_UDP_QUERY_LENGTH).len(), 256);
+        assert_eq!(unpad(&pad(&[1, 0, 2, 0], 0)), Some(vec![1, 0, 2, 0]));
+    }
+
+    #[test]
+    fn missing_padding() {
+        assert_eq!(unpad(&[1, 2, 3, 0, 0]), None);
+    }
+
+    /// Decrypts a query as the resolver does, then encrypts the response to
+    /// it, which is the query with its QR bit set.
+    fn answer(construction: Construction, packet: &[u8]) -> Vec<u8> {
+        assert_eq!(packet[..8], CLIENT_MAGIC);
+        let client_key = PublicKey::from(<[u8; 32]>::try_from(&packet[8..40]).unwrap());
+        let client_nonce = &packet[40..52];
+        let cipher = Cipher::new(construction, &client_key, &SecretKey::from(RESOLVER_SECRET));
+
+        let query = cipher
+            .decrypt(&nonce_for(client_nonce, &[0; 12]), &packet[52..])
+            .unwrap();
+        let mut response = unpad(&query).unwrap();
+        response[2] |= 0x80;
+
+        let resolver_nonce = [9; HALF_NONCE_LENGTH];
+        let nonce = nonce_for(client_nonce, &resolver_nonce);
+        let mut packet = RESOLVER_MAGIC.to_vec();
+        packet.extend_from_slice(&nonce);
+        packet.extend_from_slice(&cipher.encrypt(&nonce, &pad(&response, 0)));
+        packet
+    }
+
+    #[test]
+    fn round_trip() {
+        for version in [0x0001, 0x0002] {
+            let records = records(&[certificate(&signing_key(), version, 1, (0, u32::MAX))]);
+            let certificate = Certificate::choose(&provider(), &records, now()).unwrap();
+
+            let (packet, sealed) = certificate.encrypt(&[0x12, 0x34, 1, 0], true).unwrap();
+            let response = answer(certificate.construction(), &packet);
+
+            assert_eq!(sealed.decrypt(&response), Ok(vec![0x12, 0x34, 0x81, 0]));
+        }
+    }
+
+    #[test]
+    fn response_for_other_query() {
+        let records = records(&[certificate(&signing_key(), 0x0002, 1, (0, u32::MAX))]);
+        let certificate = Certificate::choose(&provider(), &records, now()).unwrap();
+
+        let (packet, _) = certificate.encrypt(&[0x12, 0x34, 1, 0], false).unwrap();
+        let (_, other) = certificate.encrypt(&[0x12, 0x34, 1, 0], false).unwrap();
+        let response = answer(certificate.construction(), &packet);
+
+        assert_eq!(
+            other.decrypt(&response),
+            Err(DnsCryptError::InvalidResponse)
+        );
+    }
+
+    /// Starts a stand-in resolver on UDP, which answers requests for its
+    /// certificate in plain DNS, and everything else with DNSCrypt,
+    /// counting how many times its certificate was asked for.
+    async fn serve_resolver(certificate_requests: Arc<AtomicUsize>) -> SocketAddr {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        let certificate = certificate(&signing_key(), 0x0002, 1, (0, u32::MAX));
+
+        tokio::spawn(async move {
+            let mut buffer = vec![0; 4096];
+            while let Ok((length, from)) = socket.recv_from(&mut buffer).await {
+                let packet = &buffer[..length];
+                let response = if packet.starts_with(&CLIENT_MAGIC) {
+                    assert_eq!(packet.len(), 52 + 16 + MIN_UDP_QUERY_LENGTH);
+                    answer(Construction::XChaCha20Poly1305, packet)
+                } else {
+                    certificate_requests.fetch_add(1, Ordering::SeqCst);
+                    let request = Message::from_vec(packet).unwrap();
+                    assert_eq!(request.queries[0].query_type(), RecordType::TXT);
+
+                    let mut response = Message::response(request.metadata.id, OpCode::Query);
+                    response.add_query(request.queries[0].clone());
+                    response.add_answers(records(std::slice::from_ref(&certificate)));
+                    response.to_vec().unwrap()
+                };
+                socket.send_to(&response, from).await.unwrap();
+            }
+        });
+
+        addr
+    }
+
+    #[tokio::test]
+    async fn exchange_over_udp() {
+        let certificate_requests = Arc::new(AtomicUsize::new(0));
+        let addr = serve_resolver(Arc::clone(&certificate_requests)).await;
+
+        let mut connection = ConnectionConfig::udp();
+        connection.port = addr.port();
+        let transport = Transport::new(addr.ip(), connection).with_dnscrypt(provider());
+
+        for id in [1, 2] {
+            let mut request = Message::query();
+            request.metadata.id = id;
+            request.add_query(Query::query(
+                Name::from_utf8("dns.example").unwrap(),
+                RecordType::A,
+            ));
+
+            let response = transport.send(request).await.unwrap();
+            assert_eq!(response.metadata.id, id);
+        }
+
+        assert_eq!(certificate_requests.load(Ordering::SeqCst), 1);
+        assert_eq!(transport.protocol_name(), "DNSCrypt/UDP");
+        assert_eq!(
+            transport
+                .dnscrypt()
+                .and_then(Resolver::fetched_certificate)
+                .map(|c| c.serial),
+            Some(1)
+        );
+    }
+}
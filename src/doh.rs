@@ -0,0 +1,852 @@
+/*
+ * dog - A command-line DNS client
+ * Copyright (c) 2026 l1a and contributors
+ * Original code Copyright (c) Benjamin Sago
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Sending DNS requests over HTTPS, as described in RFC 8484.
+//!
+//! hickory has its own DNS-over-HTTPS client stream, but it only sends POST
+//! requests, and keeps the HTTP response to itself. dog makes the HTTP
+//! requests itself instead, so it can use either method, so the status and
+//! headers of each response can be shown in verbose mode, and so requests
+//! can be sent over HTTP/3 as well as HTTP/2.
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use bytes::{BufMut, Bytes};
+use data_encoding::BASE64URL_NOPAD;
+use hickory_resolver::net::NetError;
+use http::header::{ACCEPT, CONTENT_LENGTH, CONTENT_TYPE};
+use http::{HeaderMap, Request, StatusCode, Version};
+use log::*;
+use quinn::crypto::rustls::QuicClientConfig;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+use tokio_rustls::rustls::ClientConfig;
+use tokio_rustls::TlsConnector;
+
+use crate::pinning::{pinned_config, CertificateHash};
+use crate::transport::server_name_for;
+
+/// The media type of a DNS message sent over HTTPS.
+const DNS_MESSAGE: &str = "application/dns-message";
+
+/// The path to send requests to when the nameserver isn’t given as a URL.
+pub const DEFAULT_PATH: &str = "/dns-query";
+
+/// The HTTP method to send requests with.
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Default)]
+pub enum Method {
+    /// Send the message as the body of a POST request.
+    #[default]
+    Post,
+
+    /// Send the message in the `dns` parameter of the URL of a GET
+    /// request, which lets caches along the way store the response.
+    Get,
+}
+
+/// The version of HTTP to send requests with.
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Default)]
+pub enum HttpVersion {
+    /// HTTP/2, over a TLS connection.
+    #[default]
+    Http2,
+
+    /// HTTP/3, over a QUIC connection.
+    Http3,
+}
+
+/// The parts of an HTTP response other than its body, which get shown in
+/// verbose mode.
+#[derive(Debug, Clone)]
+pub struct HttpResponse {
+    /// The version of HTTP the response was sent with.
+    pub version: Version,
+
+    /// The status code of the response.
+    pub status: StatusCode,
+
+    /// The headers of the response.
+    pub headers: HeaderMap,
+}
+
+/// An HTTP request that failed. If the server did respond, but with an
+/// error status or with something other than what was asked for, the
+/// response is kept, so verbose mode can still show its status and headers.
+#[derive(Debug)]
+pub struct HttpError {
+    /// What went wrong.
+    pub error: NetError,
+
+    /// The response that came back, if there was one.
+    pub response: Option<HttpResponse>,
+}
+
+impl From<NetError> for HttpError {
+    fn from(error: NetError) -> Self {
+        Self {
+            error,
+            response: None,
+        }
+    }
+}
+
+impl From<HttpError> for NetError {
+    fn from(error: HttpError) -> Self {
+        error.error
+    }
+}
+
+impl fmt::Display for HttpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
+/// An HTTPS endpoint to send requests to.
+#[derive(Debug, Copy, Clone)]
+pub struct Endpoint<'a> {
+    /// The address to connect to.
+    pub addr: SocketAddr,
+
+    /// The name to check the server’s certificate against, which also gets
+    /// sent as the host of each request.
+    pub server_name: &'a str,
+
+    /// The path of the URL to send requests to, which can include a query
+    /// string.
+    pub path: &'a str,
+}
+
+impl Endpoint<'_> {
+    /// The host and port of the endpoint, as it appears in a URL.
+    fn authority(&self) -> String {
+        let host = if self.server_name.contains(':') {
+            format!("[{}]", self.server_name)
+        } else {
+            self.server_name.to_string()
+        };
+
+        match self.addr.port() {
+            443 => host,
+            port => format!("{host}:{port}"),
+        }
+    }
+
+    /// The URL of the given path, which can include a query string, on the
+    /// endpoint.
+    pub fn url(&self, path: &str) -> String {
+        format!("https://{}{path}", self.authority())
+    }
+
+    /// Builds the HTTP request for a DNS message, which either goes in the
+    /// body, or in the URL, depending on the method.
+    fn request(
+        &self,
+        method: Method,
+        message: Vec<u8>,
+    ) -> Result<Request<Option<Bytes>>, NetError> {
+        let request = match method {
+            Method::Post => Request::post(self.url(self.path))
+                .header(CONTENT_TYPE, DNS_MESSAGE)
+                .header(CONTENT_LENGTH, message.len())
+                .header(ACCEPT, DNS_MESSAGE)
+                .body(Some(Bytes::from(message))),
+            Method::Get => {
+                let separator = if self.path.contains('?') { '&' } else { '?' };
+                let encoded = BASE64URL_NOPAD.encode(&message);
+                Request::get(self.url(&format!("{}{separator}dns={encoded}", self.path)))
+                    .header(ACCEPT, DNS_MESSAGE)
+                    .body(None)
+            }
+        };
+
+        request.map_err(|e| NetError::from(format!("Invalid HTTP request: {e}")))
+    }
+}
+
+/// A **client** sends HTTP requests to endpoints, each over a new
+/// connection, using one version of HTTP.
+#[derive(Debug, Clone)]
+pub struct Client {
+    /// The version of HTTP to send requests with.
+    version: HttpVersion,
+
+    /// The TLS settings to connect with, which decide which certificates
+    /// are trusted.
+    config: ClientConfig,
+
+    /// How long to wait for each response.
+    timeout: Duration,
+}
+
+impl Client {
+    /// Creates a new client that sends requests with the given version of
+    /// HTTP, trusting the usual set of root certificates along with any
+    /// certificate hashes that have been pinned, and waiting for the given
+    /// amount of time for each response.
+    pub fn new(
+        version: HttpVersion,
+        timeout: Duration,
+        hashes: &[CertificateHash],
+    ) -> Result<Self, NetError> {
+        Ok(Self {
+            version,
+            config: pinned_config(hashes)?,
+            timeout,
+        })
+    }
+
+    /// Sends a DNS message to an endpoint, returning the HTTP response and
+    /// the DNS message in its body. Responses with an error status, or
+    /// that aren’t DNS messages, are returned as errors.
+    pub async fn exchange(
+        &self,
+        endpoint: Endpoint<'_>,
+        method: Method,
+        message: Vec<u8>,
+    ) -> Result<(HttpResponse, Vec<u8>), HttpError> {
+        let request = endpoint.request(method, message)?;
+        self.fetch(endpoint, request, Some(DNS_MESSAGE)).await
+    }
+
+    /// Sends an HTTP request to an endpoint, returning the response and its
+    /// body. Responses with an error status, or with a different content
+    /// type to the one given, are returned as errors, along with the
+    /// response.
+    pub async fn fetch(
+        &self,
+        endpoint: Endpoint<'_>,
+        request: Request<Option<Bytes>>,
+        media_type: Option<&str>,
+    ) -> Result<(HttpResponse, Vec<u8>), HttpError> {
+        debug!(
+            "Sending HTTP request: {} {}",
+            request.method(),
+            request.uri()
+        );
+
+        let send = async {
+            match self.version {
+                HttpVersion::Http2 => self.send_h2(endpoint, request).await,
+                HttpVersion::Http3 => self.send_h3(endpoint, request).await,
+            }
+        };
+
+        let (response, body) = match tokio::time::timeout(self.timeout, send).await {
+            Ok(result) => result?,
+            Err(_) => return Err(NetError::Timeout.into()),
+        };
+
+        match check_response(&response, media_type) {
+            Ok(()) => Ok((response, body)),
+            Err(error) => Err(HttpError {
+                error,
+                response: Some(response),
+            }),
+        }
+    }
+
+    /// Connects to the endpoint over TLS and sends it the request over
+    /// HTTP/2.
+    async fn send_h2(
+        &self,
+        endpoint: Endpoint<'_>,
+        request: Request<Option<Bytes>>,
+    ) -> Result<(HttpResponse, Vec<u8>), NetError> {
+        let mut config = self.config.clone();
+        config.alpn_protocols = vec![b"h2".to_vec()];
+        let connector = TlsConnector::from(Arc::new(config));
+
+        let stream = TcpStream::connect(endpoint.addr).await?;
+        let server_name = server_name_for(endpoint.server_name)?;
+        let stream = connector.connect(server_name, stream).await?;
+
+        send_h2_over(stream, request).await
+    }
+
+    /// Connects to the endpoint over QUIC and sends it the request over
+    /// HTTP/3.
+    async fn send_h3(
+        &self,
+        endpoint: Endpoint<'_>,
+        request: Request<Option<Bytes>>,
+    ) -> Result<(HttpResponse, Vec<u8>), NetError> {
+        let connection = connect_quic(endpoint, self.config.clone()).await?;
+        send_h3_over(connection, request).await
+    }
+}
+
+/// Sends the request down a stream that’s already connected to the
+/// endpoint using HTTP/2, then reads back the response.
+async fn send_h2_over<S>(
+    stream: S,
+    request: Request<Option<Bytes>>,
+) -> Result<(HttpResponse, Vec<u8>), NetError>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (client, connection) = h2::client::handshake(stream)
+        .await
+        .map_err(|e| http_error(&e))?;
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            debug!("HTTP/2 connection closed with error: {e}");
+        }
+    });
+
+    let (mut parts, body) = request.into_parts();
+    parts.version = Version::HTTP_2;
+
+    let mut client = client.ready().await.map_err(|e| http_error(&e))?;
+    let (response, mut stream) = client
+        .send_request(Request::from_parts(parts, ()), body.is_none())
+        .map_err(|e| http_error(&e))?;
+    if let Some(body) = body {
+        stream.send_data(body, true).map_err(|e| http_error(&e))?;
+    }
+
+    let (parts, mut body) = response.await.map_err(|e| http_error(&e))?.into_parts();
+    let response = HttpResponse {
+        version: parts.version,
+        status: parts.status,
+        headers: parts.headers,
+    };
+
+    let mut data = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(|e| http_error(&e))?;
+        let _ = body.flow_control().release_capacity(chunk.len());
+        data.extend_from_slice(&chunk);
+    }
+
+    Ok((response, data))
+}
+
+/// Opens a QUIC connection to the endpoint, negotiating HTTP/3 during the
+/// handshake.
+async fn connect_quic(
+    endpoint: Endpoint<'_>,
+    mut config: ClientConfig,
+) -> Result<quinn::Connection, NetError> {
+    config.alpn_protocols = vec![b"h3".to_vec()];
+    let config = QuicClientConfig::try_from(config).map_err(|e| h3_error(&e))?;
+
+    let bind_addr = if endpoint.addr.is_ipv6() {
+        SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0))
+    } else {
+        SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0))
+    };
+    let mut client = quinn::Endpoint::client(bind_addr)?;
+    client.set_default_client_config(quinn::ClientConfig::new(Arc::new(config)));
+
+    Ok(client.connect(endpoint.addr, endpoint.server_name)?.await?)
+}
+
+/// Sends the request down a QUIC connection to the endpoint using HTTP/3,
+/// then reads back the response.
+async fn send_h3_over(
+    connection: quinn::Connection,
+    request: Request<Option<Bytes>>,
+) -> Result<(HttpResponse, Vec<u8>), NetError> {
+    let (mut driver, mut client) = h3::client::new(h3_quinn::Connection::new(connection))
+        .await
+        .map_err(|e| h3_error(&e))?;
+    tokio::spawn(async move {
+        let error = std::future::poll_fn(|cx| driver.poll_close(cx)).await;
+        debug!("HTTP/3 connection closed: {error}");
+    });
+
+    let (mut parts, body) = request.into_parts();
+    parts.version = Version::HTTP_3;
+
+    let mut stream = client
+        .send_request(Request::from_parts(parts, ()))
+        .await
+        .map_err(|e| h3_error(&e))?;
+    if let Some(body) = body {
+        stream.send_data(body).await.map_err(|e| h3_error(&e))?;
+    }
+    stream.finish().await.map_err(|e| h3_error(&e))?;
+
+    let (parts, ()) = stream
+        .recv_response()
+        .await
+        .map_err(|e| h3_error(&e))?
+        .into_parts();
+    let response = HttpResponse {
+        version: parts.version,
+        status: parts.status,
+        headers: parts.headers,
+    };
+
+    let mut data = Vec::new();
+    while let Some(chunk) = stream.recv_data().await.map_err(|e| h3_error(&e))? {
+        data.put(chunk);
+    }
+
+    Ok((response, data))
+}
+
+/// Returns an error if the response has an error status, or says it holds
+/// something other than the given media type. Media types are compared
+/// without regard to case, and any parameters after them, such as a
+/// `charset`, are ignored.
+fn check_response(response: &HttpResponse, media_type: Option<&str>) -> Result<(), NetError> {
+    if !response.status.is_success() {
+        return Err(NetError::from(format!(
+            "HTTP request failed with status {}",
+            response.status
+        )));
+    }
+
+    if let (Some(content_type), Some(media_type)) = (response.headers.get(CONTENT_TYPE), media_type)
+    {
+        let matches = content_type
+            .to_str()
+            .ok()
+            .and_then(|value| value.split(';').next())
+            .is_some_and(|value| value.trim().eq_ignore_ascii_case(media_type));
+
+        if !matches {
+            return Err(NetError::from(format!(
+                "HTTP response has unexpected content type {content_type:?}"
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Converts an error from the HTTP/2 connection into a network error.
+fn http_error(error: &h2::Error) -> NetError {
+    NetError::from(format!("HTTP/2 error: {error}"))
+}
+
+/// Converts an error from the HTTP/3 connection into a network error.
+fn h3_error(error: &impl fmt::Display) -> NetError {
+    NetError::from(format!("HTTP/3 error: {error}"))
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use data_encoding::BASE64;
+    use pretty_assertions::assert_eq;
+    use quinn::crypto::rustls::QuicServerConfig;
+    use rustls_pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+    use tokio::net::TcpListener;
+    use tokio_rustls::rustls::crypto::ring::default_provider;
+    use tokio_rustls::rustls::version::TLS13;
+    use tokio_rustls::rustls::{RootCertStore, ServerConfig};
+    use tokio_rustls::TlsAcceptor;
+
+    /// A certificate for `doh.example` that signs itself, for the test
+    /// servers to use, and that the test clients trust.
+    const CERTIFICATE: &str = "\
+        MIIBvzCCAWWgAwIBAgIUOGav2i6Md3zZ8JiwsPab4JP+RdcwCgYIKoZIzj0EAwIw\
+        FjEUMBIGA1UEAwwLZG9oLmV4YW1wbGUwIBcNMjYxMDE3MDIyMjUyWhgPMjEyNjA5\
+        MjMwMjIyNTJaMBYxFDASBgNVBAMMC2RvaC5leGFtcGxlMFkwEwYHKoZIzj0CAQYI\
+        KoZIzj0DAQcDQgAEyRh3SKULPucUK98V29xD0EcVpx9KFLmqkNOc9IgbyDilkNUF\
+        WnMf6U6voYAIId8p3Fkfbg2XV6Z6+d2jz30KfqOBjjCBizAdBgNVHQ4EFgQUN5ZE\
+        xbqjtkKWilZMbS9lJxyFN6YwHwYDVR0jBBgwFoAUN5ZExbqjtkKWilZMbS9lJxyF\
+        N6YwFgYDVR0RBA8wDYILZG9oLmV4YW1wbGUwDAYDVR0TAQH/BAIwADAOBgNVHQ8B\
+        Af8EBAMCB4AwEwYDVR0lBAwwCgYIKwYBBQUHAwEwCgYIKoZIzj0EAwIDSAAwRQIg\
+        YkLUp5LosEgn92y3BxCESs590AW99s/jbV2/GfumLkQCIQD+HcOUvQyJlObYtQ6F\
+        tax4NB9pf1IXNeBsOpDe97YITg==";
+
+    /// The private key for the certificate, in PKCS #8 format.
+    const PRIVATE_KEY: &str = "\
+        MIGHAgEAMBMGByqGSM49AgEGCCqGSM49AwEHBG0wawIBAQQgYlN3XsKYUChun0Sg\
+        +lebpcqsV6oCb/wHumt2cU6tG3qhRANCAATJGHdIpQs+5xQr3xXb3EPQRxWnH0oU\
+        uaqQ05z0iBvIOKWQ1QVacx/pTq+hgAgh3yncWR9uDZdXpnr53aPPfQp+";
+
+    /// The test certificate, decoded.
+    pub(crate) fn certificate() -> CertificateDer<'static> {
+        CertificateDer::from(BASE64.decode(CERTIFICATE.as_bytes()).unwrap())
+    }
+
+    /// The TLS settings for a test server, which uses the test certificate
+    /// and negotiates the given protocol.
+    fn server_config(alpn: &[u8]) -> ServerConfig {
+        let key = PrivatePkcs8KeyDer::from(BASE64.decode(PRIVATE_KEY.as_bytes()).unwrap());
+        let mut config = ServerConfig::builder_with_provider(Arc::new(default_provider()))
+            .with_protocol_versions(&[&TLS13])
+            .unwrap()
+            .with_no_client_auth()
+            .with_single_cert(vec![certificate()], PrivateKeyDer::Pkcs8(key))
+            .unwrap();
+        config.alpn_protocols = vec![alpn.to_vec()];
+        config
+    }
+
+    /// A client that trusts the test certificate, and no other.
+    pub(crate) fn client(version: HttpVersion) -> Client {
+        let mut roots = RootCertStore::empty();
+        roots.add(certificate()).unwrap();
+        let config = ClientConfig::builder_with_provider(Arc::new(default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+
+        Client {
+            version,
+            config,
+            timeout: Duration::from_secs(5),
+        }
+    }
+
+    /// Starts an HTTP/2 server on a local port, using the test certificate,
+    /// which hands every request and its body to the given function to
+    /// build the response and its body.
+    pub(crate) async fn serve_tls<F>(respond: F) -> SocketAddr
+    where
+        F: Fn(http::request::Parts, Vec<u8>) -> (http::Response<()>, Vec<u8>)
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let acceptor = TlsAcceptor::from(Arc::new(server_config(b"h2")));
+
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let stream = acceptor.accept(stream).await.unwrap();
+                tokio::spawn(serve_h2(stream, respond.clone()));
+            }
+        });
+
+        addr
+    }
+
+    /// Responds to every HTTP/2 request that comes down the stream using
+    /// the given function. Each request gets handled in its own task, so
+    /// the connection keeps being read while its body arrives.
+    async fn serve_h2<S, F>(stream: S, respond: F)
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+        F: Fn(http::request::Parts, Vec<u8>) -> (http::Response<()>, Vec<u8>)
+            + Clone
+            + Send
+            + 'static,
+    {
+        let mut connection = h2::server::handshake(stream).await.unwrap();
+        while let Some(request) = connection.accept().await {
+            let (request, mut sender) = request.unwrap();
+            let respond = respond.clone();
+
+            tokio::spawn(async move {
+                let (parts, mut body) = request.into_parts();
+                let mut data = Vec::new();
+                while let Some(chunk) = body.data().await {
+                    data.extend_from_slice(&chunk.unwrap());
+                }
+
+                let (response, data) = respond(parts, data);
+                let mut body = sender.send_response(response, false).unwrap();
+                body.send_data(Bytes::from(data), true).unwrap();
+            });
+        }
+    }
+
+    /// A function for a test server that responds with the given status
+    /// and content type, and a body holding the DNS message it was sent,
+    /// either in the URL or in the body, reversed. The response also has
+    /// the method of the request in a header.
+    fn reverse(
+        status: StatusCode,
+        content_type: &'static str,
+    ) -> impl Fn(http::request::Parts, Vec<u8>) -> (http::Response<()>, Vec<u8>) + Clone {
+        move |parts, body| {
+            let mut message = match parts.uri.query() {
+                Some(query) => {
+                    let encoded = query.rsplit("dns=").next().unwrap();
+                    BASE64URL_NOPAD.decode(encoded.as_bytes()).unwrap()
+                }
+                None => body,
+            };
+            message.reverse();
+
+            let response = http::Response::builder()
+                .status(status)
+                .header(CONTENT_TYPE, content_type)
+                .header("x-method", parts.method.as_str())
+                .body(())
+                .unwrap();
+            (response, message)
+        }
+    }
+
+    /// Starts an HTTP/2 server at the other end of a stream, which responds
+    /// to every request using `reverse`.
+    fn serve(status: StatusCode, content_type: &'static str) -> tokio::io::DuplexStream {
+        let (client, server) = tokio::io::duplex(4096);
+        tokio::spawn(serve_h2(server, reverse(status, content_type)));
+        client
+    }
+
+    /// Starts an HTTP/3 server on a local port, using the test certificate,
+    /// which responds to every request using `reverse`.
+    fn serve_h3(status: StatusCode, content_type: &'static str) -> SocketAddr {
+        let config = QuicServerConfig::try_from(server_config(b"h3")).unwrap();
+        let config = quinn::ServerConfig::with_crypto(Arc::new(config));
+        let server = quinn::Endpoint::server(config, "127.0.0.1:0".parse().unwrap()).unwrap();
+        let addr = server.local_addr().unwrap();
+        let respond = reverse(status, content_type);
+
+        tokio::spawn(async move {
+            while let Some(incoming) = server.accept().await {
+                let connection = h3_quinn::Connection::new(incoming.await.unwrap());
+                let mut connection = h3::server::Connection::new(connection).await.unwrap();
+                while let Ok(Some(resolver)) = connection.accept().await {
+                    let (request, mut stream) = resolver.resolve_request().await.unwrap();
+                    let (parts, ()) = request.into_parts();
+
+                    let mut data = Vec::new();
+                    while let Some(chunk) = stream.recv_data().await.unwrap() {
+                        data.put(chunk);
+                    }
+
+                    let (response, data) = respond(parts, data);
+                    stream.send_response(response).await.unwrap();
+                    stream.send_data(Bytes::from(data)).await.unwrap();
+                    stream.finish().await.unwrap();
+                }
+            }
+        });
+
+        addr
+    }
+
+    fn endpoint(addr: &str, server_name: &'static str, path: &'static str) -> Endpoint<'static> {
+        Endpoint {
+            addr: addr.parse().unwrap(),
+            server_name,
+            path,
+        }
+    }
+
+    #[test]
+    fn post_request() {
+        let endpoint = endpoint("192.0.2.1:443", "doh.example", "/dns-query");
+        let request = endpoint.request(Method::Post, vec![1, 2, 3]).unwrap();
+
+        assert_eq!(request.method(), http::Method::POST);
+        assert_eq!(request.uri(), "https://doh.example/dns-query");
+        assert_eq!(request.headers()[CONTENT_TYPE], DNS_MESSAGE);
+        assert_eq!(request.headers()[CONTENT_LENGTH], "3");
+        assert_eq!(request.headers()[ACCEPT], DNS_MESSAGE);
+        assert_eq!(request.body().as_deref(), Some(&[1, 2, 3][..]));
+    }
+
+    #[test]
+    fn get_request() {
+        let endpoint = endpoint("192.0.2.1:443", "doh.example", "/resolve");
+        let request = endpoint.request(Method::Get, vec![0, 0, 1, 0xff]).unwrap();
+
+        assert_eq!(request.method(), http::Method::GET);
+        assert_eq!(request.uri(), "https://doh.example/resolve?dns=AAAB_w");
+        assert!(request.headers().get(CONTENT_TYPE).is_none());
+        assert!(request.body().is_none());
+    }
+
+    #[test]
+    fn get_request_with_query_string() {
+        let endpoint = endpoint("192.0.2.1:443", "doh.example", "/resolve?ct");
+        let request = endpoint.request(Method::Get, vec![0]).unwrap();
+
+        assert_eq!(request.uri(), "https://doh.example/resolve?ct&dns=AA");
+    }
+
+    #[test]
+    fn authority_with_port() {
+        let endpoint = endpoint("192.0.2.1:8443", "doh.example", "/");
+        assert_eq!(endpoint.authority(), "doh.example:8443");
+    }
+
+    #[test]
+    fn authority_ipv6() {
+        let endpoint = endpoint("[2001:db8::1]:443", "2001:db8::1", "/");
+        assert_eq!(endpoint.authority(), "[2001:db8::1]");
+    }
+
+    #[tokio::test]
+    async fn post_exchange() {
+        let stream = serve(StatusCode::OK, DNS_MESSAGE);
+        let endpoint = endpoint("192.0.2.1:443", "doh.example", "/dns-query");
+        let request = endpoint.request(Method::Post, vec![1, 2, 3]).unwrap();
+
+        let (http, body) = send_h2_over(stream, request).await.unwrap();
+
+        assert_eq!(http.version, Version::HTTP_2);
+        assert_eq!(http.status, StatusCode::OK);
+        assert_eq!(http.headers["x-method"], "POST");
+        assert_eq!(body, vec![3, 2, 1]);
+    }
+
+    #[tokio::test]
+    async fn get_exchange() {
+        let stream = serve(StatusCode::OK, DNS_MESSAGE);
+        let endpoint = endpoint("192.0.2.1:443", "doh.example", "/resolve");
+        let request = endpoint.request(Method::Get, vec![1, 2, 3]).unwrap();
+
+        let (http, body) = send_h2_over(stream, request).await.unwrap();
+
+        assert_eq!(http.headers["x-method"], "GET");
+        assert_eq!(body, vec![3, 2, 1]);
+    }
+
+    #[tokio::test]
+    async fn tls_exchange() {
+        let addr = serve_tls(reverse(StatusCode::OK, DNS_MESSAGE)).await;
+        let endpoint = Endpoint {
+            addr,
+            server_name: "doh.example",
+            path: "/dns-query",
+        };
+
+        let (http, body) = client(HttpVersion::Http2)
+            .exchange(endpoint, Method::Post, vec![1, 2, 3])
+            .await
+            .unwrap();
+
+        assert_eq!(http.version, Version::HTTP_2);
+        assert_eq!(body, vec![3, 2, 1]);
+    }
+
+    #[tokio::test]
+    async fn error_status() {
+        let addr = serve_tls(reverse(StatusCode::NOT_FOUND, DNS_MESSAGE)).await;
+        let endpoint = Endpoint {
+            addr,
+            server_name: "doh.example",
+            path: "/resolve",
+        };
+
+        let result = client(HttpVersion::Http2)
+            .exchange(endpoint, Method::Post, vec![1, 2, 3])
+            .await;
+
+        let error = result.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "HTTP request failed with status 404 Not Found"
+        );
+        assert_eq!(
+            error.response.map(|response| response.status),
+            Some(StatusCode::NOT_FOUND)
+        );
+    }
+
+    #[tokio::test]
+    async fn wrong_content_type() {
+        let addr = serve_tls(reverse(StatusCode::OK, "text/html")).await;
+        let endpoint = Endpoint {
+            addr,
+            server_name: "doh.example",
+            path: "/resolve",
+        };
+
+        let result = client(HttpVersion::Http2)
+            .exchange(endpoint, Method::Post, vec![1, 2, 3])
+            .await;
+
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn content_type_with_parameters() {
+        let addr = serve_tls(reverse(
+            StatusCode::OK,
+            "Application/DNS-Message; charset=binary",
+        ))
+        .await;
+        let endpoint = Endpoint {
+            addr,
+            server_name: "doh.example",
+            path: "/resolve",
+        };
+
+        let result = client(HttpVersion::Http2)
+            .exchange(endpoint, Method::Post, vec![1, 2, 3])
+            .await;
+
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn h3_post_exchange() {
+        let endpoint = Endpoint {
+            addr: serve_h3(StatusCode::OK, DNS_MESSAGE),
+            server_name: "doh.example",
+            path: "/dns-query",
+        };
+
+        let (http, body) = client(HttpVersion::Http3)
+            .exchange(endpoint, Method::Post, vec![1, 2, 3])
+            .await
+            .unwrap();
+
+        assert_eq!(http.version, Version::HTTP_3);
+        assert_eq!(http.status, StatusCode::OK);
+        assert_eq!(http.headers["x-method"], "POST");
+        assert_eq!(body, vec![3, 2, 1]);
+    }
+
+    #[tokio::test]
+    async fn h3_get_exchange() {
+        let endpoint = Endpoint {
+            addr: serve_h3(StatusCode::OK, DNS_MESSAGE),
+            server_name: "doh.example",
+            path: "/resolve",
+        };
+
+        let (http, body) = client(HttpVersion::Http3)
+            .exchange(endpoint, Method::Get, vec![1, 2, 3])
+            .await
+            .unwrap();
+
+        assert_eq!(http.headers["x-method"], "GET");
+        assert_eq!(body, vec![3, 2, 1]);
+    }
+
+    #[tokio::test]
+    async fn h3_error_status() {
+        let endpoint = Endpoint {
+            addr: serve_h3(StatusCode::SERVICE_UNAVAILABLE, DNS_MESSAGE),
+            server_name: "doh.example",
+            path: "/dns-query",
+        };
+
+        let result = client(HttpVersion::Http3)
+            .exchange(endpoint, Method::Post, vec![1, 2, 3])
+            .await;
+
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "HTTP request failed with status 503 Service Unavailable"
+        );
+    }
+}
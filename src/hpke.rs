@@ -0,0 +1,596 @@
+/*
+ * dog - A command-line DNS client
+ * Copyright (c) 2026 l1a and contributors
+ * Original code Copyright (c) Benjamin Sago
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! The parts of Hybrid Public Key Encryption, as described in RFC 9180,
+//! that Oblivious DNS-over-HTTPS needs.
+//!
+//! Only the base mode is supported, with the one cipher suite that every
+//! Oblivious DNS-over-HTTPS target has to support: X25519 to agree on a
+//! shared secret, HKDF-SHA256 to derive keys from it, and AES-128-GCM to
+//! encrypt.
+
+use std::convert::TryFrom;
+use std::fmt;
+
+use curve25519_dalek::montgomery::MontgomeryPoint;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_128_GCM};
+use ring::hkdf::{self, Prk, HKDF_SHA256};
+use ring::hmac::{self, HMAC_SHA256};
+use ring::rand::{SecureRandom, SystemRandom};
+
+/// The identifier of the DHKEM(X25519, HKDF-SHA256) key encapsulation
+/// mechanism.
+pub const KEM_X25519: u16 = 0x0020;
+
+/// The identifier of the HKDF-SHA256 key derivation function.
+pub const KDF_SHA256: u16 = 0x0001;
+
+/// The identifier of the AES-128-GCM encryption algorithm.
+pub const AEAD_AES_128_GCM: u16 = 0x0001;
+
+/// The length of a key for the encryption algorithm.
+pub const KEY_LENGTH: usize = 16;
+
+/// The length of a nonce for the encryption algorithm.
+pub const NONCE_LENGTH: usize = 12;
+
+/// The length of an X25519 key, and of the output of the key derivation
+/// function.
+pub const SECRET_LENGTH: usize = 32;
+
+/// An error that stops a message being encrypted or decrypted.
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum HpkeError {
+    /// The public key is one that gives a shared secret of zero, which
+    /// would let anyone read the message.
+    InvalidPublicKey,
+
+    /// A random key couldn’t be generated.
+    Random,
+
+    /// The message couldn’t be decrypted, because it was encrypted with a
+    /// different key, or changed along the way.
+    Decryption,
+}
+
+impl fmt::Display for HpkeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidPublicKey => write!(f, "Invalid public key"),
+            Self::Random => write!(f, "Failed to generate a random key"),
+            Self::Decryption => write!(f, "Failed to decrypt message"),
+        }
+    }
+}
+
+/// An HPKE **context**, shared by the sender and the recipient of a
+/// message, which holds the keys to encrypt and decrypt messages between
+/// them, and to export other secrets.
+#[derive(Debug)]
+pub struct Context {
+    /// The key to encrypt messages with.
+    key: [u8; KEY_LENGTH],
+
+    /// The nonce to encrypt the first message with, which gets combined
+    /// with the sequence number for the ones after it.
+    base_nonce: [u8; NONCE_LENGTH],
+
+    /// The secret that other secrets get exported from.
+    exporter_secret: [u8; SECRET_LENGTH],
+
+    /// How many messages have been encrypted or decrypted.
+    sequence: u64,
+}
+
+impl Context {
+    /// Encrypts the next message to the recipient.
+    pub fn seal(&mut self, aad: &[u8], plaintext: &[u8]) -> Vec<u8> {
+        let ciphertext = seal(&self.key, &self.next_nonce(), aad, plaintext);
+        self.sequence += 1;
+        ciphertext
+    }
+
+    /// Decrypts the next message from the sender.
+    #[cfg(test)]
+    pub fn open(&mut self, aad: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, HpkeError> {
+        let plaintext = open(&self.key, &self.next_nonce(), aad, ciphertext)?;
+        self.sequence += 1;
+        Ok(plaintext)
+    }
+
+    /// Fills the output with a secret derived from the context, which both
+    /// sides can work out, using the given context string.
+    pub fn export(&self, exporter_context: &[u8], out: &mut [u8]) {
+        labeled_expand(
+            &hpke_suite_id(),
+            &self.exporter_secret,
+            b"sec",
+            exporter_context,
+            out,
+        );
+    }
+
+    /// The nonce for the next message, which is the base nonce combined
+    /// with the sequence number.
+    fn next_nonce(&self) -> [u8; NONCE_LENGTH] {
+        let mut nonce = self.base_nonce;
+        let sequence = self.sequence.to_be_bytes();
+        for (byte, seq) in nonce[NONCE_LENGTH - sequence.len()..]
+            .iter_mut()
+            .zip(sequence)
+        {
+            *byte ^= seq;
+        }
+        nonce
+    }
+}
+
+/// Sets up a context for sending messages to the owner of the given
+/// public key, returning the encapsulated key that has to be sent along
+/// with them, so the recipient can set up the same context.
+pub fn setup_sender(
+    public_key: &[u8; SECRET_LENGTH],
+    info: &[u8],
+) -> Result<([u8; SECRET_LENGTH], Context), HpkeError> {
+    let mut ephemeral_key = [0; SECRET_LENGTH];
+    SystemRandom::new()
+        .fill(&mut ephemeral_key)
+        .map_err(|_| HpkeError::Random)?;
+
+    setup_sender_with(public_key, info, ephemeral_key)
+}
+
+/// Sets up a context for sending messages to the owner of the given
+/// public key, using the given ephemeral private key.
+fn setup_sender_with(
+    public_key: &[u8; SECRET_LENGTH],
+    info: &[u8],
+    ephemeral_key: [u8; SECRET_LENGTH],
+) -> Result<([u8; SECRET_LENGTH], Context), HpkeError> {
+    let encapsulated = public_key_for(ephemeral_key);
+    let dh = diffie_hellman(ephemeral_key, public_key)?;
+    let shared_secret = extract_and_expand(&dh, &encapsulated, public_key);
+
+    Ok((encapsulated, key_schedule(&shared_secret, info)))
+}
+
+/// Sets up a context for receiving messages from the sender of the given
+/// encapsulated key, using the recipient’s private key.
+#[cfg(test)]
+pub fn setup_receiver(
+    encapsulated: &[u8; SECRET_LENGTH],
+    private_key: [u8; SECRET_LENGTH],
+    info: &[u8],
+) -> Result<Context, HpkeError> {
+    let dh = diffie_hellman(private_key, encapsulated)?;
+    let shared_secret = extract_and_expand(&dh, encapsulated, &public_key_for(private_key));
+
+    Ok(key_schedule(&shared_secret, info))
+}
+
+/// The X25519 public key for a private key.
+pub fn public_key_for(private_key: [u8; SECRET_LENGTH]) -> [u8; SECRET_LENGTH] {
+    MontgomeryPoint::mul_base_clamped(private_key).to_bytes()
+}
+
+/// Works out the X25519 shared secret between a private key and someone
+/// else’s public key.
+fn diffie_hellman(
+    private_key: [u8; SECRET_LENGTH],
+    public_key: &[u8; SECRET_LENGTH],
+) -> Result<[u8; SECRET_LENGTH], HpkeError> {
+    let dh = MontgomeryPoint(*public_key)
+        .mul_clamped(private_key)
+        .to_bytes();
+
+    if dh == [0; SECRET_LENGTH] {
+        Err(HpkeError::InvalidPublicKey)
+    } else {
+        Ok(dh)
+    }
+}
+
+/// Derives the shared secret from the result of the key exchange, and the
+/// public keys of both sides.
+fn extract_and_expand(
+    dh: &[u8],
+    encapsulated: &[u8; SECRET_LENGTH],
+    public_key: &[u8; SECRET_LENGTH],
+) -> [u8; SECRET_LENGTH] {
+    let suite_id = [&b"KEM"[..], &KEM_X25519.to_be_bytes()].concat();
+    let kem_context = [&encapsulated[..], &public_key[..]].concat();
+
+    let eae_prk = labeled_extract(&suite_id, b"", b"eae_prk", dh);
+    let mut shared_secret = [0; SECRET_LENGTH];
+    labeled_expand(
+        &suite_id,
+        &eae_prk,
+        b"shared_secret",
+        &kem_context,
+        &mut shared_secret,
+    );
+    shared_secret
+}
+
+/// Derives the keys of a context from the shared secret, in the base mode,
+/// without a pre-shared key.
+fn key_schedule(shared_secret: &[u8], info: &[u8]) -> Context {
+    let suite_id = hpke_suite_id();
+
+    let psk_id_hash = labeled_extract(&suite_id, b"", b"psk_id_hash", b"");
+    let info_hash = labeled_extract(&suite_id, b"", b"info_hash", info);
+    let key_schedule_context = [&[0][..], &psk_id_hash, &info_hash].concat();
+
+    let secret = labeled_extract(&suite_id, shared_secret, b"secret", b"");
+
+    let mut context = Context {
+        key: [0; KEY_LENGTH],
+        base_nonce: [0; NONCE_LENGTH],
+        exporter_secret: [0; SECRET_LENGTH],
+        sequence: 0,
+    };
+    labeled_expand(
+        &suite_id,
+        &secret,
+        b"key",
+        &key_schedule_context,
+        &mut context.key,
+    );
+    labeled_expand(
+        &suite_id,
+        &secret,
+        b"base_nonce",
+        &key_schedule_context,
+        &mut context.base_nonce,
+    );
+    labeled_expand(
+        &suite_id,
+        &secret,
+        b"exp",
+        &key_schedule_context,
+        &mut context.exporter_secret,
+    );
+    context
+}
+
+/// The identifier of the whole cipher suite, which goes in every label.
+fn hpke_suite_id() -> Vec<u8> {
+    [
+        &b"HPKE"[..],
+        &KEM_X25519.to_be_bytes(),
+        &KDF_SHA256.to_be_bytes(),
+        &AEAD_AES_128_GCM.to_be_bytes(),
+    ]
+    .concat()
+}
+
+/// Extracts a pseudorandom key from the input, with a label for what it’s
+/// going to be used for.
+fn labeled_extract(suite_id: &[u8], salt: &[u8], label: &[u8], ikm: &[u8]) -> [u8; SECRET_LENGTH] {
+    extract(salt, &[&b"HPKE-v1"[..], suite_id, label, ikm].concat())
+}
+
+/// Fills the output with key material expanded from a pseudorandom key,
+/// with a label for what it’s going to be used for.
+fn labeled_expand(suite_id: &[u8], prk: &[u8], label: &[u8], info: &[u8], out: &mut [u8]) {
+    let length = u16::try_from(out.len()).expect("Output too long");
+    let info = [&length.to_be_bytes()[..], b"HPKE-v1", suite_id, label, info].concat();
+    expand(prk, &info, out);
+}
+
+/// The HKDF-Extract function, which turns the input into a pseudorandom
+/// key using the salt.
+pub fn extract(salt: &[u8], ikm: &[u8]) -> [u8; SECRET_LENGTH] {
+    let tag = hmac::sign(&hmac::Key::new(HMAC_SHA256, salt), ikm);
+    let mut prk = [0; SECRET_LENGTH];
+    prk.copy_from_slice(tag.as_ref());
+    prk
+}
+
+/// The HKDF-Expand function, which fills the output with key material
+/// derived from a pseudorandom key.
+pub fn expand(prk: &[u8], info: &[u8], out: &mut [u8]) {
+    Prk::new_less_safe(HKDF_SHA256, prk)
+        .expand(&[info], OutputLength(out.len()))
+        .and_then(|okm| okm.fill(out))
+        .expect("Output too long");
+}
+
+/// The number of bytes to expand a key into.
+struct OutputLength(usize);
+
+impl hkdf::KeyType for OutputLength {
+    fn len(&self) -> usize {
+        self.0
+    }
+}
+
+/// Encrypts a message with AES-128-GCM, returning the ciphertext with the
+/// tag on the end.
+pub fn seal(
+    key: &[u8; KEY_LENGTH],
+    nonce: &[u8; NONCE_LENGTH],
+    aad: &[u8],
+    plaintext: &[u8],
+) -> Vec<u8> {
+    let key = LessSafeKey::new(UnboundKey::new(&AES_128_GCM, key).expect("Invalid key"));
+    let mut data = plaintext.to_vec();
+    key.seal_in_place_append_tag(
+        Nonce::assume_unique_for_key(*nonce),
+        Aad::from(aad),
+        &mut data,
+    )
+    .expect("Message too long");
+    data
+}
+
+/// Decrypts a message encrypted with AES-128-GCM, checking its tag.
+pub fn open(
+    key: &[u8; KEY_LENGTH],
+    nonce: &[u8; NONCE_LENGTH],
+    aad: &[u8],
+    ciphertext: &[u8],
+) -> Result<Vec<u8>, HpkeError> {
+    let key = LessSafeKey::new(UnboundKey::new(&AES_128_GCM, key).expect("Invalid key"));
+    let mut data = ciphertext.to_vec();
+    let length = key
+        .open_in_place(
+            Nonce::assume_unique_for_key(*nonce),
+            Aad::from(aad),
+            &mut data,
+        )
+        .map_err(|_| HpkeError::Decryption)?
+        .len();
+    data.truncate(length);
+    Ok(data)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use data_encoding::HEXLOWER;
+    use pretty_assertions::assert_eq;
+
+    fn hex(input: &str) -> Vec<u8> {
+        HEXLOWER.decode(input.as_bytes()).unwrap()
+    }
+
+    fn key(input: &str) -> [u8; SECRET_LENGTH] {
+        <[u8; SECRET_LENGTH]>::try_from(hex(input)).unwrap()
+    }
+
+    // The test vector for this cipher suite in the base mode, from
+    // appendix A.1.1 of RFC 9180
+
+    const IKM_EPHEMERAL: &str = "7268600d403fce431561aef583ee1613527cff655c1343f29812e66706df3234";
+    const IKM_RECIPIENT: &str = "6db9df30aa07dd42ee5e8181afdb977e538f5e1fec8a06223f33f7013e525037";
+    const EPHEMERAL_KEY: &str = "52c4a758a802cd8b936eceea314432798d5baf2d7e9235dc084ab1b9cfa2f736";
+    const PRIVATE_KEY: &str = "4612c550263fc8ad58375df3f557aac531d26850903e55a9f23f21d8534e8ac8";
+    const PUBLIC_KEY: &str = "3948cfe0ad1ddb695d780e59077195da6c56506b027329794ab02bca80815c4d";
+    const ENCAPSULATED: &str = "37fda3567bdbd628e88668c3c8d7e97d1d1253b6d4ea6d44c150f741f1bf4431";
+    const SHARED_SECRET: &str = "fe0e18c9f024ce43799ae393c7e8fe8fce9d218875e8227b0187c04e7d2ea1fc";
+    const INFO: &[u8] = b"Ode on a Grecian Urn";
+    const PLAINTEXT: &[u8] = b"Beauty is truth, truth beauty";
+
+    /// The sequence numbers the messages in the test vector get encrypted
+    /// with, and their ciphertexts, split before the tag.
+    const MESSAGES: &[(u64, &str)] = &[
+        (
+            0,
+            concat!(
+                "f938558b5d72f1a23810b4be2ab4f84331acc02fc97babc53a52ae8218",
+                "a355a96d8770ac83d07bea87e13c512a",
+            ),
+        ),
+        (
+            1,
+            concat!(
+                "af2d7e9ac9ae7e270f46ba1f975be53c09f8d875bdc8535458c2494e8a",
+                "6eab251c03d0c22a56b8ca42c2063b84",
+            ),
+        ),
+        (
+            2,
+            concat!(
+                "498dfcabd92e8acedc281e85af1cb4e3e31c7dc394a1ca20e173cb7251",
+                "6491588d96a19ad4a683518973dcc180",
+            ),
+        ),
+        (
+            4,
+            concat!(
+                "583bd32bc67a5994bb8ceaca813d369bca7b2a42408cddef5e22f880b6",
+                "31215a09fc0012bc69fccaa251c0246d",
+            ),
+        ),
+        (
+            255,
+            concat!(
+                "7175db9717964058640a3a11fb9007941a5d1757fda1a6935c805c21af",
+                "32505bf106deefec4a49ac38d71c9e0a",
+            ),
+        ),
+        (
+            256,
+            concat!(
+                "957f9800542b0b8891badb026d79cc54597cb2d225b54c00c5238c25d0",
+                "5c30e3fbeda97d2e0e1aba483a2df9f2",
+            ),
+        ),
+    ];
+
+    /// The exporter contexts in the test vector, and the 32-byte secrets
+    /// exported with them.
+    const EXPORTS: &[(&[u8], &str)] = &[
+        (
+            b"",
+            "3853fe2b4035195a573ffc53856e77058e15d9ea064de3e59f4961d0095250ee",
+        ),
+        (
+            b"\0",
+            "2e8f0b54673c7029649d4eb9d5e33bf1872cf76d623ff164ac185da9e88c21a5",
+        ),
+        (
+            b"TestContext",
+            "e9e43065102c3836401bed8c3c3c75ae46be1639869391d62c61f1ec7af54931",
+        ),
+    ];
+
+    /// The `DeriveKeyPair` function of the key encapsulation mechanism,
+    /// which dog has no use for, but the test vector’s keys come from.
+    fn derive_private_key(ikm: &str) -> [u8; SECRET_LENGTH] {
+        let suite_id = [&b"KEM"[..], &KEM_X25519.to_be_bytes()].concat();
+        let dkp_prk = labeled_extract(&suite_id, b"", b"dkp_prk", &hex(ikm));
+        let mut private_key = [0; SECRET_LENGTH];
+        labeled_expand(&suite_id, &dkp_prk, b"sk", b"", &mut private_key);
+        private_key
+    }
+
+    fn receiver() -> Context {
+        setup_receiver(&key(ENCAPSULATED), key(PRIVATE_KEY), INFO).unwrap()
+    }
+
+    fn aad(sequence: u64) -> Vec<u8> {
+        format!("Count-{sequence}").into_bytes()
+    }
+
+    #[test]
+    fn derive_keys() {
+        assert_eq!(derive_private_key(IKM_EPHEMERAL), key(EPHEMERAL_KEY));
+        assert_eq!(derive_private_key(IKM_RECIPIENT), key(PRIVATE_KEY));
+        assert_eq!(public_key_for(key(EPHEMERAL_KEY)), key(ENCAPSULATED));
+        assert_eq!(public_key_for(key(PRIVATE_KEY)), key(PUBLIC_KEY));
+    }
+
+    #[test]
+    fn shared_secret() {
+        let dh = diffie_hellman(key(EPHEMERAL_KEY), &key(PUBLIC_KEY)).unwrap();
+        let shared_secret = extract_and_expand(&dh, &key(ENCAPSULATED), &key(PUBLIC_KEY));
+
+        assert_eq!(shared_secret, key(SHARED_SECRET));
+    }
+
+    #[test]
+    fn sender() {
+        let (encapsulated, mut context) =
+            setup_sender_with(&key(PUBLIC_KEY), INFO, key(EPHEMERAL_KEY)).unwrap();
+
+        assert_eq!(encapsulated, key(ENCAPSULATED));
+        assert_eq!(context.seal(&aad(0), PLAINTEXT), hex(MESSAGES[0].1));
+    }
+
+    #[test]
+    fn key_schedule() {
+        let context = receiver();
+
+        assert_eq!(
+            context.key.to_vec(),
+            hex("4531685d41d65f03dc48f6b8302c05b0")
+        );
+        assert_eq!(context.base_nonce.to_vec(), hex("56d890e5accaaf011cff4b7d"));
+        assert_eq!(
+            context.exporter_secret.to_vec(),
+            hex("45ff1c2e220db587171952c0592d5f5ebe103f1561a2614e38f2ffd47e99e3f8")
+        );
+    }
+
+    #[test]
+    fn seal_messages() {
+        for &(sequence, ciphertext) in MESSAGES {
+            let mut context = Context {
+                sequence,
+                ..receiver()
+            };
+            assert_eq!(
+                context.seal(&aad(sequence), PLAINTEXT),
+                hex(ciphertext),
+                "sequence {sequence}"
+            );
+        }
+    }
+
+    #[test]
+    fn open_messages() {
+        for &(sequence, ciphertext) in MESSAGES {
+            let mut context = Context {
+                sequence,
+                ..receiver()
+            };
+            assert_eq!(
+                context.open(&aad(sequence), &hex(ciphertext)),
+                Ok(PLAINTEXT.to_vec()),
+                "sequence {sequence}"
+            );
+        }
+    }
+
+    #[test]
+    fn nonce_changes_each_message() {
+        let mut context = receiver();
+
+        let first = context.seal(&aad(0), PLAINTEXT);
+        let second = context.seal(&aad(0), PLAINTEXT);
+
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn export() {
+        for &(exporter_context, exported) in EXPORTS {
+            let mut secret = [0; 32];
+            receiver().export(exporter_context, &mut secret);
+            assert_eq!(secret.to_vec(), hex(exported));
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let private_key = [7; SECRET_LENGTH];
+        let (encapsulated, mut sender) =
+            setup_sender(&public_key_for(private_key), b"info").unwrap();
+        let mut receiver = setup_receiver(&encapsulated, private_key, b"info").unwrap();
+
+        let ciphertext = sender.seal(b"aad", b"message");
+
+        assert_eq!(receiver.open(b"aad", &ciphertext), Ok(b"message".to_vec()));
+    }
+
+    #[test]
+    fn wrong_aad() {
+        let private_key = [7; SECRET_LENGTH];
+        let (encapsulated, mut sender) =
+            setup_sender(&public_key_for(private_key), b"info").unwrap();
+        let mut receiver = setup_receiver(&encapsulated, private_key, b"info").unwrap();
+
+        let ciphertext = sender.seal(b"aad", b"message");
+
+        assert_eq!(
+            receiver.open(b"other", &ciphertext),
+            Err(HpkeError::Decryption)
+        );
+    }
+
+    #[test]
+    fn zero_public_key() {
+        assert_eq!(
+            setup_sender(&[0; SECRET_LENGTH], b"info").unwrap_err(),
+            HpkeError::InvalidPublicKey
+        );
+    }
+}
@@ -38,13 +38,18 @@
 #![allow(clippy::wildcard_imports)]
 #![deny(unsafe_code)]
 
-use hickory_resolver::config::{NameServerConfig, ResolverConfig, ResolverOpts};
+use hickory_resolver::config::{ConnectionConfig, ResolverConfig, ResolverOpts};
+use hickory_resolver::net::{DnsError, NetError};
+use hickory_resolver::proto::op::{DnsResponse, Message, ResponseCode};
 use hickory_resolver::TokioResolver;
 use log::*;
 
 use crate::options::ANY_FALLBACK_TYPES;
-use hickory_resolver::proto::rr::RecordType;
-use std::collections::HashSet;
+use crate::output::{OutputFormat, QueryError, QueryResult};
+use crate::schedule::{Scheduler, Sent};
+use crate::transport::Transport;
+use crate::tsig::TsigKey;
+use hickory_resolver::proto::rr::{DNSClass, RecordType};
 use std::fs;
 #[allow(unused_imports)]
 use std::net::{IpAddr, SocketAddr};
@@ -52,10 +57,26 @@ use std::net::{IpAddr, SocketAddr};
 // Windows-specific import for retrieving system DNS servers via ipconfig
 
 mod colours;
+mod dnscrypt;
+mod doh;
 mod hints;
+mod hpke;
 mod logger;
+mod multicast;
+mod odoh;
 mod output;
+mod pinning;
+mod request;
+mod resolv;
+mod resolvers;
+mod schedule;
+mod stamp;
 mod table;
+mod trace;
+mod transfer;
+mod transport;
+mod tsig;
+mod validate;
 
 mod options;
 use self::options::*;
@@ -70,7 +91,23 @@ async fn main() {
 
     logger::configure(env::var_os("DOG_DEBUG"));
 
-    match Options::getopts(env::args_os().skip(1)) {
+    let system = match resolv::ResolvConf::load() {
+        Ok(system) => system,
+        Err(e) => {
+            warn!("Error loading {}: {e}", resolv::RESOLV_CONF_PATH);
+            resolv::ResolvConf::default()
+        }
+    };
+
+    let resolvers = match options::load_user_resolvers() {
+        Ok(resolvers) => resolvers,
+        Err(e) => {
+            eprintln!("dog: Invalid options: {e}");
+            exit(exits::OPTIONS_ERROR);
+        }
+    };
+
+    match Options::getopts_with(env::args_os().skip(1), system, resolvers) {
         OptionsResult::Ok(options) => {
             info!("Running with options -> {options:#?}");
             exit(run(options).await);
@@ -116,6 +153,16 @@ async fn main() {
             exit(exits::SUCCESS);
         }
 
+        OptionsResult::ListResolvers(registry) => {
+            let listed = registry
+                .resolvers()
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>();
+            print!("{}", listed.join("\n"));
+            exit(exits::SUCCESS);
+        }
+
         OptionsResult::Completions(shell) => {
             use clap_complete::{generate, Shell};
             use clap_complete_nushell::Nushell;
@@ -171,14 +218,11 @@ async fn run(
         verbose,
     }: Options,
 ) -> i32 {
-    use std::net::IpAddr;
     use std::time::Instant;
 
-    let mut responses = Vec::new();
+    let mut results = Vec::new();
     let timer = if verbose { Some(Instant::now()) } else { None };
 
-    let mut errored = false;
-
     let local_host_hints = match hints::LocalHosts::load() {
         Ok(lh) => lh,
         Err(e) => {
@@ -193,294 +237,1342 @@ async fn run(
         }
     }
 
-    // Load DNS resolver configuration: use system defaults if no custom nameservers provided
-    let config = if requests.inputs.nameservers.is_empty() {
-        match requests.inputs.transport_type {
-            Some(TransportType::TLS) => ResolverConfig::tls(&hickory_resolver::config::CLOUDFLARE),
-            Some(TransportType::HTTPS) => ResolverConfig::https(&hickory_resolver::config::GOOGLE),
-            _ => {
-                // Cross-platform loading of system DNS servers for UDP/TCP transport
-                let nameservers: Vec<IpAddr> = if cfg!(target_os = "windows") {
-                    #[cfg(windows)]
-                    {
-                        // On Windows, use ipconfig to retrieve DNS servers from network adapters
-                        ipconfig::get_adapters()
-                            .unwrap_or_default()
-                            .into_iter()
-                            .flat_map(|adapter| adapter.dns_servers().to_vec())
-                            .collect()
-                    }
-                    #[cfg(not(windows))]
-                    {
-                        vec![]
-                    }
-                } else {
-                    // On Unix/Linux, parse /etc/resolv.conf for DNS server entries
-                    match fs::read_to_string("/etc/resolv.conf") {
-                        Ok(content) => content
-                            .lines()
-                            .filter_map(|line| {
-                                let line = line.trim();
-                                line.strip_prefix("nameserver ")?
-                                    .trim()
-                                    .parse::<IpAddr>()
-                                    .ok()
-                            })
-                            .collect(),
-                        Err(_) => vec![],
-                    }
-                };
-                let mut config = ResolverConfig::from_parts(None, vec![], vec![]);
-                for ns in nameservers {
-                    use hickory_resolver::config::ConnectionConfig;
-                    let ns_config = NameServerConfig::new(ns, true, vec![ConnectionConfig::udp()]);
-                    config.add_name_server(ns_config);
-                }
-                if config.name_servers().is_empty() {
-                    ResolverConfig::udp_and_tcp(&hickory_resolver::config::GOOGLE)
-                } else {
-                    config
-                }
+    if let Some(hints) = &requests.trace {
+        return run_trace(&requests, hints, format, timer).await;
+    }
+
+    // Names that weren’t taken for multicast, such as the ones with more
+    // than one label when using LLMNR, still get sent to the nameservers
+    // afterwards. A batch file does the same for each of its lines, so it
+    // runs the command line’s multicast queries itself.
+    let mut outcome = Outcome::default();
+    let mut multicast_printed = false;
+    if let Some(multicast) = &requests.multicast {
+        if requests.inputs.batch_file.is_none() {
+            if requests.inputs.domains.is_empty() {
+                let printed =
+                    run_multicast(&requests, multicast, format, verbose, timer, &mut outcome).await;
+                return outcome.exit_code(printed);
+            }
+
+            if !multicast.domains.is_empty() {
+                multicast_printed =
+                    run_multicast(&requests, multicast, format, verbose, None, &mut outcome).await;
             }
         }
+    }
+
+    if verbose && requests.search && requests.system != resolv::ResolvConf::default() {
+        println!("System resolver configuration: {}", requests.system);
+    }
+
+    let destination = match Destination::new(&requests, &requests.inputs).await {
+        Ok(destination) => destination,
+        Err(e) => {
+            eprintln!("{e}");
+            return exits::OPTIONS_ERROR;
+        }
+    };
+
+    let scheduler = Scheduler::new(requests.limits, requests.attempts.tries);
+
+    if let Some(path) = &requests.inputs.batch_file {
+        return run_batch(
+            &requests,
+            path,
+            destination,
+            &scheduler,
+            format,
+            verbose,
+            timer,
+        )
+        .await;
+    }
+
+    for lookup in lookup_all(&requests, &requests.inputs, &destination, &scheduler).await {
+        let Some(query_result) = check_lookup(
+            lookup,
+            &requests,
+            &requests.inputs,
+            &destination,
+            verbose,
+            &mut outcome,
+        )
+        .await
+        else {
+            continue;
+        };
+
+        if verbose {
+            format.print(vec![query_result], None);
+        } else {
+            results.push(query_result);
+        }
+    }
+
+    if verbose {
+        let duration = timer.map(|t| t.elapsed());
+        if let Some(duration) = duration {
+            print_counters(&scheduler);
+            let duration_ms = duration.as_secs_f64() * 1000.0;
+            println!("Ran in {duration_ms:.2}ms");
+        }
+
+        outcome.exit_code(true)
     } else {
-        let mut config = ResolverConfig::from_parts(None, vec![], vec![]);
-        for ns_str in &requests.inputs.nameservers {
-            use hickory_resolver::config::ConnectionConfig;
-            use std::sync::Arc;
-
-            if let Some(transport) = requests.inputs.transport_type {
-                match (ns_str.as_str(), transport) {
-                    ("google", TransportType::HTTPS) => {
-                        config = ResolverConfig::https(&hickory_resolver::config::GOOGLE);
-                        continue;
-                    }
-                    ("cloudflare", TransportType::HTTPS) => {
-                        config = ResolverConfig::https(&hickory_resolver::config::CLOUDFLARE);
-                        continue;
-                    }
-                    ("cloudflare" | "one.one.one.one", TransportType::TLS) => {
-                        config = ResolverConfig::tls(&hickory_resolver::config::CLOUDFLARE);
-                        continue;
-                    }
-                    _ => {}
-                }
+        let duration = timer.map(|t| t.elapsed());
+        let printed = format.print(results, duration);
+        outcome.exit_code(printed || multicast_printed)
+    }
+}
+
+/// Runs the queries read from a file, or from standard input if the path is
+/// `-`, one line at a time. The results of each line get printed as soon as
+/// they come back, instead of waiting for the whole file to be read, so dog
+/// can sit at the end of a pipeline. Any domains given on the command line
+/// get queried first. When querying over multicast, the domains on each
+/// line get split between the multicast groups and the nameservers the same
+/// way as the ones on the command line.
+///
+/// Lines are read on a separate thread, so waiting for the next one doesn’t
+/// hold up the queries already sent. As many lines as queries that can be
+/// sent at once get worked on together, and their results are printed in
+/// the order of the lines.
+///
+/// # Arguments
+///
+/// * `requests` - The settings to send every query with.
+/// * `path` - The file to read the queries from.
+/// * `destination` - Where to send the queries that don’t pick their own
+///   nameservers.
+/// * `scheduler` - When to send each query.
+/// * `format` - How to print the results.
+/// * `verbose` - Whether to print the details of each query.
+/// * `timer` - When the program started, if the duration should be printed.
+///
+/// # Returns
+///
+/// * The process exit code.
+async fn run_batch<'a>(
+    requests: &'a Requests,
+    path: &str,
+    destination: Destination<'a>,
+    scheduler: &Scheduler,
+    format: OutputFormat,
+    verbose: bool,
+    timer: Option<std::time::Instant>,
+) -> i32 {
+    use futures::stream::{self, StreamExt};
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::io::{self, BufRead, BufReader};
+    use std::rc::Rc;
+    use tokio::sync::mpsc;
+
+    let reader: Box<dyn BufRead + Send> = if path == "-" {
+        Box::new(BufReader::new(io::stdin()))
+    } else {
+        match fs::File::open(path) {
+            Ok(file) => Box::new(BufReader::new(file)),
+            Err(e) => {
+                eprintln!("dog: Cannot read batch file {path:?}: {e}");
+                return exits::OPTIONS_ERROR;
             }
+        }
+    };
 
-            let is_tls = matches!(
-                requests.inputs.transport_type,
-                Some(TransportType::TLS | TransportType::HTTPS)
-            );
-
-            let mut tls_dns_name: Option<String> = None;
-
-            let ip_addr: IpAddr = if let Ok(ip) = ns_str.parse::<IpAddr>() {
-                if is_tls {
-                    tls_dns_name = if ns_str == "8.8.8.8"
-                        || ns_str == "8.8.4.4"
-                        || ns_str == "2001:4860:4860::8888"
-                        || ns_str == "2001:4860:4860::8844"
-                    {
-                        Some("dns.google".to_string())
-                    } else if ns_str == "1.1.1.1"
-                        || ns_str == "1.0.0.1"
-                        || ns_str == "2606:4700:4700::1111"
-                        || ns_str == "2606:4700:4700::1001"
-                    {
-                        Some("cloudflare-dns.com".to_string())
-                    } else {
-                        Some(ns_str.clone())
-                    };
-                }
-                ip
+    let (sender, mut receiver) = mpsc::channel(requests.limits.concurrency);
+    std::thread::spawn(move || {
+        for (index, line) in reader.lines().enumerate() {
+            let failed = line.is_err();
+            if sender.blocking_send((index + 1, line)).is_err() || failed {
+                break;
+            }
+        }
+    });
+
+    // Lines that pick their own nameservers get their transports looked up
+    // once, and then kept for any other lines that pick the same ones
+    let destinations = RefCell::new(HashMap::new());
+    destinations
+        .borrow_mut()
+        .insert(requests.inputs.nameservers.clone(), Rc::new(destination));
+
+    let mut outcome = Outcome::default();
+    let mut printed = false;
+
+    if let Some(multicast) = &requests.multicast {
+        if !multicast.domains.is_empty() || !multicast.services.is_empty() {
+            printed = run_multicast(requests, multicast, format, verbose, None, &mut outcome).await;
+        }
+    }
+
+    let command_line =
+        Some(Ok(requests.inputs.clone())).filter(|_| !requests.inputs.domains.is_empty());
+
+    let from_file =
+        stream::poll_fn(move |cx| receiver.poll_recv(cx)).filter_map(|(number, line)| async move {
+            match line {
+                Ok(line) => requests
+                    .inputs
+                    .for_line(&line)
+                    .map_err(|e| format!("dog: Invalid query on line {number}: {e}"))
+                    .transpose(),
+                Err(e) => Some(Err(format!("dog: Cannot read batch file {path:?}: {e}"))),
+            }
+        });
+
+    let mut lines = std::pin::pin!(stream::iter(command_line)
+        .chain(from_file)
+        .map(|inputs| send_line(requests, inputs, &destinations, scheduler))
+        .buffered(requests.limits.concurrency));
+
+    while let Some(line) = lines.next().await {
+        let line = match line {
+            Ok(line) => line,
+            Err(message) => {
+                eprintln!("{message}");
+                outcome.errored = true;
+                continue;
+            }
+        };
+
+        if !line.collections.is_empty() {
+            printed |= print_collections(format, &line.collections, None, &mut outcome);
+        }
+
+        for lookup in line.lookups {
+            let query_result = check_lookup(
+                lookup,
+                requests,
+                &line.inputs,
+                &line.destination,
+                verbose,
+                &mut outcome,
+            )
+            .await;
+
+            if let Some(query_result) = query_result {
+                printed |= format.print_streamed(&query_result);
+            }
+        }
+    }
+
+    if let Some(duration) = timer.map(|t| t.elapsed()) {
+        print_counters(scheduler);
+        let duration_ms = duration.as_secs_f64() * 1000.0;
+        println!("Ran in {duration_ms:.2}ms");
+    }
+
+    if !printed {
+        eprintln!("No results");
+    }
+
+    outcome.exit_code(printed)
+}
+
+/// The queries from one line of a batch file, once they’ve been sent.
+struct SentLine<'a> {
+    /// The queries the line asked for.
+    inputs: Inputs,
+
+    /// Where the queries were sent.
+    destination: std::rc::Rc<Destination<'a>>,
+
+    /// The queries that were sent, and what came back.
+    lookups: Vec<Lookup>,
+
+    /// The queries that were sent over multicast instead, and the
+    /// responses that came back.
+    collections: Vec<multicast::Collection>,
+}
+
+/// Sends the queries from one line of a batch file, working out where to
+/// send them first if the line picks nameservers that no line before it
+/// has. Any of its domains that are meant for multicast get sent there
+/// instead. Returns a message to print if the line couldn’t be understood,
+/// or its nameservers couldn’t be resolved.
+async fn send_line<'a>(
+    requests: &'a Requests,
+    inputs: Result<Inputs, String>,
+    destinations: &std::cell::RefCell<
+        std::collections::HashMap<Vec<options::Nameserver>, std::rc::Rc<Destination<'a>>>,
+    >,
+    scheduler: &Scheduler,
+) -> Result<SentLine<'a>, String> {
+    use std::rc::Rc;
+
+    let mut inputs = inputs?;
+
+    let collections = match &requests.multicast {
+        Some(multicast) => {
+            let domains = multicast.take_domains(&mut inputs);
+            collect_multicast(requests, multicast, &inputs, &domains, &[]).await
+        }
+        None => Vec::new(),
+    };
+
+    let known = destinations.borrow().get(&inputs.nameservers).cloned();
+    let destination = if let Some(destination) = known {
+        destination
+    } else {
+        let destination = Rc::new(Destination::new(requests, &inputs).await?);
+        destinations
+            .borrow_mut()
+            .insert(inputs.nameservers.clone(), Rc::clone(&destination));
+        destination
+    };
+
+    let lookups = lookup_all(requests, &inputs, &destination, scheduler).await;
+    Ok(SentLine {
+        inputs,
+        destination,
+        lookups,
+        collections,
+    })
+}
+
+/// Prints how many queries had to be sent more than once, and how many
+/// were given up on after every retry failed.
+fn print_counters(scheduler: &Scheduler) {
+    println!(
+        "Queries retried: {}, dropped: {}",
+        scheduler.retried(),
+        scheduler.dropped()
+    );
+}
+
+/// What went wrong with the queries that have been run, which decides the
+/// status to exit with.
+#[derive(Default)]
+struct Outcome {
+    /// Whether any query failed, or got an error response.
+    errored: bool,
+
+    /// Whether the signature on any response couldn’t be verified.
+    tsig_failed: bool,
+
+    /// Whether any response failed DNSSEC validation.
+    bogus: bool,
+}
+
+impl Outcome {
+    /// Returns the status to exit with, given whether anything was printed.
+    fn exit_code(&self, printed: bool) -> i32 {
+        if self.tsig_failed {
+            exits::TSIG_ERROR
+        } else if self.bogus {
+            exits::BOGUS
+        } else if !printed {
+            exits::NO_SHORT_RESULTS
+        } else if self.errored {
+            exits::NETWORK_ERROR
+        } else {
+            exits::SUCCESS
+        }
+    }
+}
+
+/// The nameservers that queries get sent to, and the validator that checks
+/// their responses.
+struct Destination<'a> {
+    /// The nameservers to try, in order.
+    transports: Vec<Transport>,
+
+    /// Whether to re-send truncated UDP responses over TCP.
+    retry_truncated: bool,
+
+    /// The validator, if DNSSEC validation was asked for. It fetches the
+    /// keys it needs from the same nameservers, and keeps them between
+    /// responses.
+    validator: Option<validate::Validator<'a, validate::Network>>,
+
+    /// The nameserver to start the next query with, if the system’s
+    /// resolver configuration asks for queries to be spread across its
+    /// nameservers.
+    rotation: Option<std::sync::atomic::AtomicUsize>,
+}
+
+impl<'a> Destination<'a> {
+    /// Works out the nameservers to send the given inputs’ queries to, from
+    /// the ones they name or the system’s own, resolving any nameservers
+    /// given as host names. Returns a message to print if one of them
+    /// couldn’t be resolved, or can’t be reached with the transport.
+    async fn new(requests: &'a Requests, inputs: &Inputs) -> Result<Self, String> {
+        // Nameservers given as host names get looked up with the same
+        // patience as the queries themselves
+        let mut resolver_opts = ResolverOpts::default();
+        resolver_opts.timeout = requests.attempts.timeout;
+        resolver_opts.attempts = requests.attempts.tries as usize - 1;
+        resolver_opts.try_tcp_on_error = requests.attempts.retry_tcp;
+
+        // There’s no default target to send Oblivious DoH requests to, as
+        // few public resolvers accept them
+        let relay = match &requests.odoh_relay {
+            Some(_) if inputs.nameservers.is_empty() => {
+                return Err(String::from(
+                    "An ODoH target nameserver must be given with --odoh",
+                ));
+            }
+            Some(relay) => Some(resolve_relay(relay, resolver_opts.clone()).await?),
+            None => None,
+        };
+
+        // The system’s nameservers get rotated if its configuration says
+        // to, but nameservers given by the user are tried in order
+        let rotation = (inputs.nameservers.is_empty() && requests.system.rotate)
+            .then(|| std::sync::atomic::AtomicUsize::new(0));
+
+        let defaults;
+        let nameservers = if inputs.nameservers.is_empty() {
+            defaults = default_nameservers(inputs.transport_type, &requests.system);
+            &defaults
+        } else {
+            &inputs.nameservers
+        };
+
+        // Requests get sent to each nameserver in turn until one responds
+        let mut transports = Vec::new();
+        for nameserver in nameservers {
+            // Nameservers given as URLs are always DNS-over-HTTPS
+            // endpoints, whichever transport was picked
+            let transport_type = if nameserver.path.is_some() {
+                Some(TransportType::HTTPS)
             } else {
-                if is_tls {
-                    tls_dns_name = if ns_str == "8.8.8.8"
-                        || ns_str == "8.8.4.4"
-                        || ns_str == "2001:4860:4860::8888"
-                        || ns_str == "2001:4860:4860::8844"
-                    {
-                        Some("dns.google".to_string())
-                    } else if ns_str == "1.1.1.1"
-                        || ns_str == "1.0.0.1"
-                        || ns_str == "2606:4700:4700::1111"
-                        || ns_str == "2606:4700:4700::1001"
-                    {
-                        Some("cloudflare-dns.com".to_string())
-                    } else {
-                        Some(ns_str.clone())
-                    };
-                }
+                nameserver.transport_type.or(inputs.transport_type)
+            };
 
-                let resolver = TokioResolver::builder_with_config(
-                    ResolverConfig::default(),
-                    hickory_resolver::net::runtime::TokioRuntimeProvider::default(),
+            // DNSCrypt has its own encryption, so it only goes over plain
+            // UDP or TCP
+            if nameserver.dnscrypt.is_some()
+                && !matches!(
+                    transport_type,
+                    None | Some(TransportType::UDP | TransportType::TCP)
                 )
-                .with_options(ResolverOpts::default())
-                .build()
-                .unwrap();
-                match resolver.lookup_ip(ns_str.as_str()).await {
-                    Ok(lookup) => {
-                        if let Some(ip) = lookup.iter().next() {
-                            ip
-                        } else {
-                            eprintln!(
-                                "Failed to resolve nameserver '{ns_str}': No IP addresses found"
-                            );
-                            return exits::OPTIONS_ERROR;
-                        }
-                    }
-                    Err(e) => {
-                        eprintln!("Failed to resolve nameserver '{ns_str}': {e}");
-                        return exits::OPTIONS_ERROR;
-                    }
-                }
+            {
+                return Err(format!(
+                    "DNSCrypt nameserver '{nameserver}' can only be used over UDP or TCP"
+                ));
+            }
+
+            // A resolver picked by name gets all its addresses tried, and
+            // one picked by address still gets its certificate checked
+            // against the right name
+            let named = match nameserver.path {
+                Some(_) => None,
+                None => requests.resolvers.by_name(&nameserver.host),
+            };
+            let (addresses, known) = if let Some(resolver) = named {
+                (resolver.addresses.clone(), named)
+            } else if let Some(ip) = nameserver.address {
+                (vec![ip], None)
+            } else if let Some(ip) = nameserver.ip() {
+                (vec![ip], requests.resolvers.by_address(ip))
+            } else {
+                let ip = resolve_nameserver(nameserver, resolver_opts.clone()).await?;
+                (vec![ip], None)
             };
 
-            let connection = match requests.inputs.transport_type {
-                Some(TransportType::TCP) => ConnectionConfig::tcp(),
-                Some(TransportType::TLS) => {
-                    let server_name = tls_dns_name.unwrap_or_else(|| ip_addr.to_string());
-                    ConnectionConfig::tls(Arc::from(server_name.as_str()))
+            let connection = connection_for(transport_type, nameserver, known, named.is_some())?;
+            for ip in addresses {
+                let mut transport = Transport::new(ip, connection.clone());
+                if let Some(scope_id) = nameserver.scope_id {
+                    transport = transport.with_scope_id(scope_id);
+                }
+
+                if let Some(provider) = &nameserver.dnscrypt {
+                    transport = transport.with_dnscrypt(provider.clone());
                 }
-                Some(TransportType::HTTPS) => {
-                    let server_name = tls_dns_name.unwrap_or_else(|| ip_addr.to_string());
-                    ConnectionConfig::https(Arc::from(server_name.as_str()), None)
+
+                if !nameserver.certificate_hashes.is_empty() {
+                    transport =
+                        transport.with_certificate_hashes(nameserver.certificate_hashes.clone());
                 }
-                Some(TransportType::UDP) | None => ConnectionConfig::udp(),
+
+                transports.push(transport);
+            }
+        }
+
+        let transports = transports
+            .into_iter()
+            .map(|transport| {
+                transport
+                    .with_timeout(requests.attempts.timeout)
+                    .with_doh_method(requests.doh_method)
+                    .with_http_version(requests.http_version)
+            })
+            .map(|transport| match &relay {
+                Some(relay) => transport.with_relay(relay.clone()),
+                None => transport,
+            })
+            .collect::<Vec<_>>();
+
+        // Only fall back to TCP if the user hasn’t asked for a specific transport,
+        // or asked to see truncated responses
+        let retry_truncated = inputs.transport_type.is_none() && !requests.tweaks.no_tc_retry;
+
+        let validator = requests.validate.as_ref().map(|anchor| {
+            let network = validate::Network {
+                transports: transports.clone(),
+                retry_truncated,
             };
+            validate::Validator::new(anchor, network)
+        });
+
+        Ok(Self {
+            transports,
+            retry_truncated,
+            validator,
+            rotation,
+        })
+    }
 
-            let ns_config = NameServerConfig::new(ip_addr, true, vec![connection]);
-            config.add_name_server(ns_config);
+    /// Returns the nameservers to send the next query to, in order. When
+    /// rotating, each query starts with the nameserver after the one that
+    /// the previous query started with.
+    fn next_transports(&self) -> std::borrow::Cow<'_, [Transport]> {
+        use std::borrow::Cow;
+        use std::sync::atomic::Ordering;
+
+        match &self.rotation {
+            Some(next) if self.transports.len() > 1 => {
+                let start = next.fetch_add(1, Ordering::Relaxed) % self.transports.len();
+                let mut transports = self.transports.clone();
+                transports.rotate_left(start);
+                Cow::Owned(transports)
+            }
+            _ => Cow::Borrowed(&self.transports),
         }
-        config
+    }
+}
+
+/// Returns the nameservers to use when none were given: the system’s own,
+/// unless an encrypted transport was picked, which they’re unlikely to
+/// support, so a public resolver gets used instead.
+fn default_nameservers(
+    transport_type: Option<TransportType>,
+    system: &resolv::ResolvConf,
+) -> Vec<Nameserver> {
+    let public = match transport_type {
+        Some(TransportType::TLS) => Some("cloudflare"),
+        Some(TransportType::HTTPS) => Some("google"),
+        Some(TransportType::QUIC) => Some("adguard"),
+        Some(TransportType::UDP | TransportType::TCP) | None => None,
     };
 
-    let mut resolver_opts = ResolverOpts::default();
-    if requests.dnssec {
-        // Validation requires dnssec feature in hickory-resolver, which is not enabled.
-        // resolver_opts.validate = true;
-        resolver_opts.edns0 = true;
+    if let Some(name) = public {
+        return vec![Nameserver::named(name)];
+    }
+
+    let system = system_nameservers(system);
+    if system.is_empty() {
+        vec![Nameserver::named("google")]
+    } else {
+        system
+    }
+}
+
+/// Returns the nameservers the system is configured to use.
+fn system_nameservers(system: &resolv::ResolvConf) -> Vec<Nameserver> {
+    if cfg!(target_os = "windows") {
+        #[cfg(windows)]
+        {
+            // On Windows, use ipconfig to retrieve DNS servers from network adapters
+            ipconfig::get_adapters()
+                .unwrap_or_default()
+                .into_iter()
+                .flat_map(|adapter| adapter.dns_servers().to_vec())
+                .map(|ip| Nameserver::named(&ip.to_string()))
+                .collect()
+        }
+        #[cfg(not(windows))]
+        {
+            vec![]
+        }
+    } else {
+        // On Unix/Linux, use the ones read from /etc/resolv.conf
+        system.nameservers.clone()
     }
+}
+
+/// Looks up the address of a nameserver that was given as a host name,
+/// using the system’s resolver.
+async fn resolve_nameserver(
+    nameserver: &Nameserver,
+    options: ResolverOpts,
+) -> Result<IpAddr, String> {
     let resolver = TokioResolver::builder_with_config(
-        config.clone(),
+        ResolverConfig::default(),
         hickory_resolver::net::runtime::TokioRuntimeProvider::default(),
     )
-    .with_options(resolver_opts)
+    .with_options(options)
     .build()
     .unwrap();
 
-    // Collect all lookup futures for parallel execution
+    match resolver.lookup_ip(nameserver.host.as_str()).await {
+        Ok(lookup) => lookup.iter().next().ok_or_else(|| {
+            format!("Failed to resolve nameserver '{nameserver}': No IP addresses found")
+        }),
+        Err(e) => Err(format!("Failed to resolve nameserver '{nameserver}': {e}")),
+    }
+}
+
+/// Works out where to send Oblivious DNS-over-HTTPS requests to the relay at,
+/// looking up its address if it was given as a host name.
+async fn resolve_relay(relay: &Nameserver, options: ResolverOpts) -> Result<odoh::Relay, String> {
+    let ip = match relay.ip() {
+        Some(ip) => ip,
+        None => resolve_nameserver(relay, options).await?,
+    };
+
+    Ok(odoh::Relay {
+        addr: SocketAddr::new(ip, relay.port.unwrap_or(443)),
+        server_name: relay.host.clone(),
+        path: relay
+            .path
+            .clone()
+            .unwrap_or_else(|| String::from(doh::DEFAULT_PATH)),
+    })
+}
+
+/// Returns the connection settings for sending requests to a nameserver
+/// with the given transport type. If the nameserver is a resolver from the
+/// registry, its TLS name, DNS-over-HTTPS endpoint, and DNS-over-QUIC port
+/// are used; otherwise, the address or host name the user gave is. Returns
+/// a message to print if the nameserver was picked by name, but that
+/// resolver can’t be reached with the transport.
+fn connection_for(
+    transport_type: Option<TransportType>,
+    nameserver: &Nameserver,
+    known: Option<&resolvers::Resolver>,
+    named: bool,
+) -> Result<ConnectionConfig, String> {
+    use std::sync::Arc;
+
+    let tls_name = known.and_then(|r| r.tls_name.as_deref());
+    let endpoint = known.and_then(|r| r.https.as_ref());
+
+    let mut connection = match transport_type {
+        Some(TransportType::UDP) | None => ConnectionConfig::udp(),
+        Some(TransportType::TCP) => ConnectionConfig::tcp(),
+        Some(TransportType::TLS) => {
+            if named && tls_name.is_none() {
+                return Err(format!(
+                    "Resolver '{nameserver}' does not support DNS-over-TLS"
+                ));
+            }
+
+            ConnectionConfig::tls(Arc::from(tls_name.unwrap_or(&nameserver.host)))
+        }
+        Some(TransportType::HTTPS) => {
+            if named && endpoint.is_none() {
+                return Err(format!(
+                    "Resolver '{nameserver}' does not support DNS-over-HTTPS"
+                ));
+            }
+
+            let server_name = endpoint
+                .map(|e| e.host.as_str())
+                .or(tls_name)
+                .unwrap_or(&nameserver.host);
+            let path = nameserver
+                .path
+                .as_deref()
+                .or_else(|| endpoint.and_then(|e| e.path.as_deref()));
+            let mut connection =
+                ConnectionConfig::https(Arc::from(server_name), path.map(Arc::from));
+
+            if let Some(port) = endpoint
+                .and_then(|e| e.port)
+                .filter(|_| nameserver.path.is_none())
+            {
+                connection.port = port;
+            }
+
+            connection
+        }
+        Some(TransportType::QUIC) => {
+            let quic_port = known.and_then(|r| r.quic_port);
+            if named && quic_port.is_none() {
+                return Err(format!(
+                    "Resolver '{nameserver}' does not support DNS-over-QUIC"
+                ));
+            }
+
+            let mut connection =
+                ConnectionConfig::quic(Arc::from(tls_name.unwrap_or(&nameserver.host)));
+            if let Some(port) = quic_port {
+                connection.port = port;
+            }
+
+            connection
+        }
+    };
+
+    // The port applies to every transport, including the TCP one that
+    // truncated UDP responses get re-sent over
+    if let Some(port) = nameserver.port {
+        connection.port = port;
+    }
+
+    Ok(connection)
+}
+
+/// One query that has been sent, and what came back.
+struct Lookup {
+    /// The domain that was queried, which is the name from the search list
+    /// that got the answer if the search list was used.
+    domain: String,
+
+    /// The names tried from the search list, in order, or nothing if the
+    /// domain was only tried as it was given.
+    searched: Vec<String>,
+
+    /// The record type that was queried.
+    qtype: RecordType,
+
+    /// The class that was queried.
+    class: DNSClass,
+
+    /// A description of the request, if it could be built.
+    sent: Option<String>,
+
+    /// The transport the request was last sent over.
+    transport: Option<Transport>,
+
+    /// The HTTP response the DNS response came in, if it was sent over
+    /// HTTPS.
+    http: Option<doh::HttpResponse>,
+
+    /// How long the handshake and the query took, if the request was sent
+    /// over QUIC.
+    timings: Option<transport::Timings>,
+
+    /// The response, or the error that stopped one coming back.
+    result: Result<DnsResponse, QueryError>,
+
+    /// How many times the request was sent.
+    attempts: u32,
+
+    /// How long the query took.
+    elapsed: std::time::Duration,
+}
+
+/// Sends every query in the inputs, as quickly as the scheduler allows,
+/// returning them sorted by domain, then type and class, so they get
+/// printed in a stable order.
+async fn lookup_all(
+    requests: &Requests,
+    inputs: &Inputs,
+    destination: &Destination<'_>,
+    scheduler: &Scheduler,
+) -> Vec<Lookup> {
+    // Collect all lookup futures, which the scheduler holds back until
+    // they’re allowed to be sent
     let mut futures = Vec::new();
-    for domain in &requests.inputs.domains {
-        for qtype in requests.inputs.record_types.iter().copied() {
-            let resolver_clone = resolver.clone();
-            let domain_str = domain.clone();
-            futures.push(async move {
-                if qtype == RecordType::ANY {
-                    // Perform an exhaustive sweep of all record types
-                    let mut fallback_futures = Vec::new();
-                    for f_qtype in ANY_FALLBACK_TYPES.iter().copied() {
-                        let f_res_clone = resolver_clone.clone();
-                        let f_domain_str = domain_str.clone();
-                        fallback_futures.push(async move {
-                            let f_timer = Instant::now();
-                            let f_result = f_res_clone.lookup(&f_domain_str, f_qtype).await;
-                            let f_elapsed = f_timer.elapsed();
-                            (f_domain_str, f_qtype, f_result, f_elapsed)
-                        });
-                    }
-                    join_all(fallback_futures).await
-                } else {
-                    let query_timer = Instant::now();
-                    let result = resolver_clone.lookup(&domain_str, qtype).await;
-                    let elapsed = query_timer.elapsed();
-                    vec![(domain_str, qtype, result, elapsed)]
-                }
-            });
+    for domain in &inputs.domains {
+        for qtype in inputs.record_types.iter().copied() {
+            for class in inputs.classes.iter().copied() {
+                futures.push(async move {
+                    // Perform an exhaustive sweep of all record types for ANY
+                    let qtypes = if qtype == RecordType::ANY {
+                        ANY_FALLBACK_TYPES
+                    } else {
+                        std::slice::from_ref(&qtype)
+                    };
+
+                    let lookups = qtypes.iter().copied().map(|qtype| {
+                        search(
+                            requests,
+                            inputs,
+                            destination,
+                            scheduler,
+                            domain,
+                            qtype,
+                            class,
+                        )
+                    });
+                    join_all(lookups).await
+                });
+            }
         }
     }
 
-    // Execute all lookups concurrently and collect results
     let query_results = join_all(futures).await;
 
-    // Sort results by domain, then qtype to maintain output order and blocks
+    // Sort results by domain, then qtype and class to maintain output order and blocks
     let mut sorted_results: Vec<_> = query_results.into_iter().flatten().collect();
-    sorted_results.sort_by_key(|(domain, qtype, _, _)| (domain.clone(), *qtype));
+    sorted_results.sort_by_key(|l| (l.domain.clone(), l.qtype, u16::from(l.class)));
+    sorted_results
+}
 
-    // Process results in order
-    for (domain, qtype, result, elapsed) in sorted_results {
-        if verbose {
-            let nameservers_set: HashSet<String> = config
-                .name_servers()
-                .iter()
-                .map(|ns| ns.ip.to_string())
-                .collect();
-            let mut nameservers: Vec<String> = nameservers_set.into_iter().collect();
-            nameservers.sort();
-            let nameserver_str = nameservers.join(", ");
-            let transport = requests.inputs.transport_type.map_or("UDP", |t| match t {
-                TransportType::UDP => "UDP",
-                TransportType::TCP => "TCP",
-                TransportType::TLS => "TLS",
-                TransportType::HTTPS => "HTTPS",
-            });
-            let duration_ms = elapsed.as_secs_f64() * 1000.0;
-            println!("Query for {domain} {qtype} on {nameserver_str} ({transport}) finished in {duration_ms:.2}ms");
+/// Sends a query for each name that the system’s search list expands the
+/// domain into, in turn, stopping at the first one that gets an answer, or
+/// that fails without getting a response at all. If none of them get an
+/// answer, the response for the domain as it was given is the one that gets
+/// displayed.
+async fn search(
+    requests: &Requests,
+    inputs: &Inputs,
+    destination: &Destination<'_>,
+    scheduler: &Scheduler,
+    domain: &str,
+    qtype: RecordType,
+    class: DNSClass,
+) -> Lookup {
+    use std::time::Instant;
+
+    let query_timer = Instant::now();
+    let names = if requests.search {
+        requests.system.candidates(domain)
+    } else {
+        vec![domain.to_string()]
+    };
+    let expanded = names != [domain];
+
+    let mut searched = Vec::new();
+    let mut stopped = None;
+    let mut as_given = None;
+    let mut last = None;
+
+    for name in names {
+        let (description, sent, attempts) = send_query(
+            requests,
+            inputs,
+            destination,
+            scheduler,
+            &name,
+            qtype,
+            class,
+        )
+        .await;
+        searched.push(name.clone());
+
+        let answered = matches!(&sent.result, Ok(response)
+            if response.metadata.response_code == ResponseCode::NoError
+                && !response.answers.is_empty());
+        let stop = answered || sent.result.is_err();
+
+        let tried = (name, description, sent, attempts);
+        if stop {
+            stopped = Some(tried);
+            break;
+        } else if tried.0 == domain {
+            as_given = Some(tried);
+        } else {
+            last = Some(tried);
         }
+    }
 
-        match result {
-            Ok(response) => {
-                if verbose {
-                    format.print(vec![response], None);
-                } else {
-                    responses.push(response);
-                }
+    let (domain, description, sent, attempts) = stopped
+        .or(as_given)
+        .or(last)
+        .expect("The search list always has a name to try");
+
+    Lookup {
+        domain,
+        searched: if expanded { searched } else { Vec::new() },
+        qtype,
+        class,
+        sent: description,
+        transport: sent.transport,
+        http: sent.http,
+        timings: sent.timings,
+        result: sent.result,
+        attempts,
+        elapsed: query_timer.elapsed(),
+    }
+}
+
+/// Builds the request for one name, and sends it as soon as the scheduler
+/// allows, returning a description of the request if it could be built,
+/// what came back, and how many times it was sent.
+async fn send_query(
+    requests: &Requests,
+    inputs: &Inputs,
+    destination: &Destination<'_>,
+    scheduler: &Scheduler,
+    name: &str,
+    qtype: RecordType,
+    class: DNSClass,
+) -> (Option<String>, Sent, u32) {
+    match requests.build_message_for(inputs, name, qtype, class) {
+        Ok(request) => {
+            let transports = destination.next_transports();
+
+            // Some transports change the request, such as by setting its
+            // ID to 0, so it gets described the way it’s actually sent
+            let outgoing = match transports.first() {
+                Some(transport) => transport.outgoing(request.clone()),
+                None => request.clone(),
+            };
+            let description = request::describe_request(&outgoing, &requests.tweaks);
+            let (sent, tries) = scheduler
+                .send(|| {
+                    send_request(
+                        &transports,
+                        request.clone(),
+                        destination.retry_truncated,
+                        requests.attempts.retry_tcp,
+                        requests.tsig.as_ref(),
+                    )
+                })
+                .await;
+            (Some(description), sent, tries)
+        }
+        Err(e) => (None, Sent::failed(None, NetError::from(e).into()), 0),
+    }
+}
+
+/// Turns a finished query into the result to print, validating its
+/// response if asked to, and noting anything that went wrong in the
+/// outcome. In verbose mode, the details of the query get printed first.
+/// Returns `None` for the types an ANY sweep found nothing for.
+async fn check_lookup(
+    lookup: Lookup,
+    requests: &Requests,
+    inputs: &Inputs,
+    destination: &Destination<'_>,
+    verbose: bool,
+    outcome: &mut Outcome,
+) -> Option<QueryResult> {
+    let Lookup {
+        domain,
+        searched,
+        qtype,
+        class,
+        sent,
+        transport,
+        http,
+        timings,
+        result,
+        attempts,
+        elapsed,
+    } = lookup;
+
+    if verbose {
+        let (nameserver, protocol) = match &transport {
+            Some(t) => (t.address().to_string(), t.protocol_name()),
+            None => (String::from("no nameserver"), "-"),
+        };
+        let duration_ms = elapsed.as_secs_f64() * 1000.0;
+        let response_id = match &result {
+            Ok(response) => format!(", response ID {}", response.metadata.id),
+            Err(_) => String::new(),
+        };
+        let plural = if attempts == 1 { "" } else { "s" };
+        if !searched.is_empty() {
+            println!("Searched for {}", searched.join(", "));
+        }
+        println!(
+            "Query for {domain} {qtype} {class} on {nameserver} ({protocol}) \
+             finished in {duration_ms:.2}ms after {attempts} attempt{plural}{response_id}"
+        );
+
+        if let Some((relay, target)) = transport.as_ref().and_then(Transport::odoh_route) {
+            println!("ODoH relay: {relay}");
+            println!("ODoH target: {target}");
+        }
+
+        if let Some(resolver) = transport.as_ref().and_then(Transport::dnscrypt) {
+            print!("DNSCrypt provider: {}", resolver.provider().name);
+            if let Some(certificate) = resolver.fetched_certificate() {
+                let remaining = certificate.valid_until.saturating_sub(dnscrypt::now());
+                print!(
+                    ", certificate serial {} using {}, expires in {}",
+                    certificate.serial,
+                    certificate.construction(),
+                    output::format_duration_hms(remaining)
+                );
             }
-            Err(e) => {
-                if requests.inputs.any_query {
-                    let err_str = e.to_string();
-                    if e.is_no_records_found()
-                        || err_str.contains("Not Implemented")
-                        || err_str.contains("Form Error")
-                    {
-                        // Suppress this specific error for ANY queries
-                    } else {
-                        format.print_error(&e);
-                        errored = true;
+            println!();
+        }
+
+        if let Some(sent) = &sent {
+            println!("Sent {sent}");
+        }
+
+        if let Some(timings) = &timings {
+            let handshake_ms = timings.handshake.as_secs_f64() * 1000.0;
+            let query_ms = timings.query.as_secs_f64() * 1000.0;
+            println!("{protocol} handshake took {handshake_ms:.2}ms, query took {query_ms:.2}ms");
+        }
+
+        if let Some(http) = &http {
+            println!("Received {:?} {}", http.version, http.status);
+            for (name, value) in &http.headers {
+                println!("  {name}: {}", String::from_utf8_lossy(value.as_bytes()));
+            }
+        }
+
+        if matches!(&result, Ok(response) if response.metadata.truncation) {
+            println!("Response was truncated");
+        }
+    }
+
+    // Responses with an error code, or negative responses such as
+    // NXDOMAIN, still get displayed so their header and authority
+    // section can be seen, but they count as errors too
+    let (mut response, mut error) = match result {
+        Ok(response) => {
+            let error = DnsError::from_response(response.clone())
+                .err()
+                .map(|e| NetError::from(e).into());
+            (Some(response.into_message()), error)
+        }
+        Err(e) => (None, Some(e)),
+    };
+
+    if inputs.any_query {
+        // Don’t display the types that an ANY sweep found nothing for,
+        // or the types that the nameserver doesn’t support
+        if response.as_ref().is_some_and(|r| r.answers.is_empty()) {
+            response = None;
+        }
+
+        if let Some(QueryError::Network(e)) = &error {
+            let err_str = e.to_string();
+            if e.is_no_records_found()
+                || err_str.contains("Not Implemented")
+                || err_str.contains("Form Error")
+            {
+                error = None;
+            }
+        }
+
+        if response.is_none() && error.is_none() {
+            return None;
+        }
+    }
+
+    let validation = match (&destination.validator, &response) {
+        (Some(validator), Some(response)) => Some(validator.validate(response).await),
+        _ => None,
+    };
+
+    if validation
+        .as_ref()
+        .is_some_and(|v| v.security() == validate::Security::Bogus)
+    {
+        outcome.bogus = true;
+    }
+
+    if let Some(response) = &mut response {
+        if !requests.edns.should_show() {
+            response.edns = None;
+        }
+    }
+
+    if error.is_some() {
+        outcome.errored = true;
+    }
+
+    if matches!(error, Some(QueryError::Tsig(_))) {
+        outcome.tsig_failed = true;
+    }
+
+    Some(QueryResult {
+        domain,
+        qtype,
+        class,
+        nameserver: transport.as_ref().map(|t| t.address().to_string()),
+        transport: transport.as_ref().map(Transport::protocol_name),
+        http_version: http.as_ref().map(|h| h.version),
+        elapsed,
+        attempts,
+        response,
+        error,
+        validation,
+    })
+}
+
+/// Traces each query down from the root servers, following the referrals
+/// given by each nameserver until one of them gives an answer, and prints
+/// every hop taken along the way.
+///
+/// # Arguments
+///
+/// * `requests` - The queries to trace and the settings to send them with.
+/// * `hints` - The root servers to start from.
+/// * `format` - How to print the hops.
+/// * `timer` - When the program started, if the duration should be printed.
+///
+/// # Returns
+///
+/// * The process exit code.
+async fn run_trace(
+    requests: &Requests,
+    hints: &trace::RootHints,
+    format: OutputFormat,
+    timer: Option<std::time::Instant>,
+) -> i32 {
+    if !requests.inputs.nameservers.is_empty() {
+        eprintln!("warning: nameservers are ignored when tracing from the root servers");
+    }
+
+    let tracer = trace::Tracer {
+        requests,
+        hints,
+        exchange: trace::Network {
+            tcp: requests.inputs.transport_type == Some(TransportType::TCP),
+            timeout: requests.attempts.timeout,
+        },
+    };
+
+    let mut results = Vec::new();
+    let mut errored = false;
+
+    for domain in &requests.inputs.domains {
+        for qtype in &requests.inputs.record_types {
+            for class in &requests.inputs.classes {
+                let mut trace = tracer.trace(domain, *qtype, *class).await;
+
+                if !requests.edns.should_show() {
+                    for hop in &mut trace.hops {
+                        if let Ok(response) = &mut hop.result {
+                            response.edns = None;
+                        }
                     }
-                } else {
-                    format.print_error(&e);
-                    errored = true;
                 }
+
+                errored |= !trace.answer().is_some_and(|answer| {
+                    answer.metadata.response_code == ResponseCode::NoError
+                        && !answer.answers.is_empty()
+                });
+                results.push(trace);
             }
         }
     }
 
+    let duration = timer.map(|t| t.elapsed());
+    if !format.print_traces(&results, duration) {
+        exits::NO_SHORT_RESULTS
+    } else if errored {
+        exits::NETWORK_ERROR
+    } else {
+        exits::SUCCESS
+    }
+}
+
+/// Sends each query to the multicast groups instead of to nameservers, and
+/// browses for each service asked for, then prints every response that
+/// came back while listening. Returns whether anything was printed.
+async fn run_multicast(
+    requests: &Requests,
+    multicast: &options::Multicast,
+    format: OutputFormat,
+    verbose: bool,
+    timer: Option<std::time::Instant>,
+    outcome: &mut Outcome,
+) -> bool {
+    let protocol = multicast.protocol.name();
+    if !requests.inputs.nameservers.is_empty() && requests.inputs.domains.is_empty() {
+        eprintln!("warning: nameservers are ignored when querying over {protocol}");
+    }
+
     if verbose {
-        let duration = timer.map(|t| t.elapsed());
-        if let Some(duration) = duration {
-            let duration_ms = duration.as_secs_f64() * 1000.0;
-            println!("Ran in {duration_ms:.2}ms");
+        let groups = multicast
+            .protocol
+            .destinations()
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ");
+        let window_ms = multicast.window.as_secs_f64() * 1000.0;
+        println!("Sending {protocol} queries to {groups}, listening for {window_ms:.0}ms");
+    }
+
+    let collections = collect_multicast(
+        requests,
+        multicast,
+        &requests.inputs,
+        &multicast.domains,
+        &multicast.services,
+    )
+    .await;
+    print_collections(format, &collections, timer.map(|t| t.elapsed()), outcome)
+}
+
+/// Sends the queries for some domains to the multicast groups, with the
+/// types and classes in the inputs, and browses for each of the services,
+/// returning what came back for each query.
+async fn collect_multicast(
+    requests: &Requests,
+    multicast: &options::Multicast,
+    inputs: &Inputs,
+    domains: &[String],
+    services: &[String],
+) -> Vec<multicast::Collection> {
+    let querier = multicast::Querier {
+        requests,
+        destinations: multicast.protocol.destinations(),
+        hop_limit: multicast.protocol.hop_limit(),
+        window: multicast.window,
+    };
+
+    let mut pending = Vec::new();
+    for domain in domains {
+        for qtype in &inputs.record_types {
+            for class in &inputs.classes {
+                pending.push(querier.query(domain, *qtype, *class));
+            }
         }
+    }
 
-        if errored {
-            exits::NETWORK_ERROR
-        } else {
-            exits::SUCCESS
+    let mut collections = join_all(pending).await;
+    for browsed in join_all(services.iter().map(|s| querier.browse(s))).await {
+        collections.extend(browsed);
+    }
+
+    if !requests.edns.should_show() {
+        for reply in collections.iter_mut().flat_map(|c| &mut c.replies) {
+            reply.message.edns = None;
         }
-    } else {
-        let duration = timer.map(|t| t.elapsed());
-        if format.print(responses, duration) {
-            if errored {
-                exits::NETWORK_ERROR
-            } else {
-                exits::SUCCESS
+    }
+
+    collections
+}
+
+/// Prints the responses to multicast queries, noting in the outcome if any
+/// query couldn’t be sent or got no responses. Returns whether anything
+/// was printed.
+fn print_collections(
+    format: OutputFormat,
+    collections: &[multicast::Collection],
+    duration: Option<std::time::Duration>,
+    outcome: &mut Outcome,
+) -> bool {
+    outcome.errored |= collections
+        .iter()
+        .any(|c| c.error.is_some() || c.replies.is_empty());
+    format.print_collections(collections, duration)
+}
+
+/// Sends a request to each of the nameservers in turn until one of them
+/// responds, re-sending it over TCP if the response came back truncated,
+/// or if it failed over UDP and `retry_tcp` is set. Zone transfer requests
+/// are sent over TCP, with every message of the transfer combined into one
+/// response. If a TSIG key is given, the request gets signed, and the
+/// signature on the response has to be verified.
+///
+/// # Arguments
+///
+/// * `transports` - The nameservers to try, in order.
+/// * `request` - The request message to send.
+/// * `retry_truncated` - Whether to re-send truncated UDP responses over TCP.
+/// * `retry_tcp` - Whether to re-send failed UDP requests over TCP.
+/// * `tsig` - The key to sign the request with, if any.
+///
+/// # Returns
+///
+/// * The transport the request was last sent over, along with the response
+///   and the HTTP response it came in, or the last error that occurred.
+///   Responses with an error code, such as NXDOMAIN or SERVFAIL, are
+///   returned as responses rather than errors.
+async fn send_request(
+    transports: &[Transport],
+    request: Message,
+    retry_truncated: bool,
+    retry_tcp: bool,
+    tsig: Option<&TsigKey>,
+) -> Sent {
+    let mut last_error = NetError::NoConnections;
+    let mut last_http = None;
+    let mut last_transport = None;
+
+    // Zone transfers always need a stream, even if UDP was picked
+    let is_transfer = request
+        .queries
+        .first()
+        .is_some_and(|q| matches!(q.query_type(), RecordType::AXFR | RecordType::IXFR));
+
+    for transport in transports {
+        let mut transport = transport.clone();
+
+        // Each attempt is signed separately, so the time in the signature
+        // is the time it was sent
+        let mut request = request.clone();
+        let mut verifier = match tsig.map(|key| key.sign(&mut request)).transpose() {
+            Ok(verifier) => verifier,
+            Err(e) => return Sent::failed(None, NetError::from(e).into()),
+        };
+
+        if is_transfer {
+            if transport.is_udp() {
+                transport = transport.with_tcp();
+            }
+
+            let result = transport.transfer(request).await;
+            match result {
+                Ok(responses) => {
+                    // Every message of the transfer is signed, with each
+                    // signature covering the one before it
+                    if let Some(verifier) = &mut verifier {
+                        for response in &responses {
+                            if let Err(e) = verifier.verify(response) {
+                                return Sent::failed(Some(transport), QueryError::Tsig(e));
+                            }
+                        }
+                    }
+
+                    return Sent {
+                        transport: Some(transport),
+                        http: None,
+                        timings: None,
+                        result: transport::combine_transfer(responses).map_err(QueryError::from),
+                    };
+                }
+                Err(e) => {
+                    debug!("Nameserver failed to transfer the zone: {e}");
+                    last_error = e;
+                    last_transport = Some(transport);
+                    continue;
+                }
+            }
+        }
+
+        let mut result = transport.exchange(request.clone()).await;
+        if retry_truncated
+            && transport.is_udp()
+            && matches!(&result, Ok(e) if e.response.metadata.truncation)
+        {
+            debug!("Response was truncated, re-sending over TCP");
+            transport = transport.with_tcp();
+            result = transport.exchange(request).await;
+        } else if retry_tcp && transport.is_udp() && result.is_err() {
+            debug!("Request failed over UDP, re-sending over TCP");
+            transport = transport.with_tcp();
+            result = transport.exchange(request).await;
+        }
+
+        match result {
+            Ok(exchange) => {
+                if let Some(verifier) = &mut verifier {
+                    if let Err(e) = verifier.verify(&exchange.response) {
+                        return Sent::failed(Some(transport), QueryError::Tsig(e));
+                    }
+                }
+
+                return Sent {
+                    transport: Some(transport),
+                    http: exchange.http,
+                    timings: exchange.timings,
+                    result: Ok(exchange.response),
+                };
+            }
+            Err(failure) => {
+                debug!("Nameserver failed to respond: {}", failure.error);
+                last_error = failure.error;
+                last_http = failure.http;
+                last_transport = Some(transport);
             }
-        } else {
-            exits::NO_SHORT_RESULTS
         }
     }
+
+    Sent {
+        http: last_http,
+        ..Sent::failed(last_transport, last_error.into())
+    }
 }
 
 /// The possible status numbers dog can exit with.
@@ -498,4 +1590,11 @@ mod exits {
 
     /// Exit code for when the command-line options are invalid.
     pub const OPTIONS_ERROR: i32 = 3;
+
+    /// Exit code for when the TSIG signature on a response couldn’t be
+    /// verified, or the nameserver couldn’t verify the one on the request.
+    pub const TSIG_ERROR: i32 = 4;
+
+    /// Exit code for when DNSSEC validation found a response to be bogus.
+    pub const BOGUS: i32 = 5;
 }
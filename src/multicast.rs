@@ -0,0 +1,574 @@
+/*
+ * dog - A command-line DNS client
+ * Copyright (c) 2026 l1a and contributors
+ * Original code Copyright (c) Benjamin Sago
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Sending queries to every responder on the local link at once.
+//!
+//! Names such as `printer.local` aren’t served by any nameserver: the
+//! devices that own them answer for themselves, when asked on a multicast
+//! group. Multicast DNS does this for names under `.local`, and LLMNR, which
+//! Windows uses, does it for single-label names such as `router`. Any
+//! number of them may respond, or none at all, so rather than waiting for
+//! the first response, dog listens for a while and keeps every response
+//! that comes back, along with the address it came from.
+//!
+//! Queries are sent from an ordinary port, rather than the multicast DNS
+//! port, which makes them “one-shot” queries (RFC 6762 §5.1): responders
+//! send their answers straight back to dog instead of to the whole group.
+//! LLMNR responders always send their answers straight back (RFC 4795 §2).
+
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::{Duration, Instant};
+
+use futures::future::join_all;
+use hickory_resolver::net::NetError;
+use hickory_resolver::proto::op::{Message, MessageType};
+use hickory_resolver::proto::rr::{DNSClass, Name, RData, RecordType};
+use hickory_resolver::proto::ProtoError;
+use log::*;
+use tokio::net::UdpSocket;
+
+use crate::options::Requests;
+
+/// The port that multicast DNS responders listen on.
+pub const MDNS_PORT: u16 = 5353;
+
+/// The IPv4 multicast group for multicast DNS.
+pub const MDNS_V4: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
+
+/// The IPv6 link-local multicast group for multicast DNS.
+pub const MDNS_V6: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0xfb);
+
+/// The port that LLMNR responders listen on.
+pub const LLMNR_PORT: u16 = 5355;
+
+/// The IPv4 multicast group for LLMNR.
+pub const LLMNR_V4: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 252);
+
+/// The IPv6 link-local multicast group for LLMNR.
+pub const LLMNR_V6: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 1, 3);
+
+/// How long to listen for responses if the user doesn’t say.
+pub const DEFAULT_WINDOW: Duration = Duration::from_secs(1);
+
+/// The protocol to send multicast queries with, which decides the groups
+/// they get sent to.
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum Protocol {
+    /// Multicast DNS, for names under `.local`.
+    Mdns,
+
+    /// Link-Local Multicast Name Resolution, for single-label names.
+    Llmnr,
+}
+
+impl Protocol {
+    /// The name of the protocol, for the user to read.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Mdns => "mDNS",
+            Self::Llmnr => "LLMNR",
+        }
+    }
+
+    /// The IPv4 and IPv6 groups that queries get sent to.
+    pub fn destinations(self) -> Vec<SocketAddr> {
+        match self {
+            Self::Mdns => vec![(MDNS_V4, MDNS_PORT).into(), (MDNS_V6, MDNS_PORT).into()],
+            Self::Llmnr => vec![(LLMNR_V4, LLMNR_PORT).into(), (LLMNR_V6, LLMNR_PORT).into()],
+        }
+    }
+
+    /// The IPv4 time-to-live to send queries with. Multicast DNS responders
+    /// ignore queries that look like they came from off the local link,
+    /// whereas LLMNR queries must not be able to leave it.
+    pub fn hop_limit(self) -> u32 {
+        match self {
+            Self::Mdns => 255,
+            Self::Llmnr => 1,
+        }
+    }
+}
+
+/// One response to a multicast query.
+#[derive(Debug)]
+pub struct Reply {
+    /// The address of the responder that sent it.
+    pub from: SocketAddr,
+
+    /// How long after the query was sent it came back.
+    pub elapsed: Duration,
+
+    /// The response message.
+    pub message: Message,
+}
+
+/// Every response that came back to one query during the window.
+#[derive(Debug)]
+pub struct Collection {
+    /// The domain name that was queried.
+    pub domain: String,
+
+    /// The record type that was queried for.
+    pub qtype: RecordType,
+
+    /// The network class that was queried.
+    pub class: DNSClass,
+
+    /// The responses, in the order they arrived.
+    pub replies: Vec<Reply>,
+
+    /// The error that stopped the query from being sent, if it couldn’t
+    /// be. A query that was sent but that nobody responded to has no
+    /// replies and no error.
+    pub error: Option<NetError>,
+}
+
+/// Sends queries to the multicast groups, with the settings the user has
+/// picked, and collects the responses.
+pub struct Querier<'a> {
+    /// The settings to build the requests with.
+    pub requests: &'a Requests,
+
+    /// The groups to send each query to.
+    pub destinations: Vec<SocketAddr>,
+
+    /// The IPv4 time-to-live to send each query with.
+    pub hop_limit: u32,
+
+    /// How long to listen for responses after sending each query.
+    pub window: Duration,
+}
+
+impl Querier<'_> {
+    /// Sends one query, and returns every response that came back.
+    pub async fn query(&self, domain: &str, qtype: RecordType, class: DNSClass) -> Collection {
+        let request = self.requests.build_message(domain, qtype, class);
+        self.send(domain.to_string(), request, qtype, class).await
+    }
+
+    /// Browses for the instances of a DNS-SD service type, such as
+    /// `_http._tcp.local`: asks for the PTR records that name each
+    /// instance, then for the SRV and TXT records of each instance found.
+    /// Instance names are sent exactly as they came back, as they’re
+    /// allowed to have spaces and capitals in them.
+    pub async fn browse(&self, service: &str) -> Vec<Collection> {
+        let pointers = self.query(service, RecordType::PTR, DNSClass::IN).await;
+        let found = instances(&pointers.replies);
+        debug!("Found {} instances of {service}", found.len());
+
+        let details = found.iter().flat_map(|instance| {
+            [RecordType::SRV, RecordType::TXT].map(|qtype| {
+                let request =
+                    self.requests
+                        .build_message_named(instance.clone(), qtype, DNSClass::IN);
+                self.send(instance.to_string(), Ok(request), qtype, DNSClass::IN)
+            })
+        });
+
+        let mut collections = vec![pointers];
+        collections.extend(join_all(details).await);
+        collections
+    }
+
+    /// Sends a request without asking for recursion, which responders
+    /// don’t do, and collects the responses to it.
+    async fn send(
+        &self,
+        domain: String,
+        request: Result<Message, ProtoError>,
+        qtype: RecordType,
+        class: DNSClass,
+    ) -> Collection {
+        let result = match request {
+            Ok(mut request) => {
+                request.metadata.recursion_desired = false;
+                collect(&request, &self.destinations, self.hop_limit, self.window).await
+            }
+            Err(e) => Err(NetError::from(e)),
+        };
+
+        let (replies, error) = match result {
+            Ok(replies) => (replies, None),
+            Err(e) => (Vec::new(), Some(e)),
+        };
+
+        Collection {
+            domain,
+            qtype,
+            class,
+            replies,
+            error,
+        }
+    }
+}
+
+/// Sends a request to each of the destinations from a new socket, with the
+/// given time-to-live if it’s an IPv4 multicast group, then
+/// listens on every socket until the window has passed, keeping every
+/// response to the request. Sending to some of the destinations can fail,
+/// such as when there’s no IPv6 route; it’s only an error if none of them
+/// could be sent to.
+pub async fn collect(
+    request: &Message,
+    destinations: &[SocketAddr],
+    hop_limit: u32,
+    window: Duration,
+) -> Result<Vec<Reply>, NetError> {
+    let packet = request.to_vec()?;
+    let sent_at = Instant::now();
+    let deadline = tokio::time::Instant::from_std(sent_at + window);
+
+    let mut sockets = Vec::new();
+    let mut last_error = None;
+    for destination in destinations {
+        match send_packet(*destination, &packet, hop_limit).await {
+            Ok(socket) => sockets.push(socket),
+            Err(e) => {
+                debug!("Couldn’t send to {destination}: {e}");
+                last_error = Some(e);
+            }
+        }
+    }
+
+    if sockets.is_empty() {
+        return Err(last_error.unwrap_or_else(|| NetError::from("No groups to send to")));
+    }
+
+    let listens = sockets
+        .iter()
+        .map(|socket| listen(socket, request.metadata.id, sent_at, deadline));
+
+    let mut replies = join_all(listens)
+        .await
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+    replies.sort_by_key(|reply| reply.elapsed);
+    Ok(replies)
+}
+
+/// Sends a packet to a destination from a new socket, returning the socket
+/// so the responses can be read from it.
+async fn send_packet(
+    destination: SocketAddr,
+    packet: &[u8],
+    hop_limit: u32,
+) -> Result<UdpSocket, NetError> {
+    let local: SocketAddr = match destination {
+        SocketAddr::V4(_) => ([0, 0, 0, 0], 0).into(),
+        SocketAddr::V6(_) => ([0; 16], 0).into(),
+    };
+    let socket = UdpSocket::bind(local).await?;
+
+    if let SocketAddr::V4(v4) = destination {
+        if v4.ip().is_multicast() {
+            socket.set_multicast_ttl_v4(hop_limit)?;
+        }
+    }
+
+    socket.send_to(packet, destination).await?;
+    Ok(socket)
+}
+
+/// Reads datagrams from a socket until the deadline, keeping the ones that
+/// are responses to the request with the given ID. Responders are allowed
+/// to leave the ID as zero, so those are kept too.
+async fn listen(
+    socket: &UdpSocket,
+    id: u16,
+    sent_at: Instant,
+    deadline: tokio::time::Instant,
+) -> Vec<Reply> {
+    let mut replies = Vec::new();
+    let mut buffer = vec![0; usize::from(u16::MAX)];
+
+    while let Ok(received) = tokio::time::timeout_at(deadline, socket.recv_from(&mut buffer)).await
+    {
+        let (length, from) = match received {
+            Ok(received) => received,
+            Err(e) => {
+                debug!("Couldn’t receive a response: {e}");
+                break;
+            }
+        };
+
+        let message = match Message::from_vec(&buffer[..length]) {
+            Ok(message) => message,
+            Err(e) => {
+                debug!("Ignoring invalid response from {from}: {e}");
+                continue;
+            }
+        };
+
+        let elapsed = sent_at.elapsed();
+        if message.metadata.message_type != MessageType::Response
+            || (message.metadata.id != id && message.metadata.id != 0)
+        {
+            debug!("Ignoring unrelated message from {from}");
+            continue;
+        }
+
+        replies.push(Reply {
+            from,
+            elapsed,
+            message,
+        });
+    }
+
+    replies
+}
+
+/// Returns the service instances named by the PTR records in the answers of
+/// the replies, in the order they were first seen, without duplicates.
+pub fn instances(replies: &[Reply]) -> Vec<Name> {
+    let mut instances = Vec::<Name>::new();
+
+    for record in replies.iter().flat_map(|reply| &reply.message.answers) {
+        if let RData::PTR(ptr) = &record.data {
+            if !instances.contains(&ptr.0) {
+                instances.push(ptr.0.clone());
+            }
+        }
+    }
+
+    instances
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    use hickory_resolver::proto::op::{OpCode, Query};
+    use hickory_resolver::proto::rr::rdata::{PTR, SRV, TXT};
+    use hickory_resolver::proto::rr::Record;
+
+    fn name(input: &str) -> Name {
+        Name::from_utf8(input).unwrap()
+    }
+
+    fn request(id: u16) -> Message {
+        let mut request = Message::query();
+        request.metadata.id = id;
+        request.add_query(Query::query(name("printer.local."), RecordType::A));
+        request
+    }
+
+    /// Builds a response to a request, with the given answers.
+    fn response(request: &Message, answers: Vec<Record>) -> Message {
+        let mut response = Message::response(request.metadata.id, OpCode::Query);
+        response.add_query(request.queries[0].clone());
+        response.add_answers(answers);
+        response
+    }
+
+    /// Starts a stand-in responder, which answers every query it gets with
+    /// whatever the function returns, after a delay.
+    async fn serve<F>(delay: Duration, respond: F) -> SocketAddr
+    where
+        F: Fn(&Message) -> Vec<Message> + Send + 'static,
+    {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let mut buffer = vec![0; 4096];
+            while let Ok((length, from)) = socket.recv_from(&mut buffer).await {
+                let request = Message::from_vec(&buffer[..length]).unwrap();
+                tokio::time::sleep(delay).await;
+                for response in respond(&request) {
+                    socket
+                        .send_to(&response.to_vec().unwrap(), from)
+                        .await
+                        .unwrap();
+                }
+            }
+        });
+
+        addr
+    }
+
+    fn answers_with(address: Ipv4Addr) -> impl Fn(&Message) -> Vec<Message> {
+        move |request| {
+            let record = Record::from_rdata(name("printer.local."), 120, RData::A(address.into()));
+            vec![response(request, vec![record])]
+        }
+    }
+
+    #[test]
+    fn llmnr_groups() {
+        assert_eq!(
+            Protocol::Llmnr.destinations(),
+            vec![
+                "224.0.0.252:5355".parse::<SocketAddr>().unwrap(),
+                "[ff02::1:3]:5355".parse().unwrap(),
+            ]
+        );
+        assert_eq!(Protocol::Llmnr.hop_limit(), 1);
+    }
+
+    #[tokio::test]
+    async fn every_responder() {
+        let first = serve(Duration::ZERO, answers_with(Ipv4Addr::new(10, 0, 0, 1))).await;
+        let second = serve(
+            Duration::from_millis(50),
+            answers_with(Ipv4Addr::new(10, 0, 0, 2)),
+        )
+        .await;
+
+        let replies = collect(&request(7), &[second, first], 1, Duration::from_millis(500))
+            .await
+            .unwrap();
+
+        let from = replies.iter().map(|r| r.from).collect::<Vec<_>>();
+        assert_eq!(from, vec![first, second]);
+        assert!(replies[0].elapsed <= replies[1].elapsed);
+    }
+
+    #[tokio::test]
+    async fn late_responses() {
+        let prompt = serve(Duration::ZERO, answers_with(Ipv4Addr::new(10, 0, 0, 1))).await;
+        let late = serve(
+            Duration::from_millis(500),
+            answers_with(Ipv4Addr::new(10, 0, 0, 2)),
+        )
+        .await;
+
+        let replies = collect(&request(7), &[prompt, late], 1, Duration::from_millis(100))
+            .await
+            .unwrap();
+
+        assert_eq!(replies.len(), 1);
+        assert_eq!(replies[0].from, prompt);
+    }
+
+    #[tokio::test]
+    async fn unrelated_messages() {
+        let responder = serve(Duration::ZERO, |request| {
+            let mut other = response(request, Vec::new());
+            other.metadata.id = request.metadata.id.wrapping_add(1);
+            let mut unset = response(request, Vec::new());
+            unset.metadata.id = 0;
+            vec![request.clone(), other, unset]
+        })
+        .await;
+
+        let replies = collect(&request(7), &[responder], 1, Duration::from_millis(100))
+            .await
+            .unwrap();
+
+        assert_eq!(replies.len(), 1);
+        assert_eq!(replies[0].message.metadata.id, 0);
+    }
+
+    #[tokio::test]
+    async fn nobody_responds() {
+        let silent = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let destination = silent.local_addr().unwrap();
+
+        let replies = collect(&request(7), &[destination], 1, Duration::from_millis(50))
+            .await
+            .unwrap();
+
+        assert!(replies.is_empty());
+    }
+
+    #[test]
+    fn instances_in_order() {
+        let pointer = |target: &str| {
+            Record::from_rdata(
+                name("_http._tcp.local."),
+                120,
+                RData::PTR(PTR(name(target))),
+            )
+        };
+
+        let request = request(7);
+        let replies = vec![
+            pointer("Kitchen._http._tcp.local."),
+            pointer("Lounge._http._tcp.local."),
+        ]
+        .into_iter()
+        .zip(["10.0.0.1:5353", "10.0.0.2:5353"])
+        .map(|(record, from)| Reply {
+            from: from.parse().unwrap(),
+            elapsed: Duration::ZERO,
+            message: response(&request, vec![record, pointer("Kitchen._http._tcp.local.")]),
+        })
+        .collect::<Vec<_>>();
+
+        assert_eq!(
+            instances(&replies),
+            vec![
+                name("Kitchen._http._tcp.local."),
+                name("Lounge._http._tcp.local.")
+            ]
+        );
+    }
+
+    /// A service instance name, which has a space and capitals in it.
+    fn kitchen() -> Name {
+        Name::from_labels(vec![&b"Kitchen Speaker"[..], b"_http", b"_tcp", b"local"]).unwrap()
+    }
+
+    #[tokio::test]
+    async fn browse_service() {
+        let responder = serve(Duration::ZERO, |request| {
+            assert!(!request.metadata.recursion_desired);
+            let query = &request.queries[0];
+            let owner = query.name().clone();
+            let data = match query.query_type() {
+                RecordType::PTR => RData::PTR(PTR(kitchen())),
+                _ if *query.name() != kitchen() => return Vec::new(),
+                RecordType::SRV => RData::SRV(SRV::new(0, 0, 80, name("kitchen.local."))),
+                RecordType::TXT => RData::TXT(TXT::new(vec!["path=/".into()])),
+                _ => return Vec::new(),
+            };
+            vec![response(
+                request,
+                vec![Record::from_rdata(owner, 120, data)],
+            )]
+        })
+        .await;
+
+        let requests = Requests::default();
+        let querier = Querier {
+            requests: &requests,
+            destinations: vec![responder],
+            hop_limit: Protocol::Mdns.hop_limit(),
+            window: Duration::from_millis(100),
+        };
+
+        let collections = querier.browse("_http._tcp.local").await;
+        let instance = kitchen().to_string();
+        let summary = collections
+            .iter()
+            .map(|c| (c.domain.as_str(), c.qtype, c.replies.len()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            summary,
+            vec![
+                ("_http._tcp.local", RecordType::PTR, 1),
+                (instance.as_str(), RecordType::SRV, 1),
+                (instance.as_str(), RecordType::TXT, 1),
+            ]
+        );
+    }
+}
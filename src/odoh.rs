@@ -0,0 +1,735 @@
+/*
+ * dog - A command-line DNS client
+ * Copyright (c) 2026 l1a and contributors
+ * Original code Copyright (c) Benjamin Sago
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Sending DNS requests through an Oblivious DNS-over-HTTPS relay, as described in
+//! RFC 9230.
+//!
+//! Each request gets encrypted with the public key of the target, the
+//! DNS-over-HTTPS server that answers it, and sent to the relay, which
+//! passes it on without telling the target where it came from. The relay
+//! knows who sent the request, but can’t read it; the target can read it,
+//! but doesn’t know who sent it. The target’s public key is fetched from a
+//! well-known URL the first time a request is sent to it.
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::net::SocketAddr;
+
+use bytes::Bytes;
+use hickory_resolver::net::NetError;
+use http::header::{ACCEPT, CONTENT_LENGTH, CONTENT_TYPE};
+use http::Request;
+use log::*;
+use tokio::sync::OnceCell;
+
+use crate::doh::{Client, Endpoint, HttpError, HttpResponse};
+use crate::hpke::{self, HpkeError, KEY_LENGTH, NONCE_LENGTH, SECRET_LENGTH};
+
+/// The media type of an encrypted DNS message.
+const MEDIA_TYPE: &str = "application/oblivious-dns-message";
+
+/// The path of the URL that targets serve their configurations from.
+const CONFIGS_PATH: &str = "/.well-known/odohconfigs";
+
+/// The only version of the configuration format there is.
+const CONFIG_VERSION: u16 = 0x0001;
+
+/// The type of an encrypted message holding a query.
+const QUERY: u8 = 0x01;
+
+/// The type of an encrypted message holding a response.
+const RESPONSE: u8 = 0x02;
+
+/// The size of the blocks queries get padded out to, so that their length
+/// gives away less about what’s being asked for.
+const PADDING_BLOCK: usize = 128;
+
+/// An error that stops a query being encrypted, or its response being
+/// decrypted.
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum OdohError {
+    /// The target’s configurations couldn’t be parsed.
+    InvalidConfigs,
+
+    /// None of the target’s configurations use the version and cipher
+    /// suite that dog supports.
+    NoSupportedConfig,
+
+    /// The response from the target couldn’t be parsed.
+    InvalidResponse,
+
+    /// The query couldn’t be encrypted, or the response decrypted.
+    Hpke(HpkeError),
+}
+
+impl fmt::Display for OdohError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidConfigs => write!(f, "Invalid ODoH configurations from target"),
+            Self::NoSupportedConfig => write!(f, "No supported ODoH configuration from target"),
+            Self::InvalidResponse => write!(f, "Invalid ODoH response from target"),
+            Self::Hpke(why) => write!(f, "ODoH encryption error: {why}"),
+        }
+    }
+}
+
+impl From<HpkeError> for OdohError {
+    fn from(error: HpkeError) -> Self {
+        Self::Hpke(error)
+    }
+}
+
+/// A **relay** that passes requests on to the target.
+#[derive(Debug, Clone)]
+pub struct Relay {
+    /// The address of the relay.
+    pub addr: SocketAddr,
+
+    /// The relay’s host name, which its certificate is checked against.
+    pub server_name: String,
+
+    /// The path of the relay’s URL.
+    pub path: String,
+}
+
+impl Relay {
+    /// The endpoint to send requests to the relay at.
+    fn endpoint(&self) -> Endpoint<'_> {
+        Endpoint {
+            addr: self.addr,
+            server_name: &self.server_name,
+            path: &self.path,
+        }
+    }
+
+    /// The URL to send requests for the given target to, which names the
+    /// target’s host and path in the query string.
+    fn url_for(&self, target: Endpoint<'_>) -> String {
+        let separator = if self.path.contains('?') { '&' } else { '?' };
+        self.endpoint().url(&format!(
+            "{}{separator}targethost={}&targetpath={}",
+            self.path,
+            percent_encode(target.server_name),
+            percent_encode(target.path),
+        ))
+    }
+}
+
+impl fmt::Display for Relay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.endpoint().url(&self.path), self.addr)
+    }
+}
+
+/// A **route** to one target through a relay, which holds on to the
+/// target’s configuration once it’s been fetched.
+#[derive(Debug)]
+pub struct Route {
+    /// The relay to send requests through.
+    relay: Relay,
+
+    /// The target’s configuration, once it’s been fetched.
+    config: OnceCell<Config>,
+}
+
+impl Route {
+    /// Creates a new route through the given relay.
+    pub fn new(relay: Relay) -> Self {
+        Self {
+            relay,
+            config: OnceCell::new(),
+        }
+    }
+
+    /// The relay that requests get sent through.
+    pub fn relay(&self) -> &Relay {
+        &self.relay
+    }
+
+    /// Sends a DNS message to the target through the relay, returning the
+    /// relay’s HTTP response and the decrypted DNS message in its body.
+    /// The target’s configuration gets fetched first if it hasn’t been
+    /// already.
+    pub async fn exchange(
+        &self,
+        client: &Client,
+        target: Endpoint<'_>,
+        message: Vec<u8>,
+    ) -> Result<(HttpResponse, Vec<u8>), HttpError> {
+        let config = self
+            .config
+            .get_or_try_init(|| fetch_config(client, target))
+            .await?;
+
+        let (body, query) = Query::encrypt(config, &message).map_err(odoh_error)?;
+        let request = Request::post(self.relay.url_for(target))
+            .header(CONTENT_TYPE, MEDIA_TYPE)
+            .header(CONTENT_LENGTH, body.len())
+            .header(ACCEPT, MEDIA_TYPE)
+            .body(Some(Bytes::from(body)))
+            .map_err(|e| NetError::from(format!("Invalid HTTP request: {e}")))?;
+
+        let (http, body) = client
+            .fetch(self.relay.endpoint(), request, Some(MEDIA_TYPE))
+            .await?;
+        let message = query.decrypt_response(&body).map_err(odoh_error)?;
+        Ok((http, message))
+    }
+}
+
+/// Fetches the target’s configurations from its well-known URL, picking
+/// the one to encrypt queries with.
+async fn fetch_config(client: &Client, target: Endpoint<'_>) -> Result<Config, NetError> {
+    let request = Request::get(target.url(CONFIGS_PATH))
+        .body(None)
+        .map_err(|e| NetError::from(format!("Invalid HTTP request: {e}")))?;
+
+    let (_, body) = client.fetch(target, request, None).await?;
+    let config = Config::parse(&body).map_err(odoh_error)?;
+    debug!("Using ODoH config with key ID {:02x?}", config.key_id);
+    Ok(config)
+}
+
+/// Converts an Oblivious DNS-over-HTTPS error into a network error.
+fn odoh_error(error: OdohError) -> NetError {
+    NetError::from(error.to_string())
+}
+
+/// A target’s **configuration**, which holds the public key to encrypt
+/// queries to it with.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// The target’s public key.
+    public_key: [u8; SECRET_LENGTH],
+
+    /// The identifier of the key, which gets sent along with each query so
+    /// the target knows which key to decrypt it with.
+    key_id: [u8; SECRET_LENGTH],
+}
+
+impl Config {
+    /// Creates a configuration for the given public key.
+    fn new(public_key: [u8; SECRET_LENGTH]) -> Self {
+        let prk = hpke::extract(b"", &config_contents(&public_key));
+        let mut key_id = [0; SECRET_LENGTH];
+        hpke::expand(&prk, b"odoh key id", &mut key_id);
+
+        Self { public_key, key_id }
+    }
+
+    /// Parses a list of configurations, returning the first one with the
+    /// version and cipher suite that dog supports. Others are skipped.
+    fn parse(input: &[u8]) -> Result<Self, OdohError> {
+        let mut input = Reader(input);
+        let mut configs = input.vector().ok_or(OdohError::InvalidConfigs)?;
+        if !input.is_empty() {
+            return Err(OdohError::InvalidConfigs);
+        }
+
+        while !configs.is_empty() {
+            let version = configs.u16().ok_or(OdohError::InvalidConfigs)?;
+            let mut contents = configs.vector().ok_or(OdohError::InvalidConfigs)?;
+            if version != CONFIG_VERSION {
+                continue;
+            }
+
+            let suite = (contents.u16(), contents.u16(), contents.u16());
+            let public_key = contents.vector().ok_or(OdohError::InvalidConfigs)?;
+            let supported = (
+                Some(hpke::KEM_X25519),
+                Some(hpke::KDF_SHA256),
+                Some(hpke::AEAD_AES_128_GCM),
+            );
+            if suite == supported {
+                if let Ok(public_key) = <[u8; SECRET_LENGTH]>::try_from(public_key.0) {
+                    return Ok(Self::new(public_key));
+                }
+            }
+        }
+
+        Err(OdohError::NoSupportedConfig)
+    }
+}
+
+/// The contents of a configuration, which the key ID is derived from.
+fn config_contents(public_key: &[u8; SECRET_LENGTH]) -> Vec<u8> {
+    let mut contents = Vec::new();
+    contents.extend_from_slice(&hpke::KEM_X25519.to_be_bytes());
+    contents.extend_from_slice(&hpke::KDF_SHA256.to_be_bytes());
+    contents.extend_from_slice(&hpke::AEAD_AES_128_GCM.to_be_bytes());
+    push_vector(&mut contents, public_key);
+    contents
+}
+
+/// A **query** that’s been encrypted, holding on to what’s needed to
+/// decrypt its response.
+#[derive(Debug)]
+pub struct Query {
+    /// The query before it was encrypted, which goes into the key for the
+    /// response.
+    plaintext: Vec<u8>,
+
+    /// The secret exported from the encryption context, which the key for
+    /// the response is derived from.
+    secret: [u8; KEY_LENGTH],
+}
+
+impl Query {
+    /// Encrypts a DNS message for the target with the given configuration,
+    /// returning the encrypted message to send.
+    fn encrypt(config: &Config, message: &[u8]) -> Result<(Vec<u8>, Self), OdohError> {
+        let plaintext = encode_plaintext(message);
+        let (encapsulated, mut context) = hpke::setup_sender(&config.public_key, b"odoh query")?;
+
+        let mut aad = vec![QUERY];
+        push_vector(&mut aad, &config.key_id);
+        let ciphertext = context.seal(&aad, &plaintext);
+
+        let mut secret = [0; KEY_LENGTH];
+        context.export(b"odoh response", &mut secret);
+
+        let encrypted = [&encapsulated[..], &ciphertext].concat();
+        let body = encode_message(QUERY, &config.key_id, &encrypted);
+        Ok((body, Self { plaintext, secret }))
+    }
+
+    /// Decrypts the encrypted response to this query, returning the DNS
+    /// message inside it.
+    fn decrypt_response(&self, body: &[u8]) -> Result<Vec<u8>, OdohError> {
+        let (message_type, nonce, ciphertext) =
+            decode_message(body).ok_or(OdohError::InvalidResponse)?;
+        if message_type != RESPONSE {
+            return Err(OdohError::InvalidResponse);
+        }
+
+        let (key, aead_nonce) = response_key(&self.plaintext, &self.secret, nonce);
+        let mut aad = vec![RESPONSE];
+        push_vector(&mut aad, nonce);
+
+        let plaintext = hpke::open(&key, &aead_nonce, &aad, ciphertext)?;
+        decode_plaintext(&plaintext).ok_or(OdohError::InvalidResponse)
+    }
+}
+
+/// Derives the key and nonce that the response to a query is encrypted
+/// with, from the query, the secret exported from its encryption context,
+/// and the nonce the target picked.
+fn response_key(
+    plaintext: &[u8],
+    secret: &[u8],
+    response_nonce: &[u8],
+) -> ([u8; KEY_LENGTH], [u8; NONCE_LENGTH]) {
+    let mut salt = plaintext.to_vec();
+    push_vector(&mut salt, response_nonce);
+    let prk = hpke::extract(&salt, secret);
+
+    let mut key = [0; KEY_LENGTH];
+    let mut nonce = [0; NONCE_LENGTH];
+    hpke::expand(&prk, b"odoh key", &mut key);
+    hpke::expand(&prk, b"odoh nonce", &mut nonce);
+    (key, nonce)
+}
+
+/// Encodes a DNS message to be encrypted, padding it out to a multiple of
+/// the block size.
+fn encode_plaintext(message: &[u8]) -> Vec<u8> {
+    let padding = (PADDING_BLOCK - message.len() % PADDING_BLOCK) % PADDING_BLOCK;
+
+    let mut plaintext = Vec::with_capacity(message.len() + padding + 4);
+    push_vector(&mut plaintext, message);
+    push_vector(&mut plaintext, &vec![0; padding]);
+    plaintext
+}
+
+/// Decodes a decrypted DNS message, checking that its padding is all
+/// zeroes.
+fn decode_plaintext(plaintext: &[u8]) -> Option<Vec<u8>> {
+    let mut reader = Reader(plaintext);
+    let message = reader.vector()?;
+    let padding = reader.vector()?;
+
+    if reader.is_empty() && padding.0.iter().all(|b| *b == 0) {
+        Some(message.0.to_vec())
+    } else {
+        None
+    }
+}
+
+/// Encodes an encrypted message, with its type, and the key ID or nonce
+/// that goes with it.
+fn encode_message(message_type: u8, key_id: &[u8], encrypted: &[u8]) -> Vec<u8> {
+    let mut message = vec![message_type];
+    push_vector(&mut message, key_id);
+    push_vector(&mut message, encrypted);
+    message
+}
+
+/// Decodes an encrypted message into its type, the key ID or nonce that
+/// goes with it, and the encrypted part.
+fn decode_message(message: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let mut reader = Reader(message);
+    let message_type = reader.u8()?;
+    let key_id = reader.vector()?;
+    let encrypted = reader.vector()?;
+
+    if reader.is_empty() {
+        Some((message_type, key_id.0, encrypted.0))
+    } else {
+        None
+    }
+}
+
+/// Appends some bytes to the output, after their length as two bytes.
+fn push_vector(output: &mut Vec<u8>, bytes: &[u8]) {
+    let length = u16::try_from(bytes.len()).expect("Vector too long");
+    output.extend_from_slice(&length.to_be_bytes());
+    output.extend_from_slice(bytes);
+}
+
+/// Encodes a URL query string component, leaving only the characters
+/// that never need encoding.
+fn percent_encode(input: &str) -> String {
+    input
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                char::from(b).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+/// A **reader** takes values off the front of a byte slice.
+#[derive(Debug, Copy, Clone)]
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    /// Whether every byte has been read.
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Reads the given number of bytes.
+    fn bytes(&mut self, count: usize) -> Option<&'a [u8]> {
+        if self.0.len() < count {
+            return None;
+        }
+
+        let (bytes, rest) = self.0.split_at(count);
+        self.0 = rest;
+        Some(bytes)
+    }
+
+    /// Reads a single byte.
+    fn u8(&mut self) -> Option<u8> {
+        self.bytes(1).map(|b| b[0])
+    }
+
+    /// Reads a two-byte number.
+    fn u16(&mut self) -> Option<u16> {
+        self.bytes(2).map(|b| u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    /// Reads some bytes that come after their length as two bytes.
+    fn vector(&mut self) -> Option<Reader<'a>> {
+        let length = self.u16()?;
+        self.bytes(usize::from(length)).map(Reader)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::doh::test::{client, serve_tls};
+    use crate::doh::HttpVersion;
+    use http::StatusCode;
+    use pretty_assertions::assert_eq;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// The private key of the stand-in target.
+    const PRIVATE_KEY: [u8; SECRET_LENGTH] = [42; SECRET_LENGTH];
+
+    /// Encodes a list of configurations for the given public keys, with
+    /// the given versions.
+    fn encode_configs(configs: &[(u16, [u8; SECRET_LENGTH])]) -> Vec<u8> {
+        let mut list = Vec::new();
+        for (version, public_key) in configs {
+            list.extend_from_slice(&version.to_be_bytes());
+            push_vector(&mut list, &config_contents(public_key));
+        }
+
+        let mut output = Vec::new();
+        push_vector(&mut output, &list);
+        output
+    }
+
+    /// Decrypts a query as the target does, then encrypts the response to
+    /// it, which is the query with its QR bit set.
+    fn answer(body: &[u8]) -> Vec<u8> {
+        let config = Config::new(hpke::public_key_for(PRIVATE_KEY));
+        let (message_type, key_id, encrypted) = decode_message(body).unwrap();
+        assert_eq!(message_type, QUERY);
+        assert_eq!(key_id, config.key_id);
+
+        let (encapsulated, ciphertext) = encrypted.split_at(SECRET_LENGTH);
+        let encapsulated = <[u8; SECRET_LENGTH]>::try_from(encapsulated).unwrap();
+        let mut context = hpke::setup_receiver(&encapsulated, PRIVATE_KEY, b"odoh query").unwrap();
+
+        let mut aad = vec![QUERY];
+        push_vector(&mut aad, key_id);
+        let plaintext = context.open(&aad, ciphertext).unwrap();
+        assert_eq!(plaintext.len() % PADDING_BLOCK, 4);
+
+        let mut response = decode_plaintext(&plaintext).unwrap();
+        response[2] |= 0x80;
+
+        let mut secret = [0; KEY_LENGTH];
+        context.export(b"odoh response", &mut secret);
+        let nonce = [7; KEY_LENGTH];
+        let (key, aead_nonce) = response_key(&plaintext, &secret, &nonce);
+
+        let mut aad = vec![RESPONSE];
+        push_vector(&mut aad, &nonce);
+        let ciphertext = hpke::seal(&key, &aead_nonce, &aad, &encode_plaintext(&response));
+        encode_message(RESPONSE, &nonce, &ciphertext)
+    }
+
+    fn response(content_type: &str) -> http::response::Builder {
+        http::Response::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, content_type)
+    }
+
+    /// Starts a stand-in target, which serves its configuration and
+    /// answers queries, counting how many times its configuration was
+    /// fetched. It runs on its own thread, so it can still answer while
+    /// the relay is blocked waiting for it.
+    fn serve_target(config_fetches: Arc<AtomicUsize>) -> SocketAddr {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            runtime.block_on(async {
+                sender
+                    .send(serve_target_here(config_fetches).await)
+                    .unwrap();
+                std::future::pending::<()>().await;
+            });
+        });
+
+        receiver.recv().unwrap()
+    }
+
+    async fn serve_target_here(config_fetches: Arc<AtomicUsize>) -> SocketAddr {
+        serve_tls(move |parts, body| {
+            if parts.uri.path() == CONFIGS_PATH {
+                config_fetches.fetch_add(1, Ordering::SeqCst);
+                let public_key = hpke::public_key_for(PRIVATE_KEY);
+                let configs = encode_configs(&[(0xff01, [1; 32]), (CONFIG_VERSION, public_key)]);
+                (
+                    response("application/octet-stream").body(()).unwrap(),
+                    configs,
+                )
+            } else {
+                assert_eq!(parts.uri.path(), "/dns-query");
+                assert_eq!(parts.headers[CONTENT_TYPE], MEDIA_TYPE);
+                (response(MEDIA_TYPE).body(()).unwrap(), answer(&body))
+            }
+        })
+        .await
+    }
+
+    /// Starts a stand-in relay, which passes queries on to the target at
+    /// the given address, checking that it was asked to.
+    async fn serve_relay(target: SocketAddr) -> SocketAddr {
+        serve_tls(move |parts, body| {
+            assert_eq!(parts.uri.path(), "/proxy");
+            assert_eq!(
+                parts.uri.query(),
+                Some("targethost=doh.example&targetpath=%2Fdns-query")
+            );
+
+            // The relay’s own client can’t be used from inside the server,
+            // so the request gets passed on from another thread
+            let forwarded = std::thread::spawn(move || {
+                let runtime = tokio::runtime::Runtime::new().unwrap();
+                runtime.block_on(async {
+                    let target = Endpoint {
+                        addr: target,
+                        server_name: "doh.example",
+                        path: "/dns-query",
+                    };
+                    let request = Request::post(target.url(target.path))
+                        .header(CONTENT_TYPE, MEDIA_TYPE)
+                        .body(Some(Bytes::from(body)))
+                        .unwrap();
+                    client(HttpVersion::Http2)
+                        .fetch(target, request, Some(MEDIA_TYPE))
+                        .await
+                        .unwrap()
+                })
+            });
+            let (_, body) = forwarded.join().unwrap();
+
+            (response(MEDIA_TYPE).body(()).unwrap(), body)
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn exchange_through_relay() {
+        let config_fetches = Arc::new(AtomicUsize::new(0));
+        let target = serve_target(Arc::clone(&config_fetches));
+        let relay = serve_relay(target).await;
+
+        let route = Route::new(Relay {
+            addr: relay,
+            server_name: String::from("doh.example"),
+            path: String::from("/proxy"),
+        });
+        let target = Endpoint {
+            addr: target,
+            server_name: "doh.example",
+            path: "/dns-query",
+        };
+        let client = client(HttpVersion::Http2);
+
+        let query = vec![0x12, 0x34, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0];
+        for _ in 0..2 {
+            let (http, response) = route
+                .exchange(&client, target, query.clone())
+                .await
+                .unwrap();
+
+            assert_eq!(http.status, StatusCode::OK);
+            assert_eq!(
+                response,
+                vec![0x12, 0x34, 0x81, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]
+            );
+        }
+
+        assert_eq!(config_fetches.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn relay_url() {
+        let relay = Relay {
+            addr: "192.0.2.1:8443".parse().unwrap(),
+            server_name: String::from("relay.example"),
+            path: String::from("/proxy"),
+        };
+        let target = Endpoint {
+            addr: "192.0.2.2:443".parse().unwrap(),
+            server_name: "odoh.example",
+            path: "/dns-query",
+        };
+
+        assert_eq!(
+            relay.url_for(target),
+            "https://relay.example:8443/proxy?targethost=odoh.example&targetpath=%2Fdns-query"
+        );
+    }
+
+    #[test]
+    fn relay_display() {
+        let relay = Relay {
+            addr: "192.0.2.1:443".parse().unwrap(),
+            server_name: String::from("relay.example"),
+            path: String::from("/proxy"),
+        };
+
+        assert_eq!(
+            relay.to_string(),
+            "https://relay.example/proxy (192.0.2.1:443)"
+        );
+    }
+
+    #[test]
+    fn parse_config() {
+        let public_key = hpke::public_key_for(PRIVATE_KEY);
+        let config = Config::parse(&encode_configs(&[(CONFIG_VERSION, public_key)])).unwrap();
+
+        assert_eq!(config.public_key, public_key);
+    }
+
+    #[test]
+    fn parse_config_skips_unknown_versions() {
+        let configs = encode_configs(&[(0x0002, [1; 32]), (CONFIG_VERSION, [2; 32])]);
+        assert_eq!(Config::parse(&configs).unwrap().public_key, [2; 32]);
+    }
+
+    #[test]
+    fn parse_no_supported_config() {
+        let configs = encode_configs(&[(0x0002, [1; 32])]);
+        assert_eq!(
+            Config::parse(&configs).unwrap_err(),
+            OdohError::NoSupportedConfig
+        );
+    }
+
+    #[test]
+    fn parse_truncated_configs() {
+        let configs = encode_configs(&[(CONFIG_VERSION, [1; 32])]);
+        assert_eq!(
+            Config::parse(&configs[..20]).unwrap_err(),
+            OdohError::InvalidConfigs
+        );
+    }
+
+    #[test]
+    fn plaintext_padding() {
+        let plaintext = encode_plaintext(&[1; 100]);
+
+        assert_eq!(plaintext.len(), 2 + 128 + 2);
+        assert_eq!(decode_plaintext(&plaintext), Some(vec![1; 100]));
+    }
+
+    #[test]
+    fn plaintext_nonzero_padding() {
+        let mut plaintext = encode_plaintext(&[1; 100]);
+        *plaintext.last_mut().unwrap() = 1;
+
+        assert_eq!(decode_plaintext(&plaintext), None);
+    }
+
+    #[test]
+    fn response_round_trip() {
+        let config = Config::new(hpke::public_key_for(PRIVATE_KEY));
+        let (body, query) = Query::encrypt(&config, &[0, 1, 0, 0]).unwrap();
+
+        assert_eq!(
+            query.decrypt_response(&answer(&body)),
+            Ok(vec![0, 1, 0x80, 0])
+        );
+    }
+
+    #[test]
+    fn response_tampered() {
+        let config = Config::new(hpke::public_key_for(PRIVATE_KEY));
+        let (body, query) = Query::encrypt(&config, &[0, 1, 0, 0]).unwrap();
+        let mut response = answer(&body);
+        *response.last_mut().unwrap() ^= 1;
+
+        assert_eq!(
+            query.decrypt_response(&response),
+            Err(OdohError::Hpke(HpkeError::Decryption))
+        );
+    }
+}
@@ -20,13 +20,28 @@
 //! Command-line option parsing.
 
 use std::fmt;
-use std::net::IpAddr;
+use std::fs;
+use std::net::{IpAddr, Ipv6Addr};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use log::*;
 
-use hickory_resolver::proto::rr::RecordType;
+use hickory_resolver::proto::rr::{DNSClass, RecordType};
+use tokio::sync::Semaphore;
 
+use crate::dnscrypt::Provider;
+use crate::doh;
+use crate::multicast::{self, DEFAULT_WINDOW};
 use crate::output::{OutputFormat, TextFormat, UseColours};
+use crate::pinning::CertificateHash;
+use crate::resolv::ResolvConf;
+use crate::resolvers::{Registry, RegistryError};
+use crate::stamp::{self, Stamp, StampError};
+use crate::trace::{RootHints, RootHintsError};
+use crate::transport::DEFAULT_TIMEOUT;
+use crate::tsig::{TsigKey, TsigKeyError};
+use crate::validate::{TrustAnchor, TrustAnchorError};
 
 #[path = "cli.rs"]
 pub mod cli;
@@ -50,12 +65,190 @@ pub struct Requests {
     /// The inputs to generate requests from.
     pub inputs: Inputs,
 
-    /// Whether to request DNSSEC validation.
+    /// Whether to set the DO bit, asking for DNSSEC records to be sent.
     pub dnssec: bool,
+
+    /// Whether to send and display OPT records.
+    pub edns: UseEDNS,
+
+    /// The transaction ID to send, or `None` to pick a random one.
+    pub txid: Option<u16>,
+
+    /// The uncommon protocol tweaks to apply to each request.
+    pub tweaks: ProtocolTweaks,
+
+    /// How many queries can be sent at once, and how quickly.
+    pub limits: Limits,
+
+    /// How long to wait for each query, and how many times to send it.
+    pub attempts: Attempts,
+
+    /// The HTTP method to send DNS-over-HTTPS requests with.
+    pub doh_method: doh::Method,
+
+    /// The version of HTTP to send DNS-over-HTTPS requests with.
+    pub http_version: doh::HttpVersion,
+
+    /// The relay to send Oblivious DNS-over-HTTPS requests through, if any.
+    pub odoh_relay: Option<Nameserver>,
+
+    /// The resolvers that can be picked by name.
+    pub resolvers: Registry,
+
+    /// The system’s resolver configuration, which gives the nameservers to
+    /// use when none are given, and the search list.
+    pub system: ResolvConf,
+
+    /// Whether to try appending the domains in the system’s search list to
+    /// names that aren’t fully-qualified.
+    pub search: bool,
+
+    /// The key to sign each request with, if any.
+    pub tsig: Option<TsigKey>,
+
+    /// The root servers to trace the delegation chain from, if tracing.
+    pub trace: Option<RootHints>,
+
+    /// The multicast groups to send queries to instead of nameservers, if
+    /// querying the local link.
+    pub multicast: Option<Multicast>,
+
+    /// The trust anchor to validate DNSSEC signatures against, if
+    /// validating. It’s boxed because it holds a domain name, which would
+    /// make the options much larger than the error variants.
+    pub validate: Option<Box<TrustAnchor>>,
 }
 
-/// The transport protocol to use for DNS queries.
+/// Uncommon settings for the header flags and EDNS record of requests,
+/// given using the `-Z` option.
+#[derive(PartialEq, Debug, Copy, Clone, Default)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct ProtocolTweaks {
+    /// Set the AA (Authoritative Answer) flag in the header.
+    pub set_authoritative_flag: bool,
+
+    /// Set the AD (Authentic Data) flag in the header.
+    pub set_authentic_flag: bool,
+
+    /// Set the CD (Checking Disabled) flag in the header.
+    pub set_checking_disabled_flag: bool,
+
+    /// Clear the RD (Recursion Desired) flag in the header, so that the
+    /// nameserver only answers from its cache or its own zones.
+    pub no_recursion: bool,
+
+    /// The UDP payload size to advertise in the OPT record, rather than
+    /// the default.
+    pub udp_payload_size: Option<u16>,
+
+    /// Show truncated UDP responses instead of re-sending the request over
+    /// TCP.
+    pub no_tc_retry: bool,
+}
+
+/// How many queries can be waiting for a response at once, and how many
+/// can be sent each second, given using `--concurrency` and `--qps`.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct Limits {
+    /// The most queries that can be waiting for a response at once.
+    pub concurrency: usize,
+
+    /// The most queries that can be sent each second, or `None` for no
+    /// limit.
+    pub qps: Option<u32>,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            concurrency: DEFAULT_CONCURRENCY,
+            qps: None,
+        }
+    }
+}
+
+/// Which multicast protocol to send queries with, and how long to listen
+/// for responses, given using `--mdns`, `--llmnr`, `--browse`, and
+/// `--listen`.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Multicast {
+    /// The protocol, which decides the groups the queries get sent to.
+    pub protocol: multicast::Protocol,
+
+    /// The domains to send over multicast rather than to the nameservers.
+    pub domains: Vec<String>,
+
+    /// How long to keep listening for responses after sending each query.
+    pub window: Duration,
+
+    /// The DNS-SD service types to browse for instances of.
+    pub services: Vec<String>,
+}
+
+/// How many queries can be waiting for a response at once if the user
+/// doesn’t say, which is enough for one ANY sweep to be sent all at once.
+pub const DEFAULT_CONCURRENCY: usize = 32;
+
+/// The most times a query can be sent, so a typo in `--tries` doesn’t
+/// leave dog retrying for hours.
+pub const MAX_TRIES: u32 = 10;
+
+/// How long to wait for a response to each query, and how many times to
+/// send it before giving up, given using `--timeout`, `--tries`, and
+/// `--retry-tcp`.
 #[derive(PartialEq, Debug, Copy, Clone)]
+pub struct Attempts {
+    /// How long to wait for a nameserver to respond.
+    pub timeout: Duration,
+
+    /// How many times to send a query that times out or gets a SERVFAIL
+    /// response, including the first time.
+    pub tries: u32,
+
+    /// Whether to re-send a query over TCP if sending it over UDP failed.
+    pub retry_tcp: bool,
+}
+
+impl Default for Attempts {
+    fn default() -> Self {
+        Self {
+            timeout: DEFAULT_TIMEOUT,
+            tries: 3,
+            retry_tcp: false,
+        }
+    }
+}
+
+/// Whether to send or display OPT records.
+#[derive(PartialEq, Debug, Copy, Clone, Default)]
+pub enum UseEDNS {
+    /// Do not send an OPT record in requests.
+    Disable,
+
+    /// Send an OPT record in requests, but hide the one in the response.
+    /// This is the default, because the information is usually not useful
+    /// to the user.
+    #[default]
+    SendAndHide,
+
+    /// Send an OPT record in requests, and show the one in the response.
+    SendAndShow,
+}
+
+impl UseEDNS {
+    /// Whether an OPT record should be added to requests.
+    pub fn should_send(self) -> bool {
+        self != Self::Disable
+    }
+
+    /// Whether the OPT record in a response should be displayed.
+    pub fn should_show(self) -> bool {
+        self == Self::SendAndShow
+    }
+}
+
+/// The transport protocol to use for DNS queries.
+#[derive(PartialEq, Eq, Hash, Debug, Copy, Clone)]
 pub enum TransportType {
     /// UDP transport.
     UDP,
@@ -65,18 +258,36 @@ pub enum TransportType {
     TLS,
     /// HTTPS transport.
     HTTPS,
+    /// QUIC transport.
+    QUIC,
 }
 
 impl Options {
+    /// Parses and interprets a set of options like `getopts_with`, but
+    /// without a system resolver configuration or the user’s resolvers, so
+    /// that tests don’t depend on the machine running them.
+    #[cfg(test)]
+    pub fn getopts<C>(args: C) -> OptionsResult
+    where
+        C: IntoIterator,
+        C::Item: Into<std::ffi::OsString> + Clone,
+    {
+        Self::getopts_with(args, ResolvConf::default(), Registry::builtin())
+    }
+
     /// Parses and interprets a set of options from the user’s command-line
-    /// arguments.
+    /// arguments. The system’s resolver configuration supplies the
+    /// nameservers and search list, and the timeout and number of tries
+    /// if the user doesn’t give them. The resolvers that can be picked by
+    /// name come from `load_user_resolvers`, with any `--resolvers` file
+    /// added to them.
     ///
     /// This returns an `Ok` set of options if successful and running
     /// normally, a `Help` or `Version` variant if one of those options is
     /// specified, or an error variant if there’s an invalid option or
     /// inconsistency within the options after they were parsed.
     #[allow(unused_results)]
-    pub fn getopts<C>(args: C) -> OptionsResult
+    pub fn getopts_with<C>(args: C, system: ResolvConf, resolvers: Registry) -> OptionsResult
     where
         C: IntoIterator,
         C::Item: Into<std::ffi::OsString> + Clone,
@@ -96,6 +307,11 @@ impl Options {
             OptionsResult::Help(HelpReason::Flag, uc)
         } else if matches.get_flag("list") {
             OptionsResult::ListTypes
+        } else if matches.get_flag("list-resolvers") {
+            match load_resolvers(&matches, resolvers) {
+                Ok(registry) => OptionsResult::ListResolvers(registry),
+                Err(e) => OptionsResult::InvalidOptions(e),
+            }
         } else if let Some(shell) = matches.get_one::<String>("completions") {
             OptionsResult::Completions(shell.clone())
         } else {
@@ -105,14 +321,28 @@ impl Options {
                 Some(TransportType::TCP)
             } else if matches.get_flag("tls") {
                 Some(TransportType::TLS)
-            } else if matches.get_flag("https") {
+            } else if matches.get_flag("https")
+                || matches.get_flag("http3")
+                || matches.contains_id("odoh")
+            {
                 Some(TransportType::HTTPS)
+            } else if matches.get_flag("quic") {
+                Some(TransportType::QUIC)
             } else {
                 None
             };
-            match Self::deduce(&matches, transport_type) {
+            match Self::deduce(&matches, transport_type, system, resolvers) {
                 Ok(opts) => {
-                    if opts.requests.inputs.domains.is_empty() {
+                    let multicasting = opts
+                        .requests
+                        .multicast
+                        .as_ref()
+                        .is_some_and(|m| !m.domains.is_empty() || !m.services.is_empty());
+
+                    if opts.requests.inputs.domains.is_empty()
+                        && opts.requests.inputs.batch_file.is_none()
+                        && !multicasting
+                    {
                         OptionsResult::Help(HelpReason::NoDomains, uc)
                     } else {
                         OptionsResult::Ok(opts)
@@ -127,10 +357,12 @@ impl Options {
     fn deduce(
         matches: &clap::ArgMatches,
         transport_type: Option<TransportType>,
+        system: ResolvConf,
+        resolvers: Registry,
     ) -> Result<Self, OptionsError> {
         let verbose = matches.get_flag("verbose");
         let format = OutputFormat::deduce(matches);
-        let requests = Requests::deduce(matches, transport_type)?;
+        let requests = Requests::deduce(matches, transport_type, system, resolvers)?;
 
         Ok(Self {
             requests,
@@ -141,33 +373,303 @@ impl Options {
 }
 
 impl Requests {
-    /// Deduce the requests from the command-line matches.
+    /// Deduce the requests from the command-line matches, the system’s
+    /// resolver configuration, and the resolvers that can be picked by name.
     fn deduce(
         matches: &clap::ArgMatches,
         transport_type: Option<TransportType>,
+        system: ResolvConf,
+        resolvers: Registry,
     ) -> Result<Self, OptionsError> {
         let mut dnssec = false;
-        let tweaks = matches
+        let mut tweaks = ProtocolTweaks::default();
+        let tweak_args = matches
             .get_many::<String>("Z")
             .unwrap_or_default()
             .cloned()
             .collect::<Vec<_>>();
-        for tweak in tweaks {
+        for tweak in tweak_args {
             if tweak.eq_ignore_ascii_case("do") || tweak.eq_ignore_ascii_case("dnssec-ok") {
                 dnssec = true;
             } else {
-                return Err(OptionsError::InvalidTweak(tweak));
+                tweaks.set_tweak(&tweak)?;
+            }
+        }
+
+        let edns = match matches.get_one::<String>("edns") {
+            Some(setting) => UseEDNS::deduce(setting)?,
+            None => UseEDNS::default(),
+        };
+
+        let txid = match matches.get_one::<String>("txid") {
+            Some(txid) => Some(
+                txid.parse::<u16>()
+                    .map_err(|_| OptionsError::InvalidTxid(txid.clone()))?,
+            ),
+            None => None,
+        };
+
+        let limits = Limits::deduce(matches)?;
+        let attempts = Attempts::deduce(matches, &system)?;
+        let search = !matches.get_flag("no-search");
+
+        let doh_method = match matches.get_one::<String>("doh-method") {
+            Some(method) if method.eq_ignore_ascii_case("get") => doh::Method::Get,
+            _ => doh::Method::Post,
+        };
+
+        let http_version = if matches.get_flag("http3") {
+            doh::HttpVersion::Http3
+        } else {
+            doh::HttpVersion::Http2
+        };
+
+        // The relay has to be given as a URL, because it needs a path to
+        // send requests to
+        let odoh_relay = match matches.get_one::<String>("odoh") {
+            Some(relay) => match Nameserver::parse(relay) {
+                Ok(nameserver) if nameserver.path.is_some() => Some(nameserver),
+                _ => return Err(OptionsError::InvalidRelay(relay.clone())),
+            },
+            None => None,
+        };
+
+        let resolvers = load_resolvers(matches, resolvers)?;
+
+        let tsig = match matches.get_one::<String>("tsig") {
+            Some(key) => Some(load_tsig_key(key)?),
+            None => None,
+        };
+
+        let trace = if matches.get_flag("trace") {
+            match matches.get_one::<String>("root-hints") {
+                Some(path) => Some(load_root_hints(path)?),
+                None => Some(RootHints::builtin()),
+            }
+        } else {
+            None
+        };
+
+        // Validation needs the signatures, which only get sent when the DO
+        // bit is set
+        let validate = if matches.get_flag("validate") {
+            dnssec = true;
+            match matches.get_one::<String>("trust-anchor") {
+                Some(path) => Some(Box::new(load_trust_anchor(path)?)),
+                None => Some(Box::new(TrustAnchor::builtin())),
+            }
+        } else {
+            None
+        };
+
+        // The DO bit lives in the OPT record, so it can’t be sent without
+        // one
+        if dnssec && !edns.should_send() {
+            return Err(OptionsError::DnssecWithoutEdns);
+        }
+
+        let mut inputs = Inputs::deduce(matches, transport_type)?;
+        let multicast = Multicast::deduce(matches, &mut inputs)?;
+
+        Ok(Self {
+            inputs,
+            dnssec,
+            edns,
+            txid,
+            tweaks,
+            limits,
+            attempts,
+            doh_method,
+            http_version,
+            odoh_relay,
+            resolvers,
+            system,
+            search,
+            tsig,
+            trace,
+            multicast,
+            validate,
+        })
+    }
+}
+
+impl ProtocolTweaks {
+    /// Applies one of the tweaks given with `-Z`, returning an error if it
+    /// isn’t one that dog knows about.
+    fn set_tweak(&mut self, tweak: &str) -> Result<(), OptionsError> {
+        let lowercase = tweak.to_ascii_lowercase();
+
+        match lowercase.as_str() {
+            "aa" | "authoritative" => self.set_authoritative_flag = true,
+            "ad" | "authentic" => self.set_authentic_flag = true,
+            "cd" | "checking-disabled" => self.set_checking_disabled_flag = true,
+            "rd=0" => self.no_recursion = true,
+            "tc-retry=off" => self.no_tc_retry = true,
+            _ => {
+                let size = lowercase
+                    .strip_prefix("bufsize=")
+                    .and_then(|size| size.parse::<u16>().ok())
+                    .ok_or_else(|| OptionsError::InvalidTweak(tweak.to_string()))?;
+                self.udp_payload_size = Some(size);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Limits {
+    /// Deduce the limits from the `--concurrency` and `--qps` options,
+    /// neither of which can be zero. The concurrency can’t be more than
+    /// the number of permits a semaphore can hold, either.
+    fn deduce(matches: &clap::ArgMatches) -> Result<Self, OptionsError> {
+        let mut limits = Self::default();
+
+        if let Some(concurrency) = matches.get_one::<String>("concurrency") {
+            limits.concurrency = concurrency
+                .parse::<usize>()
+                .ok()
+                .filter(|c| (1..=Semaphore::MAX_PERMITS).contains(c))
+                .ok_or_else(|| OptionsError::InvalidConcurrency(concurrency.clone()))?;
+        }
+
+        if let Some(qps) = matches.get_one::<String>("qps") {
+            let qps = qps
+                .parse::<u32>()
+                .ok()
+                .filter(|q| *q > 0)
+                .ok_or_else(|| OptionsError::InvalidQps(qps.clone()))?;
+            limits.qps = Some(qps);
+        }
+
+        Ok(limits)
+    }
+}
+
+impl Multicast {
+    /// Deduce the multicast settings from the `--mdns`, `--llmnr`,
+    /// `--browse`, and `--listen` options, returning `None` if queries
+    /// should be sent to nameservers as usual. Browsing for a service
+    /// implies `--mdns`. The domains to send over multicast get taken out
+    /// of the inputs.
+    fn deduce(
+        matches: &clap::ArgMatches,
+        inputs: &mut Inputs,
+    ) -> Result<Option<Self>, OptionsError> {
+        let services = matches
+            .get_many::<String>("browse")
+            .unwrap_or_default()
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let protocol = if matches.get_flag("llmnr") {
+            multicast::Protocol::Llmnr
+        } else if matches.get_flag("mdns") || !services.is_empty() {
+            multicast::Protocol::Mdns
+        } else {
+            return Ok(None);
+        };
+
+        let window = match matches.get_one::<String>("listen") {
+            Some(window) => {
+                parse_duration(window).ok_or_else(|| OptionsError::InvalidListen(window.clone()))?
+            }
+            None => DEFAULT_WINDOW,
+        };
+
+        let mut multicast = Self {
+            protocol,
+            domains: Vec::new(),
+            window,
+            services,
+        };
+        multicast.domains = multicast.take_domains(inputs);
+        Ok(Some(multicast))
+    }
+
+    /// Takes the domains to send over multicast out of some inputs, such
+    /// as those on the command line or on a line of a batch file: all of
+    /// them for mDNS, but only the single-label ones for LLMNR, which isn’t
+    /// meant for anything else. Names with more than one label still get
+    /// sent to the nameservers.
+    pub fn take_domains(&self, inputs: &mut Inputs) -> Vec<String> {
+        match self.protocol {
+            multicast::Protocol::Llmnr => {
+                let (single, qualified) = inputs
+                    .domains
+                    .drain(..)
+                    .partition(|domain| is_single_label(domain));
+                inputs.domains = qualified;
+                single
             }
+            multicast::Protocol::Mdns => std::mem::take(&mut inputs.domains),
+        }
+    }
+}
+
+impl Attempts {
+    /// Deduce the attempts from the `--timeout`, `--tries`, and
+    /// `--retry-tcp` options, falling back to the `timeout` and `attempts`
+    /// options in the system’s resolver configuration.
+    fn deduce(matches: &clap::ArgMatches, system: &ResolvConf) -> Result<Self, OptionsError> {
+        let defaults = Self::default();
+        let mut attempts = Self {
+            timeout: system.timeout.unwrap_or(defaults.timeout),
+            tries: system.attempts.unwrap_or(defaults.tries),
+            retry_tcp: matches.get_flag("retry-tcp"),
+        };
+
+        if let Some(timeout) = matches.get_one::<String>("timeout") {
+            attempts.timeout = parse_duration(timeout)
+                .ok_or_else(|| OptionsError::InvalidTimeout(timeout.clone()))?;
         }
 
-        let inputs = Inputs::deduce(matches, transport_type)?;
+        if let Some(tries) = matches.get_one::<String>("tries") {
+            attempts.tries = tries
+                .parse::<u32>()
+                .ok()
+                .filter(|t| (1..=MAX_TRIES).contains(t))
+                .ok_or_else(|| OptionsError::InvalidTries(tries.clone()))?;
+        }
+
+        Ok(attempts)
+    }
+}
+
+/// Parses a duration given on the command line, which is a number of
+/// seconds, optionally followed by `s`, or a number of milliseconds
+/// followed by `ms`. Returns `None` if it isn’t one, or if it’s zero.
+fn parse_duration(input: &str) -> Option<Duration> {
+    let (number, scale) = match input.strip_suffix("ms") {
+        Some(millis) => (millis, 0.001),
+        None => (input.strip_suffix('s').unwrap_or(input), 1.0),
+    };
+
+    let number = number.parse::<f64>().ok()?;
+    if !number.is_finite() || number <= 0.0 {
+        return None;
+    }
+
+    Duration::try_from_secs_f64(number * scale).ok()
+}
 
-        Ok(Self { inputs, dnssec })
+impl UseEDNS {
+    /// Deduce the EDNS setting from the value of the `--edns` option.
+    fn deduce(setting: &str) -> Result<Self, OptionsError> {
+        if setting.eq_ignore_ascii_case("disable") || setting.eq_ignore_ascii_case("off") {
+            Ok(Self::Disable)
+        } else if setting.eq_ignore_ascii_case("hide") {
+            Ok(Self::SendAndHide)
+        } else if setting.eq_ignore_ascii_case("show") {
+            Ok(Self::SendAndShow)
+        } else {
+            Err(OptionsError::InvalidEDNS(setting.to_string()))
+        }
     }
 }
 
 /// Which things the user has specified they want queried.
-#[derive(PartialEq, Debug, Default)]
+#[derive(PartialEq, Debug, Clone, Default)]
 pub struct Inputs {
     /// The list of domain names to query.
     pub domains: Vec<String>,
@@ -175,6 +677,9 @@ pub struct Inputs {
     /// The list of DNS record types to query for.
     pub record_types: Vec<RecordType>,
 
+    /// The list of network classes to query for.
+    pub classes: Vec<DNSClass>,
+
     /// Whether the user requested an "ANY" query.
     pub any_query: bool,
 
@@ -182,7 +687,15 @@ pub struct Inputs {
     pub transport_type: Option<TransportType>,
 
     /// The nameservers to use.
-    pub nameservers: Vec<String>,
+    pub nameservers: Vec<Nameserver>,
+
+    /// The serial number to send with IXFR queries, which asks for the
+    /// changes made to the zone since that version.
+    pub ixfr_serial: Option<u32>,
+
+    /// The file to read more queries from, one per line, or `-` to read
+    /// them from standard input.
+    pub batch_file: Option<String>,
 }
 
 impl Inputs {
@@ -196,11 +709,53 @@ impl Inputs {
             ..Self::default()
         };
         inputs.load_named_args(matches)?;
-        inputs.load_free_args(matches);
+        inputs.load_free_args(matches)?;
         inputs.load_fallbacks();
+        inputs.batch_file = matches.get_one::<String>("file").cloned();
         Ok(inputs)
     }
 
+    /// Deduce the inputs for one line of a batch file, whose words follow
+    /// the same rules as the free arguments on the command line. Types,
+    /// classes, and nameservers that the line doesn’t give are taken from
+    /// the command line. Returns `None` for blank lines and comments, which
+    /// start with `#`.
+    pub fn for_line(&self, line: &str) -> Result<Option<Self>, OptionsError> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return Ok(None);
+        }
+
+        let mut inputs = Self {
+            transport_type: self.transport_type,
+            ..Self::default()
+        };
+
+        for argument in line.split_whitespace() {
+            inputs.load_free_arg(argument)?;
+        }
+
+        if inputs.domains.is_empty() {
+            return Err(OptionsError::NoDomainInLine(line.to_string()));
+        }
+
+        if inputs.record_types.is_empty() {
+            inputs.record_types.clone_from(&self.record_types);
+            inputs.any_query = self.any_query;
+            inputs.ixfr_serial = self.ixfr_serial;
+        }
+
+        if inputs.classes.is_empty() {
+            inputs.classes.clone_from(&self.classes);
+        }
+
+        if inputs.nameservers.is_empty() {
+            inputs.nameservers.clone_from(&self.nameservers);
+        }
+
+        Ok(Some(inputs))
+    }
+
     /// Load the named arguments from the command-line matches.
     fn load_named_args(&mut self, matches: &clap::ArgMatches) -> Result<(), OptionsError> {
         let queries = matches
@@ -218,9 +773,7 @@ impl Inputs {
             .cloned()
             .collect::<Vec<_>>();
         for record_name in types {
-            if record_name.eq_ignore_ascii_case("ANY") {
-                self.add_type(RecordType::ANY);
-            } else if let Ok(record_type) = record_name.to_uppercase().parse() {
+            if let Some(record_type) = parse_type_name(&record_name) {
                 self.add_type(record_type);
             } else {
                 return Err(OptionsError::InvalidQueryType(record_name));
@@ -233,44 +786,80 @@ impl Inputs {
             .cloned()
             .collect::<Vec<_>>();
         for ns in nameservers {
-            self.add_nameserver(&ns);
+            self.add_nameserver(&ns)?;
+        }
+
+        let classes = matches
+            .get_many::<String>("class")
+            .unwrap_or_default()
+            .cloned()
+            .collect::<Vec<_>>();
+        for class_name in classes {
+            if let Some(class) = parse_class_name(&class_name) {
+                self.add_class(class);
+            } else {
+                return Err(OptionsError::InvalidQueryClass(class_name));
+            }
         }
 
         Ok(())
     }
 
     /// Load the free arguments from the command-line matches.
-    fn load_free_args(&mut self, matches: &clap::ArgMatches) {
+    fn load_free_args(&mut self, matches: &clap::ArgMatches) -> Result<(), OptionsError> {
         let free_args = matches
             .get_many::<String>("free")
             .unwrap_or_default()
             .cloned()
             .collect::<Vec<_>>();
         for argument in free_args {
-            if let Some(nameserver) = argument.strip_prefix('@') {
-                self.add_nameserver(nameserver);
-            } else if is_constant_name(&argument) {
-                if argument.eq_ignore_ascii_case("ANY") {
-                    self.add_type(RecordType::ANY);
-                } else if let Ok(record_type) = argument.to_uppercase().parse() {
-                    trace!("Got qtype -> {:?}", &argument);
-                    self.add_type(record_type);
-                } else {
-                    trace!("Got single-word domain -> {:?}", &argument);
-                    self.add_domain(&argument);
-                }
+            self.load_free_arg(&argument)?;
+        }
+
+        Ok(())
+    }
+
+    /// Load one free argument, which is a nameserver if it starts with `@`,
+    /// a type or class if it’s one of their names, or otherwise a domain.
+    fn load_free_arg(&mut self, argument: &str) -> Result<(), OptionsError> {
+        if let Some(nameserver) = argument.strip_prefix('@') {
+            self.add_nameserver(nameserver)?;
+        } else if let Some(serial) = strip_prefix_ignore_case(argument, "IXFR=") {
+            trace!("Got IXFR serial -> {:?}", &serial);
+            let serial = serial
+                .parse()
+                .map_err(|_| OptionsError::InvalidSerial(serial.to_string()))?;
+            self.ixfr_serial = Some(serial);
+            self.add_type(RecordType::IXFR);
+        } else if is_constant_name(argument) {
+            if let Some(record_type) = parse_type_name(argument) {
+                trace!("Got qtype -> {:?}", &argument);
+                self.add_type(record_type);
+            } else if let Some(class) = parse_class_name(argument)
+                // As `in`, `ch`, and `hs` are also single-label domains,
+                // a class name only counts as one if it’s in capitals or
+                // comes after a domain
+                .filter(|_| argument == argument.to_uppercase() || !self.domains.is_empty())
+            {
+                trace!("Got class -> {:?}", &argument);
+                self.add_class(class);
             } else {
-                trace!("Got domain -> {:?}", &argument);
-
-                if let Ok(ip) = argument.parse::<IpAddr>() {
-                    let reverse_domain = reverse_lookup_domain(ip);
-                    self.add_domain(&reverse_domain);
-                    self.add_type(RecordType::PTR);
-                } else {
-                    self.add_domain(&argument);
-                }
+                trace!("Got single-word domain -> {:?}", &argument);
+                self.add_domain(argument);
+            }
+        } else {
+            trace!("Got domain -> {:?}", &argument);
+
+            if let Ok(ip) = argument.parse::<IpAddr>() {
+                let reverse_domain = reverse_lookup_domain(ip);
+                self.add_domain(&reverse_domain);
+                self.add_type(RecordType::PTR);
+            } else {
+                self.add_domain(argument);
             }
         }
+
+        Ok(())
     }
 
     /// Load the fallback values for the inputs.
@@ -278,6 +867,10 @@ impl Inputs {
         if self.record_types.is_empty() {
             self.record_types.push(RecordType::A);
         }
+
+        if self.classes.is_empty() {
+            self.classes.push(DNSClass::IN);
+        }
     }
 
     /// Add a domain to the list of domains to query.
@@ -294,73 +887,436 @@ impl Inputs {
     }
 
     /// Add a nameserver to the list of nameservers to use.
-    fn add_nameserver(&mut self, input: &str) {
-        self.nameservers.push(input.to_string());
+    fn add_nameserver(&mut self, input: &str) -> Result<(), OptionsError> {
+        self.nameservers.push(Nameserver::parse(input)?);
+        Ok(())
     }
-}
 
-/// The list of record types to query when falling back from an ANY query.
-pub const ANY_FALLBACK_TYPES: &[RecordType] = &[
-    RecordType::A,
-    RecordType::AAAA,
-    RecordType::ANAME,
-    RecordType::CAA,
-    RecordType::CDNSKEY,
-    RecordType::CDS,
-    RecordType::CNAME,
-    RecordType::CSYNC,
-    RecordType::DNSKEY,
-    RecordType::DS,
-    RecordType::HINFO,
-    RecordType::HTTPS,
-    RecordType::KEY,
-    RecordType::MX,
-    RecordType::NAPTR,
-    RecordType::NS,
-    RecordType::NSEC,
-    RecordType::NSEC3,
-    RecordType::NSEC3PARAM,
-    RecordType::OPENPGPKEY,
-    RecordType::PTR,
-    RecordType::RRSIG,
-    RecordType::SIG,
-    RecordType::SOA,
-    RecordType::SRV,
-    RecordType::SSHFP,
-    RecordType::SVCB,
-    RecordType::TLSA,
-    RecordType::TXT,
-];
+    /// Add a class to the list of classes to query.
+    fn add_class(&mut self, class: DNSClass) {
+        self.classes.push(class);
+    }
+}
 
-/// Returns `true` if the argument is a constant-like name.
-fn is_constant_name(argument: &str) -> bool {
-    let Some(first_char) = argument.chars().next() else {
-        return false;
-    };
+/// A nameserver given on the command line, as a host name or IP address,
+/// with an optional port. IPv6 addresses need to be surrounded by brackets
+/// when they have a port, such as `[::1]:5353`, and link-local ones can be
+/// followed by the network interface to send requests out of, such as
+/// `fe80::1%eth0`. DNS-over-HTTPS endpoints can be given as a whole URL,
+/// such as `https://dns.lookup.dog/dns-query`.
+#[derive(PartialEq, Eq, Hash, Debug, Clone)]
+pub struct Nameserver {
+    /// The host name or IP address of the nameserver.
+    pub host: String,
+
+    /// The index of the network interface to reach a link-local IPv6
+    /// address through, if one was given.
+    pub scope_id: Option<u32>,
+
+    /// The port to send requests to, or `None` to use the default port of
+    /// the transport.
+    pub port: Option<u16>,
+
+    /// The path to send DNS-over-HTTPS requests to, if the nameserver was
+    /// given as a URL.
+    pub path: Option<String>,
+
+    /// The address to send requests to, if it was given separately from
+    /// the host name, as it can be in a stamp.
+    pub address: Option<IpAddr>,
+
+    /// The transport the nameserver has to be reached over, if it was
+    /// given as a stamp for one.
+    pub transport_type: Option<TransportType>,
 
-    if !first_char.is_ascii_alphabetic() {
-        return false;
-    }
+    /// The DNSCrypt provider to encrypt requests for, if the nameserver was
+    /// given as a DNSCrypt stamp.
+    pub dnscrypt: Option<Provider>,
 
-    argument.chars().all(|c| c.is_ascii_alphanumeric())
+    /// The hashes of certificates in the nameserver’s chain, one of which
+    /// has to be there, if the nameserver was given as a stamp that pins
+    /// them.
+    pub certificate_hashes: Vec<CertificateHash>,
 }
 
-use std::fmt::Write;
-/// Returns the reverse lookup domain for an IP address.
-fn reverse_lookup_domain(ip: IpAddr) -> String {
-    match ip {
-        IpAddr::V4(v4) => {
-            let octets = v4.octets();
-            format!(
-                "{}.{}.{}.{}.in-addr.arpa",
-                octets[3], octets[2], octets[1], octets[0]
-            )
+impl Nameserver {
+    /// Parses a nameserver given on the command line, returning an error
+    /// if it’s an IPv6 address with a port but without brackets, or its
+    /// port or network interface isn’t valid. Port 0 can’t be sent to, so
+    /// it counts as invalid.
+    pub fn parse(input: &str) -> Result<Self, OptionsError> {
+        let invalid = || OptionsError::InvalidNameserver(input.to_string());
+        let parse_port = |port: &str| {
+            port.parse::<u16>()
+                .ok()
+                .filter(|port| *port != 0)
+                .ok_or_else(invalid)
+        };
+
+        if strip_prefix_ignore_case(input, stamp::PREFIX).is_some() {
+            let stamp = Stamp::parse(input)
+                .map_err(|e| OptionsError::InvalidStamp(input.to_string(), e))?;
+            return Ok(Self::from_stamp(stamp));
         }
-        IpAddr::V6(v6) => {
-            let mut reversed = String::new();
-            for octet in v6.octets().iter().rev() {
-                let nibble1 = octet & 0x0F;
-                let nibble2 = (octet >> 4) & 0x0F;
+
+        if let Some(url) = strip_prefix_ignore_case(input, "https://") {
+            let (authority, path) = match url.find(['/', '?']) {
+                Some(index) => url.split_at(index),
+                None => (url, ""),
+            };
+
+            let path = match path {
+                "" => String::from(doh::DEFAULT_PATH),
+                path if path.starts_with('?') => format!("/{path}"),
+                path => path.to_string(),
+            };
+
+            let nameserver = Self::parse(authority).map_err(|_| invalid())?;
+            return Ok(Self {
+                path: Some(path),
+                ..nameserver
+            });
+        }
+
+        let (address, port) = if let Some(rest) = input.strip_prefix('[') {
+            let (address, rest) = rest.split_once(']').ok_or_else(invalid)?;
+            let port = match rest.strip_prefix(':') {
+                Some(port) => Some(parse_port(port)?),
+                None if rest.is_empty() => None,
+                None => return Err(invalid()),
+            };
+            (address, port)
+        } else if input.matches(':').count() == 1 {
+            let (host, port) = input.split_once(':').ok_or_else(invalid)?;
+            (host, Some(parse_port(port)?))
+        } else {
+            (input, None)
+        };
+
+        let (host, scope) = match address.split_once('%') {
+            Some((host, scope)) => (host, Some(scope)),
+            None => (address, None),
+        };
+
+        // Anything with more than one colon has to be an IPv6 address, as
+        // do addresses in brackets and addresses with an interface
+        let is_ipv6 = host.parse::<Ipv6Addr>().is_ok();
+        if host.is_empty()
+            || (!is_ipv6 && (host.contains(':') || input.starts_with('[') || scope.is_some()))
+        {
+            return Err(invalid());
+        }
+
+        let scope_id = match scope {
+            Some(scope) => Some(
+                interface_index(scope)
+                    .ok_or_else(|| OptionsError::UnknownInterface(scope.to_string()))?,
+            ),
+            None => None,
+        };
+
+        Ok(Self {
+            host: host.to_string(),
+            scope_id,
+            port,
+            path: None,
+            address: None,
+            transport_type: None,
+            dnscrypt: None,
+            certificate_hashes: Vec::new(),
+        })
+    }
+
+    /// A nameserver for the protocol and address in a stamp. The host name
+    /// in the stamp, if it has one, is what certificates get checked
+    /// against, with the address used to reach it without a lookup.
+    fn from_stamp(stamp: Stamp) -> Self {
+        let transport_type = stamp.protocol.transport_type();
+        let (path, dnscrypt) = match stamp.protocol {
+            stamp::Protocol::Https(path) => (Some(path), None),
+            stamp::Protocol::DnsCrypt(provider) => (None, Some(provider)),
+            stamp::Protocol::Plain | stamp::Protocol::Tls | stamp::Protocol::Quic => (None, None),
+        };
+
+        let (host, address) = match (stamp.hostname, stamp.ip) {
+            (Some(hostname), ip) => (hostname, ip),
+            (None, Some(ip)) => (ip.to_string(), None),
+            (None, None) => unreachable!("Stamps have a host name or an address"),
+        };
+
+        Self {
+            host,
+            scope_id: None,
+            port: stamp.port,
+            path,
+            address,
+            transport_type,
+            dnscrypt,
+            certificate_hashes: stamp.hashes,
+        }
+    }
+
+    /// A nameserver given only by its host name or address, such as the
+    /// name of a resolver in the registry.
+    pub fn named(host: &str) -> Self {
+        Self {
+            host: host.to_string(),
+            scope_id: None,
+            port: None,
+            path: None,
+            address: None,
+            transport_type: None,
+            dnscrypt: None,
+            certificate_hashes: Vec::new(),
+        }
+    }
+
+    /// The IP address of the nameserver, or `None` if it was given as a
+    /// host name that needs to be looked up.
+    pub fn ip(&self) -> Option<IpAddr> {
+        self.host.parse().ok()
+    }
+}
+
+impl fmt::Display for Nameserver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_some() {
+            write!(f, "https://")?;
+        }
+
+        let bracketed = self.host.contains(':') && (self.port.is_some() || self.path.is_some());
+        if bracketed {
+            write!(f, "[")?;
+        }
+
+        write!(f, "{}", self.host)?;
+        if let Some(scope_id) = self.scope_id {
+            write!(f, "%{scope_id}")?;
+        }
+
+        if bracketed {
+            write!(f, "]")?;
+        }
+
+        if let Some(port) = self.port {
+            write!(f, ":{port}")?;
+        }
+
+        if let Some(path) = &self.path {
+            write!(f, "{path}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Looks up the index of a network interface from its name, or returns the
+/// number itself if it was given as one. Names can only be looked up on
+/// Linux, where the index is kept in `/sys/class/net`.
+fn interface_index(name: &str) -> Option<u32> {
+    if let Ok(index) = name.parse() {
+        return Some(index);
+    }
+
+    if !cfg!(target_os = "linux") || name.contains('/') {
+        return None;
+    }
+
+    fs::read_to_string(format!("/sys/class/net/{name}/ifindex"))
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Removes a prefix from the start of a string, ignoring its case, or
+/// returns `None` if the string doesn’t start with it.
+fn strip_prefix_ignore_case<'a>(input: &'a str, prefix: &str) -> Option<&'a str> {
+    if input.len() >= prefix.len()
+        && input.is_char_boundary(prefix.len())
+        && input[..prefix.len()].eq_ignore_ascii_case(prefix)
+    {
+        Some(&input[prefix.len()..])
+    } else {
+        None
+    }
+}
+
+/// Loads the TSIG key given with `--tsig`, which is either the path to a
+/// BIND-style key file or the key itself.
+fn load_tsig_key(input: &str) -> Result<TsigKey, OptionsError> {
+    let key = if Path::new(input).is_file() {
+        let contents = fs::read_to_string(input)
+            .map_err(|e| OptionsError::TsigKeyFile(input.to_string(), e.to_string()))?;
+        TsigKey::from_key_file(&contents)
+    } else {
+        TsigKey::from_argument(input)
+    };
+
+    key.map_err(OptionsError::InvalidTsigKey)
+}
+
+/// Loads the resolvers that can be picked by name before the command-line
+/// gets looked at: the ones built into dog, then the ones in the user’s
+/// resolvers file, if there is one, which replace the built-in ones with
+/// the same name.
+pub fn load_user_resolvers() -> Result<Registry, OptionsError> {
+    let mut registry = Registry::builtin();
+
+    if let Some(path) = user_resolvers_path().filter(|path| path.is_file()) {
+        registry.extend(read_resolvers(&path.to_string_lossy())?);
+    }
+
+    Ok(registry)
+}
+
+/// Adds the resolvers in the file given with `--resolvers`, if there is
+/// one, to the ones that were already loaded, replacing the ones with the
+/// same name.
+fn load_resolvers(
+    matches: &clap::ArgMatches,
+    mut registry: Registry,
+) -> Result<Registry, OptionsError> {
+    if let Some(path) = matches.get_one::<String>("resolvers") {
+        registry.extend(read_resolvers(path)?);
+    }
+
+    Ok(registry)
+}
+
+/// Reads the resolvers out of a resolvers file.
+fn read_resolvers(path: &str) -> Result<Registry, OptionsError> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| OptionsError::ResolversFile(path.to_string(), e.to_string()))?;
+
+    Registry::parse(&contents).map_err(|e| OptionsError::InvalidResolvers(path.to_string(), e))
+}
+
+/// The path of the user’s resolvers file, `dog/resolvers.conf` in their
+/// configuration directory.
+fn user_resolvers_path() -> Option<PathBuf> {
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+        .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))?;
+
+    Some(config_dir.join("dog").join("resolvers.conf"))
+}
+
+/// Loads the root servers to trace from out of the file given with
+/// `--root-hints`.
+fn load_root_hints(path: &str) -> Result<RootHints, OptionsError> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| OptionsError::RootHintsFile(path.to_string(), e.to_string()))?;
+
+    RootHints::parse(&contents).map_err(|e| OptionsError::InvalidRootHints(path.to_string(), e))
+}
+
+/// Loads the trust anchor to validate against out of the file given with
+/// `--trust-anchor`.
+fn load_trust_anchor(path: &str) -> Result<TrustAnchor, OptionsError> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| OptionsError::TrustAnchorFile(path.to_string(), e.to_string()))?;
+
+    TrustAnchor::parse(&contents).map_err(|e| OptionsError::InvalidTrustAnchor(path.to_string(), e))
+}
+
+/// Parses the name of a record type, returning `None` if it isn’t one. The
+/// `ANY` and `IXFR` types are handled here as hickory doesn’t parse them.
+fn parse_type_name(input: &str) -> Option<RecordType> {
+    if input.eq_ignore_ascii_case("ANY") {
+        Some(RecordType::ANY)
+    } else if input.eq_ignore_ascii_case("IXFR") {
+        Some(RecordType::IXFR)
+    } else {
+        input.to_uppercase().parse().ok()
+    }
+}
+
+/// Parses the name of a network class, returning `None` if it isn’t one of
+/// the classes that can be queried for.
+fn parse_class_name(input: &str) -> Option<DNSClass> {
+    if input.eq_ignore_ascii_case("IN") {
+        Some(DNSClass::IN)
+    } else if input.eq_ignore_ascii_case("CH") {
+        Some(DNSClass::CH)
+    } else if input.eq_ignore_ascii_case("HS") {
+        Some(DNSClass::HS)
+    } else {
+        None
+    }
+}
+
+/// The list of record types to query when falling back from an ANY query.
+pub const ANY_FALLBACK_TYPES: &[RecordType] = &[
+    RecordType::A,
+    RecordType::AAAA,
+    RecordType::ANAME,
+    RecordType::CAA,
+    RecordType::CDNSKEY,
+    RecordType::CDS,
+    RecordType::CNAME,
+    RecordType::CSYNC,
+    RecordType::DNSKEY,
+    RecordType::DS,
+    RecordType::HINFO,
+    RecordType::HTTPS,
+    RecordType::KEY,
+    RecordType::MX,
+    RecordType::NAPTR,
+    RecordType::NS,
+    RecordType::NSEC,
+    RecordType::NSEC3,
+    RecordType::NSEC3PARAM,
+    RecordType::OPENPGPKEY,
+    RecordType::PTR,
+    RecordType::RRSIG,
+    RecordType::SIG,
+    RecordType::SOA,
+    RecordType::SRV,
+    RecordType::SSHFP,
+    RecordType::SVCB,
+    RecordType::TLSA,
+    RecordType::TXT,
+];
+
+/// Returns `true` if the argument is a constant-like name.
+fn is_constant_name(argument: &str) -> bool {
+    let Some(first_char) = argument.chars().next() else {
+        return false;
+    };
+
+    if !first_char.is_ascii_alphabetic() {
+        return false;
+    }
+
+    argument.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+/// Returns `true` if the domain has only one label, such as the single
+/// words that `is_constant_name` lets through when they aren’t a type or a
+/// class. A trailing dot doesn’t count as another label.
+fn is_single_label(domain: &str) -> bool {
+    let domain = domain.strip_suffix('.').unwrap_or(domain);
+    !domain.is_empty() && !domain.contains('.')
+}
+
+use std::fmt::Write;
+/// Returns the reverse lookup domain for an IP address.
+fn reverse_lookup_domain(ip: IpAddr) -> String {
+    match ip {
+        IpAddr::V4(v4) => {
+            let octets = v4.octets();
+            format!(
+                "{}.{}.{}.{}.in-addr.arpa",
+                octets[3], octets[2], octets[1], octets[0]
+            )
+        }
+        IpAddr::V6(v6) => {
+            let mut reversed = String::new();
+            for octet in v6.octets().iter().rev() {
+                let nibble1 = octet & 0x0F;
+                let nibble2 = (octet >> 4) & 0x0F;
                 let _ = write!(reversed, "{nibble1:x}.{nibble2:x}.");
             }
             reversed.push_str("ip6.arpa");
@@ -414,8 +1370,11 @@ impl TextFormat {
     }
 }
 
-/// The result of the `Options::getopts` function.
+/// The result of the `Options::getopts` function. Only one of these gets
+/// made, so it doesn’t matter that the options are much larger than the
+/// other variants.
 #[derive(PartialEq, Debug)]
+#[allow(clippy::large_enum_variant)]
 pub enum OptionsResult {
     /// The options were parsed successfully.
     Ok(Options),
@@ -435,6 +1394,10 @@ pub enum OptionsResult {
     /// One of the arguments was `--list`, to display the list of record types.
     ListTypes,
 
+    /// One of the arguments was `--list-resolvers`, to display the list of
+    /// resolvers that can be picked by name.
+    ListResolvers(Registry),
+
     /// One of the arguments was `--completions`, to generate shell completions.
     Completions(String),
 }
@@ -453,18 +1416,106 @@ pub enum HelpReason {
 
 /// Something wrong with the combination of options the user has picked.
 #[derive(PartialEq, Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum OptionsError {
     /// The query type is invalid.
     InvalidQueryType(String),
+    /// The query class is invalid.
+    InvalidQueryClass(String),
     /// The protocol tweak is invalid.
     InvalidTweak(String),
+    /// The EDNS setting is invalid.
+    InvalidEDNS(String),
+    /// The DO bit was asked for, but EDNS is disabled, so there’s no OPT
+    /// record to set it in.
+    DnssecWithoutEdns,
+    /// The transaction ID is not a number that fits in 16 bits.
+    InvalidTxid(String),
+    /// The number of queries to send at once is not a positive number.
+    InvalidConcurrency(String),
+    /// The number of queries to send each second is not a positive number.
+    InvalidQps(String),
+    /// The timeout is not a positive duration.
+    InvalidTimeout(String),
+    /// The number of times to send each query is not a positive number, or
+    /// is more than the most allowed.
+    InvalidTries(String),
+    /// The time to listen for multicast responses is not a positive
+    /// duration.
+    InvalidListen(String),
+    /// The IXFR serial number is not a number that fits in 32 bits.
+    InvalidSerial(String),
+    /// The TSIG key is invalid. The input isn’t kept, as it has the secret.
+    InvalidTsigKey(TsigKeyError),
+    /// The TSIG key file couldn’t be read.
+    TsigKeyFile(String, String),
+    /// The root hints file couldn’t be read.
+    RootHintsFile(String, String),
+    /// The resolvers file couldn’t be read.
+    ResolversFile(String, String),
+    /// The resolvers file is invalid.
+    InvalidResolvers(String, RegistryError),
+    /// The root hints file is invalid.
+    InvalidRootHints(String, RootHintsError),
+    /// The trust anchor file couldn’t be read.
+    TrustAnchorFile(String, String),
+    /// The trust anchor file is invalid.
+    InvalidTrustAnchor(String, TrustAnchorError),
+    /// A line of the batch file has no domain to query.
+    NoDomainInLine(String),
+    /// The nameserver address is not in a form that can be understood.
+    InvalidNameserver(String),
+    /// The network interface given after a link-local address doesn’t
+    /// exist.
+    UnknownInterface(String),
+    /// The nameserver was given as a stamp that can’t be used.
+    InvalidStamp(String, StampError),
+    /// The Oblivious DNS-over-HTTPS relay isn’t an HTTPS URL.
+    InvalidRelay(String),
 }
 
 impl fmt::Display for OptionsError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::InvalidQueryType(qt) => write!(f, "Invalid query type {qt:?}"),
+            Self::InvalidQueryClass(qc) => write!(f, "Invalid query class {qc:?}"),
             Self::InvalidTweak(tw) => write!(f, "Invalid protocol tweak {tw:?}"),
+            Self::InvalidEDNS(ed) => write!(f, "Invalid EDNS setting {ed:?}"),
+            Self::DnssecWithoutEdns => write!(f, "The DO bit cannot be set with EDNS disabled"),
+            Self::InvalidTxid(txid) => write!(f, "Invalid transaction ID {txid:?}"),
+            Self::InvalidConcurrency(c) => write!(f, "Invalid concurrency {c:?}"),
+            Self::InvalidQps(qps) => write!(f, "Invalid queries per second {qps:?}"),
+            Self::InvalidTimeout(timeout) => write!(f, "Invalid timeout {timeout:?}"),
+            Self::InvalidTries(tries) => write!(f, "Invalid number of tries {tries:?}"),
+            Self::InvalidListen(window) => write!(f, "Invalid listening time {window:?}"),
+            Self::InvalidSerial(serial) => write!(f, "Invalid IXFR serial number {serial:?}"),
+            Self::InvalidTsigKey(why) => write!(f, "Invalid TSIG key: {why}"),
+            Self::TsigKeyFile(path, why) => {
+                write!(f, "Cannot read TSIG key file {path:?}: {why}")
+            }
+            Self::RootHintsFile(path, why) => {
+                write!(f, "Cannot read root hints file {path:?}: {why}")
+            }
+            Self::ResolversFile(path, why) => {
+                write!(f, "Cannot read resolvers file {path:?}: {why}")
+            }
+            Self::InvalidResolvers(path, why) => {
+                write!(f, "Invalid resolvers file {path:?}: {why}")
+            }
+            Self::InvalidRootHints(path, why) => {
+                write!(f, "Invalid root hints file {path:?}: {why}")
+            }
+            Self::TrustAnchorFile(path, why) => {
+                write!(f, "Cannot read trust anchor file {path:?}: {why}")
+            }
+            Self::InvalidTrustAnchor(path, why) => {
+                write!(f, "Invalid trust anchor file {path:?}: {why}")
+            }
+            Self::NoDomainInLine(line) => write!(f, "No domain to query in {line:?}"),
+            Self::InvalidNameserver(ns) => write!(f, "Invalid nameserver {ns:?}"),
+            Self::UnknownInterface(name) => write!(f, "Unknown network interface {name:?}"),
+            Self::InvalidStamp(stamp, why) => write!(f, "Invalid stamp {stamp:?}: {why}"),
+            Self::InvalidRelay(relay) => write!(f, "Invalid ODoH relay {relay:?}"),
         }
     }
 }
@@ -670,13 +1721,20 @@ mod test {
             Inputs {
                 domains: vec![ /* No domains by default */ ],
                 record_types: vec![RecordType::A],
+                classes: vec![DNSClass::IN],
                 any_query: false,
                 transport_type: None,
                 nameservers: vec![],
+                ixfr_serial: None,
+                batch_file: None,
             }
         }
     }
 
+    fn nameserver(input: &str) -> Nameserver {
+        Nameserver::parse(input).unwrap()
+    }
+
     impl OptionsResult {
         fn unwrap(self) -> Options {
             match self {
@@ -822,7 +1880,7 @@ mod test {
             Inputs {
                 domains: vec!["lookup.dog".to_string()],
                 record_types: vec![RecordType::NS],
-                nameservers: vec!["1.1.1.1".to_string()],
+                nameservers: vec![nameserver("1.1.1.1")],
                 ..Inputs::fallbacks()
             }
         );
@@ -844,7 +1902,7 @@ mod test {
             Inputs {
                 domains: vec!["lookup.dog".to_string()],
                 record_types: vec![RecordType::SOA],
-                nameservers: vec!["1.1.1.1".to_string()],
+                nameservers: vec![nameserver("1.1.1.1")],
                 ..Inputs::fallbacks()
             }
         );
@@ -866,7 +1924,7 @@ mod test {
             Inputs {
                 domains: vec!["lookup.dog".to_string()],
                 record_types: vec![RecordType::SOA],
-                nameservers: vec!["1.1.1.1".to_string()],
+                nameservers: vec![nameserver("1.1.1.1")],
                 ..Inputs::fallbacks()
             }
         );
@@ -894,7 +1952,7 @@ mod test {
             Inputs {
                 domains: vec!["lookup.dog".to_string()],
                 record_types: vec![RecordType::SOA],
-                nameservers: vec!["1.1.1.1".to_string()],
+                nameservers: vec![nameserver("1.1.1.1")],
                 ..Inputs::fallbacks()
             }
         );
@@ -913,6 +1971,79 @@ mod test {
         );
     }
 
+    #[test]
+    fn domain_and_class() {
+        let options = Options::getopts(&["version.bind", "TXT", "--class", "CH"]).unwrap();
+        assert_eq!(
+            options.requests.inputs,
+            Inputs {
+                domains: vec!["version.bind".to_string()],
+                record_types: vec![RecordType::TXT],
+                classes: vec![DNSClass::CH],
+                ..Inputs::fallbacks()
+            }
+        );
+    }
+
+    #[test]
+    fn domain_and_class_lowercase() {
+        let options = Options::getopts(&["id.server", "TXT", "--class", "ch"]).unwrap();
+        assert_eq!(options.requests.inputs.classes, vec![DNSClass::CH]);
+    }
+
+    #[test]
+    fn free_class() {
+        let options = Options::getopts(&["lookup.dog", "HS"]).unwrap();
+        assert_eq!(
+            options.requests.inputs,
+            Inputs {
+                domains: vec!["lookup.dog".to_string()],
+                classes: vec![DNSClass::HS],
+                ..Inputs::fallbacks()
+            }
+        );
+    }
+
+    #[test]
+    fn free_class_lowercase_after_domain() {
+        let options = Options::getopts(&["version.bind", "txt", "ch"]).unwrap();
+        assert_eq!(options.requests.inputs.classes, vec![DNSClass::CH]);
+    }
+
+    #[test]
+    fn free_class_lowercase_is_domain() {
+        let options = Options::getopts(&["in"]).unwrap();
+        assert_eq!(
+            options.requests.inputs,
+            Inputs {
+                domains: vec!["in".to_string()],
+                ..Inputs::fallbacks()
+            }
+        );
+    }
+
+    #[test]
+    fn free_class_uppercase_before_domain() {
+        let options = Options::getopts(&["CH", "version.bind"]).unwrap();
+        assert_eq!(
+            options.requests.inputs,
+            Inputs {
+                domains: vec!["version.bind".to_string()],
+                classes: vec![DNSClass::CH],
+                ..Inputs::fallbacks()
+            }
+        );
+    }
+
+    #[test]
+    fn two_classes() {
+        let options = Options::getopts(&["lookup.dog", "--class", "IN", "--class", "CH"]).unwrap();
+        assert_eq!(
+            options.requests.inputs.classes,
+            vec![DNSClass::IN, DNSClass::CH]
+        );
+    }
+
     #[test]
     fn short_mode() {
         let tf = TextFormat {
@@ -955,6 +2086,14 @@ mod test {
         }
     }
 
+    #[test]
+    fn invalid_class() {
+        assert_eq!(
+            Options::getopts(&["lookup.dog", "--class", "XY"]),
+            OptionsResult::InvalidOptions(OptionsError::InvalidQueryClass("XY".into()))
+        );
+    }
+
     #[test]
     fn invalid_tweak() {
         assert_eq!(
@@ -977,44 +2116,931 @@ mod test {
         assert_eq!(options.requests.dnssec, true);
     }
 
-    // reverse lookup tests
+    // tweak tests
 
-    /// Verifies that IPv4 addresses are correctly converted to in-addr.arpa domains
     #[test]
-    fn reverse_lookup_ipv4() {
-        let ip: IpAddr = "8.8.4.4".parse().unwrap();
-        assert_eq!(reverse_lookup_domain(ip), "4.4.8.8.in-addr.arpa");
+    fn header_flag_tweaks() {
+        let options = Options::getopts(&["dom.ain", "-Z", "aa", "-Z", "AD", "-Z", "cd"]).unwrap();
+        assert_eq!(
+            options.requests.tweaks,
+            ProtocolTweaks {
+                set_authoritative_flag: true,
+                set_authentic_flag: true,
+                set_checking_disabled_flag: true,
+                ..ProtocolTweaks::default()
+            }
+        );
     }
 
-    /// Verifies that IPv6 addresses are correctly converted to ip6.arpa domains
     #[test]
-    fn reverse_lookup_ipv6() {
-        let ip: IpAddr = "2001:4860:4860::8888".parse().unwrap();
-        // 2001:4860:4860:0000:0000:0000:0000:8888
-        // reverse nibbles...
-        assert_eq!(
-            reverse_lookup_domain(ip),
-            "8.8.8.8.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.6.8.4.0.6.8.4.1.0.0.2.ip6.arpa"
-        );
+    fn no_recursion_tweak() {
+        let options = Options::getopts(&["dom.ain", "-Z", "rd=0"]).unwrap();
+        assert_eq!(options.requests.tweaks.no_recursion, true);
     }
 
     #[test]
-    fn extended_record_types() {
-        let options =
-            Options::getopts(&["lookup.dog", "CDS", "CDNSKEY", "CSYNC", "KEY", "SIG"]).unwrap();
+    fn bufsize_tweak() {
+        let options = Options::getopts(&["dom.ain", "-Z", "bufsize=4096"]).unwrap();
+        assert_eq!(options.requests.tweaks.udp_payload_size, Some(4096));
+    }
+
+    #[test]
+    fn bufsize_tweak_too_big() {
         assert_eq!(
-            options.requests.inputs,
-            Inputs {
-                domains: vec!["lookup.dog".to_string()],
-                record_types: vec![
-                    RecordType::CDS,
-                    RecordType::CDNSKEY,
-                    RecordType::CSYNC,
-                    RecordType::KEY,
-                    RecordType::SIG,
-                ],
-                ..Inputs::fallbacks()
-            }
+            Options::getopts(&["dom.ain", "-Z", "bufsize=65536"]),
+            OptionsResult::InvalidOptions(OptionsError::InvalidTweak("bufsize=65536".into()))
         );
     }
+
+    #[test]
+    fn tc_retry_tweak() {
+        let options = Options::getopts(&["dom.ain", "-Z", "tc-retry=off"]).unwrap();
+        assert_eq!(options.requests.tweaks.no_tc_retry, true);
+    }
+
+    // edns tests
+
+    #[test]
+    fn edns_default() {
+        let options = Options::getopts(&["dom.ain"]).unwrap();
+        assert_eq!(options.requests.edns, UseEDNS::SendAndHide);
+    }
+
+    #[test]
+    fn edns_disable() {
+        let options = Options::getopts(&["dom.ain", "--edns", "disable"]).unwrap();
+        assert_eq!(options.requests.edns, UseEDNS::Disable);
+    }
+
+    #[test]
+    fn edns_show() {
+        let options = Options::getopts(&["dom.ain", "--edns", "SHOW"]).unwrap();
+        assert_eq!(options.requests.edns, UseEDNS::SendAndShow);
+    }
+
+    #[test]
+    fn invalid_edns() {
+        assert_eq!(
+            Options::getopts(&["dom.ain", "--edns", "yes"]),
+            OptionsResult::InvalidOptions(OptionsError::InvalidEDNS("yes".into()))
+        );
+    }
+
+    #[test]
+    fn dnssec_ok_without_edns() {
+        assert_eq!(
+            Options::getopts(&["dom.ain", "-Z", "do", "--edns", "disable"]),
+            OptionsResult::InvalidOptions(OptionsError::DnssecWithoutEdns)
+        );
+    }
+
+    #[test]
+    fn validate_without_edns() {
+        assert_eq!(
+            Options::getopts(&["dom.ain", "--validate", "--edns", "disable"]),
+            OptionsResult::InvalidOptions(OptionsError::DnssecWithoutEdns)
+        );
+    }
+
+    // zone transfer tests
+
+    #[test]
+    fn ixfr_serial() {
+        let options = Options::getopts(&["lookup.dog", "ixfr=2024010101"]).unwrap();
+        assert_eq!(
+            options.requests.inputs,
+            Inputs {
+                domains: vec![String::from("lookup.dog")],
+                record_types: vec![RecordType::IXFR],
+                ixfr_serial: Some(2_024_010_101),
+                ..Inputs::fallbacks()
+            }
+        );
+    }
+
+    #[test]
+    fn ixfr_without_serial() {
+        let options = Options::getopts(&["lookup.dog", "IXFR"]).unwrap();
+        assert_eq!(
+            options.requests.inputs,
+            Inputs {
+                domains: vec![String::from("lookup.dog")],
+                record_types: vec![RecordType::IXFR],
+                ..Inputs::fallbacks()
+            }
+        );
+    }
+
+    #[test]
+    fn ixfr_serial_invalid() {
+        assert_eq!(
+            Options::getopts(&["lookup.dog", "IXFR=yesterday"]),
+            OptionsResult::InvalidOptions(OptionsError::InvalidSerial("yesterday".into()))
+        );
+    }
+
+    // txid tests
+
+    #[test]
+    fn txid() {
+        let options = Options::getopts(&["dom.ain", "--txid", "4660"]).unwrap();
+        assert_eq!(options.requests.txid, Some(4660));
+    }
+
+    #[test]
+    fn txid_default() {
+        let options = Options::getopts(&["dom.ain"]).unwrap();
+        assert_eq!(options.requests.txid, None);
+    }
+
+    #[test]
+    fn txid_too_big() {
+        assert_eq!(
+            Options::getopts(&["dom.ain", "--txid", "65536"]),
+            OptionsResult::InvalidOptions(OptionsError::InvalidTxid("65536".into()))
+        );
+    }
+
+    #[test]
+    fn txid_not_a_number() {
+        assert_eq!(
+            Options::getopts(&["dom.ain", "--txid", "0x12"]),
+            OptionsResult::InvalidOptions(OptionsError::InvalidTxid("0x12".into()))
+        );
+    }
+
+    // limits tests
+
+    #[test]
+    fn limits() {
+        let options = Options::getopts(&["dom.ain", "--concurrency", "4", "--qps", "100"]).unwrap();
+        assert_eq!(
+            options.requests.limits,
+            Limits {
+                concurrency: 4,
+                qps: Some(100),
+            }
+        );
+    }
+
+    #[test]
+    fn limits_default() {
+        let options = Options::getopts(&["dom.ain"]).unwrap();
+        assert_eq!(options.requests.limits, Limits::default());
+    }
+
+    #[test]
+    fn concurrency_zero() {
+        assert_eq!(
+            Options::getopts(&["dom.ain", "--concurrency", "0"]),
+            OptionsResult::InvalidOptions(OptionsError::InvalidConcurrency("0".into()))
+        );
+    }
+
+    #[test]
+    fn concurrency_too_large() {
+        let max = usize::MAX.to_string();
+        assert_eq!(
+            Options::getopts(&["dom.ain", "--concurrency", &max]),
+            OptionsResult::InvalidOptions(OptionsError::InvalidConcurrency(max.clone()))
+        );
+    }
+
+    #[test]
+    fn qps_not_a_number() {
+        assert_eq!(
+            Options::getopts(&["dom.ain", "--qps", "fast"]),
+            OptionsResult::InvalidOptions(OptionsError::InvalidQps("fast".into()))
+        );
+    }
+
+    // attempts tests
+
+    #[test]
+    fn attempts() {
+        let options = Options::getopts(&[
+            "dom.ain",
+            "--timeout",
+            "500ms",
+            "--tries",
+            "5",
+            "--retry-tcp",
+        ])
+        .unwrap();
+        assert_eq!(
+            options.requests.attempts,
+            Attempts {
+                timeout: Duration::from_millis(500),
+                tries: 5,
+                retry_tcp: true,
+            }
+        );
+    }
+
+    #[test]
+    fn attempts_default() {
+        let options = Options::getopts(&["dom.ain"]).unwrap();
+        assert_eq!(options.requests.attempts, Attempts::default());
+    }
+
+    #[test]
+    fn timeout_seconds() {
+        assert_eq!(parse_duration("2"), Some(Duration::from_secs(2)));
+        assert_eq!(parse_duration("1.5s"), Some(Duration::from_millis(1500)));
+    }
+
+    #[test]
+    fn timeout_invalid() {
+        assert_eq!(
+            Options::getopts(&["dom.ain", "--timeout", "0s"]),
+            OptionsResult::InvalidOptions(OptionsError::InvalidTimeout("0s".into()))
+        );
+    }
+
+    #[test]
+    fn tries_zero() {
+        assert_eq!(
+            Options::getopts(&["dom.ain", "--tries", "0"]),
+            OptionsResult::InvalidOptions(OptionsError::InvalidTries("0".into()))
+        );
+    }
+
+    #[test]
+    fn attempts_from_system() {
+        let system = ResolvConf::parse("options timeout:2 attempts:4");
+        let options = Options::getopts_with(&["dom.ain"], system, Registry::builtin()).unwrap();
+        assert_eq!(
+            options.requests.attempts,
+            Attempts {
+                timeout: Duration::from_secs(2),
+                tries: 4,
+                retry_tcp: false,
+            }
+        );
+    }
+
+    #[test]
+    fn attempts_override_system() {
+        let system = ResolvConf::parse("options timeout:2 attempts:4");
+        let args = ["dom.ain", "--timeout", "7", "--tries", "1"];
+        let options = Options::getopts_with(&args, system, Registry::builtin()).unwrap();
+        assert_eq!(options.requests.attempts.timeout, Duration::from_secs(7));
+        assert_eq!(options.requests.attempts.tries, 1);
+    }
+
+    // search list tests
+
+    #[test]
+    fn search_by_default() {
+        let system = ResolvConf::parse("search corp.example");
+        let options =
+            Options::getopts_with(&["myhost"], system.clone(), Registry::builtin()).unwrap();
+        assert!(options.requests.search);
+        assert_eq!(options.requests.system, system);
+    }
+
+    #[test]
+    fn no_search() {
+        let options = Options::getopts(&["myhost", "--no-search"]).unwrap();
+        assert!(!options.requests.search);
+    }
+
+    #[test]
+    fn tries_too_many() {
+        assert_eq!(
+            Options::getopts(&["dom.ain", "--tries", "11"]),
+            OptionsResult::InvalidOptions(OptionsError::InvalidTries("11".into()))
+        );
+    }
+
+    // tsig tests
+
+    #[test]
+    fn tsig_argument() {
+        let options =
+            Options::getopts(&["dom.ain", "--tsig", "transfer-key:hmac-sha256:c2VjcmV0"]).unwrap();
+        assert_eq!(
+            options.requests.tsig,
+            Some(TsigKey::from_argument("transfer-key:hmac-sha256:c2VjcmV0").unwrap())
+        );
+    }
+
+    #[test]
+    fn tsig_default() {
+        let options = Options::getopts(&["dom.ain"]).unwrap();
+        assert_eq!(options.requests.tsig, None);
+    }
+
+    #[test]
+    fn tsig_invalid() {
+        assert_eq!(
+            Options::getopts(&["dom.ain", "--tsig", "transfer-key:hmac-sha256"]),
+            OptionsResult::InvalidOptions(OptionsError::InvalidTsigKey(TsigKeyError::Format))
+        );
+    }
+
+    // validate tests
+
+    #[test]
+    fn validate() {
+        let options = Options::getopts(&["dom.ain", "--validate"]).unwrap();
+        assert_eq!(
+            options.requests.validate,
+            Some(Box::new(TrustAnchor::builtin()))
+        );
+        assert_eq!(options.requests.dnssec, true);
+    }
+
+    #[test]
+    fn validate_default() {
+        let options = Options::getopts(&["dom.ain"]).unwrap();
+        assert_eq!(options.requests.validate, None);
+        assert_eq!(options.requests.dnssec, false);
+    }
+
+    #[test]
+    fn trust_anchor_without_validate() {
+        assert!(matches!(
+            Options::getopts(&["dom.ain", "--trust-anchor", "anchor.txt"]),
+            OptionsResult::InvalidOptionsFormat(_)
+        ));
+    }
+
+    // batch file tests
+
+    #[test]
+    fn batch_file_without_domains() {
+        let options = Options::getopts(&["--file", "queries.txt"]).unwrap();
+        assert_eq!(
+            options.requests.inputs,
+            Inputs {
+                batch_file: Some(String::from("queries.txt")),
+                ..Inputs::fallbacks()
+            }
+        );
+    }
+
+    #[test]
+    fn batch_line() {
+        let options = Options::getopts(&["-f", "-", "MX", "@1.1.1.1"]).unwrap();
+        let inputs = options
+            .requests
+            .inputs
+            .for_line("  lookup.dog TXT CH @8.8.8.8 ");
+        assert_eq!(
+            inputs,
+            Ok(Some(Inputs {
+                domains: vec![String::from("lookup.dog")],
+                record_types: vec![RecordType::TXT],
+                classes: vec![DNSClass::CH],
+                nameservers: vec![nameserver("8.8.8.8")],
+                ..Inputs::fallbacks()
+            }))
+        );
+    }
+
+    #[test]
+    fn batch_line_defaults() {
+        let options = Options::getopts(&["-f", "-", "MX", "@1.1.1.1"]).unwrap();
+        let inputs = options.requests.inputs.for_line("lookup.dog");
+        assert_eq!(
+            inputs,
+            Ok(Some(Inputs {
+                domains: vec![String::from("lookup.dog")],
+                record_types: vec![RecordType::MX],
+                nameservers: vec![nameserver("1.1.1.1")],
+                ..Inputs::fallbacks()
+            }))
+        );
+    }
+
+    #[test]
+    fn batch_line_comments() {
+        let inputs = Inputs::fallbacks();
+        assert_eq!(inputs.for_line(""), Ok(None));
+        assert_eq!(inputs.for_line("   # lookup.dog"), Ok(None));
+    }
+
+    #[test]
+    fn batch_line_without_domain() {
+        assert_eq!(
+            Inputs::fallbacks().for_line("MX @1.1.1.1"),
+            Err(OptionsError::NoDomainInLine("MX @1.1.1.1".into()))
+        );
+    }
+
+    // resolvers tests
+
+    #[test]
+    fn list_resolvers() {
+        assert!(matches!(
+            Options::getopts(&["--list-resolvers"]),
+            OptionsResult::ListResolvers(registry) if registry.by_name("quad9").is_some()
+        ));
+    }
+
+    #[test]
+    fn resolvers_file() {
+        let path = std::env::temp_dir().join("dog-test-resolvers.conf");
+        fs::write(&path, "[corp]\naddresses = 10.0.0.53\n").unwrap();
+
+        let path = path.to_string_lossy();
+        let options = Options::getopts(&["lookup.dog", "--resolvers", &path]).unwrap();
+        assert!(options.requests.resolvers.by_name("corp").is_some());
+        assert!(options.requests.resolvers.by_name("google").is_some());
+    }
+
+    #[test]
+    fn user_resolvers() {
+        let mut resolvers = Registry::builtin();
+        resolvers.extend(Registry::parse("[home]\naddresses = 192.168.1.1\n").unwrap());
+
+        let options =
+            Options::getopts_with(&["lookup.dog"], ResolvConf::default(), resolvers).unwrap();
+        assert!(options.requests.resolvers.by_name("home").is_some());
+    }
+
+    #[test]
+    fn missing_resolvers_file() {
+        assert!(matches!(
+            Options::getopts(&["lookup.dog", "--resolvers", "/no/such/resolvers.conf"]),
+            OptionsResult::InvalidOptions(OptionsError::ResolversFile(..))
+        ));
+    }
+
+    // nameserver tests
+
+    #[test]
+    fn nameserver_ipv4_port() {
+        assert_eq!(
+            nameserver("127.0.0.1:5353"),
+            Nameserver {
+                host: String::from("127.0.0.1"),
+                scope_id: None,
+                port: Some(5353),
+                path: None,
+                address: None,
+                transport_type: None,
+                dnscrypt: None,
+                certificate_hashes: Vec::new(),
+            }
+        );
+    }
+
+    #[test]
+    fn nameserver_ipv6() {
+        assert_eq!(
+            nameserver("2606:4700:4700::1111"),
+            Nameserver {
+                host: String::from("2606:4700:4700::1111"),
+                scope_id: None,
+                port: None,
+                path: None,
+                address: None,
+                transport_type: None,
+                dnscrypt: None,
+                certificate_hashes: Vec::new(),
+            }
+        );
+    }
+
+    #[test]
+    fn nameserver_ipv6_port() {
+        let ns = nameserver("[::1]:5353");
+        assert_eq!(ns.ip(), Some(IpAddr::from(Ipv6Addr::LOCALHOST)));
+        assert_eq!(ns.port, Some(5353));
+        assert_eq!(ns.to_string(), "[::1]:5353");
+    }
+
+    #[test]
+    fn nameserver_ipv6_brackets_without_port() {
+        assert_eq!(nameserver("[::1]"), nameserver("::1"));
+    }
+
+    #[test]
+    fn nameserver_scoped() {
+        assert_eq!(
+            nameserver("fe80::1%2"),
+            Nameserver {
+                host: String::from("fe80::1"),
+                scope_id: Some(2),
+                port: None,
+                path: None,
+                address: None,
+                transport_type: None,
+                dnscrypt: None,
+                certificate_hashes: Vec::new(),
+            }
+        );
+        assert_eq!(nameserver("[fe80::1%2]:53").to_string(), "[fe80::1%2]:53");
+    }
+
+    #[test]
+    fn nameserver_host_port() {
+        let ns = nameserver("dns.lookup.dog:8053");
+        assert_eq!(ns.host, "dns.lookup.dog");
+        assert_eq!(ns.ip(), None);
+        assert_eq!(ns.port, Some(8053));
+    }
+
+    #[test]
+    fn nameserver_free_argument() {
+        let options = Options::getopts(&["lookup.dog", "@[::1]:5353"]).unwrap();
+        assert_eq!(
+            options.requests.inputs.nameservers,
+            vec![nameserver("[::1]:5353")]
+        );
+    }
+
+    #[test]
+    fn nameserver_invalid_port() {
+        assert_eq!(
+            Options::getopts(&["lookup.dog", "@127.0.0.1:99999"]),
+            OptionsResult::InvalidOptions(OptionsError::InvalidNameserver(
+                "127.0.0.1:99999".into()
+            ))
+        );
+    }
+
+    #[test]
+    fn nameserver_port_zero() {
+        assert_eq!(
+            Nameserver::parse("127.0.0.1:0"),
+            Err(OptionsError::InvalidNameserver("127.0.0.1:0".into()))
+        );
+        assert_eq!(
+            Nameserver::parse("[::1]:0"),
+            Err(OptionsError::InvalidNameserver("[::1]:0".into()))
+        );
+    }
+
+    #[test]
+    fn nameserver_invalid_ipv6() {
+        assert_eq!(
+            Nameserver::parse("fe80::zz"),
+            Err(OptionsError::InvalidNameserver("fe80::zz".into()))
+        );
+    }
+
+    #[test]
+    fn nameserver_scope_on_ipv4() {
+        assert_eq!(
+            Nameserver::parse("127.0.0.1%2"),
+            Err(OptionsError::InvalidNameserver("127.0.0.1%2".into()))
+        );
+    }
+
+    #[test]
+    fn nameserver_unknown_interface() {
+        assert_eq!(
+            Nameserver::parse("fe80::1%no/such"),
+            Err(OptionsError::UnknownInterface("no/such".into()))
+        );
+    }
+
+    #[test]
+    fn nameserver_url() {
+        assert_eq!(
+            nameserver("https://doh.corp.example/resolve"),
+            Nameserver {
+                host: String::from("doh.corp.example"),
+                scope_id: None,
+                port: None,
+                path: Some(String::from("/resolve")),
+                address: None,
+                transport_type: None,
+                dnscrypt: None,
+                certificate_hashes: Vec::new(),
+            }
+        );
+    }
+
+    #[test]
+    fn nameserver_url_with_port() {
+        let ns = nameserver("https://[2001:db8::53]:8443/dns-query?ct");
+        assert_eq!(ns.host, "2001:db8::53");
+        assert_eq!(ns.port, Some(8443));
+        assert_eq!(ns.path.as_deref(), Some("/dns-query?ct"));
+        assert_eq!(ns.to_string(), "https://[2001:db8::53]:8443/dns-query?ct");
+    }
+
+    #[test]
+    fn nameserver_url_without_path() {
+        let ns = nameserver("https://1.1.1.1");
+        assert_eq!(ns.path.as_deref(), Some("/dns-query"));
+        assert_eq!(ns.to_string(), "https://1.1.1.1/dns-query");
+    }
+
+    #[test]
+    fn nameserver_https_stamp() {
+        assert_eq!(
+            nameserver("sdns://AgcAAAAAAAAABzEuMC4wLjEAEmRucy5jbG91ZGZsYXJlLmNvbQovZG5zLXF1ZXJ5"),
+            Nameserver {
+                host: String::from("dns.cloudflare.com"),
+                scope_id: None,
+                port: None,
+                path: Some(String::from("/dns-query")),
+                address: Some("1.0.0.1".parse().unwrap()),
+                transport_type: Some(TransportType::HTTPS),
+                dnscrypt: None,
+                certificate_hashes: Vec::new(),
+            }
+        );
+    }
+
+    #[test]
+    fn nameserver_tls_stamp() {
+        let ns = nameserver("sdns://AwAAAAAAAAAADVsyNjIwOmZlOjpmZV0ADWRucy5xdWFkOS5uZXQ");
+        assert_eq!(ns.host, "dns.quad9.net");
+        assert_eq!(ns.address, Some("2620:fe::fe".parse().unwrap()));
+        assert_eq!(ns.transport_type, Some(TransportType::TLS));
+    }
+
+    #[test]
+    fn nameserver_dnscrypt_stamp() {
+        let ns = nameserver(concat!(
+            "sdns://AQMAAAAAAAAAETk0LjE0MC4xNC4xNDo1NDQzINErR_JS3PLCu_iZEIbq95zkSV2L",
+            "FsigxDIuUso_OQhzIjIuZG5zY3J5cHQuZGVmYXVsdC5uczEuYWRndWFyZC5jb20",
+        ));
+        assert_eq!(ns.host, "94.140.14.14");
+        assert_eq!(ns.port, Some(5443));
+        assert_eq!(ns.address, None);
+        assert_eq!(ns.transport_type, None);
+        assert_eq!(
+            ns.dnscrypt.map(|provider| provider.name),
+            Some(String::from("2.dnscrypt.default.ns1.adguard.com"))
+        );
+    }
+
+    #[test]
+    fn nameserver_stamp_invalid() {
+        assert_eq!(
+            Nameserver::parse("sdns://BQAAAAAAAAAADG9kb2guZXhhbXBsZQovZG5zLXF1ZXJ5"),
+            Err(OptionsError::InvalidStamp(
+                String::from("sdns://BQAAAAAAAAAADG9kb2guZXhhbXBsZQovZG5zLXF1ZXJ5"),
+                StampError::UnsupportedProtocol(0x05)
+            ))
+        );
+    }
+
+    #[test]
+    fn nameserver_url_invalid() {
+        assert_eq!(
+            Nameserver::parse("https://:443/dns-query"),
+            Err(OptionsError::InvalidNameserver(
+                "https://:443/dns-query".into()
+            ))
+        );
+    }
+
+    #[test]
+    fn nameserver_plain_http() {
+        assert_eq!(
+            Nameserver::parse("http://doh.corp.example/resolve"),
+            Err(OptionsError::InvalidNameserver(
+                "http://doh.corp.example/resolve".into()
+            ))
+        );
+    }
+
+    #[test]
+    fn doh_method_get() {
+        let options = Options::getopts(&["lookup.dog", "-H", "--doh-method", "get"]).unwrap();
+        assert_eq!(options.requests.doh_method, doh::Method::Get);
+    }
+
+    #[test]
+    fn doh_method_default() {
+        let options = Options::getopts(&["lookup.dog", "-H"]).unwrap();
+        assert_eq!(options.requests.doh_method, doh::Method::Post);
+    }
+
+    #[test]
+    fn doh_method_invalid() {
+        assert!(matches!(
+            Options::getopts(&["lookup.dog", "--doh-method", "PUT"]),
+            OptionsResult::InvalidOptionsFormat(_)
+        ));
+    }
+
+    #[test]
+    fn http3() {
+        let options = Options::getopts(&["lookup.dog", "--http3"]).unwrap();
+        assert_eq!(
+            options.requests.inputs.transport_type,
+            Some(TransportType::HTTPS)
+        );
+        assert_eq!(options.requests.http_version, doh::HttpVersion::Http3);
+    }
+
+    #[test]
+    fn http3_tls() {
+        assert!(matches!(
+            Options::getopts(&["lookup.dog", "--http3", "--tls"]),
+            OptionsResult::InvalidOptionsFormat(_)
+        ));
+    }
+
+    #[test]
+    fn odoh() {
+        let options = Options::getopts(&[
+            "lookup.dog",
+            "@https://odoh.example/dns-query",
+            "--odoh",
+            "https://relay.example/proxy",
+        ])
+        .unwrap();
+        assert_eq!(
+            options.requests.inputs.transport_type,
+            Some(TransportType::HTTPS)
+        );
+        assert_eq!(
+            options.requests.odoh_relay,
+            Some(Nameserver {
+                host: String::from("relay.example"),
+                scope_id: None,
+                port: None,
+                path: Some(String::from("/proxy")),
+                address: None,
+                transport_type: None,
+                dnscrypt: None,
+                certificate_hashes: Vec::new(),
+            })
+        );
+    }
+
+    #[test]
+    fn odoh_relay_not_url() {
+        assert_eq!(
+            Options::getopts(&["lookup.dog", "--odoh", "relay.example"]),
+            OptionsResult::InvalidOptions(OptionsError::InvalidRelay("relay.example".into()))
+        );
+    }
+
+    #[test]
+    fn odoh_quic() {
+        assert!(matches!(
+            Options::getopts(&["lookup.dog", "--odoh", "https://relay.example/", "-Q"]),
+            OptionsResult::InvalidOptionsFormat(_)
+        ));
+    }
+
+    #[test]
+    fn quic() {
+        let options = Options::getopts(&["lookup.dog", "-Q"]).unwrap();
+        assert_eq!(
+            options.requests.inputs.transport_type,
+            Some(TransportType::QUIC)
+        );
+    }
+
+    #[test]
+    fn quic_trace() {
+        assert!(matches!(
+            Options::getopts(&["lookup.dog", "--quic", "--trace"]),
+            OptionsResult::InvalidOptionsFormat(_)
+        ));
+    }
+
+    // reverse lookup tests
+
+    /// Verifies that IPv4 addresses are correctly converted to in-addr.arpa domains
+    #[test]
+    fn reverse_lookup_ipv4() {
+        let ip: IpAddr = "8.8.4.4".parse().unwrap();
+        assert_eq!(reverse_lookup_domain(ip), "4.4.8.8.in-addr.arpa");
+    }
+
+    /// Verifies that IPv6 addresses are correctly converted to ip6.arpa domains
+    #[test]
+    fn reverse_lookup_ipv6() {
+        let ip: IpAddr = "2001:4860:4860::8888".parse().unwrap();
+        // 2001:4860:4860:0000:0000:0000:0000:8888
+        // reverse nibbles...
+        assert_eq!(
+            reverse_lookup_domain(ip),
+            "8.8.8.8.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.6.8.4.0.6.8.4.1.0.0.2.ip6.arpa"
+        );
+    }
+
+    #[test]
+    fn extended_record_types() {
+        let options =
+            Options::getopts(&["lookup.dog", "CDS", "CDNSKEY", "CSYNC", "KEY", "SIG"]).unwrap();
+        assert_eq!(
+            options.requests.inputs,
+            Inputs {
+                domains: vec!["lookup.dog".to_string()],
+                record_types: vec![
+                    RecordType::CDS,
+                    RecordType::CDNSKEY,
+                    RecordType::CSYNC,
+                    RecordType::KEY,
+                    RecordType::SIG,
+                ],
+                ..Inputs::fallbacks()
+            }
+        );
+    }
+
+    #[test]
+    fn mdns() {
+        let options = Options::getopts(&["printer.local", "--mdns"]).unwrap();
+        assert_eq!(options.requests.inputs.domains, Vec::<String>::new());
+        assert_eq!(
+            options.requests.multicast,
+            Some(Multicast {
+                protocol: multicast::Protocol::Mdns,
+                domains: vec![String::from("printer.local")],
+                window: DEFAULT_WINDOW,
+                services: Vec::new(),
+            })
+        );
+    }
+
+    #[test]
+    fn browse_without_domains() {
+        let options =
+            Options::getopts(&["--browse", "_http._tcp.local", "--listen", "3s"]).unwrap();
+        assert_eq!(options.requests.inputs.domains, Vec::<String>::new());
+        assert_eq!(
+            options.requests.multicast,
+            Some(Multicast {
+                protocol: multicast::Protocol::Mdns,
+                domains: Vec::new(),
+                window: Duration::from_secs(3),
+                services: vec![String::from("_http._tcp.local")],
+            })
+        );
+    }
+
+    #[test]
+    fn invalid_listen() {
+        assert_eq!(
+            Options::getopts(&["printer.local", "--mdns", "--listen", "soon"]),
+            OptionsResult::InvalidOptions(OptionsError::InvalidListen("soon".into()))
+        );
+    }
+
+    #[test]
+    fn listen_without_mdns() {
+        assert!(matches!(
+            Options::getopts(&["printer.local", "--listen", "2s"]),
+            OptionsResult::InvalidOptionsFormat(_)
+        ));
+    }
+
+    #[test]
+    fn mdns_tcp() {
+        assert!(matches!(
+            Options::getopts(&["printer.local", "--mdns", "-T"]),
+            OptionsResult::InvalidOptionsFormat(_)
+        ));
+    }
+
+    #[test]
+    fn llmnr_single_labels() {
+        let options =
+            Options::getopts(&["router", "nas-2", "MX", "lookup.dog", "printer.", "--llmnr"])
+                .unwrap();
+        assert_eq!(
+            options.requests.inputs.domains,
+            vec![String::from("lookup.dog")]
+        );
+        assert_eq!(
+            options.requests.multicast,
+            Some(Multicast {
+                protocol: multicast::Protocol::Llmnr,
+                domains: vec![
+                    String::from("router"),
+                    String::from("nas-2"),
+                    String::from("printer."),
+                ],
+                window: DEFAULT_WINDOW,
+                services: Vec::new(),
+            })
+        );
+        assert_eq!(options.requests.inputs.record_types, vec![RecordType::MX]);
+    }
+
+    #[test]
+    fn llmnr_without_single_labels() {
+        let options = Options::getopts(&["lookup.dog", "--llmnr"]).unwrap();
+        assert_eq!(
+            options.requests.inputs.domains,
+            vec![String::from("lookup.dog")]
+        );
+        assert_eq!(
+            options.requests.multicast.map(|m| m.domains),
+            Some(Vec::new())
+        );
+    }
+
+    #[test]
+    fn llmnr_mdns() {
+        assert!(matches!(
+            Options::getopts(&["router", "--llmnr", "--mdns"]),
+            OptionsResult::InvalidOptionsFormat(_)
+        ));
+    }
 }
@@ -19,16 +19,107 @@
 
 //! Text and JSON output.
 
+use std::convert::TryFrom;
 use std::env;
+use std::fmt::{self, Write as _};
 use std::io::{self, BufWriter, IsTerminal, Write};
 use std::time::Duration;
 
-use hickory_resolver::lookup::Lookup;
+use anstyle::Style;
+use data_encoding::BASE32_DNSSEC;
 use hickory_resolver::net::NetError as ResolveError;
-use json::object;
+use hickory_resolver::proto::dnssec::rdata::{DNSSECRData, DNSKEY, DS, NSEC3, RRSIG};
+use hickory_resolver::proto::dnssec::PublicKey;
+use hickory_resolver::proto::op::{Edns, Message, MessageType, ResponseCode};
+use hickory_resolver::proto::rr::rdata::opt::{EdnsCode, EdnsOption};
+use hickory_resolver::proto::rr::rdata::svcb::{SvcParamValue, SVCB};
+use hickory_resolver::proto::rr::rdata::TLSA;
+use hickory_resolver::proto::rr::{DNSClass, RData, Record, RecordType};
+use json::{object, JsonValue};
 
-use crate::colours::Colours;
+use crate::colours::{Colours, Paint};
+use crate::multicast::{Collection, Reply};
 use crate::table::{Section, Table};
+use crate::trace::{Hop, Trace};
+use crate::transfer::{Change, Transfer};
+use crate::tsig::VerificationError;
+use crate::validate::{Security, Validation};
+
+/// The version of the structure of the JSON output. This gets bumped
+/// whenever a key is removed, renamed, or changes its type, so that scripts
+/// can tell whether they understand the output; new keys may be added
+/// without bumping it.
+pub const JSON_SCHEMA_VERSION: u32 = 1;
+
+/// The outcome of sending one query: the response that came back, if there
+/// was one, any error that occurred, and where the query was sent.
+#[derive(Debug)]
+pub struct QueryResult {
+    /// The domain name that was queried.
+    pub domain: String,
+
+    /// The record type that was queried for.
+    pub qtype: RecordType,
+
+    /// The network class that was queried.
+    pub class: DNSClass,
+
+    /// The address of the nameserver the query was last sent to, or `None`
+    /// if it never got sent.
+    pub nameserver: Option<String>,
+
+    /// The name of the transport the query was last sent over.
+    pub transport: Option<&'static str>,
+
+    /// The version of HTTP the response came back over, if the query was
+    /// sent over HTTPS.
+    pub http_version: Option<http::Version>,
+
+    /// How long it took to send the query and receive the response.
+    pub elapsed: Duration,
+
+    /// How many times the query was sent, which is more than once if it
+    /// timed out or got a SERVFAIL response and was tried again.
+    pub attempts: u32,
+
+    /// The response that came back, if any.
+    pub response: Option<Message>,
+
+    /// The error that occurred, if any. Responses with an error code, such
+    /// as NXDOMAIN, have both a response and an error.
+    pub error: Option<QueryError>,
+
+    /// The result of validating the DNSSEC signatures on the response, if
+    /// validation was asked for.
+    pub validation: Option<Validation>,
+}
+
+/// An error that occurred while sending a query or checking its response.
+#[derive(Debug)]
+pub enum QueryError {
+    /// The request couldn’t be sent, the response couldn’t be received, or
+    /// the response had an error code.
+    Network(ResolveError),
+
+    /// The response’s TSIG signature couldn’t be verified, so it can’t be
+    /// trusted and doesn’t get displayed.
+    Tsig(VerificationError),
+}
+
+impl From<ResolveError> for QueryError {
+    fn from(error: ResolveError) -> Self {
+        Self::Network(error)
+    }
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Network(error) => write!(f, "{error}"),
+            Self::Tsig(error) => write!(f, "{error}"),
+        }
+    }
+}
 
 /// How to format the output data.
 #[derive(PartialEq, Debug, Copy, Clone)]
@@ -88,12 +179,23 @@ impl OutputFormat {
     /// settings. If the duration has been measured, it should also be
     /// printed. Returns `false` if there were no results to print, and `true`
     /// otherwise.
-    pub fn print(self, responses: Vec<Lookup>, duration: Option<Duration>) -> bool {
+    pub fn print(self, results: Vec<QueryResult>, duration: Option<Duration>) -> bool {
         match self {
             Self::Short(_) => {
-                let all_answers = responses
+                for error in results.iter().filter_map(|r| r.error.as_ref()) {
+                    print_error(error);
+                }
+
+                for validation in results.iter().filter_map(|r| r.validation.as_ref()) {
+                    if validation.security() == Security::Bogus {
+                        print_bogus(validation);
+                    }
+                }
+
+                let all_answers = results
                     .into_iter()
-                    .flat_map(|r| r.answers().to_vec())
+                    .filter_map(|r| r.response)
+                    .flat_map(|r| r.answers)
                     .collect::<Vec<_>>();
 
                 if all_answers.is_empty() {
@@ -106,18 +208,11 @@ impl OutputFormat {
                 }
             }
             Self::JSON => {
-                let mut rs = Vec::new();
-
-                for response in responses {
-                    let json = object! {
-                        "answers": response.answers().iter().map(std::string::ToString::to_string).collect::<Vec<_>>(),
-                    };
-
-                    rs.push(json);
-                }
+                let rs = results.iter().map(json_result).collect::<Vec<_>>();
 
                 if let Some(duration) = duration {
                     let object = object! {
+                        "schema_version": JSON_SCHEMA_VERSION,
                         "responses": rs,
                         "duration": {
                             "secs": duration.as_secs(),
@@ -128,6 +223,7 @@ impl OutputFormat {
                     println!("{object}");
                 } else {
                     let object = object! {
+                        "schema_version": JSON_SCHEMA_VERSION,
                         "responses": rs,
                     };
 
@@ -135,28 +231,37 @@ impl OutputFormat {
                 }
             }
             Self::Text(uc, tf) => {
-                let total_records = responses
+                let total_records = results
                     .iter()
-                    .flat_map(hickory_resolver::lookup::Lookup::answers)
-                    .count();
+                    .filter_map(|r| r.response.as_ref())
+                    .map(|r| r.answers.len() + r.authorities.len() + r.additionals.len())
+                    .sum::<usize>();
                 if total_records > 100 {
                     let stdout = io::stdout();
                     let mut writer = BufWriter::new(stdout);
-                    for response in responses {
-                        let mut table = Table::new(uc.palette(), tf);
-                        for a in response.answers() {
-                            table.add_row(a, Section::Answer);
+                    for result in &results {
+                        if let Some(error) = &result.error {
+                            print_error(error);
+                        }
+
+                        if let Some(response) = &result.response {
+                            let colours = uc.palette();
+                            writeln!(&mut writer, "{}", header_summary(response, &colours))
+                                .unwrap();
+                            let table = response_table(response, uc, tf);
+                            write!(&mut writer, "{}", table.render()).unwrap();
+                        }
+
+                        if let Some(validation) = &result.validation {
+                            let colours = uc.palette();
+                            write!(&mut writer, "{}", validation_summary(validation, &colours))
+                                .unwrap();
                         }
-                        write!(&mut writer, "{}", table.render()).unwrap();
                     }
                     writer.flush().unwrap();
                 } else {
-                    for response in responses {
-                        let mut table = Table::new(uc.palette(), tf);
-                        for a in response.answers() {
-                            table.add_row(a, Section::Answer);
-                        }
-                        print!("{}", table.render());
+                    for result in &results {
+                        self.print_streamed(result);
                     }
                 }
 
@@ -169,23 +274,878 @@ impl OutputFormat {
         true
     }
 
-    /// Print an error that’s ocurred while sending or receiving DNS packets
-    /// to standard error.
-    pub fn print_error(self, error: &ResolveError) {
+    /// Prints the result of one query as soon as it has come back, rather
+    /// than waiting for every query to finish, which is used when queries
+    /// are read from a file. In JSON mode, each result is printed as its own
+    /// object on one line, with the same keys as the objects in the
+    /// `responses` array. Returns `false` if there were no answers to print
+    /// in short mode, and `true` otherwise.
+    pub fn print_streamed(self, result: &QueryResult) -> bool {
+        if let Some(error) = &result.error {
+            if self != Self::JSON {
+                print_error(error);
+            }
+        }
+
         match self {
-            Self::Short(..) | Self::Text(..) => {
-                eprintln!("Error: {error}");
+            Self::Short(_) => {
+                if let Some(validation) = &result.validation {
+                    if validation.security() == Security::Bogus {
+                        print_bogus(validation);
+                    }
+                }
+
+                let Some(response) = &result.response else {
+                    return false;
+                };
+
+                for answer in &response.answers {
+                    println!("{}", TextFormat::record_payload_summary(&answer.data));
+                }
+
+                return !response.answers.is_empty();
+            }
+            Self::JSON => {
+                let mut object = object! {
+                    "schema_version": JSON_SCHEMA_VERSION,
+                };
+
+                for (key, value) in json_result(result).entries() {
+                    object[key] = value.clone();
+                }
+
+                println!("{object}");
             }
+            Self::Text(uc, tf) => {
+                if let Some(response) = &result.response {
+                    println!("{}", header_summary(response, &uc.palette()));
+                    let table = response_table(response, uc, tf);
+                    print!("{}", table.render());
+                }
+
+                if let Some(validation) = &result.validation {
+                    print!("{}", validation_summary(validation, &uc.palette()));
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Prints the hops taken by each trace, formatted according to the
+    /// settings: which nameserver was asked about which zone, how long it
+    /// took, and the response it gave. In short mode, only the answers at
+    /// the end of each trace are printed. Returns `false` if there were no
+    /// results to print, and `true` otherwise.
+    pub fn print_traces(self, traces: &[Trace], duration: Option<Duration>) -> bool {
+        match self {
+            Self::Short(_) => {
+                for error in traces.iter().filter_map(|t| t.error.as_ref()) {
+                    eprintln!("Error: {error}");
+                }
+
+                let all_answers = traces
+                    .iter()
+                    .filter_map(Trace::answer)
+                    .flat_map(|r| &r.answers)
+                    .collect::<Vec<_>>();
+
+                if all_answers.is_empty() {
+                    eprintln!("No results");
+                    return false;
+                }
 
+                for answer in all_answers {
+                    println!("{}", TextFormat::record_payload_summary(&answer.data));
+                }
+            }
             Self::JSON => {
-                let object = object! {
-                    "error": true,
-                    "error_message": error.to_string(),
+                let mut object = object! {
+                    "schema_version": JSON_SCHEMA_VERSION,
+                    "traces": traces.iter().map(json_trace).collect::<Vec<_>>(),
                 };
 
-                eprintln!("{object}");
+                if let Some(duration) = duration {
+                    object["duration"] = object! {
+                        "secs": duration.as_secs(),
+                        "millis": duration.subsec_millis(),
+                    };
+                }
+
+                println!("{object}");
+            }
+            Self::Text(uc, tf) => {
+                for trace in traces {
+                    for (index, hop) in trace.hops.iter().enumerate() {
+                        println!("{}", hop_summary(index + 1, hop));
+
+                        if let Ok(response) = &hop.result {
+                            println!("{}", header_summary(response, &uc.palette()));
+                            let table = response_table(response, uc, tf);
+                            print!("{}", table.render());
+                        }
+                    }
+
+                    if let Some(error) = &trace.error {
+                        eprintln!("Error: {error}");
+                    }
+                }
+
+                if let Some(duration) = duration {
+                    println!("Ran in {}ms", duration.as_millis());
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Prints every response to each multicast query, formatted according
+    /// to the settings, along with the address of the responder that sent
+    /// it. In short mode, only the answers are printed. Returns `false` if
+    /// nobody responded to any of the queries, and `true` otherwise.
+    pub fn print_collections(self, collections: &[Collection], duration: Option<Duration>) -> bool {
+        let any_replies = collections.iter().any(|c| !c.replies.is_empty());
+
+        match self {
+            Self::Short(_) => {
+                print_collection_errors(collections);
+
+                let all_answers = collections
+                    .iter()
+                    .flat_map(|c| &c.replies)
+                    .flat_map(|r| &r.message.answers)
+                    .collect::<Vec<_>>();
+
+                if all_answers.is_empty() {
+                    eprintln!("No results");
+                    return false;
+                }
+
+                for answer in all_answers {
+                    println!("{}", TextFormat::record_payload_summary(&answer.data));
+                }
+            }
+            Self::JSON => {
+                let mut object = object! {
+                    "schema_version": JSON_SCHEMA_VERSION,
+                    "queries": collections.iter().map(json_collection).collect::<Vec<_>>(),
+                };
+
+                if let Some(duration) = duration {
+                    object["duration"] = object! {
+                        "secs": duration.as_secs(),
+                        "millis": duration.subsec_millis(),
+                    };
+                }
+
+                println!("{object}");
+            }
+            Self::Text(uc, tf) => {
+                for collection in collections {
+                    for reply in &collection.replies {
+                        println!("{}", reply_summary(collection, reply));
+                        println!("{}", header_summary(&reply.message, &uc.palette()));
+                        let table = response_table(&reply.message, uc, tf);
+                        print!("{}", table.render());
+                    }
+                }
+
+                print_collection_errors(collections);
+
+                if let Some(duration) = duration {
+                    println!("Ran in {}ms", duration.as_millis());
+                }
+            }
+        }
+
+        any_replies
+    }
+}
+
+/// Prints the queries that couldn’t be sent, or that nobody responded to,
+/// to standard error.
+fn print_collection_errors(collections: &[Collection]) {
+    for collection in collections {
+        match &collection.error {
+            Some(error) => eprintln!("Error: {error}"),
+            None if collection.replies.is_empty() => {
+                eprintln!("No responses to {} {}", collection.domain, collection.qtype);
+            }
+            None => {}
+        }
+    }
+}
+
+/// Describes one response to a multicast query as a line of text: which
+/// query it was for, the responder that sent it, and how long it took.
+fn reply_summary(collection: &Collection, reply: &Reply) -> String {
+    let elapsed_ms = reply.elapsed.as_secs_f64() * 1000.0;
+    format!(
+        "Response to {} {} from {} in {elapsed_ms:.2}ms",
+        collection.domain,
+        collection.qtype,
+        reply.from.ip(),
+    )
+}
+
+/// Converts the responses to one multicast query into a JSON object: the
+/// query that was sent, each response with the responder that sent it, and
+/// the reason the query couldn’t be sent, if it couldn’t.
+fn json_collection(collection: &Collection) -> JsonValue {
+    let replies = collection
+        .replies
+        .iter()
+        .map(|reply| {
+            let mut json = object! {
+                "responder": reply.from.ip().to_string(),
+                "elapsed_ms": reply.elapsed.as_secs_f64() * 1000.0,
+            };
+            add_json_response(&mut json, &reply.message);
+            json
+        })
+        .collect::<Vec<_>>();
+
+    let mut json = object! {
+        "query": {
+            "name": collection.domain.clone(),
+            "type": collection.qtype.to_string(),
+            "class": collection.class.to_string(),
+        },
+        "responses": replies,
+    };
+
+    if let Some(error) = &collection.error {
+        json["error"] = true.into();
+        json["error_message"] = error.to_string().into();
+    }
+
+    json
+}
+
+/// Describes one hop of a trace as a line of text: the nameserver that was
+/// asked, which zone it was asked about, how long it took, and whether it
+/// gave a referral or failed.
+fn hop_summary(number: usize, hop: &Hop) -> String {
+    let elapsed_ms = hop.elapsed.as_secs_f64() * 1000.0;
+    let mut summary = format!(
+        "Hop {number}: asked {} ({}, {}) about the {} zone in {elapsed_ms:.2}ms",
+        hop.server,
+        hop.transport.address(),
+        hop.transport.protocol_name(),
+        hop.zone,
+    );
+
+    match (&hop.result, &hop.referral) {
+        (Err(e), _) => write!(summary, ", failed: {e}").unwrap(),
+        (Ok(_), Some(referral)) => write!(summary, ", referred to {referral}").unwrap(),
+        (Ok(_), None) => {}
+    }
+
+    summary
+}
+
+/// Converts a trace into a JSON object: the query that was traced, each
+/// hop taken, and the reason the trace failed, if it did.
+fn json_trace(trace: &Trace) -> JsonValue {
+    let hops = trace.hops.iter().map(json_hop).collect::<Vec<_>>();
+    let mut json = object! {
+        "query": {
+            "name": trace.domain.clone(),
+            "type": trace.qtype.to_string(),
+            "class": trace.class.to_string(),
+        },
+        "hops": hops,
+    };
+
+    if let Some(error) = &trace.error {
+        json["error"] = true.into();
+        json["error_message"] = error.to_string().into();
+    }
+
+    json
+}
+
+/// Converts one hop of a trace into a JSON object, with the same keys for
+/// the response as the objects in the `responses` array.
+fn json_hop(hop: &Hop) -> JsonValue {
+    let mut json = object! {
+        "zone": hop.zone.to_string(),
+        "server": hop.server.to_string(),
+        "nameserver": hop.transport.address().to_string(),
+        "transport": hop.transport.protocol_name(),
+        "elapsed_ms": hop.elapsed.as_secs_f64() * 1000.0,
+        "referral": hop.referral.as_ref().map(ToString::to_string),
+    };
+
+    match &hop.result {
+        Ok(response) => add_json_response(&mut json, response),
+        Err(e) => {
+            json["error"] = true.into();
+            json["error_message"] = e.to_string().into();
+        }
+    }
+
+    json
+}
+
+/// Prints an error that’s occurred while sending or receiving DNS packets
+/// to standard error. In JSON mode, errors are part of the output instead.
+fn print_error(error: &QueryError) {
+    eprintln!("Error: {error}");
+}
+
+/// Prints the reason a response failed DNSSEC validation to standard
+/// error, for short mode, where the chain of trust isn’t displayed.
+fn print_bogus(validation: &Validation) {
+    match validation
+        .cause()
+        .and_then(|link| Some((link, link.reason.as_ref()?)))
+    {
+        Some((link, reason)) => eprintln!(
+            "Error: DNSSEC validation failed: {reason} for {} {}",
+            link.name, link.record_type
+        ),
+        None => eprintln!("Error: DNSSEC validation failed"),
+    }
+}
+
+/// Converts the result of one query into a JSON object: the details of the
+/// query and where it was sent, then the response that came back, and any
+/// error that occurred.
+fn json_result(result: &QueryResult) -> JsonValue {
+    let mut json = object! {
+        "query": {
+            "name": result.domain.clone(),
+            "type": result.qtype.to_string(),
+            "class": result.class.to_string(),
+        },
+        "nameserver": result.nameserver.clone(),
+        "transport": result.transport,
+        "elapsed_ms": result.elapsed.as_secs_f64() * 1000.0,
+        "attempts": result.attempts,
+    };
+
+    if let Some(version) = result.http_version {
+        json["http_version"] = format!("{version:?}").into();
+    }
+
+    if let Some(response) = &result.response {
+        add_json_response(&mut json, response);
+    }
+
+    if let Some(error) = &result.error {
+        json["error"] = true.into();
+        json["error_message"] = error.to_string().into();
+    }
+
+    if let Some(validation) = &result.validation {
+        json["validation"] = json_validation(validation);
+    }
+
+    json
+}
+
+/// Converts the result of validating a response into a JSON object: its
+/// overall state, and each link of the chain of trust.
+fn json_validation(validation: &Validation) -> JsonValue {
+    let chain = validation
+        .links
+        .iter()
+        .map(|link| {
+            let mut json = object! {
+                "name": link.name.to_string(),
+                "type": link.record_type.to_string(),
+                "status": link.security.as_str(),
+            };
+
+            if let Some(reason) = &link.reason {
+                json["reason"] = reason.to_string().into();
+            }
+
+            json
+        })
+        .collect::<Vec<_>>();
+
+    object! {
+        "status": validation.security().as_str(),
+        "chain": chain,
+    }
+}
+
+/// Formats the result of validating a response as lines of text: its
+/// overall state, then each link of the chain of trust, starting from the
+/// trust anchor, with the reason for any link that isn’t secure.
+fn validation_summary(validation: &Validation, colours: &Colours) -> String {
+    let style = |security| match security {
+        Security::Secure => colours.rcode_success,
+        Security::Bogus => colours.rcode_error,
+        Security::Insecure | Security::Indeterminate => Style::default(),
+    };
+
+    let security = validation.security();
+    let mut summary = format!("DNSSEC {}\n", style(security).paint(security.as_str()));
+    for link in &validation.links {
+        let status = format!("{:<13}", link.security.as_str());
+        write!(
+            summary,
+            "  {} {} {}",
+            style(link.security).paint(status),
+            link.record_type,
+            link.name
+        )
+        .unwrap();
+
+        if let Some(reason) = &link.reason {
+            write!(summary, " ({reason})").unwrap();
+        }
+
+        summary.push('\n');
+    }
+
+    summary
+}
+
+/// Adds the keys describing a response to a JSON object: its code and ID,
+/// the header, and the records in each section.
+fn add_json_response(json: &mut JsonValue, response: &Message) {
+    json["rcode"] = rcode_name(response.metadata.response_code).into();
+    json["id"] = response.metadata.id.into();
+    json["header"] = json_header(response);
+    json["answers"] = response
+        .answers
+        .iter()
+        .map(json_record)
+        .collect::<Vec<_>>()
+        .into();
+    json["authorities"] = response
+        .authorities
+        .iter()
+        .map(json_record)
+        .collect::<Vec<_>>()
+        .into();
+    json["additionals"] = response
+        .additionals
+        .iter()
+        .map(json_record)
+        .collect::<Vec<_>>()
+        .into();
+
+    if let Some(edns) = &response.edns {
+        json["edns"] = json_edns(edns);
+    }
+
+    if let Some(changes) = incremental_changes(response) {
+        json["changes"] = changes.iter().map(json_change).collect::<Vec<_>>().into();
+    }
+}
+
+/// Formats the header of a response as one line of text: the response code,
+/// the flags that were set, and the number of records in each section.
+fn header_summary(response: &Message, colours: &Colours) -> String {
+    let rcode = response.metadata.response_code;
+    let rcode_style = if rcode == ResponseCode::NoError {
+        colours.rcode_success
+    } else {
+        colours.rcode_error
+    };
+
+    let answers = response.answers.len();
+    format!(
+        "{}  {}  {} {}, {} authority, {} additional",
+        rcode_style.paint(rcode_name(rcode)),
+        header_flags(response).join(" "),
+        answers,
+        if answers == 1 { "answer" } else { "answers" },
+        response.authorities.len(),
+        response.additionals.len(),
+    )
+}
+
+/// Returns the names of the flags set in the header of a response, in the
+/// order they appear in the packet.
+fn header_flags(response: &Message) -> Vec<&'static str> {
+    let metadata = &response.metadata;
+    [
+        (metadata.message_type == MessageType::Response, "qr"),
+        (metadata.authoritative, "aa"),
+        (metadata.truncation, "tc"),
+        (metadata.recursion_desired, "rd"),
+        (metadata.recursion_available, "ra"),
+        (metadata.authentic_data, "ad"),
+        (metadata.checking_disabled, "cd"),
+    ]
+    .iter()
+    .filter(|(set, _)| *set)
+    .map(|(_, name)| *name)
+    .collect()
+}
+
+/// Returns the mnemonic for a response code, as it’s written in the RFCs,
+/// falling back to its number for codes that don’t have one.
+fn rcode_name(rcode: ResponseCode) -> String {
+    let name = match rcode {
+        ResponseCode::NoError => "NOERROR",
+        ResponseCode::FormErr => "FORMERR",
+        ResponseCode::ServFail => "SERVFAIL",
+        ResponseCode::NXDomain => "NXDOMAIN",
+        ResponseCode::NotImp => "NOTIMP",
+        ResponseCode::Refused => "REFUSED",
+        ResponseCode::YXDomain => "YXDOMAIN",
+        ResponseCode::YXRRSet => "YXRRSET",
+        ResponseCode::NXRRSet => "NXRRSET",
+        ResponseCode::NotAuth => "NOTAUTH",
+        ResponseCode::NotZone => "NOTZONE",
+        ResponseCode::BADVERS => "BADVERS",
+        ResponseCode::BADSIG => "BADSIG",
+        ResponseCode::BADKEY => "BADKEY",
+        ResponseCode::BADTIME => "BADTIME",
+        ResponseCode::BADMODE => "BADMODE",
+        ResponseCode::BADNAME => "BADNAME",
+        ResponseCode::BADALG => "BADALG",
+        ResponseCode::BADTRUNC => "BADTRUNC",
+        ResponseCode::BADCOOKIE => "BADCOOKIE",
+        ResponseCode::Unknown(_) => return format!("RCODE{}", u16::from(rcode)),
+    };
+
+    name.to_string()
+}
+
+/// Returns the changes listed in the answer section of a response to an
+/// IXFR request, or `None` if the nameserver sent something else, such as
+/// the entire zone.
+fn incremental_changes(response: &Message) -> Option<Vec<Change>> {
+    if response.queries.first()?.query_type() != RecordType::IXFR {
+        return None;
+    }
+
+    match Transfer::from_records(&response.answers, true)? {
+        Transfer::Incremental(changes) => Some(changes),
+        Transfer::Full(_) | Transfer::UpToDate(_) => None,
+    }
+}
+
+/// Builds the table of records to display for one response, going through
+/// each section in turn. Incremental zone transfers are displayed as a list
+/// of removed and added records instead of an answer section. The OPT
+/// pseudo-record is only present if the user asked to see it.
+fn response_table(response: &Message, uc: UseColours, tf: TextFormat) -> Table {
+    let mut table = Table::new(&uc.palette(), tf);
+
+    if let Some(changes) = incremental_changes(response) {
+        for change in &changes {
+            for a in &change.removed {
+                table.add_row(a, Section::Removed);
+            }
+
+            for a in &change.added {
+                table.add_row(a, Section::Added);
             }
         }
+    } else {
+        for a in &response.answers {
+            table.add_row(a, Section::Answer);
+        }
+    }
+
+    for a in &response.authorities {
+        table.add_row(a, Section::Authority);
+    }
+
+    for a in &response.additionals {
+        table.add_row(a, Section::Additional);
+    }
+
+    if let Some(edns) = &response.edns {
+        table.add_opt_row(edns);
+    }
+
+    table
+}
+
+/// Converts a record into a JSON object, with the record’s data given as
+/// an object of its own.
+fn json_record(record: &Record) -> JsonValue {
+    object! {
+        "name": record.name.to_string(),
+        "type": record.record_type().to_string(),
+        "class": record.dns_class.to_string(),
+        "ttl": record.ttl,
+        "data": json_record_data(&record.data),
+    }
+}
+
+/// Converts the data of a record into a JSON object. The keys depend on the
+/// type of the record, and are listed in the man page. Binary data is given
+/// as hexadecimal, and character strings are decoded as UTF-8. Types without
+/// their own keys have their presentation format given under `text`, except
+/// unknown types, which have their raw bytes given under `bytes`.
+fn json_record_data(data: &RData) -> JsonValue {
+    match data {
+        RData::A(a) => object! {
+            "address": a.0.to_string(),
+        },
+        RData::AAAA(aaaa) => object! {
+            "address": aaaa.0.to_string(),
+        },
+        RData::ANAME(name) => object! {
+            "target": name.0.to_string(),
+        },
+        RData::CNAME(name) => object! {
+            "target": name.0.to_string(),
+        },
+        RData::PTR(name) => object! {
+            "target": name.0.to_string(),
+        },
+        RData::NS(name) => object! {
+            "nameserver": name.0.to_string(),
+        },
+        RData::CAA(caa) => object! {
+            "critical": caa.issuer_critical,
+            "tag": caa.tag.clone(),
+            "value": String::from_utf8_lossy(&caa.value).into_owned(),
+        },
+        RData::CERT(cert) => object! {
+            "cert_type": u16::from(cert.cert_type),
+            "key_tag": cert.key_tag,
+            "algorithm": u8::from(cert.algorithm),
+            "cert_data": hex_string(&cert.cert_data),
+        },
+        RData::CSYNC(csync) => object! {
+            "serial": csync.soa_serial,
+            "immediate": csync.immediate,
+            "soa_minimum": csync.soa_minimum,
+            "types": csync.type_bit_maps.iter().map(|t| t.to_string()).collect::<Vec<_>>(),
+        },
+        RData::HINFO(hinfo) => object! {
+            "cpu": String::from_utf8_lossy(&hinfo.cpu).into_owned(),
+            "os": String::from_utf8_lossy(&hinfo.os).into_owned(),
+        },
+        RData::HTTPS(https) => json_svcb(&https.0),
+        RData::SVCB(svcb) => json_svcb(svcb),
+        RData::MX(mx) => object! {
+            "preference": mx.preference,
+            "exchange": mx.exchange.to_string(),
+        },
+        RData::NAPTR(naptr) => object! {
+            "order": naptr.order,
+            "preference": naptr.preference,
+            "flags": String::from_utf8_lossy(&naptr.flags).into_owned(),
+            "services": String::from_utf8_lossy(&naptr.services).into_owned(),
+            "regexp": String::from_utf8_lossy(&naptr.regexp).into_owned(),
+            "replacement": naptr.replacement.to_string(),
+        },
+        RData::NULL(null) => object! {
+            "bytes": hex_string(&null.anything),
+        },
+        RData::OPENPGPKEY(key) => object! {
+            "public_key": hex_string(&key.public_key),
+        },
+        RData::SMIMEA(smimea) => json_tlsa(&smimea.0),
+        RData::TLSA(tlsa) => json_tlsa(tlsa),
+        RData::SOA(soa) => object! {
+            "mname": soa.mname.to_string(),
+            "rname": soa.rname.to_string(),
+            "serial": soa.serial,
+            "refresh": soa.refresh,
+            "retry": soa.retry,
+            "expire": soa.expire,
+            "minimum": soa.minimum,
+        },
+        RData::SRV(srv) => object! {
+            "priority": srv.priority,
+            "weight": srv.weight,
+            "port": srv.port,
+            "target": srv.target.to_string(),
+        },
+        RData::SSHFP(sshfp) => object! {
+            "algorithm": u8::from(sshfp.algorithm),
+            "fingerprint_type": u8::from(sshfp.fingerprint_type),
+            "fingerprint": hex_string(&sshfp.fingerprint),
+        },
+        RData::TXT(txt) => object! {
+            "strings": txt
+                .txt_data
+                .iter()
+                .map(|s| String::from_utf8_lossy(s).into_owned())
+                .collect::<Vec<_>>(),
+        },
+        RData::Unknown { rdata, .. } => object! {
+            "bytes": hex_string(&rdata.anything),
+        },
+        RData::DNSSEC(DNSSECRData::DNSKEY(key)) => json_dnskey(key),
+        RData::DNSSEC(DNSSECRData::DS(ds)) => json_ds(ds),
+        RData::DNSSEC(DNSSECRData::RRSIG(rrsig)) => json_rrsig(rrsig),
+        RData::DNSSEC(DNSSECRData::NSEC(nsec)) => object! {
+            "next_domain": nsec.next_domain_name().to_string(),
+            "types": nsec.type_bit_maps().map(|t| t.to_string()).collect::<Vec<_>>(),
+        },
+        RData::DNSSEC(DNSSECRData::NSEC3(nsec3)) => json_nsec3(nsec3),
+        RData::DNSSEC(DNSSECRData::NSEC3PARAM(param)) => object! {
+            "hash_algorithm": u8::from(param.hash_algorithm()),
+            "opt_out": param.opt_out(),
+            "iterations": param.iterations(),
+            "salt": hex_string(param.salt()),
+        },
+        other => object! {
+            "text": other.to_string(),
+        },
+    }
+}
+
+/// Converts the data of an SVCB or HTTPS record into a JSON object, with
+/// each of its parameters under `params`, keyed by name.
+fn json_svcb(svcb: &SVCB) -> JsonValue {
+    let mut params = JsonValue::new_object();
+    for (key, value) in &svcb.svc_params {
+        params[key.to_string()] = match value {
+            SvcParamValue::Mandatory(mandatory) => mandatory
+                .0
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .into(),
+            SvcParamValue::Alpn(alpn) => alpn.0.clone().into(),
+            SvcParamValue::NoDefaultAlpn => true.into(),
+            SvcParamValue::Port(port) => (*port).into(),
+            SvcParamValue::Ipv4Hint(hint) => hint
+                .0
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .into(),
+            SvcParamValue::Ipv6Hint(hint) => hint
+                .0
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .into(),
+            SvcParamValue::EchConfigList(ech) => hex_string(&ech.0).into(),
+            SvcParamValue::Unknown(unknown) => hex_string(&unknown.0).into(),
+        };
+    }
+
+    object! {
+        "priority": svcb.svc_priority,
+        "target": svcb.target_name.to_string(),
+        "params": params,
+    }
+}
+
+/// Converts the data of a DNSKEY record into a JSON object.
+fn json_dnskey(key: &DNSKEY) -> JsonValue {
+    object! {
+        "flags": key.flags(),
+        "protocol": 3,
+        "algorithm": u8::from(key.public_key().algorithm()),
+        "public_key": hex_string(key.public_key().public_bytes()),
+    }
+}
+
+/// Converts the data of a DS record into a JSON object.
+fn json_ds(ds: &DS) -> JsonValue {
+    object! {
+        "key_tag": ds.key_tag(),
+        "algorithm": u8::from(ds.algorithm()),
+        "digest_type": u8::from(ds.digest_type()),
+        "digest": hex_string(ds.digest()),
+    }
+}
+
+/// Converts the data of an RRSIG record into a JSON object, with the times
+/// the signature is valid between as Unix timestamps.
+fn json_rrsig(rrsig: &RRSIG) -> JsonValue {
+    let input = rrsig.input();
+    object! {
+        "type_covered": input.type_covered.to_string(),
+        "algorithm": u8::from(input.algorithm),
+        "labels": input.num_labels,
+        "original_ttl": input.original_ttl,
+        "expiration": input.sig_expiration.get(),
+        "inception": input.sig_inception.get(),
+        "key_tag": input.key_tag,
+        "signer_name": input.signer_name.to_string(),
+        "signature": hex_string(rrsig.sig()),
+    }
+}
+
+/// Converts the data of an NSEC3 record into a JSON object, with the next
+/// hashed owner name in the same base32 form it has as a domain name.
+fn json_nsec3(nsec3: &NSEC3) -> JsonValue {
+    object! {
+        "hash_algorithm": u8::from(nsec3.hash_algorithm()),
+        "opt_out": nsec3.opt_out(),
+        "iterations": nsec3.iterations(),
+        "salt": hex_string(nsec3.salt()),
+        "next_domain": BASE32_DNSSEC.encode(nsec3.next_hashed_owner_name()),
+        "types": nsec3.type_bit_maps().map(|t| t.to_string()).collect::<Vec<_>>(),
+    }
+}
+
+/// Converts the data of a TLSA or SMIMEA record into a JSON object.
+fn json_tlsa(tlsa: &TLSA) -> JsonValue {
+    object! {
+        "cert_usage": u8::from(tlsa.cert_usage),
+        "selector": u8::from(tlsa.selector),
+        "matching_type": u8::from(tlsa.matching),
+        "cert_data": hex_string(&tlsa.cert_data),
+    }
+}
+
+/// Converts one set of changes from an incremental zone transfer into a
+/// JSON object.
+fn json_change(change: &Change) -> JsonValue {
+    object! {
+        "from_serial": change.from_serial,
+        "to_serial": change.to_serial,
+        "removed": change.removed.iter().map(json_record).collect::<Vec<_>>(),
+        "added": change.added.iter().map(json_record).collect::<Vec<_>>(),
+    }
+}
+
+/// Converts the header of a response into a JSON object.
+fn json_header(response: &Message) -> JsonValue {
+    let metadata = &response.metadata;
+    object! {
+        "rcode": rcode_name(metadata.response_code),
+        "opcode": metadata.op_code.to_string(),
+        "flags": {
+            "qr": metadata.message_type == MessageType::Response,
+            "aa": metadata.authoritative,
+            "tc": metadata.truncation,
+            "rd": metadata.recursion_desired,
+            "ra": metadata.recursion_available,
+            "ad": metadata.authentic_data,
+            "cd": metadata.checking_disabled,
+        },
+        "answer_count": response.answers.len(),
+        "authority_count": response.authorities.len(),
+        "additional_count": response.additionals.len(),
+    }
+}
+
+/// Converts the EDNS settings from a response’s OPT pseudo-record into a
+/// JSON object.
+fn json_edns(edns: &Edns) -> JsonValue {
+    let options = edns
+        .options()
+        .options
+        .iter()
+        .map(|(code, option)| {
+            object! {
+                "code": u16::from(*code),
+                "name": edns_code_name(*code),
+                "data": edns_option_summary(option),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    object! {
+        "udp_payload_size": edns.max_payload(),
+        "version": edns.version(),
+        "extended_rcode": edns.rcode_high(),
+        "dnssec_ok": edns.flags().dnssec_ok,
+        "options": options,
     }
 }
 
@@ -197,6 +1157,29 @@ impl TextFormat {
         record.to_string()
     }
 
+    /// Formats a summary of the EDNS settings in an OPT pseudo-record: the
+    /// UDP payload size, version, upper bits of the response code, the DO
+    /// flag, and then each of the options it contains.
+    pub fn opt_summary(edns: &Edns) -> String {
+        let flags = if edns.flags().dnssec_ok { "do" } else { "-" };
+        let mut summary = format!(
+            "payload {}, version {}, ext-rcode {}, flags {}",
+            edns.max_payload(),
+            edns.version(),
+            edns.rcode_high(),
+            flags,
+        );
+
+        for (code, option) in &edns.options().options {
+            summary.push_str(", ");
+            summary.push_str(&edns_code_name(*code));
+            summary.push(' ');
+            summary.push_str(&edns_option_summary(option));
+        }
+
+        summary
+    }
+
     /// Formats a duration depending on whether it should be displayed as
     /// seconds, or as computed units.
     pub fn format_duration(self, seconds: u32) -> String {
@@ -208,9 +1191,67 @@ impl TextFormat {
     }
 }
 
+/// Returns the name of an EDNS option code, falling back to its number for
+/// codes that don’t have one.
+fn edns_code_name(code: EdnsCode) -> String {
+    let name = match code {
+        EdnsCode::LLQ => "LLQ",
+        EdnsCode::UL => "UL",
+        EdnsCode::NSID => "NSID",
+        EdnsCode::DAU => "DAU",
+        EdnsCode::DHU => "DHU",
+        EdnsCode::N3U => "N3U",
+        EdnsCode::Subnet => "ECS",
+        EdnsCode::Expire => "EXPIRE",
+        EdnsCode::Cookie => "COOKIE",
+        EdnsCode::Keepalive => "KEEPALIVE",
+        EdnsCode::Padding => "PADDING",
+        EdnsCode::Chain => "CHAIN",
+        _ => {
+            return format!("OPT{}", u16::from(code));
+        }
+    };
+
+    name.to_string()
+}
+
+/// Formats the data of an EDNS option. Client subnets get printed as an
+/// address with their prefix lengths, name server IDs as text if they’re
+/// printable, and everything else as hexadecimal.
+fn edns_option_summary(option: &EdnsOption) -> String {
+    match option {
+        EdnsOption::Subnet(subnet) => format!(
+            "{}/{}/{}",
+            subnet.addr(),
+            subnet.source_prefix(),
+            subnet.scope_prefix()
+        ),
+        EdnsOption::NSID(payload)
+            if payload
+                .as_ref()
+                .iter()
+                .all(|b| b.is_ascii_graphic() || *b == b' ') =>
+        {
+            format!("{:?}", String::from_utf8_lossy(payload.as_ref()))
+        }
+        other => match Vec::<u8>::try_from(other) {
+            Ok(bytes) => hex_string(&bytes),
+            Err(_) => String::from("?"),
+        },
+    }
+}
+
+/// Formats a sequence of bytes as lowercase hexadecimal.
+fn hex_string(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut out, b| {
+        let _ = write!(out, "{b:02x}");
+        out
+    })
+}
+
 /// Formats a duration as days, hours, minutes, and seconds, skipping leading
 /// zero units.
-fn format_duration_hms(seconds: u32) -> String {
+pub fn format_duration_hms(seconds: u32) -> String {
     if seconds < 60 {
         format!("{seconds}s")
     } else if seconds < 60 * 60 {
@@ -236,6 +1277,15 @@ fn format_duration_hms(seconds: u32) -> String {
 #[cfg(test)]
 mod test {
     use super::*;
+    use hickory_resolver::proto::dnssec::rdata::{SigInput, NSEC};
+    use hickory_resolver::proto::dnssec::{
+        Algorithm, DigestType, Nsec3HashAlgorithm, PublicKeyBuf,
+    };
+    use hickory_resolver::proto::op::OpCode;
+    use hickory_resolver::proto::rr::rdata::opt::{ClientSubnet, NSIDPayload};
+    use hickory_resolver::proto::rr::rdata::svcb::{Alpn, SvcParamKey};
+    use hickory_resolver::proto::rr::rdata::{MX, SOA, TXT};
+    use hickory_resolver::proto::rr::{Name, RecordType, SerialNumber};
 
     #[test]
     fn test_format_duration() {
@@ -247,4 +1297,277 @@ mod test {
         assert_eq!(format_duration_hms(86399), "23h59m59s");
         assert_eq!(format_duration_hms(86400), "1d0h00m00s");
     }
+
+    #[test]
+    fn opt_summary_defaults() {
+        let mut edns = Edns::new();
+        edns.set_max_payload(1232);
+        edns.set_dnssec_ok(true);
+
+        assert_eq!(
+            TextFormat::opt_summary(&edns),
+            "payload 1232, version 0, ext-rcode 0, flags do"
+        );
+    }
+
+    #[test]
+    fn edns_options() {
+        let nsid = EdnsOption::NSID(NSIDPayload::new("ns1.lookup.dog").unwrap());
+        assert_eq!(edns_option_summary(&nsid), "\"ns1.lookup.dog\"");
+
+        let subnet = EdnsOption::Subnet(ClientSubnet::new("192.0.2.0".parse().unwrap(), 24, 0));
+        assert_eq!(edns_option_summary(&subnet), "192.0.2.0/24/0");
+
+        let cookie = EdnsOption::Unknown(10, vec![0xde, 0xad, 0xbe, 0xef]);
+        assert_eq!(edns_code_name(EdnsCode::from(10)), "COOKIE");
+        assert_eq!(edns_option_summary(&cookie), "deadbeef");
+    }
+
+    #[test]
+    fn header_line() {
+        let mut response = Message::response(1, OpCode::Query);
+        response.metadata.recursion_desired = true;
+        response.metadata.recursion_available = true;
+        response.metadata.response_code = ResponseCode::NXDomain;
+
+        assert_eq!(
+            header_summary(&response, &Colours::plain()),
+            "NXDOMAIN  qr rd ra  0 answers, 0 authority, 0 additional"
+        );
+    }
+
+    #[test]
+    fn unnamed_rcode() {
+        assert_eq!(rcode_name(ResponseCode::Unknown(3841)), "RCODE3841");
+    }
+
+    #[test]
+    fn json_mx_data() {
+        let mx = RData::MX(MX::new(10, Name::from_ascii("mail.lookup.dog.").unwrap()));
+        assert_eq!(
+            json_record_data(&mx),
+            object! {
+                "preference": 10,
+                "exchange": "mail.lookup.dog.",
+            }
+        );
+    }
+
+    #[test]
+    fn json_soa_data() {
+        let soa = RData::SOA(SOA::new(
+            Name::from_ascii("ns.lookup.dog.").unwrap(),
+            Name::from_ascii("admin.lookup.dog.").unwrap(),
+            2_024_010_101,
+            3600,
+            600,
+            86400,
+            300,
+        ));
+        assert_eq!(
+            json_record_data(&soa),
+            object! {
+                "mname": "ns.lookup.dog.",
+                "rname": "admin.lookup.dog.",
+                "serial": 2_024_010_101,
+                "refresh": 3600,
+                "retry": 600,
+                "expire": 86400,
+                "minimum": 300,
+            }
+        );
+    }
+
+    #[test]
+    fn json_txt_data() {
+        let txt = RData::TXT(TXT::new(vec!["v=spf1".into(), "-all".into()]));
+        assert_eq!(
+            json_record_data(&txt),
+            object! {
+                "strings": ["v=spf1", "-all"],
+            }
+        );
+    }
+
+    #[test]
+    fn json_dnskey_data() {
+        let key = RData::DNSSEC(DNSSECRData::DNSKEY(DNSKEY::with_flags(
+            257,
+            PublicKeyBuf::new(vec![0xab, 0xcd], Algorithm::ED25519),
+        )));
+        assert_eq!(
+            json_record_data(&key),
+            object! {
+                "flags": 257,
+                "protocol": 3,
+                "algorithm": 15,
+                "public_key": "abcd",
+            }
+        );
+    }
+
+    #[test]
+    fn json_ds_data() {
+        let ds = RData::DNSSEC(DNSSECRData::DS(DS::new(
+            20326,
+            Algorithm::RSASHA256,
+            DigestType::SHA256,
+            vec![0xe0, 0x6d],
+        )));
+        assert_eq!(
+            json_record_data(&ds),
+            object! {
+                "key_tag": 20326,
+                "algorithm": 8,
+                "digest_type": 2,
+                "digest": "e06d",
+            }
+        );
+    }
+
+    #[test]
+    fn json_rrsig_data() {
+        let input = SigInput {
+            type_covered: RecordType::A,
+            algorithm: Algorithm::ECDSAP256SHA256,
+            num_labels: 2,
+            original_ttl: 300,
+            sig_expiration: SerialNumber::new(1_700_086_400),
+            sig_inception: SerialNumber::new(1_700_000_000),
+            key_tag: 12345,
+            signer_name: Name::from_ascii("lookup.dog.").unwrap(),
+        };
+        let rrsig = RData::DNSSEC(DNSSECRData::RRSIG(RRSIG::from_sig(input, vec![0x01, 0x02])));
+        assert_eq!(
+            json_record_data(&rrsig),
+            object! {
+                "type_covered": "A",
+                "algorithm": 13,
+                "labels": 2,
+                "original_ttl": 300,
+                "expiration": 1_700_086_400,
+                "inception": 1_700_000_000,
+                "key_tag": 12345,
+                "signer_name": "lookup.dog.",
+                "signature": "0102",
+            }
+        );
+    }
+
+    #[test]
+    fn json_nsec_data() {
+        let nsec = RData::DNSSEC(DNSSECRData::NSEC(NSEC::new(
+            Name::from_ascii("www.lookup.dog.").unwrap(),
+            [RecordType::A, RecordType::RRSIG, RecordType::NSEC],
+        )));
+        assert_eq!(
+            json_record_data(&nsec),
+            object! {
+                "next_domain": "www.lookup.dog.",
+                "types": ["A", "RRSIG", "NSEC"],
+            }
+        );
+    }
+
+    #[test]
+    fn json_nsec3_data() {
+        let nsec3 = RData::DNSSEC(DNSSECRData::NSEC3(NSEC3::new(
+            Nsec3HashAlgorithm::SHA1,
+            true,
+            0,
+            vec![0xaa, 0xbb],
+            vec![0xff; 20],
+            [RecordType::NS, RecordType::DS],
+        )));
+        assert_eq!(
+            json_record_data(&nsec3),
+            object! {
+                "hash_algorithm": 1,
+                "opt_out": true,
+                "iterations": 0,
+                "salt": "aabb",
+                "next_domain": "vvvvvvvvvvvvvvvvvvvvvvvvvvvvvvvv",
+                "types": ["NS", "DS"],
+            }
+        );
+    }
+
+    #[test]
+    fn json_svcb_data() {
+        let svcb = RData::SVCB(SVCB::new(
+            1,
+            Name::root(),
+            vec![
+                (
+                    SvcParamKey::Alpn,
+                    SvcParamValue::Alpn(Alpn(vec!["h2".into(), "h3".into()])),
+                ),
+                (SvcParamKey::Port, SvcParamValue::Port(8443)),
+            ],
+        ));
+        assert_eq!(
+            json_record_data(&svcb),
+            object! {
+                "priority": 1,
+                "target": ".",
+                "params": {
+                    "alpn": ["h2", "h3"],
+                    "port": 8443,
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn json_inline_error() {
+        let result = QueryResult {
+            domain: String::from("lookup.dog"),
+            qtype: RecordType::MX,
+            class: DNSClass::IN,
+            nameserver: Some(String::from("127.0.0.1:53")),
+            transport: Some("UDP"),
+            http_version: None,
+            elapsed: Duration::from_millis(5),
+            attempts: 3,
+            response: None,
+            error: Some(ResolveError::Timeout.into()),
+            validation: None,
+        };
+
+        assert_eq!(
+            json_result(&result),
+            object! {
+                "query": {
+                    "name": "lookup.dog",
+                    "type": "MX",
+                    "class": "IN",
+                },
+                "nameserver": "127.0.0.1:53",
+                "transport": "UDP",
+                "elapsed_ms": 5.0,
+                "attempts": 3,
+                "error": true,
+                "error_message": ResolveError::Timeout.to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn json_http_version() {
+        let result = QueryResult {
+            domain: String::from("lookup.dog"),
+            qtype: RecordType::A,
+            class: DNSClass::IN,
+            nameserver: Some(String::from("1.1.1.1:443")),
+            transport: Some("HTTPS"),
+            http_version: Some(http::Version::HTTP_3),
+            elapsed: Duration::from_millis(5),
+            attempts: 1,
+            response: None,
+            error: Some(ResolveError::Timeout.into()),
+            validation: None,
+        };
+
+        assert_eq!(json_result(&result)["http_version"], "HTTP/3.0");
+    }
 }
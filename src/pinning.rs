@@ -0,0 +1,240 @@
+/*
+ * dog - A command-line DNS client
+ * Copyright (c) 2026 l1a and contributors
+ * Original code Copyright (c) Benjamin Sago
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Checking nameservers’ certificates against the hashes pinned in their
+//! stamps.
+//!
+//! A stamp for a protocol that uses TLS can list the SHA-256 hashes of the
+//! to-be-signed parts of certificates in the nameserver’s chain. The chain
+//! still has to lead to one of the usual root certificates, and on top of
+//! that, at least one of the certificates in it has to have one of those
+//! hashes.
+
+use std::convert::TryFrom;
+use std::sync::Arc;
+
+use hickory_resolver::net::tls::client_config;
+use log::*;
+use ring::digest::{digest, SHA256};
+use rustls_pki_types::{CertificateDer, ServerName, UnixTime};
+use tokio_rustls::rustls::client::danger::{
+    HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier,
+};
+use tokio_rustls::rustls::client::WebPkiServerVerifier;
+use tokio_rustls::rustls::crypto::ring::default_provider;
+use tokio_rustls::rustls::{
+    CertificateError, ClientConfig, DigitallySignedStruct, Error, RootCertStore, SignatureScheme,
+};
+
+/// The SHA-256 hash of the to-be-signed part of a certificate.
+pub type CertificateHash = [u8; 32];
+
+/// Returns the TLS settings to connect to a nameserver with, which trust
+/// the usual set of root certificates and, if any hashes are given, also
+/// require a certificate in the chain to have one of them.
+pub fn pinned_config(hashes: &[CertificateHash]) -> Result<ClientConfig, Error> {
+    let mut config = client_config()?;
+    if !hashes.is_empty() {
+        let roots = webpki_roots::TLS_SERVER_ROOTS.iter().cloned().collect();
+        let verifier = PinnedVerifier::new(roots, hashes)?;
+        config
+            .dangerous()
+            .set_certificate_verifier(Arc::new(verifier));
+    }
+
+    Ok(config)
+}
+
+/// A **pinned verifier** checks a nameserver’s certificate chain against
+/// a set of root certificates, then checks that one of the certificates in
+/// it has been pinned.
+#[derive(Debug)]
+pub struct PinnedVerifier {
+    /// The verifier that checks the chain against the root certificates.
+    roots: Arc<WebPkiServerVerifier>,
+
+    /// The hashes that one of the certificates has to have.
+    hashes: Vec<CertificateHash>,
+}
+
+impl PinnedVerifier {
+    /// Creates a new verifier that trusts the given root certificates and
+    /// requires one of the given hashes.
+    pub fn new(roots: RootCertStore, hashes: &[CertificateHash]) -> Result<Self, Error> {
+        let roots = WebPkiServerVerifier::builder_with_provider(
+            Arc::new(roots),
+            Arc::new(default_provider()),
+        )
+        .build()
+        .map_err(|e| Error::General(e.to_string()))?;
+
+        Ok(Self {
+            roots,
+            hashes: hashes.to_vec(),
+        })
+    }
+}
+
+impl ServerCertVerifier for PinnedVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, Error> {
+        let verified = self.roots.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            ocsp_response,
+            now,
+        )?;
+
+        let pinned = std::iter::once(end_entity)
+            .chain(intermediates)
+            .filter_map(|certificate| tbs_hash(certificate))
+            .any(|hash| self.hashes.contains(&hash));
+        if pinned {
+            Ok(verified)
+        } else {
+            warn!("No certificate in the chain has one of the pinned hashes");
+            Err(Error::InvalidCertificate(
+                CertificateError::ApplicationVerificationFailure,
+            ))
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        certificate: &CertificateDer<'_>,
+        signature: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        self.roots
+            .verify_tls12_signature(message, certificate, signature)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        certificate: &CertificateDer<'_>,
+        signature: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        self.roots
+            .verify_tls13_signature(message, certificate, signature)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.roots.supported_verify_schemes()
+    }
+}
+
+/// Returns the hash of the to-be-signed part of a certificate, which is the
+/// first element of the sequence that the certificate is made of, or `None`
+/// if the certificate can’t be read that far.
+fn tbs_hash(certificate: &[u8]) -> Option<CertificateHash> {
+    let (_, contents) = der_sequence(certificate)?;
+    let (tbs, _) = der_sequence(contents)?;
+    CertificateHash::try_from(digest(&SHA256, tbs).as_ref()).ok()
+}
+
+/// Reads the DER sequence at the start of the input, returning the whole
+/// of it, including its tag and length, and then just its contents.
+fn der_sequence(input: &[u8]) -> Option<(&[u8], &[u8])> {
+    if *input.first()? != 0x30 {
+        return None;
+    }
+
+    // Lengths over 127 are given as the number of bytes, then the bytes
+    let (header, length) = match *input.get(1)? {
+        length @ 0x00..=0x7f => (2, usize::from(length)),
+        count @ 0x81..=0x84 => {
+            let header = 2 + usize::from(count & 0x7f);
+            let bytes = input.get(2..header)?;
+            let length = bytes
+                .iter()
+                .fold(0, |length, b| length << 8 | usize::from(*b));
+            (header, length)
+        }
+        _ => return None,
+    };
+
+    let sequence = input.get(..header.checked_add(length)?)?;
+    Some((sequence, &sequence[header..]))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::doh::test::certificate;
+    use pretty_assertions::assert_eq;
+
+    /// The hash of the to-be-signed part of the test certificate.
+    const HASH: CertificateHash = [
+        0x30, 0xe2, 0xce, 0x05, 0xc5, 0xbf, 0x8b, 0x4e, 0x2a, 0x6b, 0x38, 0xad, 0x0a, 0xaa, 0x24,
+        0xe7, 0x6a, 0x31, 0x1e, 0x01, 0x38, 0x7d, 0x67, 0x9f, 0x3f, 0xda, 0x60, 0x51, 0xe2, 0x3f,
+        0xc9, 0xab,
+    ];
+
+    fn verifier(hashes: &[CertificateHash]) -> PinnedVerifier {
+        let mut roots = RootCertStore::empty();
+        roots.add(certificate()).unwrap();
+        PinnedVerifier::new(roots, hashes).unwrap()
+    }
+
+    fn verify(verifier: &PinnedVerifier, name: &'static str) -> Result<ServerCertVerified, Error> {
+        let name = ServerName::try_from(name).unwrap();
+        verifier.verify_server_cert(&certificate(), &[], &name, &[], UnixTime::now())
+    }
+
+    #[test]
+    fn hash_of_certificate() {
+        assert_eq!(tbs_hash(&certificate()), Some(HASH));
+    }
+
+    #[test]
+    fn hash_of_garbage() {
+        assert_eq!(tbs_hash(&[0x30, 0x82, 0x01]), None);
+    }
+
+    #[test]
+    fn pinned() {
+        assert!(verify(&verifier(&[[0xab; 32], HASH]), "doh.example").is_ok());
+    }
+
+    #[test]
+    fn not_pinned() {
+        assert_eq!(
+            verify(&verifier(&[[0xab; 32]]), "doh.example").unwrap_err(),
+            Error::InvalidCertificate(CertificateError::ApplicationVerificationFailure)
+        );
+    }
+
+    #[test]
+    fn pinned_but_wrong_name() {
+        assert!(matches!(
+            verify(&verifier(&[HASH]), "other.example"),
+            Err(Error::InvalidCertificate(
+                CertificateError::NotValidForNameContext { .. }
+            ))
+        ));
+    }
+}
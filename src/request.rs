@@ -0,0 +1,241 @@
+/*
+ * dog - A command-line DNS client
+ * Copyright (c) 2026 l1a and contributors
+ * Original code Copyright (c) Benjamin Sago
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Building the request messages that get sent to nameservers.
+
+use hickory_resolver::proto::op::{Edns, Message, Query, DEFAULT_MAX_PAYLOAD_LEN};
+use hickory_resolver::proto::rr::rdata::SOA;
+use hickory_resolver::proto::rr::{DNSClass, Name, RData, Record, RecordType};
+use hickory_resolver::proto::ProtoError;
+
+use crate::options::{Inputs, ProtocolTweaks, Requests};
+
+impl Requests {
+    /// Builds the request message for one domain, record type, and class
+    /// combination, using the settings the user has picked.
+    pub fn build_message(
+        &self,
+        domain: &str,
+        qtype: RecordType,
+        class: DNSClass,
+    ) -> Result<Message, ProtoError> {
+        self.build_message_for(&self.inputs, domain, qtype, class)
+    }
+
+    /// Builds the request message like `build_message`, but for a name
+    /// that’s already been parsed, such as one that came back in a
+    /// response. These can have labels that a name typed in by the user
+    /// couldn’t, such as spaces and capitals.
+    pub fn build_message_named(&self, name: Name, qtype: RecordType, class: DNSClass) -> Message {
+        self.build_request(&self.inputs, name, qtype, class)
+    }
+
+    /// Builds the request message like `build_message`, but takes the IXFR
+    /// serial number from the given inputs instead of the command line, as
+    /// each line of a batch file can have its own.
+    pub fn build_message_for(
+        &self,
+        inputs: &Inputs,
+        domain: &str,
+        qtype: RecordType,
+        class: DNSClass,
+    ) -> Result<Message, ProtoError> {
+        let name = Name::from_utf8(domain)?;
+        Ok(self.build_request(inputs, name, qtype, class))
+    }
+
+    /// Builds the request message for a name, taking the IXFR serial
+    /// number from the given inputs.
+    fn build_request(
+        &self,
+        inputs: &Inputs,
+        name: Name,
+        qtype: RecordType,
+        class: DNSClass,
+    ) -> Message {
+        let mut query = Query::query(name.clone(), qtype);
+        query.set_query_class(class);
+
+        let mut message = Message::query();
+        if let Some(txid) = self.txid {
+            message.metadata.id = txid;
+        }
+        message.metadata.recursion_desired = !self.tweaks.no_recursion;
+        message.metadata.authoritative = self.tweaks.set_authoritative_flag;
+        message.metadata.authentic_data = self.tweaks.set_authentic_flag;
+        message.metadata.checking_disabled = self.tweaks.set_checking_disabled_flag;
+        message.add_query(query);
+
+        // IXFR requests carry the version of the zone the client already
+        // has, as an SOA record in the authority section
+        if qtype == RecordType::IXFR {
+            let serial = inputs.ixfr_serial.unwrap_or(0);
+            let soa = SOA::new(Name::root(), Name::root(), serial, 0, 0, 0, 0);
+            message.add_authority(Record::from_rdata(name, 0, RData::SOA(soa)));
+        }
+
+        // The DO bit lives in the OPT record, so it can’t be sent when EDNS
+        // is disabled.
+        if self.edns.should_send() {
+            let mut edns = Edns::new();
+            edns.set_max_payload(
+                self.tweaks
+                    .udp_payload_size
+                    .unwrap_or(DEFAULT_MAX_PAYLOAD_LEN),
+            );
+            edns.set_dnssec_ok(self.dnssec);
+            message.set_edns(edns);
+        }
+
+        message
+    }
+}
+
+/// Describes the ID, header flags, and EDNS settings of a request message,
+/// along with the tweaks that change how it gets sent rather than what’s
+/// in it, so that the verbose output can show exactly what was sent.
+pub fn describe_request(message: &Message, tweaks: &ProtocolTweaks) -> String {
+    let metadata = &message.metadata;
+    let flags = [
+        (metadata.authoritative, "aa"),
+        (metadata.recursion_desired, "rd"),
+        (metadata.authentic_data, "ad"),
+        (metadata.checking_disabled, "cd"),
+    ]
+    .iter()
+    .filter(|(set, _)| *set)
+    .map(|(_, name)| *name)
+    .collect::<Vec<_>>();
+
+    let flags = if flags.is_empty() {
+        String::from("none")
+    } else {
+        flags.join(" ")
+    };
+
+    let edns = match &message.edns {
+        Some(edns) if edns.flags().dnssec_ok => {
+            format!("EDNS payload {}, do", edns.max_payload())
+        }
+        Some(edns) => format!("EDNS payload {}", edns.max_payload()),
+        None => String::from("no EDNS"),
+    };
+
+    let mut description = format!("ID {}, flags {flags}, {edns}", metadata.id);
+    if tweaks.no_tc_retry {
+        description.push_str(", tc-retry=off");
+    }
+
+    description
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::options::UseEDNS;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn query_class() {
+        let message = Requests::default()
+            .build_message("version.bind", RecordType::TXT, DNSClass::CH)
+            .unwrap();
+
+        assert_eq!(message.queries.len(), 1);
+        assert_eq!(message.queries[0].query_class(), DNSClass::CH);
+        assert_eq!(message.queries[0].query_type(), RecordType::TXT);
+    }
+
+    #[test]
+    fn dnssec_ok_bit() {
+        let requests = Requests {
+            dnssec: true,
+            ..Requests::default()
+        };
+        let message = requests
+            .build_message("lookup.dog", RecordType::A, DNSClass::IN)
+            .unwrap();
+
+        assert!(message.edns.unwrap().flags().dnssec_ok);
+    }
+
+    #[test]
+    fn ixfr_serial() {
+        let mut requests = Requests::default();
+        requests.inputs.ixfr_serial = Some(42);
+        let message = requests
+            .build_message("lookup.dog", RecordType::IXFR, DNSClass::IN)
+            .unwrap();
+
+        match &message.authorities[0].data {
+            RData::SOA(soa) => assert_eq!(soa.serial, 42),
+            other => panic!("Expected an SOA record, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn transaction_id() {
+        let requests = Requests {
+            txid: Some(0xABCD),
+            ..Requests::default()
+        };
+        let message = requests
+            .build_message("lookup.dog", RecordType::A, DNSClass::IN)
+            .unwrap();
+
+        assert_eq!(message.metadata.id, 0xABCD);
+    }
+
+    #[test]
+    fn edns_disabled() {
+        let requests = Requests {
+            edns: UseEDNS::Disable,
+            ..Requests::default()
+        };
+        let message = requests
+            .build_message("lookup.dog", RecordType::A, DNSClass::IN)
+            .unwrap();
+
+        assert!(message.edns.is_none());
+    }
+
+    #[test]
+    fn header_tweaks() {
+        let requests = Requests {
+            txid: Some(1),
+            tweaks: ProtocolTweaks {
+                set_authoritative_flag: true,
+                set_checking_disabled_flag: true,
+                no_recursion: true,
+                udp_payload_size: Some(4096),
+                no_tc_retry: true,
+                ..ProtocolTweaks::default()
+            },
+            ..Requests::default()
+        };
+        let message = requests
+            .build_message("lookup.dog", RecordType::A, DNSClass::IN)
+            .unwrap();
+
+        assert_eq!(
+            describe_request(&message, &requests.tweaks),
+            "ID 1, flags aa cd, EDNS payload 4096, tc-retry=off"
+        );
+    }
+}
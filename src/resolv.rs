@@ -0,0 +1,382 @@
+/*
+ * dog - A command-line DNS client
+ * Copyright (c) 2026 l1a and contributors
+ * Original code Copyright (c) Benjamin Sago
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! The system’s resolver configuration, read from `/etc/resolv.conf`.
+//!
+//! As well as the nameservers, the file has the search list that the
+//! system’s resolver appends to names that aren’t fully-qualified, and
+//! options that say how patient it should be. dog reads it the same way
+//! the C library does, so that `dog myhost` asks about the same names as
+//! other programs on the machine would.
+
+use std::fmt;
+use std::io;
+use std::time::Duration;
+
+use log::*;
+
+use crate::options::Nameserver;
+
+/// The path to the resolver configuration file.
+pub const RESOLV_CONF_PATH: &str = "/etc/resolv.conf";
+
+/// The most nameservers that get used, as any more are ignored by the C
+/// library.
+const MAX_NAMESERVERS: usize = 3;
+
+/// The highest `ndots` value allowed; larger ones are capped to this.
+const MAX_NDOTS: usize = 15;
+
+/// The longest timeout allowed, in seconds; longer ones are capped to this.
+const MAX_TIMEOUT_SECS: u64 = 30;
+
+/// The most attempts allowed; more are capped to this.
+const MAX_ATTEMPTS: u32 = 5;
+
+/// The **resolver configuration** of the system: its nameservers, its
+/// search list, and the options that go with them. Options that aren’t
+/// given are `None`, so dog’s own defaults can be used instead.
+#[derive(PartialEq, Debug, Clone)]
+pub struct ResolvConf {
+    /// The nameservers to send queries to, in order.
+    pub nameservers: Vec<Nameserver>,
+
+    /// The domains to try appending to names that aren’t fully-qualified,
+    /// in order.
+    pub search: Vec<String>,
+
+    /// How many dots a name needs to have to be tried as it was given
+    /// before the search list, rather than after it.
+    pub ndots: usize,
+
+    /// How long to wait for each nameserver to respond.
+    pub timeout: Option<Duration>,
+
+    /// How many times to send each query.
+    pub attempts: Option<u32>,
+
+    /// Whether to spread queries across the nameservers, instead of always
+    /// starting with the first one.
+    pub rotate: bool,
+}
+
+impl Default for ResolvConf {
+    fn default() -> Self {
+        Self {
+            nameservers: Vec::new(),
+            search: Vec::new(),
+            ndots: 1,
+            timeout: None,
+            attempts: None,
+            rotate: false,
+        }
+    }
+}
+
+impl ResolvConf {
+    /// Loads the resolver configuration from `/etc/resolv.conf` on Unix.
+    /// A machine without the file has the default configuration.
+    #[cfg(unix)]
+    pub fn load() -> io::Result<Self> {
+        debug!("Reading resolver configuration from {RESOLV_CONF_PATH}");
+        match std::fs::read_to_string(RESOLV_CONF_PATH) {
+            Ok(contents) => Ok(Self::parse(&contents)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// On other machines, which don’t have the file, use the default
+    /// configuration.
+    #[cfg(not(unix))]
+    pub fn load() -> io::Result<Self> {
+        Ok(Self::default())
+    }
+
+    /// Parses the contents of a resolver configuration file. Like the C
+    /// library, this never fails: lines and options it doesn’t understand
+    /// are skipped, and values that are too large are capped. Comments
+    /// start with a `#` or a `;`. The `domain` and `search` keywords both
+    /// set the search list, so whichever comes last wins.
+    pub fn parse(contents: &str) -> Self {
+        let mut conf = Self::default();
+
+        for line in contents.lines() {
+            let line = line.split(['#', ';']).next().unwrap_or_default();
+            let mut fields = line.split_whitespace();
+
+            match fields.next() {
+                Some("nameserver") => conf.add_nameserver(fields.next()),
+                Some("domain") => {
+                    if let Some(domain) = fields.next() {
+                        conf.search = vec![domain.to_string()];
+                    }
+                }
+                Some("search") => conf.search = fields.map(String::from).collect(),
+                Some("options") => fields.for_each(|option| conf.set_option(option)),
+                Some(keyword) => debug!("Ignoring resolv.conf keyword {keyword:?}"),
+                None => {}
+            }
+        }
+
+        conf
+    }
+
+    /// Adds a nameserver, if it’s an IP address and there’s room for it.
+    fn add_nameserver(&mut self, address: Option<&str>) {
+        let Some(address) = address else {
+            return;
+        };
+
+        match Nameserver::parse(address) {
+            Ok(nameserver) if nameserver.ip().is_some() => {
+                if self.nameservers.len() < MAX_NAMESERVERS {
+                    self.nameservers.push(nameserver);
+                } else {
+                    debug!("Ignoring nameserver {address:?} after the first {MAX_NAMESERVERS}");
+                }
+            }
+            _ => debug!("Ignoring invalid nameserver {address:?}"),
+        }
+    }
+
+    /// Applies one of the words on an `options` line. There’s no need for
+    /// `edns0`, as dog sends EDNS unless told not to with `--edns`, so it
+    /// gets ignored along with the other options dog has no use for.
+    fn set_option(&mut self, option: &str) {
+        let (name, value) = match option.split_once(':') {
+            Some((name, value)) => (name, Some(value)),
+            None => (option, None),
+        };
+
+        match (name, value) {
+            ("ndots", Some(n)) => {
+                if let Ok(n) = n.parse::<usize>() {
+                    self.ndots = n.min(MAX_NDOTS);
+                }
+            }
+            ("timeout", Some(n)) => {
+                if let Some(n) = n.parse::<u64>().ok().filter(|n| *n > 0) {
+                    self.timeout = Some(Duration::from_secs(n.min(MAX_TIMEOUT_SECS)));
+                }
+            }
+            ("attempts", Some(n)) => {
+                if let Some(n) = n.parse::<u32>().ok().filter(|n| *n > 0) {
+                    self.attempts = Some(n.min(MAX_ATTEMPTS));
+                }
+            }
+            ("rotate", None) => self.rotate = true,
+            _ => debug!("Ignoring resolv.conf option {option:?}"),
+        }
+    }
+
+    /// Returns the names to try for a domain, in order, by appending each
+    /// domain in the search list to it. A name with at least `ndots` dots
+    /// gets tried as it was given first, and any other name gets tried
+    /// after the search list. A name ending in a dot is fully-qualified, so
+    /// it only gets tried as it was given.
+    pub fn candidates(&self, domain: &str) -> Vec<String> {
+        if domain.ends_with('.') || self.search.is_empty() {
+            return vec![domain.to_string()];
+        }
+
+        let searched = self
+            .search
+            .iter()
+            .map(|suffix| match suffix.trim_end_matches('.') {
+                "" => format!("{domain}."),
+                suffix => format!("{domain}.{suffix}"),
+            });
+
+        let mut candidates = Vec::new();
+        if domain.matches('.').count() >= self.ndots {
+            candidates.push(domain.to_string());
+            candidates.extend(searched);
+        } else {
+            candidates.extend(searched);
+            candidates.push(domain.to_string());
+        }
+
+        candidates
+    }
+}
+
+impl fmt::Display for ResolvConf {
+    /// Describes the search list and options, for verbose mode.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.search.is_empty() {
+            write!(f, "no search list")?;
+        } else {
+            write!(f, "search {}", self.search.join(" "))?;
+        }
+
+        write!(f, ", ndots {}", self.ndots)?;
+        if let Some(timeout) = self.timeout {
+            write!(f, ", timeout {}s", timeout.as_secs())?;
+        }
+        if let Some(attempts) = self.attempts {
+            write!(f, ", attempts {attempts}")?;
+        }
+        if self.rotate {
+            write!(f, ", rotate")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn nameserver(input: &str) -> Nameserver {
+        Nameserver::parse(input).unwrap()
+    }
+
+    #[test]
+    fn full_file() {
+        let conf = ResolvConf::parse(
+            "# Generated by NetworkManager
+             search corp.example example.net
+             nameserver 10.0.0.53
+             nameserver fe80::1%1 ; the router
+             options ndots:2 timeout:3 attempts:4 rotate edns0",
+        );
+
+        assert_eq!(
+            conf,
+            ResolvConf {
+                nameservers: vec![nameserver("10.0.0.53"), nameserver("fe80::1%1")],
+                search: vec![String::from("corp.example"), String::from("example.net")],
+                ndots: 2,
+                timeout: Some(Duration::from_secs(3)),
+                attempts: Some(4),
+                rotate: true,
+            }
+        );
+    }
+
+    #[test]
+    fn empty_file() {
+        assert_eq!(ResolvConf::parse(""), ResolvConf::default());
+    }
+
+    #[test]
+    fn last_search_list_wins() {
+        let conf = ResolvConf::parse("search one.example two.example\ndomain three.example");
+        assert_eq!(conf.search, vec![String::from("three.example")]);
+
+        let conf = ResolvConf::parse("domain three.example\nsearch one.example");
+        assert_eq!(conf.search, vec![String::from("one.example")]);
+    }
+
+    #[test]
+    fn only_three_nameservers() {
+        let conf = ResolvConf::parse(
+            "nameserver 10.0.0.1
+             nameserver 10.0.0.2
+             nameserver 10.0.0.3
+             nameserver 10.0.0.4",
+        );
+        assert_eq!(conf.nameservers.len(), 3);
+    }
+
+    #[test]
+    fn invalid_lines() {
+        let conf = ResolvConf::parse(
+            "nameserver
+             nameserver dns.example
+             sortlist 130.155.160.0/255.255.240.0
+             options ndots:many timeout:0 attempts:-1 single-request",
+        );
+        assert_eq!(conf, ResolvConf::default());
+    }
+
+    #[test]
+    fn capped_options() {
+        let conf = ResolvConf::parse("options ndots:40 timeout:120 attempts:10");
+        assert_eq!(conf.ndots, 15);
+        assert_eq!(conf.timeout, Some(Duration::from_secs(30)));
+        assert_eq!(conf.attempts, Some(5));
+    }
+
+    fn searching(ndots: usize) -> ResolvConf {
+        ResolvConf {
+            search: vec![String::from("corp.example"), String::from("example.net.")],
+            ndots,
+            ..ResolvConf::default()
+        }
+    }
+
+    #[test]
+    fn single_label_searched_first() {
+        assert_eq!(
+            searching(1).candidates("myhost"),
+            vec!["myhost.corp.example", "myhost.example.net", "myhost"]
+        );
+    }
+
+    #[test]
+    fn enough_dots_tried_first() {
+        assert_eq!(
+            searching(1).candidates("myhost.lab"),
+            vec![
+                "myhost.lab",
+                "myhost.lab.corp.example",
+                "myhost.lab.example.net"
+            ]
+        );
+    }
+
+    #[test]
+    fn not_enough_dots() {
+        assert_eq!(
+            searching(2).candidates("myhost.lab"),
+            vec![
+                "myhost.lab.corp.example",
+                "myhost.lab.example.net",
+                "myhost.lab"
+            ]
+        );
+    }
+
+    #[test]
+    fn fully_qualified() {
+        assert_eq!(searching(1).candidates("myhost."), vec!["myhost."]);
+    }
+
+    #[test]
+    fn no_search_list() {
+        assert_eq!(ResolvConf::default().candidates("myhost"), vec!["myhost"]);
+    }
+
+    #[test]
+    fn description() {
+        let conf = ResolvConf {
+            timeout: Some(Duration::from_secs(2)),
+            rotate: true,
+            ..searching(1)
+        };
+        assert_eq!(
+            conf.to_string(),
+            "search corp.example example.net., ndots 1, timeout 2s, rotate"
+        );
+    }
+}
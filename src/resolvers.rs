@@ -0,0 +1,448 @@
+/*
+ * dog - A command-line DNS client
+ * Copyright (c) 2026 l1a and contributors
+ * Original code Copyright (c) Benjamin Sago
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Well-known public resolvers that can be picked by name.
+//!
+//! Rather than having to remember the addresses of a public resolver, and
+//! the name on its TLS certificate, and the URL of its DNS-over-HTTPS
+//! endpoint, users can pass its name as the nameserver, such as
+//! `@quad9`. The same details get used when one of these resolvers is
+//! given by its IP address, so that its certificate can be checked.
+//!
+//! dog comes with a list of resolvers built in, and more can be added, or
+//! the built-in ones changed, in a configuration file written in the same
+//! format.
+
+use std::fmt;
+use std::net::IpAddr;
+
+use crate::options::Nameserver;
+
+/// The resolvers that are built into dog.
+const BUILTIN_RESOLVERS: &str = "
+[google]
+description = Google Public DNS
+addresses = 8.8.8.8, 8.8.4.4, 2001:4860:4860::8888, 2001:4860:4860::8844
+tls = dns.google
+https = https://dns.google/dns-query
+
+[cloudflare]
+description = Cloudflare
+addresses = 1.1.1.1, 1.0.0.1, 2606:4700:4700::1111, 2606:4700:4700::1001
+tls = cloudflare-dns.com
+https = https://cloudflare-dns.com/dns-query
+
+[quad9]
+description = Quad9
+addresses = 9.9.9.9, 149.112.112.112, 2620:fe::fe, 2620:fe::9
+tls = dns.quad9.net
+https = https://dns.quad9.net/dns-query
+
+[adguard]
+description = AdGuard DNS
+addresses = 94.140.14.14, 94.140.15.15, 2a10:50c0::ad1:ff, 2a10:50c0::ad2:ff
+tls = dns.adguard-dns.com
+https = https://dns.adguard-dns.com/dns-query
+quic = 853
+
+[mullvad]
+description = Mullvad DNS
+addresses = 194.242.2.2, 2a07:e340::2
+tls = dns.mullvad.net
+https = https://dns.mullvad.net/dns-query
+
+[nextdns]
+description = NextDNS
+addresses = 45.90.28.0, 45.90.30.0, 2a07:a8c0::, 2a07:a8c1::
+tls = dns.nextdns.io
+https = https://dns.nextdns.io/
+quic = 853
+
+[opendns]
+description = OpenDNS
+addresses = 208.67.222.222, 208.67.220.220, 2620:119:35::35, 2620:119:53::53
+https = https://doh.opendns.com/dns-query
+";
+
+/// The **registry** is the list of resolvers that can be picked by name.
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct Registry {
+    /// The resolvers, in the order they were defined.
+    resolvers: Vec<Resolver>,
+}
+
+/// A public resolver, along with the ways it can be reached.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Resolver {
+    /// The name to pick the resolver by, in lowercase.
+    pub name: String,
+
+    /// A description of the resolver, such as the company that runs it.
+    pub description: Option<String>,
+
+    /// The addresses of the resolver, which get tried in order.
+    pub addresses: Vec<IpAddr>,
+
+    /// The name on the resolver’s TLS certificate, if it supports
+    /// DNS-over-TLS or DNS-over-QUIC.
+    pub tls_name: Option<String>,
+
+    /// The URL of the resolver’s DNS-over-HTTPS endpoint, if it has one.
+    pub https: Option<Nameserver>,
+
+    /// The port the resolver accepts DNS-over-QUIC connections on, if it
+    /// supports it.
+    pub quic_port: Option<u16>,
+}
+
+impl Registry {
+    /// Returns the resolvers built into dog.
+    pub fn builtin() -> Self {
+        Self::parse(BUILTIN_RESOLVERS).expect("Built-in resolvers are invalid")
+    }
+
+    /// Parses a resolvers file. Each resolver starts with its name in
+    /// square brackets, followed by its settings, one per line in the form
+    /// `key = value`. Every resolver needs at least one address. Comments
+    /// start with a `#`.
+    pub fn parse(contents: &str) -> Result<Self, RegistryError> {
+        let mut registry = Self::default();
+        let mut current: Option<Resolver> = None;
+
+        for (index, line) in contents.lines().enumerate() {
+            let number = index + 1;
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                let name = name.trim();
+                if name.is_empty() || name.contains(char::is_whitespace) {
+                    return Err(RegistryError::InvalidLine(number));
+                }
+
+                if let Some(resolver) = current.take() {
+                    registry.add(resolver.check()?);
+                }
+
+                current = Some(Resolver::new(name));
+                continue;
+            }
+
+            let (Some(resolver), Some((key, value))) = (current.as_mut(), line.split_once('='))
+            else {
+                return Err(RegistryError::InvalidLine(number));
+            };
+
+            resolver.set(key.trim(), value.trim(), number)?;
+        }
+
+        if let Some(resolver) = current {
+            registry.add(resolver.check()?);
+        }
+
+        Ok(registry)
+    }
+
+    /// Adds every resolver from another registry to this one, replacing
+    /// the ones that have the same name.
+    pub fn extend(&mut self, other: Self) {
+        for resolver in other.resolvers {
+            self.add(resolver);
+        }
+    }
+
+    /// Adds a resolver, replacing any that has the same name.
+    fn add(&mut self, resolver: Resolver) {
+        match self.resolvers.iter_mut().find(|r| r.name == resolver.name) {
+            Some(existing) => *existing = resolver,
+            None => self.resolvers.push(resolver),
+        }
+    }
+
+    /// Returns the resolver with the given name, ignoring case.
+    pub fn by_name(&self, name: &str) -> Option<&Resolver> {
+        self.resolvers
+            .iter()
+            .find(|r| r.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Returns the resolver that has the given address.
+    pub fn by_address(&self, address: IpAddr) -> Option<&Resolver> {
+        self.resolvers
+            .iter()
+            .find(|r| r.addresses.contains(&address))
+    }
+
+    /// Returns every resolver, in the order they were defined.
+    pub fn resolvers(&self) -> &[Resolver] {
+        &self.resolvers
+    }
+}
+
+impl Resolver {
+    /// Creates a new resolver with the given name and no settings.
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_ascii_lowercase(),
+            description: None,
+            addresses: Vec::new(),
+            tls_name: None,
+            https: None,
+            quic_port: None,
+        }
+    }
+
+    /// Applies one setting from a resolvers file.
+    fn set(&mut self, key: &str, value: &str, number: usize) -> Result<(), RegistryError> {
+        let invalid = || RegistryError::InvalidValue(number, key.to_string());
+
+        match key {
+            "description" => {
+                self.description = Some(value.to_string());
+            }
+            "addresses" => {
+                self.addresses = value
+                    .split(|c: char| c == ',' || c.is_whitespace())
+                    .filter(|address| !address.is_empty())
+                    .map(str::parse)
+                    .collect::<Result<_, _>>()
+                    .map_err(|_| invalid())?;
+            }
+            "tls" => {
+                if value.is_empty() || value.contains(char::is_whitespace) {
+                    return Err(invalid());
+                }
+                self.tls_name = Some(value.to_string());
+            }
+            "https" => {
+                let url = Nameserver::parse(value)
+                    .ok()
+                    .filter(|url| url.path.is_some())
+                    .ok_or_else(invalid)?;
+                self.https = Some(url);
+            }
+            "quic" => {
+                self.quic_port = Some(value.parse().map_err(|_| invalid())?);
+            }
+            _ => return Err(RegistryError::UnknownSetting(number, key.to_string())),
+        }
+
+        Ok(())
+    }
+
+    /// Returns the resolver if it has everything it needs, which is at
+    /// least one address.
+    fn check(self) -> Result<Self, RegistryError> {
+        if self.addresses.is_empty() {
+            Err(RegistryError::NoAddresses(self.name))
+        } else {
+            Ok(self)
+        }
+    }
+}
+
+/// Resolvers are displayed in the same format they’re read in, so the
+/// list can be copied into a resolvers file and changed.
+impl fmt::Display for Resolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "[{}]", self.name)?;
+
+        if let Some(description) = &self.description {
+            writeln!(f, "description = {description}")?;
+        }
+
+        let addresses = self
+            .addresses
+            .iter()
+            .map(IpAddr::to_string)
+            .collect::<Vec<_>>();
+        writeln!(f, "addresses = {}", addresses.join(", "))?;
+
+        if let Some(tls_name) = &self.tls_name {
+            writeln!(f, "tls = {tls_name}")?;
+        }
+
+        if let Some(https) = &self.https {
+            writeln!(f, "https = {https}")?;
+        }
+
+        if let Some(quic_port) = self.quic_port {
+            writeln!(f, "quic = {quic_port}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Something wrong with a resolvers file.
+#[derive(PartialEq, Debug)]
+pub enum RegistryError {
+    /// A line isn’t a resolver name or a setting, or comes before the
+    /// first resolver name.
+    InvalidLine(usize),
+
+    /// A line has a setting that dog doesn’t know about.
+    UnknownSetting(usize, String),
+
+    /// A setting has a value that can’t be understood.
+    InvalidValue(usize, String),
+
+    /// A resolver has no addresses.
+    NoAddresses(String),
+}
+
+impl fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidLine(number) => {
+                write!(f, "line {number} is not a resolver name or a setting")
+            }
+            Self::UnknownSetting(number, key) => {
+                write!(f, "line {number} has unknown setting {key:?}")
+            }
+            Self::InvalidValue(number, key) => {
+                write!(f, "line {number} has an invalid {key:?} setting")
+            }
+            Self::NoAddresses(name) => write!(f, "resolver {name:?} has no addresses"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn builtin_resolvers() {
+        let registry = Registry::builtin();
+        let google = registry.by_name("google").unwrap();
+
+        assert_eq!(google.tls_name.as_deref(), Some("dns.google"));
+        assert_eq!(
+            google.https.as_ref().unwrap().to_string(),
+            "https://dns.google/dns-query"
+        );
+        assert_eq!(google.addresses[0], "8.8.8.8".parse::<IpAddr>().unwrap());
+    }
+
+    #[test]
+    fn by_name_ignores_case() {
+        let registry = Registry::builtin();
+        assert_eq!(registry.by_name("Quad9").unwrap().name, "quad9");
+        assert_eq!(registry.by_name("nope"), None);
+    }
+
+    #[test]
+    fn by_address() {
+        let registry = Registry::builtin();
+        let address = "2606:4700:4700::1001".parse().unwrap();
+        assert_eq!(registry.by_address(address).unwrap().name, "cloudflare");
+    }
+
+    #[test]
+    fn parse_resolver() {
+        let registry = Registry::parse(
+            "# our own resolver\n\
+             [Corp]\n\
+             description = Corporate DNS\n\
+             addresses = 10.0.0.53 10.0.1.53\n\
+             tls = dns.corp.example\n\
+             https = https://doh.corp.example/resolve\n\
+             quic = 8853\n",
+        )
+        .unwrap();
+
+        let corp = &registry.resolvers()[0];
+        assert_eq!(corp.name, "corp");
+        assert_eq!(corp.description.as_deref(), Some("Corporate DNS"));
+        assert_eq!(corp.addresses.len(), 2);
+        assert_eq!(corp.tls_name.as_deref(), Some("dns.corp.example"));
+        assert_eq!(
+            corp.https.as_ref().unwrap().path.as_deref(),
+            Some("/resolve")
+        );
+        assert_eq!(corp.quic_port, Some(8853));
+    }
+
+    #[test]
+    fn display_round_trips() {
+        let registry = Registry::builtin();
+        let listed = registry
+            .resolvers()
+            .iter()
+            .map(Resolver::to_string)
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        assert_eq!(Registry::parse(&listed), Ok(registry));
+    }
+
+    #[test]
+    fn extend_replaces() {
+        let mut registry = Registry::builtin();
+        let count = registry.resolvers().len();
+        registry.extend(Registry::parse("[google]\naddresses = 192.0.2.1\n").unwrap());
+
+        assert_eq!(registry.resolvers().len(), count);
+        assert_eq!(registry.by_name("google").unwrap().tls_name, None);
+    }
+
+    #[test]
+    fn setting_before_name() {
+        assert_eq!(
+            Registry::parse("addresses = 192.0.2.1\n"),
+            Err(RegistryError::InvalidLine(1))
+        );
+    }
+
+    #[test]
+    fn unknown_setting() {
+        assert_eq!(
+            Registry::parse("[corp]\naddresses = 192.0.2.1\nport = 53\n"),
+            Err(RegistryError::UnknownSetting(3, "port".into()))
+        );
+    }
+
+    #[test]
+    fn invalid_address() {
+        assert_eq!(
+            Registry::parse("[corp]\naddresses = 192.0.2.999\n"),
+            Err(RegistryError::InvalidValue(2, "addresses".into()))
+        );
+    }
+
+    #[test]
+    fn https_needs_url() {
+        assert_eq!(
+            Registry::parse("[corp]\naddresses = 192.0.2.1\nhttps = doh.corp.example\n"),
+            Err(RegistryError::InvalidValue(3, "https".into()))
+        );
+    }
+
+    #[test]
+    fn no_addresses() {
+        assert_eq!(
+            Registry::parse("[corp]\ntls = dns.corp.example\n"),
+            Err(RegistryError::NoAddresses("corp".into()))
+        );
+    }
+}
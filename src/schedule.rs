@@ -0,0 +1,362 @@
+/*
+ * dog - A command-line DNS client
+ * Copyright (c) 2026 l1a and contributors
+ * Original code Copyright (c) Benjamin Sago
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Deciding when each query gets sent, so that a long list of queries
+//! doesn’t flood the nameserver with requests.
+//!
+//! Every query goes through the same scheduler, which only lets a certain
+//! number of them wait for a response at once, and spaces them out if
+//! there’s a limit on how many can be sent each second. Queries that fail
+//! in a way that might not happen a second time, by timing out or getting a
+//! SERVFAIL response, get sent again after waiting a little longer each
+//! time, until they’ve been tried as many times as the user allows.
+
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use hickory_resolver::net::NetError;
+use hickory_resolver::proto::op::{DnsResponse, ResponseCode};
+use log::*;
+use tokio::sync::Semaphore;
+use tokio::time::{sleep, sleep_until, Instant};
+
+use crate::doh::HttpResponse;
+use crate::options::Limits;
+use crate::output::QueryError;
+use crate::transport::{Timings, Transport};
+
+/// How long to wait before sending a query again the first time. This
+/// doubles for each retry after that, up to `MAX_BACKOFF`.
+const BACKOFF: Duration = Duration::from_millis(250);
+
+/// The longest to wait before sending a query again.
+const MAX_BACKOFF: Duration = Duration::from_secs(4);
+
+/// What happened when a query was sent.
+#[derive(Debug)]
+pub struct Sent {
+    /// The transport the query was last sent over, if it got sent at all.
+    pub transport: Option<Transport>,
+
+    /// The HTTP response the DNS response came in, if it was sent over
+    /// HTTPS.
+    pub http: Option<HttpResponse>,
+
+    /// How long the handshake and the query took, if it was sent over
+    /// QUIC.
+    pub timings: Option<Timings>,
+
+    /// The response, or the error that occurred.
+    pub result: Result<DnsResponse, QueryError>,
+}
+
+impl Sent {
+    /// A query that failed with the given error, after last being sent
+    /// over the given transport.
+    pub fn failed(transport: Option<Transport>, error: QueryError) -> Self {
+        Self {
+            transport,
+            http: None,
+            timings: None,
+            result: Err(error),
+        }
+    }
+}
+
+/// The **scheduler** holds back queries until they’re allowed to be sent,
+/// and keeps count of the ones that had to be retried.
+#[derive(Debug)]
+pub struct Scheduler {
+    /// One permit for each query that can be waiting for a response.
+    permits: Semaphore,
+
+    /// The time to leave between sending each query, if there’s a limit on
+    /// how many can be sent each second.
+    interval: Option<Duration>,
+
+    /// The earliest time the next query can be sent.
+    next_slot: Mutex<Instant>,
+
+    /// How many times to send a query before giving up on it.
+    tries: u32,
+
+    /// How many queries have been sent more than once.
+    retried: AtomicUsize,
+
+    /// How many queries have been given up on after every retry failed.
+    dropped: AtomicUsize,
+}
+
+impl Scheduler {
+    /// Creates a new scheduler that keeps to the given limits, and sends
+    /// each query up to the given number of times.
+    pub fn new(limits: Limits, tries: u32) -> Self {
+        Self {
+            permits: Semaphore::new(limits.concurrency),
+            interval: limits.qps.map(|qps| Duration::from_secs(1) / qps),
+            next_slot: Mutex::new(Instant::now()),
+            tries,
+            retried: AtomicUsize::new(0),
+            dropped: AtomicUsize::new(0),
+        }
+    }
+
+    /// Sends a query by calling the given function once it’s allowed to,
+    /// calling it again if it times out or gets a SERVFAIL response. The
+    /// query doesn’t hold on to its permit while waiting to be retried, so
+    /// other queries can be sent in the meantime. Returns what came back
+    /// the last time, along with the number of times it was sent.
+    pub async fn send<F, Fut>(&self, mut attempt: F) -> (Sent, u32)
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Sent>,
+    {
+        let mut tries = 0;
+
+        loop {
+            let sent = {
+                let _permit = self.permits.acquire().await.expect("Semaphore closed");
+                self.wait_for_slot().await;
+                attempt().await
+            };
+            tries += 1;
+
+            if !should_retry(&sent.result) {
+                return (sent, tries);
+            }
+
+            if tries >= self.tries {
+                debug!("Giving up on query after {tries} tries");
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                return (sent, tries);
+            }
+
+            if tries == 1 {
+                self.retried.fetch_add(1, Ordering::Relaxed);
+            }
+
+            let backoff = BACKOFF
+                .saturating_mul(2_u32.saturating_pow(tries - 1))
+                .min(MAX_BACKOFF);
+            debug!("Query failed, retrying in {backoff:?}");
+            sleep(backoff).await;
+        }
+    }
+
+    /// Waits until the next query is allowed to be sent, reserving the time
+    /// after that for the query after it.
+    async fn wait_for_slot(&self) {
+        let Some(interval) = self.interval else {
+            return;
+        };
+
+        let slot = {
+            let mut next_slot = self.next_slot.lock().expect("Mutex poisoned");
+            let slot = (*next_slot).max(Instant::now());
+            *next_slot = slot + interval;
+            slot
+        };
+
+        sleep_until(slot).await;
+    }
+
+    /// How many queries have been sent more than once.
+    pub fn retried(&self) -> usize {
+        self.retried.load(Ordering::Relaxed)
+    }
+
+    /// How many queries have been given up on after every retry failed.
+    pub fn dropped(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+/// Whether a query failed in a way that might not happen if it gets sent
+/// again: the nameserver didn’t respond in time, or it responded with
+/// SERVFAIL, which resolvers often send when they couldn’t get an answer in
+/// time themselves.
+fn should_retry(result: &Result<DnsResponse, QueryError>) -> bool {
+    match result {
+        Ok(response) => response.metadata.response_code == ResponseCode::ServFail,
+        Err(QueryError::Network(NetError::Timeout)) => true,
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use hickory_resolver::proto::op::{Message, OpCode};
+    use pretty_assertions::assert_eq;
+    use std::cell::Cell;
+
+    fn response(code: ResponseCode) -> Sent {
+        let mut message = Message::response(1, OpCode::Query);
+        message.metadata.response_code = code;
+        Sent {
+            transport: None,
+            http: None,
+            timings: None,
+            result: Ok(DnsResponse::from_message(message).unwrap()),
+        }
+    }
+
+    fn scheduler() -> Scheduler {
+        Scheduler::new(Limits::default(), 3)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn success_not_retried() {
+        let scheduler = scheduler();
+
+        let (Sent { result, .. }, tries) = scheduler
+            .send(|| async { response(ResponseCode::NoError) })
+            .await;
+
+        assert!(result.is_ok());
+        assert_eq!(tries, 1);
+        assert_eq!((scheduler.retried(), scheduler.dropped()), (0, 0));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn nxdomain_not_retried() {
+        let scheduler = scheduler();
+
+        let (Sent { result, .. }, tries) = scheduler
+            .send(|| async { response(ResponseCode::NXDomain) })
+            .await;
+
+        assert!(result.is_ok());
+        assert_eq!(tries, 1);
+        assert_eq!((scheduler.retried(), scheduler.dropped()), (0, 0));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn servfail_retried_until_success() {
+        let scheduler = scheduler();
+        let attempts = Cell::new(0);
+
+        let (Sent { result, .. }, tries) = scheduler
+            .send(|| {
+                attempts.set(attempts.get() + 1);
+                let code = if attempts.get() < 2 {
+                    ResponseCode::ServFail
+                } else {
+                    ResponseCode::NoError
+                };
+                async move { response(code) }
+            })
+            .await;
+
+        assert_eq!(
+            result.unwrap().metadata.response_code,
+            ResponseCode::NoError
+        );
+        assert_eq!(tries, 2);
+        assert_eq!((scheduler.retried(), scheduler.dropped()), (1, 0));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn timeout_dropped_after_retries() {
+        let scheduler = scheduler();
+        let start = Instant::now();
+
+        let (Sent { result, .. }, tries) = scheduler
+            .send(|| async { Sent::failed(None, NetError::Timeout.into()) })
+            .await;
+
+        assert!(matches!(
+            result,
+            Err(QueryError::Network(NetError::Timeout))
+        ));
+        assert_eq!(tries, 3);
+        assert_eq!(start.elapsed(), BACKOFF * 3);
+        assert_eq!((scheduler.retried(), scheduler.dropped()), (1, 1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn backoff_capped() {
+        let scheduler = Scheduler::new(Limits::default(), 8);
+        let start = Instant::now();
+
+        let (_, tries) = scheduler
+            .send(|| async { Sent::failed(None, NetError::Timeout.into()) })
+            .await;
+
+        assert_eq!(tries, 8);
+        assert_eq!(start.elapsed(), BACKOFF * 15 + MAX_BACKOFF * 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn single_try() {
+        let scheduler = Scheduler::new(Limits::default(), 1);
+
+        let (_, tries) = scheduler
+            .send(|| async { response(ResponseCode::ServFail) })
+            .await;
+
+        assert_eq!(tries, 1);
+        assert_eq!((scheduler.retried(), scheduler.dropped()), (0, 1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn queries_paced() {
+        let limits = Limits {
+            concurrency: 10,
+            qps: Some(4),
+        };
+        let scheduler = Scheduler::new(limits, 3);
+        let start = Instant::now();
+
+        for _ in 0..3 {
+            let (Sent { result, .. }, _) = scheduler
+                .send(|| async { response(ResponseCode::NoError) })
+                .await;
+            assert!(result.is_ok());
+        }
+
+        assert_eq!(start.elapsed(), Duration::from_millis(500));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn concurrency_bounded() {
+        let limits = Limits {
+            concurrency: 2,
+            qps: None,
+        };
+        let scheduler = Scheduler::new(limits, 3);
+        let waiting = Cell::new(0);
+        let most_waiting = Cell::new(0);
+
+        let queries = (0..5).map(|_| {
+            scheduler.send(|| async {
+                waiting.set(waiting.get() + 1);
+                most_waiting.set(most_waiting.get().max(waiting.get()));
+                sleep(Duration::from_millis(10)).await;
+                waiting.set(waiting.get() - 1);
+                response(ResponseCode::NoError)
+            })
+        });
+        futures::future::join_all(queries).await;
+
+        assert_eq!(most_waiting.get(), 2);
+    }
+}
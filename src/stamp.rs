@@ -0,0 +1,505 @@
+/*
+ * dog - A command-line DNS client
+ * Copyright (c) 2026 l1a and contributors
+ * Original code Copyright (c) Benjamin Sago
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Parsing DNS stamps, the `sdns://` strings that pack everything needed
+//! to reach a nameserver into one line.
+//!
+//! A stamp is a protocol byte followed by a list of fields, encoded as
+//! URL-safe base64. Which fields there are depends on the protocol: a
+//! DNSCrypt stamp has the provider’s public key and name, a
+//! DNS-over-HTTPS stamp has a host name and a path, and so on. The format
+//! is described at <https://dnscrypt.info/stamps-specifications>.
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::net::IpAddr;
+
+use data_encoding::BASE64URL_NOPAD;
+
+use crate::dnscrypt::Provider;
+use crate::options::TransportType;
+use crate::pinning::CertificateHash;
+
+/// The prefix that every stamp starts with.
+pub const PREFIX: &str = "sdns://";
+
+/// The port DNSCrypt resolvers listen on when the stamp doesn’t say.
+const DNSCRYPT_PORT: u16 = 443;
+
+/// A parsed **stamp**.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Stamp {
+    /// The protocol to use, along with the fields that only it has.
+    pub protocol: Protocol,
+
+    /// The address of the nameserver, if the stamp gives one.
+    pub ip: Option<IpAddr>,
+
+    /// The host name of the nameserver, which its certificate is checked
+    /// against, for the protocols that use TLS.
+    pub hostname: Option<String>,
+
+    /// The port to send requests to, if the stamp gives one.
+    pub port: Option<u16>,
+
+    /// The hashes of certificates in the nameserver’s chain, one of which
+    /// has to be there, for the protocols that use TLS.
+    pub hashes: Vec<CertificateHash>,
+}
+
+/// The **protocol** a stamp is for.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum Protocol {
+    /// Plain DNS, over UDP or TCP.
+    Plain,
+
+    /// DNSCrypt, with the provider to fetch the certificate of.
+    DnsCrypt(Provider),
+
+    /// DNS-over-HTTPS, with the path to send requests to.
+    Https(String),
+
+    /// DNS-over-TLS.
+    Tls,
+
+    /// DNS-over-QUIC.
+    Quic,
+}
+
+impl Protocol {
+    /// The transport that requests for this protocol get sent over, or
+    /// `None` for the protocols that work over either UDP or TCP.
+    pub fn transport_type(&self) -> Option<TransportType> {
+        match self {
+            Self::Plain | Self::DnsCrypt(_) => None,
+            Self::Https(_) => Some(TransportType::HTTPS),
+            Self::Tls => Some(TransportType::TLS),
+            Self::Quic => Some(TransportType::QUIC),
+        }
+    }
+}
+
+impl Stamp {
+    /// Parses a stamp, including its `sdns://` prefix.
+    pub fn parse(input: &str) -> Result<Self, StampError> {
+        let encoded = input
+            .get(..PREFIX.len())
+            .filter(|prefix| prefix.eq_ignore_ascii_case(PREFIX))
+            .map(|_| input[PREFIX.len()..].trim_end_matches('='))
+            .ok_or(StampError::InvalidEncoding)?;
+        let bytes = BASE64URL_NOPAD
+            .decode(encoded.as_bytes())
+            .map_err(|_| StampError::InvalidEncoding)?;
+
+        let mut reader = Reader(&bytes);
+        let protocol = reader.byte()?;
+        if matches!(protocol, 0x00..=0x04) {
+            // The properties say whether the nameserver validates DNSSEC,
+            // keeps logs, or filters, none of which change how to reach it
+            reader.bytes(8)?;
+        }
+
+        match protocol {
+            0x00 => {
+                let (ip, port) = parse_address(&reader.string()?)?;
+                let ip = ip.ok_or(StampError::MissingAddress)?;
+
+                Ok(Self {
+                    protocol: Protocol::Plain,
+                    ip: Some(ip),
+                    hostname: None,
+                    port,
+                    hashes: Vec::new(),
+                })
+            }
+            0x01 => {
+                let (ip, port) = parse_address(&reader.string()?)?;
+                let ip = ip.ok_or(StampError::MissingAddress)?;
+                let public_key = reader.field()?;
+                let public_key =
+                    <[u8; 32]>::try_from(public_key).map_err(|_| StampError::InvalidPublicKey)?;
+                let name = reader.string()?;
+
+                Ok(Self {
+                    protocol: Protocol::DnsCrypt(Provider { public_key, name }),
+                    ip: Some(ip),
+                    hostname: None,
+                    port: Some(port.unwrap_or(DNSCRYPT_PORT)),
+                    hashes: Vec::new(),
+                })
+            }
+            0x02..=0x04 => {
+                let (ip, address_port) = parse_address(&reader.string()?)?;
+
+                // A stamp that pins nothing still has one empty hash
+                let hashes = reader
+                    .fields()?
+                    .into_iter()
+                    .filter(|hash| !hash.is_empty())
+                    .map(|hash| {
+                        CertificateHash::try_from(hash).map_err(|_| StampError::InvalidHash)
+                    })
+                    .collect::<Result<_, _>>()?;
+
+                let (hostname, port) = parse_hostname(&reader.string()?)?;
+
+                let protocol = match protocol {
+                    0x02 => Protocol::Https(reader.string()?),
+                    0x03 => Protocol::Tls,
+                    _ => Protocol::Quic,
+                };
+
+                Ok(Self {
+                    protocol,
+                    ip,
+                    hostname: Some(hostname),
+                    port: port.or(address_port),
+                    hashes,
+                })
+            }
+            other => Err(StampError::UnsupportedProtocol(other)),
+        }
+    }
+}
+
+/// Parses the address field of a stamp, which is an IP address with an
+/// optional port, or just a port, or nothing at all.
+fn parse_address(input: &str) -> Result<(Option<IpAddr>, Option<u16>), StampError> {
+    let invalid = || StampError::InvalidAddress(input.to_string());
+
+    let (address, port) = if let Some(rest) = input.strip_prefix('[') {
+        let (address, rest) = rest.split_once(']').ok_or_else(invalid)?;
+        match rest.strip_prefix(':') {
+            Some(port) => (address, Some(port)),
+            None if rest.is_empty() => (address, None),
+            None => return Err(invalid()),
+        }
+    } else if input.matches(':').count() == 1 {
+        let (address, port) = input.split_once(':').ok_or_else(invalid)?;
+        (address, Some(port))
+    } else {
+        (input, None)
+    };
+
+    let ip = match address {
+        "" => None,
+        address => Some(address.parse().map_err(|_| invalid())?),
+    };
+    let port = match port {
+        Some(port) => Some(port.parse().map_err(|_| invalid())?),
+        None => None,
+    };
+
+    Ok((ip, port))
+}
+
+/// Parses the host name field of a stamp, which can have a port after it.
+/// An IPv6 address has to be in square brackets, as in the address field,
+/// so its colons don’t get mistaken for the one before the port.
+fn parse_hostname(input: &str) -> Result<(String, Option<u16>), StampError> {
+    let invalid = || StampError::InvalidHostname(input.to_string());
+
+    let (hostname, port) = if let Some(rest) = input.strip_prefix('[') {
+        let (hostname, rest) = rest.split_once(']').ok_or_else(invalid)?;
+        match rest.strip_prefix(':') {
+            Some(port) => (hostname, Some(port)),
+            None if rest.is_empty() => (hostname, None),
+            None => return Err(invalid()),
+        }
+    } else {
+        match input.rsplit_once(':') {
+            Some((hostname, port)) => (hostname, Some(port)),
+            None => (input, None),
+        }
+    };
+
+    let port = match port {
+        Some(port) => Some(port.parse().map_err(|_| invalid())?),
+        None => None,
+    };
+
+    if hostname.is_empty() {
+        return Err(invalid());
+    }
+
+    Ok((hostname.to_string(), port))
+}
+
+/// A **reader** takes fields off the front of a decoded stamp.
+#[derive(Debug)]
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    /// Reads the given number of bytes.
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8], StampError> {
+        if self.0.len() < count {
+            return Err(StampError::Truncated);
+        }
+
+        let (bytes, rest) = self.0.split_at(count);
+        self.0 = rest;
+        Ok(bytes)
+    }
+
+    /// Reads a single byte.
+    fn byte(&mut self) -> Result<u8, StampError> {
+        self.bytes(1).map(|b| b[0])
+    }
+
+    /// Reads a field that comes after its length as one byte.
+    fn field(&mut self) -> Result<&'a [u8], StampError> {
+        let length = self.byte()?;
+        self.bytes(usize::from(length))
+    }
+
+    /// Reads a field as a string.
+    fn string(&mut self) -> Result<String, StampError> {
+        let field = self.field()?;
+        String::from_utf8(field.to_vec()).map_err(|_| StampError::InvalidEncoding)
+    }
+
+    /// Reads a list of fields, where every length but the last has its top
+    /// bit set to say there’s another one after it.
+    fn fields(&mut self) -> Result<Vec<&'a [u8]>, StampError> {
+        let mut fields = Vec::new();
+        loop {
+            let length = self.byte()?;
+            fields.push(self.bytes(usize::from(length & 0x7f))?);
+            if length & 0x80 == 0 {
+                return Ok(fields);
+            }
+        }
+    }
+}
+
+/// Something wrong with a stamp.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum StampError {
+    /// The stamp isn’t valid base64, or a field that should be text isn’t.
+    InvalidEncoding,
+
+    /// The stamp ends before all its fields have been read.
+    Truncated,
+
+    /// The stamp is for a protocol that dog doesn’t support.
+    UnsupportedProtocol(u8),
+
+    /// The stamp is for a protocol that needs an address, but doesn’t give
+    /// one.
+    MissingAddress,
+
+    /// The address field isn’t an IP address and port.
+    InvalidAddress(String),
+
+    /// The host name field is empty, or its port isn’t a number.
+    InvalidHostname(String),
+
+    /// The DNSCrypt provider’s public key isn’t 32 bytes long.
+    InvalidPublicKey,
+
+    /// One of the certificate hashes isn’t 32 bytes long.
+    InvalidHash,
+}
+
+impl fmt::Display for StampError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidEncoding => write!(f, "Invalid encoding"),
+            Self::Truncated => write!(f, "Stamp is truncated"),
+            Self::UnsupportedProtocol(protocol) => {
+                write!(f, "Unsupported protocol {protocol:#04x}")
+            }
+            Self::MissingAddress => write!(f, "No address given"),
+            Self::InvalidAddress(address) => write!(f, "Invalid address {address:?}"),
+            Self::InvalidHostname(hostname) => write!(f, "Invalid host name {hostname:?}"),
+            Self::InvalidPublicKey => write!(f, "Invalid provider public key"),
+            Self::InvalidHash => write!(f, "Invalid certificate hash"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use data_encoding::HEXLOWER;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn dnscrypt() {
+        let stamp = Stamp::parse(concat!(
+            "sdns://AQMAAAAAAAAAETk0LjE0MC4xNC4xNDo1NDQzINErR_JS3PLCu_iZEIbq95zkSV2L",
+            "FsigxDIuUso_OQhzIjIuZG5zY3J5cHQuZGVmYXVsdC5uczEuYWRndWFyZC5jb20",
+        ))
+        .unwrap();
+
+        let Protocol::DnsCrypt(provider) = &stamp.protocol else {
+            panic!("Not a DNSCrypt stamp: {:?}", stamp);
+        };
+        assert_eq!(provider.name, "2.dnscrypt.default.ns1.adguard.com");
+        assert_eq!(provider.public_key[..4], [0xd1, 0x2b, 0x47, 0xf2]);
+        assert_eq!(stamp.ip, Some("94.140.14.14".parse().unwrap()));
+        assert_eq!(stamp.port, Some(5443));
+        assert_eq!(stamp.protocol.transport_type(), None);
+    }
+
+    #[test]
+    fn https() {
+        let stamp =
+            Stamp::parse("sdns://AgcAAAAAAAAABzEuMC4wLjEAEmRucy5jbG91ZGZsYXJlLmNvbQovZG5zLXF1ZXJ5")
+                .unwrap();
+
+        assert_eq!(
+            stamp,
+            Stamp {
+                protocol: Protocol::Https(String::from("/dns-query")),
+                ip: Some("1.0.0.1".parse().unwrap()),
+                hostname: Some(String::from("dns.cloudflare.com")),
+                port: None,
+                hashes: Vec::new(),
+            }
+        );
+        assert_eq!(stamp.protocol.transport_type(), Some(TransportType::HTTPS));
+    }
+
+    #[test]
+    fn tls_ipv6() {
+        let stamp =
+            Stamp::parse("sdns://AwAAAAAAAAAADVsyNjIwOmZlOjpmZV0ADWRucy5xdWFkOS5uZXQ").unwrap();
+
+        assert_eq!(
+            stamp,
+            Stamp {
+                protocol: Protocol::Tls,
+                ip: Some("2620:fe::fe".parse().unwrap()),
+                hostname: Some(String::from("dns.quad9.net")),
+                port: None,
+                hashes: Vec::new(),
+            }
+        );
+        assert_eq!(stamp.protocol.transport_type(), Some(TransportType::TLS));
+    }
+
+    #[test]
+    fn quic_hostname_port() {
+        let stamp =
+            Stamp::parse("sdns://BAAAAAAAAAAADDk0LjE0MC4xNC4xNAAXZG5zLmFkZ3VhcmQtZG5zLmNvbTo3ODQ")
+                .unwrap();
+
+        assert_eq!(stamp.hostname.as_deref(), Some("dns.adguard-dns.com"));
+        assert_eq!(stamp.port, Some(784));
+        assert_eq!(stamp.protocol.transport_type(), Some(TransportType::QUIC));
+    }
+
+    /// Encodes a TLS stamp with the given certificate hash and host name.
+    fn tls_stamp(hash: &[u8], hostname: &str) -> String {
+        let mut bytes = vec![0x03, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        bytes.push(u8::try_from(hash.len()).unwrap());
+        bytes.extend(hash);
+        bytes.push(u8::try_from(hostname.len()).unwrap());
+        bytes.extend(hostname.as_bytes());
+        format!("{PREFIX}{}", BASE64URL_NOPAD.encode(&bytes))
+    }
+
+    #[test]
+    fn tls_bracketed_hostname() {
+        let stamp = Stamp::parse(&tls_stamp(&[], "[2001:db8::53]:8853")).unwrap();
+
+        assert_eq!(stamp.hostname.as_deref(), Some("2001:db8::53"));
+        assert_eq!(stamp.port, Some(8853));
+    }
+
+    #[test]
+    fn tls_bracketed_hostname_without_port() {
+        let stamp = Stamp::parse(&tls_stamp(&[], "[2001:db8::53]")).unwrap();
+
+        assert_eq!(stamp.hostname.as_deref(), Some("2001:db8::53"));
+        assert_eq!(stamp.port, None);
+    }
+
+    #[test]
+    fn pinned_certificates() {
+        let stamp = Stamp::parse(concat!(
+            "sdns://AgcAAAAAAAAABzEuMC4wLjGgENk8mGSlIfMGXMOlIlCcKvq7AVgcrZxtjon911-ep0cg63Ul-I8Nl",
+            "Fj4GplQGb_TTLiczclX57DvMV8Q-JdjgRgSZG5zLmNsb3VkZmxhcmUuY29tCi9kbnMtcXVlcnk"
+        ))
+        .unwrap();
+
+        assert_eq!(
+            stamp.hashes,
+            vec![
+                hash("10d93c9864a521f3065cc3a522509c2afabb01581cad9c6d8e89fdd75f9ea747"),
+                hash("eb7525f88f0d9458f81a995019bfd34cb89ccdc957e7b0ef315f10f897638118"),
+            ]
+        );
+        assert_eq!(stamp.hostname.as_deref(), Some("dns.cloudflare.com"));
+    }
+
+    #[test]
+    fn pinned_certificate_wrong_length() {
+        assert_eq!(
+            Stamp::parse(&tls_stamp(&[0xab; 20], "dns.example")),
+            Err(StampError::InvalidHash)
+        );
+    }
+
+    fn hash(hex: &str) -> CertificateHash {
+        let bytes = HEXLOWER.decode(hex.as_bytes()).unwrap();
+        CertificateHash::try_from(bytes.as_slice()).unwrap()
+    }
+
+    #[test]
+    fn plain() {
+        let stamp = Stamp::parse("sdns://AAAAAAAAAAAADDkuOS45Ljk6NTM1Mw").unwrap();
+
+        assert_eq!(
+            stamp,
+            Stamp {
+                protocol: Protocol::Plain,
+                ip: Some("9.9.9.9".parse().unwrap()),
+                hostname: None,
+                port: Some(5353),
+                hashes: Vec::new(),
+            }
+        );
+    }
+
+    #[test]
+    fn unsupported_protocol() {
+        assert_eq!(
+            Stamp::parse("sdns://BQAAAAAAAAAADG9kb2guZXhhbXBsZQovZG5zLXF1ZXJ5"),
+            Err(StampError::UnsupportedProtocol(0x05))
+        );
+    }
+
+    #[test]
+    fn truncated() {
+        assert_eq!(
+            Stamp::parse("sdns://AgcAAAAAAAAABzEuMC4wLjEAEmRucy5jbG91ZGZsYXJl"),
+            Err(StampError::Truncated)
+        );
+    }
+
+    #[test]
+    fn invalid_base64() {
+        assert_eq!(
+            Stamp::parse("sdns://not base64"),
+            Err(StampError::InvalidEncoding)
+        );
+    }
+}
@@ -22,7 +22,8 @@
 use std::fmt::Display;
 use std::time::Duration;
 
-use hickory_resolver::proto::rr::{Record, RecordType};
+use hickory_resolver::proto::op::Edns;
+use hickory_resolver::proto::rr::{DNSClass, Record, RecordType};
 use std::fmt::Write;
 
 use crate::colours::{Colours, Paint};
@@ -42,6 +43,7 @@ pub struct Table {
 struct Row {
     qtype: StyledString,
     qname: String,
+    class: Option<DNSClass>,
     ttl: Option<String>,
     section: Section,
     summary: String,
@@ -64,13 +66,31 @@ impl Display for StyledString {
 pub enum Section {
     /// This record was found in the **Answer** section.
     Answer,
+
+    /// This record was found in the **Authority** section.
+    Authority,
+
+    /// This record was found in the **Additional** section.
+    Additional,
+
+    /// This record was removed from the zone, according to an incremental
+    /// zone transfer.
+    Removed,
+
+    /// This record was added to the zone, according to an incremental zone
+    /// transfer.
+    Added,
+
+    /// This is the OPT pseudo-record from the **Additional** section, which
+    /// holds the EDNS settings of the response rather than any data.
+    Opt,
 }
 
 impl Table {
     /// Create a new table with no rows.
-    pub fn new(colours: Colours, text_format: TextFormat) -> Self {
+    pub fn new(colours: &Colours, text_format: TextFormat) -> Self {
         Self {
-            colours,
+            colours: *colours,
             text_format,
             rows: Vec::new(),
         }
@@ -82,17 +102,33 @@ impl Table {
         let data = &record.data;
         let qtype = self.coloured_record_type(record);
         let qname = record.name.to_string();
+        let class = Some(record.dns_class);
         let summary = TextFormat::record_payload_summary(data);
         let ttl = Some(self.text_format.format_duration(record.ttl));
         self.rows.push(Row {
             qtype,
             qname,
+            class,
             ttl,
             section,
             summary,
         });
     }
 
+    /// Adds a row to the table for the OPT pseudo-record in a response.
+    /// Its class and TTL fields are re-purposed to hold EDNS settings, so
+    /// they are summarised along with its options instead.
+    pub fn add_opt_row(&mut self, edns: &Edns) {
+        self.rows.push(Row {
+            qtype: paint_styled(self.colours.opt, "OPT"),
+            qname: String::from("."),
+            class: None,
+            ttl: None,
+            section: Section::Opt,
+            summary: TextFormat::opt_summary(edns),
+        });
+    }
+
     /// Renders the formatted table to a string.
     pub fn render(&self) -> String {
         let mut output = String::new();
@@ -101,6 +137,7 @@ impl Table {
             let qtype_len = self.max_qtype_len();
             let qname_len = self.max_qname_len();
             let ttl_len = self.max_ttl_len();
+            let class_len = self.max_class_len();
 
             for r in &self.rows {
                 output.push_str(&" ".repeat(qtype_len - r.qtype.display_len));
@@ -112,6 +149,12 @@ impl Table {
                 );
                 output.push_str(&" ".repeat(qname_len - r.qname.len()));
 
+                if let Some(class_len) = class_len {
+                    let class = r.class.map(|c| c.to_string()).unwrap_or_default();
+                    output.push_str(&class);
+                    output.push_str(&" ".repeat(class_len - class.len() + 1));
+                }
+
                 if let Some(ttl) = &r.ttl {
                     output.push_str(&" ".repeat(ttl_len - ttl.len()));
                     output.push_str(ttl);
@@ -183,6 +226,24 @@ impl Table {
         self.rows.iter().map(|r| r.qname.len()).max().unwrap_or(0)
     }
 
+    /// Returns the maximum length of a class string, or `None` if every row
+    /// is in the `IN` class, in which case the column isn’t displayed.
+    fn max_class_len(&self) -> Option<usize> {
+        if self
+            .rows
+            .iter()
+            .all(|r| r.class.is_none_or(|c| c == DNSClass::IN))
+        {
+            return None;
+        }
+
+        self.rows
+            .iter()
+            .filter_map(|r| r.class)
+            .map(|c| c.to_string().len())
+            .max()
+    }
+
     /// Returns the maximum length of a TTL string.
     fn max_ttl_len(&self) -> usize {
         self.rows
@@ -196,6 +257,10 @@ impl Table {
     fn format_section(&self, section: Section) -> String {
         match section {
             Section::Answer => self.colours.answer.paint(" "),
+            Section::Authority => self.colours.authority.paint("A"),
+            Section::Additional | Section::Opt => self.colours.additional.paint("+"),
+            Section::Removed => self.colours.removed.paint("-"),
+            Section::Added => self.colours.added.paint("+"),
         }
     }
 }
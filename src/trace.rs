@@ -0,0 +1,867 @@
+/*
+ * dog - A command-line DNS client
+ * Copyright (c) 2026 l1a and contributors
+ * Original code Copyright (c) Benjamin Sago
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Tracing the delegation chain of a domain from the root servers.
+//!
+//! Rather than asking a recursive resolver, which hides every step, the
+//! tracer does the resolver’s job itself: it asks a root server, which
+//! refers it to the nameservers of a top-level domain, which refer it
+//! further down, until a nameserver gives an answer. Each of these requests
+//! is recorded as a hop, so the user can see where a delegation breaks.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::pin::Pin;
+use std::time::{Duration, Instant};
+
+use hickory_resolver::config::ConnectionConfig;
+use hickory_resolver::net::NetError;
+use hickory_resolver::proto::op::{DnsResponse, Message, ResponseCode};
+use hickory_resolver::proto::rr::{DNSClass, Name, RData, RecordType};
+use hickory_resolver::proto::ProtoError;
+use log::*;
+
+use crate::options::Requests;
+use crate::transport::Transport;
+
+/// The root hints built into dog, in the format of the `named.root` file
+/// published by IANA.
+const BUILTIN_ROOT_HINTS: &str = "
+.                        3600000      NS    A.ROOT-SERVERS.NET.
+A.ROOT-SERVERS.NET.      3600000      A     198.41.0.4
+A.ROOT-SERVERS.NET.      3600000      AAAA  2001:503:ba3e::2:30
+.                        3600000      NS    B.ROOT-SERVERS.NET.
+B.ROOT-SERVERS.NET.      3600000      A     170.247.170.2
+B.ROOT-SERVERS.NET.      3600000      AAAA  2801:1b8:10::b
+.                        3600000      NS    C.ROOT-SERVERS.NET.
+C.ROOT-SERVERS.NET.      3600000      A     192.33.4.12
+C.ROOT-SERVERS.NET.      3600000      AAAA  2001:500:2::c
+.                        3600000      NS    D.ROOT-SERVERS.NET.
+D.ROOT-SERVERS.NET.      3600000      A     199.7.91.13
+D.ROOT-SERVERS.NET.      3600000      AAAA  2001:500:2d::d
+.                        3600000      NS    E.ROOT-SERVERS.NET.
+E.ROOT-SERVERS.NET.      3600000      A     192.203.230.10
+E.ROOT-SERVERS.NET.      3600000      AAAA  2001:500:a8::e
+.                        3600000      NS    F.ROOT-SERVERS.NET.
+F.ROOT-SERVERS.NET.      3600000      A     192.5.5.241
+F.ROOT-SERVERS.NET.      3600000      AAAA  2001:500:2f::f
+.                        3600000      NS    G.ROOT-SERVERS.NET.
+G.ROOT-SERVERS.NET.      3600000      A     192.112.36.4
+G.ROOT-SERVERS.NET.      3600000      AAAA  2001:500:12::d0d
+.                        3600000      NS    H.ROOT-SERVERS.NET.
+H.ROOT-SERVERS.NET.      3600000      A     198.97.190.53
+H.ROOT-SERVERS.NET.      3600000      AAAA  2001:500:1::53
+.                        3600000      NS    I.ROOT-SERVERS.NET.
+I.ROOT-SERVERS.NET.      3600000      A     192.36.148.17
+I.ROOT-SERVERS.NET.      3600000      AAAA  2001:7fe::53
+.                        3600000      NS    J.ROOT-SERVERS.NET.
+J.ROOT-SERVERS.NET.      3600000      A     192.58.128.30
+J.ROOT-SERVERS.NET.      3600000      AAAA  2001:503:c27::2:30
+.                        3600000      NS    K.ROOT-SERVERS.NET.
+K.ROOT-SERVERS.NET.      3600000      A     193.0.14.129
+K.ROOT-SERVERS.NET.      3600000      AAAA  2001:7fd::1
+.                        3600000      NS    L.ROOT-SERVERS.NET.
+L.ROOT-SERVERS.NET.      3600000      A     199.7.83.42
+L.ROOT-SERVERS.NET.      3600000      AAAA  2001:500:9f::42
+.                        3600000      NS    M.ROOT-SERVERS.NET.
+M.ROOT-SERVERS.NET.      3600000      A     202.12.27.33
+M.ROOT-SERVERS.NET.      3600000      AAAA  2001:dc3::35
+";
+
+/// The most referrals to follow before giving up, in case nameservers
+/// refer to each other in a loop.
+const MAX_REFERRALS: usize = 16;
+
+/// The most levels of nested traces to perform to look up the addresses of
+/// nameservers that were referred to without glue records.
+const MAX_DEPTH: usize = 4;
+
+/// The port that nameservers get asked on, unless the root hints say
+/// otherwise.
+const DEFAULT_PORT: u16 = 53;
+
+/// A nameserver that can be asked during a trace, with the addresses it
+/// was given for it. The addresses are empty if a referral came without
+/// glue, in which case they have to be looked up.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Nameserver {
+    /// The name of the nameserver.
+    pub name: Name,
+
+    /// Its IPv4 and IPv6 addresses, with the IPv4 ones first.
+    pub addresses: Vec<IpAddr>,
+
+    /// The port to ask it on. A root server’s port comes from the root
+    /// hints, and every other nameserver is asked on the same port as the
+    /// one that referred to it, so a trace through local servers that
+    /// aren’t on port 53 stays on theirs.
+    pub port: u16,
+}
+
+/// The list of root servers that a trace starts from.
+#[derive(PartialEq, Debug, Clone)]
+pub struct RootHints {
+    /// The root servers, in the order they should be tried.
+    pub servers: Vec<Nameserver>,
+}
+
+impl RootHints {
+    /// Returns the root hints built into dog.
+    pub fn builtin() -> Self {
+        Self::parse(BUILTIN_ROOT_HINTS).expect("Built-in root hints are invalid")
+    }
+
+    /// Parses a root hints file in the format of `named.root`: a list of
+    /// NS records for the root zone, and the A and AAAA records of the
+    /// nameservers they name. Comments start with a `;`. A file with only
+    /// address records uses every server it lists. An address can have a
+    /// port after it, as in `127.0.0.1:5300` or `[::1]:5300`, as long as
+    /// every address of the same server has the same one.
+    pub fn parse(contents: &str) -> Result<Self, RootHintsError> {
+        let mut names = Vec::new();
+        let mut addresses = HashMap::<Name, Vec<IpAddr>>::new();
+        let mut ports = HashMap::<Name, u16>::new();
+        let mut address_order = Vec::new();
+
+        for (index, line) in contents.lines().enumerate() {
+            let line = line.split(';').next().unwrap_or_default();
+            let fields = line.split_whitespace().collect::<Vec<_>>();
+            if fields.is_empty() {
+                continue;
+            }
+
+            let invalid = || RootHintsError::InvalidLine(index + 1);
+            let type_index = fields
+                .iter()
+                .position(|f| {
+                    ["NS", "A", "AAAA"]
+                        .iter()
+                        .any(|t| f.eq_ignore_ascii_case(t))
+                })
+                .filter(|&i| i > 0)
+                .ok_or_else(invalid)?;
+
+            let owner = parse_name(fields[0]).ok_or_else(invalid)?;
+            let data = match &fields[type_index + 1..] {
+                [data] => *data,
+                _ => return Err(invalid()),
+            };
+
+            if fields[type_index].eq_ignore_ascii_case("NS") {
+                names.push(parse_name(data).ok_or_else(invalid)?);
+            } else {
+                let (address, port) = parse_address(data).ok_or_else(invalid)?;
+                if *ports.entry(owner.clone()).or_insert(port) != port {
+                    return Err(invalid());
+                }
+                if !addresses.contains_key(&owner) {
+                    address_order.push(owner.clone());
+                }
+                addresses.entry(owner).or_default().push(address);
+            }
+        }
+
+        if names.is_empty() {
+            names = address_order;
+        }
+
+        let servers = names
+            .into_iter()
+            .filter_map(|name| {
+                let mut addresses = addresses.get(&name)?.clone();
+                addresses.sort_by_key(IpAddr::is_ipv6);
+                let port = ports[&name];
+                Some(Nameserver {
+                    name,
+                    addresses,
+                    port,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        if servers.is_empty() {
+            Err(RootHintsError::NoServers)
+        } else {
+            Ok(Self { servers })
+        }
+    }
+}
+
+/// Parses a domain name from a root hints file, making it fully-qualified.
+fn parse_name(input: &str) -> Option<Name> {
+    let mut name = Name::from_ascii(input).ok()?;
+    name.set_fqdn(true);
+    Some(name)
+}
+
+/// Parses an address from a root hints file, which can be followed by a
+/// port, with an IPv6 address in square brackets if it is.
+fn parse_address(input: &str) -> Option<(IpAddr, u16)> {
+    let (address, port) = if let Some(rest) = input.strip_prefix('[') {
+        let (address, port) = rest.split_once("]:")?;
+        (address.parse::<Ipv6Addr>().ok()?.into(), port)
+    } else if let Ok(address) = input.parse::<IpAddr>() {
+        return Some((address, DEFAULT_PORT));
+    } else {
+        let (address, port) = input.split_once(':')?;
+        (address.parse::<Ipv4Addr>().ok()?.into(), port)
+    };
+
+    let port = port.parse::<u16>().ok().filter(|port| *port != 0)?;
+    Some((address, port))
+}
+
+/// Something wrong with a root hints file.
+#[derive(PartialEq, Debug)]
+pub enum RootHintsError {
+    /// A line isn’t an NS, A, or AAAA record.
+    InvalidLine(usize),
+
+    /// None of the root servers have addresses.
+    NoServers,
+}
+
+impl fmt::Display for RootHintsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidLine(number) => write!(f, "line {number} is not an NS, A, or AAAA record"),
+            Self::NoServers => write!(f, "no root servers with addresses"),
+        }
+    }
+}
+
+/// The way the tracer sends requests to nameservers. This is a trait so the
+/// trace can be tested against stand-in nameservers.
+pub trait Exchange {
+    /// Sends a request to the nameserver at the given address and port,
+    /// returning the transport it was sent over, and the response or the
+    /// error.
+    fn exchange(
+        &self,
+        address: SocketAddr,
+        request: Message,
+    ) -> impl Future<Output = (Transport, Result<DnsResponse, NetError>)>;
+}
+
+/// Sends requests over the network, using UDP unless `tcp` is set, and
+/// re-sending truncated responses over TCP.
+pub struct Network {
+    /// Whether to send every request over TCP.
+    pub tcp: bool,
+
+    /// How long to wait for each nameserver to respond.
+    pub timeout: Duration,
+}
+
+impl Exchange for Network {
+    async fn exchange(
+        &self,
+        address: SocketAddr,
+        request: Message,
+    ) -> (Transport, Result<DnsResponse, NetError>) {
+        let mut connection = if self.tcp {
+            ConnectionConfig::tcp()
+        } else {
+            ConnectionConfig::udp()
+        };
+        connection.port = address.port();
+
+        let mut transport = Transport::new(address.ip(), connection).with_timeout(self.timeout);
+        let mut result = transport.send(request.clone()).await;
+        if transport.is_udp() && matches!(&result, Ok(r) if r.metadata.truncation) {
+            debug!("Response was truncated, re-sending over TCP");
+            transport = transport.with_tcp();
+            result = transport.send(request).await;
+        }
+
+        (transport, result)
+    }
+}
+
+/// The request sent to one nameserver during a trace, and what came back.
+#[derive(Debug)]
+pub struct Hop {
+    /// The zone the nameserver was expected to be authoritative for.
+    pub zone: Name,
+
+    /// The name of the nameserver.
+    pub server: Name,
+
+    /// The nameserver’s address and the protocol used.
+    pub transport: Transport,
+
+    /// How long it took for the response to come back.
+    pub elapsed: Duration,
+
+    /// The response, or the error that occurred.
+    pub result: Result<Message, NetError>,
+
+    /// The zone the response referred the tracer to, if it was a referral.
+    pub referral: Option<Name>,
+}
+
+/// The hops taken while tracing one query, ending with the nameserver that
+/// gave the answer.
+#[derive(Debug)]
+pub struct Trace {
+    /// The domain name that was traced.
+    pub domain: String,
+
+    /// The record type that was queried for.
+    pub qtype: RecordType,
+
+    /// The network class that was queried.
+    pub class: DNSClass,
+
+    /// Every request sent, in order, including the ones that failed.
+    pub hops: Vec<Hop>,
+
+    /// The reason the trace didn’t end with an answer, if it didn’t.
+    pub error: Option<TraceError>,
+}
+
+impl Trace {
+    /// The response that ended the trace, if it ended with one.
+    pub fn answer(&self) -> Option<&Message> {
+        if self.error.is_some() {
+            return None;
+        }
+
+        self.hops.last().and_then(|hop| hop.result.as_ref().ok())
+    }
+}
+
+/// The reason a trace didn’t end with an answer.
+#[derive(Debug)]
+pub enum TraceError {
+    /// The request message couldn’t be built.
+    Request(ProtoError),
+
+    /// None of the nameservers for a zone responded.
+    NoResponse(Name),
+
+    /// A nameserver referred to a zone that isn’t below the zone it was
+    /// asked about, or doesn’t contain the name being traced.
+    BadReferral(Name, Name),
+
+    /// There were more referrals than dog is willing to follow.
+    TooManyReferrals,
+}
+
+impl fmt::Display for TraceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Request(e) => write!(f, "{e}"),
+            Self::NoResponse(zone) => {
+                write!(f, "No nameserver for the {zone} zone responded")
+            }
+            Self::BadReferral(zone, child) => {
+                write!(
+                    f,
+                    "Nameserver for the {zone} zone gave a bad referral to {child}"
+                )
+            }
+            Self::TooManyReferrals => {
+                write!(f, "Gave up after {MAX_REFERRALS} referrals")
+            }
+        }
+    }
+}
+
+/// What the tracer should do after getting a response.
+enum Step {
+    /// The response is the answer, or a negative answer.
+    Answer,
+
+    /// The response refers to the nameservers of a zone further down.
+    Referral(Name, Vec<Nameserver>),
+
+    /// The response refers to a zone that doesn’t get any closer.
+    BadReferral(Name),
+}
+
+/// A future that resolves to the final response of a trace.
+type TraceFuture<'a> = Pin<Box<dyn Future<Output = Result<Message, TraceError>> + 'a>>;
+
+/// Follows the delegation chain from the root servers, sending each request
+/// with the settings the user has picked, but with recursion turned off.
+pub struct Tracer<'a, E> {
+    /// The settings to build the requests with.
+    pub requests: &'a Requests,
+
+    /// The root servers to start from.
+    pub hints: &'a RootHints,
+
+    /// How to send the requests.
+    pub exchange: E,
+}
+
+impl<E: Exchange> Tracer<'_, E> {
+    /// Traces one domain, record type, and class combination, returning
+    /// every hop taken.
+    pub async fn trace(&self, domain: &str, qtype: RecordType, class: DNSClass) -> Trace {
+        let mut hops = Vec::new();
+        let error = match Name::from_utf8(domain) {
+            Ok(mut name) => {
+                name.set_fqdn(true);
+                self.iterate(name, qtype, class, 0, &mut hops).await.err()
+            }
+            Err(e) => Some(TraceError::Request(e)),
+        };
+
+        Trace {
+            domain: domain.to_string(),
+            qtype,
+            class,
+            hops,
+            error,
+        }
+    }
+
+    /// Asks the root servers about the name, then follows each referral,
+    /// until a nameserver gives an answer. Every request sent gets added to
+    /// the list of hops.
+    fn iterate<'b>(
+        &'b self,
+        name: Name,
+        qtype: RecordType,
+        class: DNSClass,
+        depth: usize,
+        hops: &'b mut Vec<Hop>,
+    ) -> TraceFuture<'b> {
+        Box::pin(async move {
+            let mut zone = Name::root();
+            let mut servers = self.hints.servers.clone();
+
+            for _ in 0..MAX_REFERRALS {
+                let (response, port) = self
+                    .ask(&zone, &servers, &name, qtype, class, depth, hops)
+                    .await?;
+
+                match next_step(&response, &zone, &name, port) {
+                    Step::Answer => return Ok(response),
+                    Step::Referral(child, nameservers) => {
+                        if let Some(hop) = hops.last_mut() {
+                            hop.referral = Some(child.clone());
+                        }
+                        zone = child;
+                        servers = nameservers;
+                    }
+                    Step::BadReferral(child) => {
+                        return Err(TraceError::BadReferral(zone, child));
+                    }
+                }
+            }
+
+            Err(TraceError::TooManyReferrals)
+        })
+    }
+
+    /// Asks each nameserver for a zone in turn until one responds, looking
+    /// up the addresses of any nameservers that came without glue. The
+    /// response comes back with the port of the nameserver that sent it.
+    #[allow(clippy::too_many_arguments)]
+    async fn ask(
+        &self,
+        zone: &Name,
+        servers: &[Nameserver],
+        name: &Name,
+        qtype: RecordType,
+        class: DNSClass,
+        depth: usize,
+        hops: &mut Vec<Hop>,
+    ) -> Result<(Message, u16), TraceError> {
+        for server in servers {
+            let addresses = if !server.addresses.is_empty() {
+                server.addresses.clone()
+            } else if depth < MAX_DEPTH {
+                self.look_up(&server.name, depth + 1).await
+            } else {
+                Vec::new()
+            };
+
+            for address in addresses {
+                let mut request = self
+                    .requests
+                    .build_message(&name.to_string(), qtype, class)
+                    .map_err(TraceError::Request)?;
+                request.metadata.recursion_desired = false;
+
+                let address = SocketAddr::new(address, server.port);
+                debug!("Asking {} ({address}) about {name}", server.name);
+                let timer = Instant::now();
+                let (transport, result) = self.exchange.exchange(address, request).await;
+                let result = result.map(DnsResponse::into_message);
+                let response = result.as_ref().ok().cloned();
+
+                hops.push(Hop {
+                    zone: zone.clone(),
+                    server: server.name.clone(),
+                    transport,
+                    elapsed: timer.elapsed(),
+                    result,
+                    referral: None,
+                });
+
+                if let Some(response) = response {
+                    return Ok((response, server.port));
+                }
+            }
+        }
+
+        Err(TraceError::NoResponse(zone.clone()))
+    }
+
+    /// Looks up the addresses of a nameserver that was referred to without
+    /// glue, by performing another trace. Its hops aren’t recorded. IPv6
+    /// addresses are only looked up if it has no IPv4 ones.
+    async fn look_up(&self, server: &Name, depth: usize) -> Vec<IpAddr> {
+        for qtype in [RecordType::A, RecordType::AAAA] {
+            let mut hops = Vec::new();
+            let trace = self.iterate(server.clone(), qtype, DNSClass::IN, depth, &mut hops);
+            if let Ok(response) = trace.await {
+                let addresses = response
+                    .answers
+                    .iter()
+                    .filter_map(|r| address_of(&r.data))
+                    .collect::<Vec<_>>();
+
+                if !addresses.is_empty() {
+                    return addresses;
+                }
+            }
+        }
+
+        Vec::new()
+    }
+}
+
+/// Works out whether a response is an answer or a referral. A response is
+/// a referral if it has no answers, but has NS records in its authority
+/// section for a zone between the one that was asked and the name. The
+/// nameservers it refers to get asked on the same port as the one that
+/// sent it.
+fn next_step(response: &Message, zone: &Name, name: &Name, port: u16) -> Step {
+    if response.response_code != ResponseCode::NoError || !response.answers.is_empty() {
+        return Step::Answer;
+    }
+
+    let ns_records = response
+        .authorities
+        .iter()
+        .filter_map(|r| match &r.data {
+            RData::NS(ns) => Some((&r.name, &ns.0)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    let Some((child, _)) = ns_records.first() else {
+        return Step::Answer;
+    };
+
+    let child = (*child).clone();
+    if child.num_labels() <= zone.num_labels() || !zone.zone_of(&child) || !child.zone_of(name) {
+        return Step::BadReferral(child);
+    }
+
+    let nameservers = ns_records
+        .iter()
+        .filter(|(owner, _)| **owner == child)
+        .map(|(_, target)| {
+            let mut addresses = response
+                .additionals
+                .iter()
+                .filter(|r| r.name == **target)
+                .filter_map(|r| address_of(&r.data))
+                .collect::<Vec<_>>();
+            addresses.sort_by_key(IpAddr::is_ipv6);
+
+            Nameserver {
+                name: (*target).clone(),
+                addresses,
+                port,
+            }
+        })
+        .collect();
+
+    Step::Referral(child, nameservers)
+}
+
+/// Returns the address in an A or AAAA record.
+fn address_of(data: &RData) -> Option<IpAddr> {
+    match data {
+        RData::A(a) => Some(IpAddr::V4(a.0)),
+        RData::AAAA(aaaa) => Some(IpAddr::V6(aaaa.0)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    use hickory_resolver::proto::rr::rdata::{A, NS, SOA};
+    use hickory_resolver::proto::rr::Record;
+
+    /// The root hints for the stand-in nameservers.
+    const TEST_ROOT_HINTS: &str = "
+        ; stand-in root server
+        .                   3600000  IN  NS  root.test.
+        root.test.          3600000  IN  A   10.0.0.1
+    ";
+
+    /// A set of stand-in nameservers, each of which has its own canned
+    /// responses: the root server delegates `dog.` with glue, the `dog.`
+    /// server delegates `lookup.dog.` without glue, and the nameserver for
+    /// `lookup.dog.` gets its address from the `dog.` server.
+    struct StandIns;
+
+    fn name(input: &str) -> Name {
+        Name::from_ascii(input).unwrap()
+    }
+
+    fn a_record(owner: &str, last_octet: u8) -> Record {
+        Record::from_rdata(name(owner), 300, RData::A(A::new(10, 0, 0, last_octet)))
+    }
+
+    fn ns_record(owner: &str, target: &str) -> Record {
+        Record::from_rdata(name(owner), 300, RData::NS(NS(name(target))))
+    }
+
+    impl Exchange for StandIns {
+        async fn exchange(
+            &self,
+            address: SocketAddr,
+            request: Message,
+        ) -> (Transport, Result<DnsResponse, NetError>) {
+            assert!(!request.metadata.recursion_desired);
+            let mut connection = ConnectionConfig::udp();
+            connection.port = address.port();
+            let transport = Transport::new(address.ip(), connection);
+
+            let query = request.queries[0].clone();
+            let qname = query.name().to_string();
+            let mut response = Message::response(request.metadata.id, request.metadata.op_code);
+            response.add_query(query);
+
+            match address.ip().to_string().as_str() {
+                "10.0.0.1" => {
+                    response.add_authority(ns_record("dog.", "ns.nic.dog."));
+                    response.add_additional(a_record("ns.nic.dog.", 2));
+                }
+                "10.0.0.2" if qname == "ns.lookup-servers.dog." => {
+                    response.metadata.authoritative = true;
+                    response.add_answer(a_record("ns.lookup-servers.dog.", 3));
+                }
+                "10.0.0.2" => {
+                    response.add_authority(ns_record("lookup.dog.", "ns.lookup-servers.dog."));
+                }
+                "10.0.0.3" => {
+                    response.metadata.authoritative = true;
+                    response.add_answer(a_record("www.lookup.dog.", 80));
+                }
+                "10.0.0.4" => {
+                    response.add_authority(ns_record("com.", "ns.nic.com."));
+                }
+                _ => return (transport, Err(NetError::Timeout)),
+            }
+
+            (transport, Ok(DnsResponse::from_message(response).unwrap()))
+        }
+    }
+
+    async fn trace(hints: &str, domain: &str) -> Trace {
+        let requests = Requests::default();
+        let hints = RootHints::parse(hints).unwrap();
+        let tracer = Tracer {
+            requests: &requests,
+            hints: &hints,
+            exchange: StandIns,
+        };
+
+        tracer.trace(domain, RecordType::A, DNSClass::IN).await
+    }
+
+    fn summary(trace: &Trace) -> Vec<(String, String, Option<String>)> {
+        trace
+            .hops
+            .iter()
+            .map(|hop| {
+                (
+                    hop.zone.to_string(),
+                    hop.transport.address().ip().to_string(),
+                    hop.referral.as_ref().map(Name::to_string),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn builtin_root_hints() {
+        let hints = RootHints::builtin();
+        assert_eq!(hints.servers.len(), 13);
+        assert_eq!(hints.servers[0].name, name("A.ROOT-SERVERS.NET."));
+        assert_eq!(
+            hints.servers[0].addresses,
+            vec![
+                "198.41.0.4".parse::<IpAddr>().unwrap(),
+                "2001:503:ba3e::2:30".parse::<IpAddr>().unwrap(),
+            ]
+        );
+    }
+
+    #[test]
+    fn root_hints_addresses_only() {
+        let hints = RootHints::parse("root.test. A 10.0.0.1\nother.test. AAAA ::1\n").unwrap();
+        assert_eq!(hints.servers.len(), 2);
+        assert_eq!(hints.servers[1].name, name("other.test."));
+    }
+
+    #[test]
+    fn root_hints_ports() {
+        let hints = RootHints::parse(
+            "four.test. A 127.0.0.1:5300\nsix.test. AAAA [::1]:5301\nplain.test. AAAA ::1\n",
+        )
+        .unwrap();
+        assert_eq!(
+            hints.servers[0].addresses,
+            vec![IpAddr::from([127, 0, 0, 1])]
+        );
+        assert_eq!(hints.servers[0].port, 5300);
+        assert_eq!(hints.servers[1].port, 5301);
+        assert_eq!(hints.servers[2].port, 53);
+    }
+
+    #[test]
+    fn root_hints_different_ports() {
+        assert_eq!(
+            RootHints::parse("root.test. A 10.0.0.1:5300\nroot.test. AAAA [::1]:5301\n"),
+            Err(RootHintsError::InvalidLine(2))
+        );
+    }
+
+    #[test]
+    fn root_hints_invalid_port() {
+        assert_eq!(
+            RootHints::parse("root.test. A 10.0.0.1:0\n"),
+            Err(RootHintsError::InvalidLine(1))
+        );
+    }
+
+    #[test]
+    fn root_hints_invalid_line() {
+        assert_eq!(
+            RootHints::parse(". NS root.test.\nroot.test. A not-an-address\n"),
+            Err(RootHintsError::InvalidLine(2))
+        );
+    }
+
+    #[test]
+    fn root_hints_no_addresses() {
+        assert_eq!(
+            RootHints::parse(". NS root.test.\n"),
+            Err(RootHintsError::NoServers)
+        );
+    }
+
+    #[tokio::test]
+    async fn follows_delegations() {
+        let trace = trace(TEST_ROOT_HINTS, "www.lookup.dog").await;
+        assert!(trace.error.is_none());
+        assert_eq!(
+            summary(&trace),
+            vec![
+                (
+                    String::from("."),
+                    String::from("10.0.0.1"),
+                    Some(String::from("dog."))
+                ),
+                (
+                    String::from("dog."),
+                    String::from("10.0.0.2"),
+                    Some(String::from("lookup.dog."))
+                ),
+                (String::from("lookup.dog."), String::from("10.0.0.3"), None),
+            ]
+        );
+
+        let answer = trace.answer().unwrap();
+        assert_eq!(answer.answers, vec![a_record("www.lookup.dog.", 80)]);
+    }
+
+    #[tokio::test]
+    async fn follows_delegations_on_hint_port() {
+        let trace = trace("root.test. A 10.0.0.1:5300", "www.lookup.dog").await;
+        assert!(trace.error.is_none());
+        assert_eq!(
+            trace
+                .hops
+                .iter()
+                .map(|hop| hop.transport.address().to_string())
+                .collect::<Vec<_>>(),
+            vec!["10.0.0.1:5300", "10.0.0.2:5300", "10.0.0.3:5300"]
+        );
+    }
+
+    #[tokio::test]
+    async fn unresponsive_server_skipped() {
+        let hints = "
+            . NS dead.test.
+            . NS root.test.
+            dead.test. A 10.0.0.99
+            root.test. A 10.0.0.1
+        ";
+        let trace = trace(hints, "www.lookup.dog").await;
+        assert!(trace.error.is_none());
+        assert!(trace.hops[0].result.is_err());
+        assert_eq!(trace.hops.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn no_server_responds() {
+        let trace = trace("dead.test. A 10.0.0.99", "www.lookup.dog").await;
+        assert!(matches!(trace.error, Some(TraceError::NoResponse(ref zone)) if zone.is_root()));
+        assert!(trace.answer().is_none());
+    }
+
+    #[tokio::test]
+    async fn bad_referral() {
+        let trace = trace("sideways.test. A 10.0.0.4", "www.lookup.dog").await;
+        assert!(matches!(
+            trace.error,
+            Some(TraceError::BadReferral(_, ref child)) if *child == name("com.")
+        ));
+    }
+
+    #[test]
+    fn negative_answer_ends_trace() {
+        let mut response = Message::response(1, hickory_resolver::proto::op::OpCode::Query);
+        response.metadata.response_code = ResponseCode::NXDomain;
+        let soa = SOA::new(name("dog."), name("dog."), 1, 1, 1, 1, 1);
+        response.add_authority(Record::from_rdata(name("dog."), 300, RData::SOA(soa)));
+
+        assert!(matches!(
+            next_step(&response, &Name::root(), &name("www.lookup.dog."), 53),
+            Step::Answer
+        ));
+    }
+}
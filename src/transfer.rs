@@ -0,0 +1,236 @@
+/*
+ * dog - A command-line DNS client
+ * Copyright (c) 2026 l1a and contributors
+ * Original code Copyright (c) Benjamin Sago
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Interpreting the records received during a zone transfer.
+//!
+//! A transfer gets streamed over several messages, and there’s no field in
+//! any of them that says it’s the last one. Instead, the records themselves
+//! have to be read to find out where the transfer ends: a full transfer
+//! (AXFR) is bracketed by the zone’s SOA record, and an incremental one
+//! (IXFR) consists of sequences of removed and added records, each of which
+//! starts with an SOA record giving the version it applies to.
+
+use hickory_resolver::proto::rr::{RData, Record};
+
+/// The records received during a zone transfer, once it has finished.
+#[derive(PartialEq, Debug)]
+pub enum Transfer {
+    /// The entire zone, in the order the nameserver sent it, starting and
+    /// ending with its SOA record.
+    Full(Vec<Record>),
+
+    /// The changes made to the zone since the version in the request, in
+    /// the order they were made.
+    Incremental(Vec<Change>),
+
+    /// The zone hasn’t changed since the version in the request, so only
+    /// its current SOA record was sent.
+    UpToDate(Box<Record>),
+}
+
+/// One set of changes from an incremental zone transfer, which takes the
+/// zone from one version to the next.
+#[derive(PartialEq, Debug)]
+pub struct Change {
+    /// The serial number of the version the changes apply to.
+    pub from_serial: u32,
+
+    /// The serial number of the version the changes result in.
+    pub to_serial: u32,
+
+    /// The records that were removed, including the old SOA record.
+    pub removed: Vec<Record>,
+
+    /// The records that were added, including the new SOA record.
+    pub added: Vec<Record>,
+}
+
+impl Transfer {
+    /// Interprets the records that have been received so far, returning
+    /// `None` if the transfer hasn’t finished yet. Pass `incremental` for
+    /// IXFR requests, where the nameserver may respond with either a full
+    /// or an incremental transfer.
+    ///
+    /// A transfer that doesn’t start with an SOA record is treated as
+    /// finished, as there’s no way to tell where it would end.
+    pub fn from_records(records: &[Record], incremental: bool) -> Option<Self> {
+        let (first, rest) = records.split_first()?;
+        let Some(serial) = soa_serial(first) else {
+            return Some(Self::Full(records.to_vec()));
+        };
+
+        if rest.is_empty() {
+            return if incremental {
+                Some(Self::UpToDate(Box::new(first.clone())))
+            } else {
+                None
+            };
+        }
+
+        if incremental && soa_serial(&rest[0]).is_some_and(|s| s != serial) {
+            return incremental_changes(rest, serial).map(Self::Incremental);
+        }
+
+        match rest.last().and_then(soa_serial) {
+            Some(last) if last == serial => Some(Self::Full(records.to_vec())),
+            _ => None,
+        }
+    }
+}
+
+/// Splits the records after the first SOA record of an incremental transfer
+/// into sets of changes, returning `None` if the final SOA record, with the
+/// zone’s current serial number, hasn’t been received yet.
+fn incremental_changes(records: &[Record], serial: u32) -> Option<Vec<Change>> {
+    let mut changes = Vec::new();
+    let mut index = 0;
+
+    loop {
+        let from_serial = soa_serial(records.get(index)?)?;
+        if from_serial == serial && index == records.len() - 1 {
+            return Some(changes);
+        }
+
+        let removed_end = index + 1 + position_of_soa(&records[index + 1..])?;
+        let to_serial = soa_serial(&records[removed_end])?;
+        let added_end = removed_end + 1 + position_of_soa(&records[removed_end + 1..])?;
+
+        changes.push(Change {
+            from_serial,
+            to_serial,
+            removed: records[index..removed_end].to_vec(),
+            added: records[removed_end..added_end].to_vec(),
+        });
+
+        index = added_end;
+    }
+}
+
+/// Returns the index of the first SOA record in the list, if there is one.
+fn position_of_soa(records: &[Record]) -> Option<usize> {
+    records.iter().position(|r| soa_serial(r).is_some())
+}
+
+/// Returns the serial number of a record, if it’s an SOA record.
+fn soa_serial(record: &Record) -> Option<u32> {
+    match &record.data {
+        RData::SOA(soa) => Some(soa.serial),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    use hickory_resolver::proto::rr::rdata::{A, SOA};
+    use hickory_resolver::proto::rr::Name;
+
+    fn soa(serial: u32) -> Record {
+        let name = Name::from_ascii("lookup.dog.").unwrap();
+        let soa = SOA::new(name.clone(), name.clone(), serial, 3600, 600, 86400, 300);
+        Record::from_rdata(name, 300, RData::SOA(soa))
+    }
+
+    fn a(last_octet: u8) -> Record {
+        let name = Name::from_ascii("www.lookup.dog.").unwrap();
+        Record::from_rdata(name, 300, RData::A(A::new(192, 0, 2, last_octet)))
+    }
+
+    #[test]
+    fn full_transfer() {
+        let records = vec![soa(3), a(1), a(2), soa(3)];
+        assert_eq!(
+            Transfer::from_records(&records, false),
+            Some(Transfer::Full(records))
+        );
+    }
+
+    #[test]
+    fn full_transfer_unfinished() {
+        assert_eq!(Transfer::from_records(&[soa(3)], false), None);
+        assert_eq!(Transfer::from_records(&[soa(3), a(1)], false), None);
+    }
+
+    #[test]
+    fn full_transfer_for_ixfr() {
+        let records = vec![soa(3), a(1), soa(3)];
+        assert_eq!(
+            Transfer::from_records(&records, true),
+            Some(Transfer::Full(records))
+        );
+    }
+
+    #[test]
+    fn up_to_date() {
+        assert_eq!(
+            Transfer::from_records(&[soa(3)], true),
+            Some(Transfer::UpToDate(Box::new(soa(3))))
+        );
+    }
+
+    #[test]
+    fn incremental_transfer() {
+        let records = vec![
+            soa(3),
+            soa(1),
+            a(1),
+            soa(2),
+            a(2),
+            soa(2),
+            soa(3),
+            a(3),
+            soa(3),
+        ];
+
+        assert_eq!(
+            Transfer::from_records(&records, true),
+            Some(Transfer::Incremental(vec![
+                Change {
+                    from_serial: 1,
+                    to_serial: 2,
+                    removed: vec![soa(1), a(1)],
+                    added: vec![soa(2), a(2)],
+                },
+                Change {
+                    from_serial: 2,
+                    to_serial: 3,
+                    removed: vec![soa(2)],
+                    added: vec![soa(3), a(3)],
+                },
+            ]))
+        );
+    }
+
+    #[test]
+    fn incremental_transfer_unfinished() {
+        let records = vec![soa(3), soa(1), a(1), soa(3), a(2)];
+        assert_eq!(Transfer::from_records(&records, true), None);
+    }
+
+    #[test]
+    fn not_a_transfer() {
+        let records = vec![a(1)];
+        assert_eq!(
+            Transfer::from_records(&records, false),
+            Some(Transfer::Full(records))
+        );
+    }
+}
@@ -0,0 +1,669 @@
+/*
+ * dog - A command-line DNS client
+ * Copyright (c) 2026 l1a and contributors
+ * Original code Copyright (c) Benjamin Sago
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Sending DNS requests to a nameserver and receiving the responses.
+//!
+//! Rather than going through a resolver, which builds its own queries, dog
+//! constructs each request message itself and sends it using one of the
+//! client streams from hickory, so that every field the user asked for
+//! (such as the query class) ends up on the wire.
+
+use std::convert::TryFrom;
+use std::net::{IpAddr, SocketAddr, SocketAddrV6};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use futures::StreamExt;
+use hickory_resolver::config::{ConnectionConfig, ProtocolConfig};
+use hickory_resolver::net::quic::QuicClientStream;
+use hickory_resolver::net::runtime::TokioRuntimeProvider;
+use hickory_resolver::net::tcp::TcpClientStream;
+use hickory_resolver::net::tls::{tls_client_connect, TlsClientStream};
+use hickory_resolver::net::udp::UdpClientStream;
+use hickory_resolver::net::xfer::{
+    BufDnsStreamHandle, DnsRequestSender, DnsStreamHandle, FirstAnswer,
+};
+use hickory_resolver::net::NetError;
+use hickory_resolver::proto::op::{
+    DnsRequest, DnsRequestOptions, DnsResponse, Message, ResponseCode, SerialMessage,
+};
+use hickory_resolver::proto::rr::RecordType;
+use log::*;
+use rustls_pki_types::ServerName;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+
+use crate::dnscrypt;
+use crate::doh::{self, Endpoint, HttpResponse};
+use crate::odoh;
+use crate::pinning::{pinned_config, CertificateHash};
+use crate::transfer::Transfer;
+
+/// How long to wait for a nameserver to respond before giving up, if the
+/// user doesn’t say.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A **transport** is a nameserver address, combined with the protocol to use
+/// when sending requests to it.
+#[derive(Debug, Clone)]
+pub struct Transport {
+    /// The address of the nameserver.
+    ip: IpAddr,
+
+    /// The protocol and port to use.
+    connection: ConnectionConfig,
+
+    /// How long to wait for the nameserver to respond.
+    timeout: Duration,
+
+    /// The index of the network interface to reach a link-local IPv6
+    /// address through, or 0 to let the system pick.
+    scope_id: u32,
+
+    /// The HTTP method to send requests with, if using HTTPS.
+    doh_method: doh::Method,
+
+    /// The version of HTTP to send requests with, if using HTTPS.
+    http_version: doh::HttpVersion,
+
+    /// The relay to send HTTPS requests through, encrypted so that only
+    /// the nameserver can read them, if using Oblivious DNS-over-HTTPS.
+    odoh: Option<Arc<odoh::Route>>,
+
+    /// The DNSCrypt resolver to encrypt UDP and TCP requests for, if the
+    /// nameserver was given as a DNSCrypt stamp.
+    dnscrypt: Option<Arc<dnscrypt::Resolver>>,
+
+    /// The hashes of certificates in the nameserver’s chain, one of which
+    /// has to be there, if the nameserver was given as a stamp that pins
+    /// them.
+    certificate_hashes: Vec<CertificateHash>,
+}
+
+/// A response from a nameserver, along with the details of how it got
+/// sent that are shown in verbose mode.
+#[derive(Debug)]
+pub struct Exchange {
+    /// The response itself.
+    pub response: DnsResponse,
+
+    /// The HTTP response it came in, if it was sent over HTTPS.
+    pub http: Option<HttpResponse>,
+
+    /// How long setting up the connection took, compared to sending the
+    /// request down it, if it was sent over QUIC.
+    pub timings: Option<Timings>,
+}
+
+/// How long the two parts of sending a request over a new connection
+/// took.
+#[derive(Debug, Copy, Clone)]
+pub struct Timings {
+    /// How long the handshake took to set up the connection.
+    pub handshake: Duration,
+
+    /// How long it took to get a response once the connection was set up.
+    pub query: Duration,
+}
+
+/// A request that got no usable response, along with the HTTP response
+/// that came back instead, if it was sent over HTTPS and the server
+/// answered with an error status or something other than a DNS message.
+#[derive(Debug)]
+pub struct Failure {
+    /// What went wrong.
+    pub error: NetError,
+
+    /// The HTTP response that came back, if there was one.
+    pub http: Option<HttpResponse>,
+}
+
+impl From<NetError> for Failure {
+    fn from(error: NetError) -> Self {
+        Self { error, http: None }
+    }
+}
+
+impl From<doh::HttpError> for Failure {
+    fn from(error: doh::HttpError) -> Self {
+        Self {
+            error: error.error,
+            http: error.response,
+        }
+    }
+}
+
+impl Transport {
+    /// Creates a new transport that sends requests to the given address using
+    /// the given connection settings.
+    pub fn new(ip: IpAddr, connection: ConnectionConfig) -> Self {
+        Self {
+            ip,
+            connection,
+            timeout: DEFAULT_TIMEOUT,
+            scope_id: 0,
+            doh_method: doh::Method::default(),
+            http_version: doh::HttpVersion::default(),
+            odoh: None,
+            dnscrypt: None,
+            certificate_hashes: Vec::new(),
+        }
+    }
+
+    /// Returns this transport, sending HTTPS requests with the given method
+    /// instead of POST.
+    pub fn with_doh_method(self, doh_method: doh::Method) -> Self {
+        Self { doh_method, ..self }
+    }
+
+    /// Returns this transport, sending HTTPS requests with the given
+    /// version of HTTP instead of HTTP/2.
+    pub fn with_http_version(self, http_version: doh::HttpVersion) -> Self {
+        Self {
+            http_version,
+            ..self
+        }
+    }
+
+    /// Returns this transport, sending HTTPS requests through the given
+    /// Oblivious DNS-over-HTTPS relay instead of straight to the nameserver.
+    pub fn with_relay(self, relay: odoh::Relay) -> Self {
+        Self {
+            odoh: Some(Arc::new(odoh::Route::new(relay))),
+            ..self
+        }
+    }
+
+    /// The Oblivious DNS-over-HTTPS relay that HTTPS requests get sent through, along
+    /// with the URL of the nameserver they’re sent on to, if there is one.
+    pub fn odoh_route(&self) -> Option<(&odoh::Relay, String)> {
+        let route = self.odoh.as_ref()?;
+        match &self.connection.protocol {
+            ProtocolConfig::Https { server_name, path } => {
+                let target = Endpoint {
+                    addr: self.address(),
+                    server_name,
+                    path,
+                };
+                Some((
+                    route.relay(),
+                    format!("{} ({})", target.url(path), target.addr),
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns this transport, encrypting UDP and TCP requests for the
+    /// given DNSCrypt provider’s resolver.
+    pub fn with_dnscrypt(self, provider: dnscrypt::Provider) -> Self {
+        Self {
+            dnscrypt: Some(Arc::new(dnscrypt::Resolver::new(provider))),
+            ..self
+        }
+    }
+
+    /// Returns this transport, only trusting a certificate chain for TLS,
+    /// HTTPS, and QUIC that has one of the given certificate hashes in it.
+    pub fn with_certificate_hashes(self, certificate_hashes: Vec<CertificateHash>) -> Self {
+        Self {
+            certificate_hashes,
+            ..self
+        }
+    }
+
+    /// The DNSCrypt resolver that requests get encrypted for, if there is
+    /// one.
+    pub fn dnscrypt(&self) -> Option<&dnscrypt::Resolver> {
+        self.dnscrypt.as_deref()
+    }
+
+    /// Returns this transport, sending requests out of the network
+    /// interface with the given index, which is needed for link-local IPv6
+    /// addresses.
+    pub fn with_scope_id(self, scope_id: u32) -> Self {
+        Self { scope_id, ..self }
+    }
+
+    /// Returns this transport, waiting for the given amount of time for the
+    /// nameserver to respond, instead of the default.
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        Self { timeout, ..self }
+    }
+
+    /// Returns a transport for the same nameserver and port that uses TCP
+    /// instead. This is used to re-send requests that got truncated, or
+    /// that failed, over UDP.
+    pub fn with_tcp(&self) -> Self {
+        let mut connection = ConnectionConfig::tcp();
+        connection.port = self.connection.port;
+
+        Self {
+            connection,
+            ..self.clone()
+        }
+    }
+
+    /// Whether this transport sends requests over UDP.
+    pub fn is_udp(&self) -> bool {
+        matches!(self.connection.protocol, ProtocolConfig::Udp)
+    }
+
+    /// The address and port of the nameserver.
+    pub fn address(&self) -> SocketAddr {
+        match self.ip {
+            IpAddr::V6(ip) => SocketAddrV6::new(ip, self.connection.port, 0, self.scope_id).into(),
+            IpAddr::V4(_) => SocketAddr::new(self.ip, self.connection.port),
+        }
+    }
+
+    /// The name of the protocol used to send requests, for displaying to
+    /// the user.
+    pub fn protocol_name(&self) -> &'static str {
+        match self.connection.protocol {
+            ProtocolConfig::Udp if self.dnscrypt.is_some() => "DNSCrypt/UDP",
+            ProtocolConfig::Tcp if self.dnscrypt.is_some() => "DNSCrypt/TCP",
+            ProtocolConfig::Udp => "UDP",
+            ProtocolConfig::Tcp => "TCP",
+            ProtocolConfig::Tls { .. } => "TLS",
+            ProtocolConfig::Https { .. } if self.odoh.is_some() => "ODoH",
+            ProtocolConfig::Https { .. } => "HTTPS",
+            ProtocolConfig::Quic { .. } => "QUIC",
+        }
+    }
+
+    /// Returns the request as it gets sent over this transport. Requests
+    /// sent over HTTPS or QUIC always have an ID of 0: over HTTPS so that
+    /// identical requests can be cached, as RFC 8484 recommends, and over
+    /// QUIC because RFC 9250 requires it.
+    pub fn outgoing(&self, mut request: Message) -> Message {
+        if matches!(
+            self.connection.protocol,
+            ProtocolConfig::Https { .. } | ProtocolConfig::Quic { .. }
+        ) {
+            request.metadata.id = 0;
+        }
+
+        request
+    }
+
+    /// Sends the given request message to the nameserver, returning the
+    /// response that comes back, or the error that occurred.
+    pub async fn send(&self, request: Message) -> Result<DnsResponse, NetError> {
+        self.exchange(request)
+            .await
+            .map(|exchange| exchange.response)
+            .map_err(|failure| failure.error)
+    }
+
+    /// Sends the given request message to the nameserver, returning the
+    /// response that comes back along with how it got sent, or the error
+    /// that occurred.
+    pub async fn exchange(&self, request: Message) -> Result<Exchange, Failure> {
+        match &self.dnscrypt {
+            Some(resolver) => Ok(self.exchange_dnscrypt(resolver, request).await?),
+            None => self.exchange_plain(request).await,
+        }
+    }
+
+    /// Sends the given request message to the nameserver without
+    /// encrypting it for DNSCrypt, which is how DNSCrypt certificates get
+    /// fetched.
+    async fn exchange_plain(&self, request: Message) -> Result<Exchange, Failure> {
+        let addr = self.address();
+        let provider = TokioRuntimeProvider::default();
+        debug!(
+            "Sending request to {addr} over {:?}",
+            self.connection.protocol
+        );
+
+        match &self.connection.protocol {
+            ProtocolConfig::Udp => {
+                let mut stream = UdpClientStream::builder(addr, provider)
+                    .with_timeout(Some(self.timeout))
+                    .build();
+                Ok(Exchange::from(send_request(&mut stream, request).await?))
+            }
+            ProtocolConfig::Tcp => {
+                let (stream, handle) =
+                    TcpClientStream::new(addr, None, Some(self.timeout), provider);
+                let stream = stream.await?;
+                let response = send_framed(stream, handle, addr, request, self.timeout).await?;
+                Ok(Exchange::from(response))
+            }
+            ProtocolConfig::Tls { server_name } => {
+                let server_name = server_name_for(server_name)?;
+                let config = pinned_config(&self.certificate_hashes).map_err(NetError::from)?;
+                let config = Arc::new(config);
+                let (stream, handle) = tls_client_connect(addr, server_name, config, provider);
+                let stream: TlsClientStream<_> = stream.await?;
+                let response = send_framed(stream, handle, addr, request, self.timeout).await?;
+                Ok(Exchange::from(response))
+            }
+            ProtocolConfig::Https { server_name, path } => {
+                let message = self.outgoing(request).to_vec().map_err(NetError::from)?;
+
+                let endpoint = Endpoint {
+                    addr,
+                    server_name,
+                    path,
+                };
+                // Requests sent through a relay go over a connection to the
+                // relay, whose certificate the hashes aren’t for
+                let hashes = match self.odoh {
+                    Some(_) => &[][..],
+                    None => &self.certificate_hashes[..],
+                };
+                let client = doh::Client::new(self.http_version, self.timeout, hashes)?;
+                let (http, body) = match &self.odoh {
+                    Some(route) => route.exchange(&client, endpoint, message).await?,
+                    None => client.exchange(endpoint, self.doh_method, message).await?,
+                };
+
+                // A body that isn’t a DNS message still came in a response
+                // worth showing
+                match DnsResponse::from_buffer(body) {
+                    Ok(response) => Ok(Exchange {
+                        response,
+                        http: Some(http),
+                        timings: None,
+                    }),
+                    Err(e) => Err(Failure {
+                        error: e.into(),
+                        http: Some(http),
+                    }),
+                }
+            }
+            ProtocolConfig::Quic { server_name } => {
+                // The QUIC stream sets the ID to 0 before sending the
+                // request, as RFC 9250 requires
+                let exchange = async {
+                    let start = Instant::now();
+                    let mut stream = QuicClientStream::builder()
+                        .crypto_config(pinned_config(&self.certificate_hashes)?)
+                        .build(addr, Arc::clone(server_name))
+                        .await?;
+                    let handshake = start.elapsed();
+
+                    let start = Instant::now();
+                    let response = send_request(&mut stream, request).await?;
+                    let timings = Timings {
+                        handshake,
+                        query: start.elapsed(),
+                    };
+
+                    Ok::<_, NetError>(Exchange {
+                        response,
+                        http: None,
+                        timings: Some(timings),
+                    })
+                };
+
+                match tokio::time::timeout(self.timeout, exchange).await {
+                    Ok(result) => Ok(result?),
+                    Err(_) => Err(NetError::Timeout.into()),
+                }
+            }
+        }
+    }
+
+    /// Sends the given request message to a DNSCrypt resolver, fetching its
+    /// certificate first if it hasn’t been already, then encrypting the
+    /// request and decrypting the response.
+    async fn exchange_dnscrypt(
+        &self,
+        resolver: &dnscrypt::Resolver,
+        request: Message,
+    ) -> Result<Exchange, NetError> {
+        let certificate = resolver
+            .certificate(|request| async move {
+                self.exchange_plain(request)
+                    .await
+                    .map(|exchange| exchange.response)
+                    .map_err(|failure| failure.error)
+            })
+            .await?;
+
+        let addr = self.address();
+        let tcp = !self.is_udp();
+        debug!(
+            "Sending DNSCrypt request to {addr} with {}",
+            certificate.construction()
+        );
+        let (packet, sealed) = certificate
+            .encrypt(&request.to_vec()?, tcp)
+            .map_err(|e| NetError::from(e.to_string()))?;
+
+        let send = async {
+            if tcp {
+                send_tcp_packet(addr, &packet).await
+            } else {
+                send_udp_packet(addr, &packet).await
+            }
+        };
+        let packet = match tokio::time::timeout(self.timeout, send).await {
+            Ok(result) => result?,
+            Err(_) => return Err(NetError::Timeout),
+        };
+
+        let message = sealed
+            .decrypt(&packet)
+            .map_err(|e| NetError::from(e.to_string()))?;
+        Ok(Exchange::from(DnsResponse::from_buffer(message)?))
+    }
+
+    /// Sends a zone transfer request to the nameserver, reading every
+    /// message of the transfer until it’s finished, and returning them in
+    /// the order they were received. Transfers need a stream, so this only
+    /// works over TCP or TLS.
+    pub async fn transfer(&self, request: Message) -> Result<Vec<DnsResponse>, NetError> {
+        if self.dnscrypt.is_some() {
+            return Err(NetError::from(String::from(
+                "Zone transfers are not supported over DNSCrypt",
+            )));
+        }
+
+        let addr = self.address();
+        let provider = TokioRuntimeProvider::default();
+        let incremental = request
+            .queries
+            .first()
+            .is_some_and(|q| q.query_type() == RecordType::IXFR);
+        debug!(
+            "Requesting zone transfer from {addr} over {:?}",
+            self.connection.protocol
+        );
+
+        // Stop reading once the transfer is complete, or as soon as there’s
+        // a message that can’t be part of one
+        let mut records = Vec::new();
+        let is_finished = move |response: &DnsResponse| {
+            if response.response_code != ResponseCode::NoError || response.answers.is_empty() {
+                return true;
+            }
+
+            records.extend(response.answers.iter().cloned());
+            Transfer::from_records(&records, incremental).is_some()
+        };
+
+        match &self.connection.protocol {
+            ProtocolConfig::Tcp => {
+                let (stream, handle) =
+                    TcpClientStream::new(addr, None, Some(self.timeout), provider);
+                let stream = stream.await?;
+                receive_framed(stream, handle, addr, request, self.timeout, is_finished).await
+            }
+            ProtocolConfig::Tls { server_name } => {
+                let server_name = server_name_for(server_name)?;
+                let config = Arc::new(pinned_config(&self.certificate_hashes)?);
+                let (stream, handle) = tls_client_connect(addr, server_name, config, provider);
+                let stream: TlsClientStream<_> = stream.await?;
+                receive_framed(stream, handle, addr, request, self.timeout, is_finished).await
+            }
+            _ => Err(NetError::from(
+                "Zone transfers can only be made over TCP or TLS",
+            )),
+        }
+    }
+}
+
+impl From<DnsResponse> for Exchange {
+    fn from(response: DnsResponse) -> Self {
+        Self {
+            response,
+            http: None,
+            timings: None,
+        }
+    }
+}
+
+/// Combines the messages of a zone transfer into one response, with the
+/// records from every message in its answer section, in the order they were
+/// received.
+pub fn combine_transfer(responses: Vec<DnsResponse>) -> Result<DnsResponse, NetError> {
+    let mut responses = responses.into_iter().map(DnsResponse::into_message);
+    let mut combined = responses
+        .next()
+        .ok_or_else(|| NetError::from("No response was received"))?;
+
+    for message in responses {
+        combined.answers.extend(message.answers);
+    }
+
+    Ok(DnsResponse::from_message(combined)?)
+}
+
+/// Sends a packet that’s already been encoded in a UDP datagram, and
+/// returns the first datagram that comes back.
+async fn send_udp_packet(addr: SocketAddr, packet: &[u8]) -> Result<Vec<u8>, NetError> {
+    let local: SocketAddr = match addr {
+        SocketAddr::V4(_) => ([0, 0, 0, 0], 0).into(),
+        SocketAddr::V6(_) => ([0; 16], 0).into(),
+    };
+    let socket = UdpSocket::bind(local).await?;
+    socket.connect(addr).await?;
+    socket.send(packet).await?;
+
+    let mut buffer = vec![0; usize::from(u16::MAX)];
+    let length = socket.recv(&mut buffer).await?;
+    buffer.truncate(length);
+    Ok(buffer)
+}
+
+/// Sends a packet that’s already been encoded over a new TCP connection,
+/// with its length in front, and returns the packet that comes back.
+async fn send_tcp_packet(addr: SocketAddr, packet: &[u8]) -> Result<Vec<u8>, NetError> {
+    let length = u16::try_from(packet.len())
+        .map_err(|_| NetError::from(String::from("Request too long for TCP")))?;
+
+    let mut stream = TcpStream::connect(addr).await?;
+    stream.write_all(&length.to_be_bytes()).await?;
+    stream.write_all(packet).await?;
+
+    let mut length = [0; 2];
+    stream.read_exact(&mut length).await?;
+    let mut buffer = vec![0; usize::from(u16::from_be_bytes(length))];
+    stream.read_exact(&mut buffer).await?;
+    Ok(buffer)
+}
+
+/// Sends a request using a client stream that handles the serialisation and
+/// matching up of requests and responses itself.
+async fn send_request<S: DnsRequestSender>(
+    stream: &mut S,
+    request: Message,
+) -> Result<DnsResponse, NetError> {
+    let request = DnsRequest::new(request, DnsRequestOptions::default());
+    stream.send_message(request).first_answer().await
+}
+
+/// Sends a request down a stream that frames each message, such as a TCP or
+/// TLS connection, and reads back the response with the same transaction ID.
+async fn send_framed<S>(
+    stream: S,
+    handle: BufDnsStreamHandle,
+    addr: SocketAddr,
+    request: Message,
+    timeout: Duration,
+) -> Result<DnsResponse, NetError>
+where
+    S: futures::Stream<Item = Result<SerialMessage, NetError>> + Unpin,
+{
+    let mut responses = receive_framed(stream, handle, addr, request, timeout, |_| true).await?;
+    Ok(responses.remove(0))
+}
+
+/// Sends a request down a stream that frames each message, then reads back
+/// every response with the same transaction ID until the `is_finished`
+/// function says there are no more to come.
+async fn receive_framed<S, F>(
+    mut stream: S,
+    mut handle: BufDnsStreamHandle,
+    addr: SocketAddr,
+    request: Message,
+    timeout: Duration,
+    mut is_finished: F,
+) -> Result<Vec<DnsResponse>, NetError>
+where
+    S: futures::Stream<Item = Result<SerialMessage, NetError>> + Unpin,
+    F: FnMut(&DnsResponse) -> bool,
+{
+    let id = request.metadata.id;
+    handle.send(SerialMessage::new(request.to_vec()?, addr))?;
+
+    let mut responses = Vec::new();
+    loop {
+        let receive = async {
+            while let Some(message) = stream.next().await {
+                let response = DnsResponse::from_buffer(message?.into_parts().0)?;
+                if response.metadata.id == id {
+                    return Ok(response);
+                }
+
+                warn!(
+                    "Ignoring response with mismatched ID {}",
+                    response.metadata.id
+                );
+            }
+
+            Err(NetError::from(
+                "Connection closed before a response was received",
+            ))
+        };
+
+        // The timeout applies to each message, so long transfers don’t
+        // time out as long as the nameserver keeps sending data
+        let response = match tokio::time::timeout(timeout, receive).await {
+            Ok(result) => result?,
+            Err(_) => return Err(NetError::Timeout),
+        };
+
+        let finished = is_finished(&response);
+        responses.push(response);
+        if finished {
+            return Ok(responses);
+        }
+    }
+}
+
+/// Converts a TLS server name into the form needed to verify a certificate.
+pub fn server_name_for(name: &str) -> Result<ServerName<'static>, NetError> {
+    ServerName::try_from(name.to_string())
+        .map_err(|e| NetError::from(format!("Invalid TLS server name {name:?}: {e}")))
+}
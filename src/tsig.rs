@@ -0,0 +1,418 @@
+/*
+ * dog - A command-line DNS client
+ * Copyright (c) 2026 l1a and contributors
+ * Original code Copyright (c) Benjamin Sago
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Signing requests with a TSIG key, and verifying the signatures on the
+//! responses.
+//!
+//! TSIG (RFC 8945) authenticates a request and its response using a secret
+//! shared with the nameserver. The request gets a MAC computed over it,
+//! and the nameserver signs its response with a MAC that covers the one in
+//! the request, so each response can only be verified against the request
+//! it answers. Nameservers commonly require this for zone transfers.
+
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use hickory_resolver::proto::op::{DnsResponse, Message};
+use hickory_resolver::proto::rr::rdata::tsig::{TsigAlgorithm, TsigError};
+use hickory_resolver::proto::rr::{Name, TSigVerifier, TSigner};
+use hickory_resolver::proto::ProtoError;
+
+/// How many seconds the nameserver’s clock is allowed to differ from ours,
+/// which is the value recommended by the RFC.
+const FUDGE: u16 = 300;
+
+/// A TSIG key, made up of its name, which the nameserver uses to look it
+/// up, the HMAC algorithm it’s used with, and the shared secret.
+#[derive(Clone)]
+pub struct TsigKey(TSigner);
+
+impl TsigKey {
+    /// Parses a key given on the command-line in the form
+    /// `name:algorithm:secret`, with the secret encoded in base64.
+    pub fn from_argument(input: &str) -> Result<Self, TsigKeyError> {
+        let mut parts = input.split(':');
+        match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(name), Some(algorithm), Some(secret), None) => {
+                Self::from_parts(name, algorithm, secret)
+            }
+            _ => Err(TsigKeyError::Format),
+        }
+    }
+
+    /// Parses the first key statement in a BIND-style key file, such as
+    /// one generated by `tsig-keygen`:
+    ///
+    /// ```text
+    /// key "transfer-key" {
+    ///     algorithm hmac-sha256;
+    ///     secret "c2VjcmV0";
+    /// };
+    /// ```
+    pub fn from_key_file(contents: &str) -> Result<Self, TsigKeyError> {
+        let tokens = key_file_tokens(contents);
+        let start = tokens
+            .iter()
+            .position(|t| t == "key")
+            .ok_or(TsigKeyError::NoKeyStatement)?;
+
+        let name = tokens.get(start + 1).ok_or(TsigKeyError::NoKeyStatement)?;
+        let mut algorithm = None;
+        let mut secret = None;
+
+        let mut statement = tokens[start + 2..].iter();
+        while let Some(token) = statement.next() {
+            match token.as_str() {
+                "algorithm" => algorithm = statement.next(),
+                "secret" => secret = statement.next(),
+                "}" => break,
+                _ => {}
+            }
+        }
+
+        let algorithm = algorithm.ok_or(TsigKeyError::MissingField("algorithm"))?;
+        let secret = secret.ok_or(TsigKeyError::MissingField("secret"))?;
+        Self::from_parts(name, algorithm, secret)
+    }
+
+    /// Creates a key from its name, algorithm name, and base64 secret.
+    fn from_parts(name: &str, algorithm: &str, secret: &str) -> Result<Self, TsigKeyError> {
+        let mut name =
+            Name::from_ascii(name).map_err(|_| TsigKeyError::InvalidName(name.to_string()))?;
+        name.set_fqdn(true);
+
+        let algorithm = Name::from_ascii(algorithm.to_ascii_lowercase())
+            .map(TsigAlgorithm::from_name)
+            .ok()
+            .filter(TsigAlgorithm::supported)
+            .ok_or_else(|| TsigKeyError::UnsupportedAlgorithm(algorithm.to_string()))?;
+
+        let secret = data_encoding::BASE64
+            .decode(secret.as_bytes())
+            .map_err(|_| TsigKeyError::InvalidSecret)?;
+
+        TSigner::new(secret, algorithm.clone(), name, FUDGE)
+            .map(Self)
+            .map_err(|_| TsigKeyError::UnsupportedAlgorithm(algorithm.to_string()))
+    }
+
+    /// Signs a request message with this key, adding the TSIG record to
+    /// it, and returns the verifier to check the responses with.
+    pub fn sign(&self, request: &mut Message) -> Result<Verifier, ProtoError> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+
+        let verifier = request
+            .finalize(&self.0, now)?
+            .ok_or_else(|| ProtoError::from("TSIG signing produced no verifier"))?;
+        Ok(Verifier(verifier))
+    }
+}
+
+impl PartialEq for TsigKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.signer_name() == other.0.signer_name()
+            && self.0.algorithm() == other.0.algorithm()
+            && self.0.key() == other.0.key()
+    }
+}
+
+impl fmt::Debug for TsigKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The secret is left out, as the options get logged
+        f.debug_struct("TsigKey")
+            .field("name", self.0.signer_name())
+            .field("algorithm", self.0.algorithm())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Splits the contents of a key file into words, quoted strings, and the
+/// `{`, `}`, and `;` symbols, leaving out comments and the quotes.
+fn key_file_tokens(contents: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+
+    for line in contents.lines() {
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '#' => break,
+                '/' if chars.peek() == Some(&'/') => break,
+                '{' | '}' | ';' => tokens.push(c.to_string()),
+                '"' => tokens.push(chars.by_ref().take_while(|&c| c != '"').collect()),
+                c if c.is_whitespace() => {}
+                c => {
+                    let mut word = c.to_string();
+                    while let Some(&next) = chars.peek() {
+                        if next.is_whitespace() || "{};\"".contains(next) {
+                            break;
+                        }
+                        word.push(next);
+                        chars.next();
+                    }
+                    tokens.push(word);
+                }
+            }
+        }
+    }
+
+    tokens
+}
+
+/// Something wrong with a TSIG key given by the user.
+#[derive(PartialEq, Debug)]
+pub enum TsigKeyError {
+    /// The argument wasn’t in the `name:algorithm:secret` form.
+    Format,
+
+    /// The key file has no `key` statement.
+    NoKeyStatement,
+
+    /// The `key` statement in the key file is missing a field.
+    MissingField(&'static str),
+
+    /// The name of the key is not a valid domain name.
+    InvalidName(String),
+
+    /// The algorithm is unknown, or isn’t one that can be used to sign.
+    UnsupportedAlgorithm(String),
+
+    /// The secret isn’t valid base64.
+    InvalidSecret,
+}
+
+impl fmt::Display for TsigKeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Format => write!(f, "expected NAME:ALGORITHM:SECRET or a key file"),
+            Self::NoKeyStatement => write!(f, "no key statement in key file"),
+            Self::MissingField(field) => write!(f, "key statement has no {field}"),
+            Self::InvalidName(name) => write!(f, "invalid key name {name:?}"),
+            Self::UnsupportedAlgorithm(algorithm) => {
+                write!(f, "unsupported algorithm {algorithm:?}")
+            }
+            Self::InvalidSecret => write!(f, "secret is not valid base64"),
+        }
+    }
+}
+
+/// Checks the signatures on the responses to a signed request. Every
+/// message of a zone transfer must be passed in, in order, as each one’s
+/// MAC covers the one before it.
+pub struct Verifier(TSigVerifier);
+
+impl Verifier {
+    /// Verifies the TSIG record on a response, returning an error if it’s
+    /// missing, the nameserver rejected the request, or the MAC or time in
+    /// it is wrong.
+    pub fn verify(&mut self, response: &DnsResponse) -> Result<(), VerificationError> {
+        let signature = response.signature().ok_or(VerificationError::Unsigned)?;
+
+        if let Some(error) = signature.data.error {
+            return Err(VerificationError::Rejected(error));
+        }
+
+        self.0
+            .verify(response.as_buffer())
+            .map(drop)
+            .map_err(|e| VerificationError::Invalid(e.to_string()))
+    }
+}
+
+/// The reason the signature on a response couldn’t be verified.
+#[derive(PartialEq, Debug)]
+pub enum VerificationError {
+    /// The response didn’t have a TSIG record.
+    Unsigned,
+
+    /// The nameserver couldn’t verify the request, and sent back an error.
+    Rejected(TsigError),
+
+    /// The MAC or the time in the TSIG record was wrong.
+    Invalid(String),
+}
+
+impl fmt::Display for VerificationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "TSIG verification failed: ")?;
+        match self {
+            Self::Unsigned => write!(f, "the response was not signed"),
+            Self::Rejected(error) => {
+                write!(f, "the nameserver rejected the request with ")?;
+                match error {
+                    TsigError::BadSig => write!(f, "BADSIG"),
+                    TsigError::BadKey => write!(f, "BADKEY"),
+                    TsigError::BadTime => write!(f, "BADTIME"),
+                    TsigError::BadTrunc => write!(f, "BADTRUNC"),
+                    TsigError::Unknown(code) => write!(f, "error {code}"),
+                }
+            }
+            Self::Invalid(reason) => write!(f, "{reason}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    use hickory_resolver::proto::op::Query;
+    use hickory_resolver::proto::rr::rdata::A;
+    use hickory_resolver::proto::rr::{RData, Record, RecordType, TSigResponseContext};
+
+    const SECRET: &str = "c2VjcmV0LWtleS1mb3ItdGVzdGluZy1kb2ctdHNpZw==";
+
+    fn key() -> TsigKey {
+        TsigKey::from_argument(&format!("transfer-key:hmac-sha256:{SECRET}")).unwrap()
+    }
+
+    /// Signs a response to the request the way the nameserver would, with
+    /// the same key.
+    fn signed_response(request: &Message, key: &TsigKey) -> DnsResponse {
+        let mut response = Message::response(request.metadata.id, request.metadata.op_code);
+        response.add_query(request.queries[0].clone());
+        response.add_answer(Record::from_rdata(
+            request.queries[0].name().clone(),
+            300,
+            RData::A(A::new(192, 0, 2, 1)),
+        ));
+
+        let signer = key.0.clone();
+        let request_mac = request.signature().unwrap().data.mac.clone();
+        let time = request.signature().unwrap().data.time;
+        let context =
+            TSigResponseContext::new(response.metadata.id, time, signer, request_mac, None);
+        let signature = context.sign(&response.to_vec().unwrap()).unwrap();
+        response.set_signature(signature);
+
+        DnsResponse::from_buffer(response.to_vec().unwrap()).unwrap()
+    }
+
+    fn request() -> Message {
+        let name = Name::from_ascii("lookup.dog.").unwrap();
+        let mut message = Message::query();
+        message.add_query(Query::query(name, RecordType::A));
+        message
+    }
+
+    #[test]
+    fn argument() {
+        let key = key();
+        assert_eq!(
+            key.0.signer_name(),
+            &Name::from_ascii("transfer-key.").unwrap()
+        );
+        assert_eq!(key.0.algorithm(), &TsigAlgorithm::HmacSha256);
+        assert_eq!(key.0.key(), b"secret-key-for-testing-dog-tsig");
+    }
+
+    #[test]
+    fn argument_wrong_format() {
+        assert_eq!(
+            TsigKey::from_argument("transfer-key:c2VjcmV0"),
+            Err(TsigKeyError::Format)
+        );
+    }
+
+    #[test]
+    fn argument_unsupported_algorithm() {
+        assert_eq!(
+            TsigKey::from_argument("transfer-key:hmac-md5:c2VjcmV0"),
+            Err(TsigKeyError::UnsupportedAlgorithm(String::from("hmac-md5")))
+        );
+    }
+
+    #[test]
+    fn argument_invalid_secret() {
+        assert_eq!(
+            TsigKey::from_argument("transfer-key:hmac-sha256:not base64!"),
+            Err(TsigKeyError::InvalidSecret)
+        );
+    }
+
+    #[test]
+    fn key_file() {
+        let contents = format!(
+            "# generated by tsig-keygen\n\
+             key \"transfer-key\" {{\n\
+             \talgorithm hmac-sha256;\n\
+             \tsecret \"{SECRET}\";\n\
+             }};\n"
+        );
+        assert_eq!(TsigKey::from_key_file(&contents), Ok(key()));
+    }
+
+    #[test]
+    fn key_file_missing_secret() {
+        assert_eq!(
+            TsigKey::from_key_file("key transfer-key { algorithm hmac-sha256; };"),
+            Err(TsigKeyError::MissingField("secret"))
+        );
+    }
+
+    #[test]
+    fn key_file_empty() {
+        assert_eq!(
+            TsigKey::from_key_file("// nothing here"),
+            Err(TsigKeyError::NoKeyStatement)
+        );
+    }
+
+    #[test]
+    fn secret_not_logged() {
+        assert!(!format!("{:?}", key()).contains("secret"));
+    }
+
+    #[test]
+    fn signed_response_verifies() {
+        let key = key();
+        let mut request = request();
+        let mut verifier = key.sign(&mut request).unwrap();
+        assert!(request.signature().is_some());
+
+        let response = signed_response(&request, &key);
+        assert_eq!(verifier.verify(&response), Ok(()));
+    }
+
+    #[test]
+    fn wrong_key_fails() {
+        let mut request = request();
+        let mut verifier = key().sign(&mut request).unwrap();
+
+        let other = TsigKey::from_argument("transfer-key:hmac-sha256:b3RoZXI=").unwrap();
+        let response = signed_response(&request, &other);
+        assert!(matches!(
+            verifier.verify(&response),
+            Err(VerificationError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn unsigned_response_fails() {
+        let mut request = request();
+        let mut verifier = key().sign(&mut request).unwrap();
+
+        let mut response = Message::response(request.metadata.id, request.metadata.op_code);
+        response.add_query(request.queries[0].clone());
+        let response = DnsResponse::from_message(response).unwrap();
+        assert_eq!(verifier.verify(&response), Err(VerificationError::Unsigned));
+    }
+}
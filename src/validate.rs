@@ -0,0 +1,1732 @@
+/*
+ * dog - A command-line DNS client
+ * Copyright (c) 2026 l1a and contributors
+ * Original code Copyright (c) Benjamin Sago
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Validating the DNSSEC signatures on a response.
+//!
+//! Each set of records in a response is signed by the zone it came from,
+//! using one of the keys in the zone’s DNSKEY records. Those keys are in
+//! turn vouched for by a DS record in the parent zone, which is signed by
+//! the parent’s keys, and so on up to a trust anchor. The validator fetches
+//! every DNSKEY and DS record along the way, and records each link of this
+//! chain as secure, insecure, bogus, or indeterminate, so the user can see
+//! exactly where the chain breaks.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use data_encoding::{BASE32HEX_NOPAD, BASE64};
+use hickory_resolver::net::NetError;
+use hickory_resolver::proto::dnssec::rdata::{DNSSECRData, DNSKEY, DS, NSEC3, RRSIG};
+use hickory_resolver::proto::dnssec::{Algorithm, DigestType, PublicKeyBuf, Verifier};
+use hickory_resolver::proto::op::{Edns, Message, Query, DEFAULT_MAX_PAYLOAD_LEN};
+use hickory_resolver::proto::rr::{Name, RData, Record, RecordType};
+use log::*;
+
+use crate::transport::Transport;
+
+/// The trust anchor built into dog: the DS records of the root zone’s key
+/// signing keys, as published by IANA.
+const BUILTIN_TRUST_ANCHOR: &str = "
+.  IN  DS  20326 8 2 E06D44B80B8F1D39A95C0B0D7C65D08458E880409BBC683457104237C7F8EC8D
+.  IN  DS  38696 8 2 683D2D0ACB8C9B712A1948B27F741219298D0A450D612C483AF444A4C0FB2B16
+";
+
+/// The most zones to follow up the chain of trust before giving up, in
+/// case signatures point at each other in a loop.
+const MAX_DEPTH: usize = 32;
+
+/// The keys that are trusted without needing to be vouched for by another
+/// zone, which is where every chain of trust has to end.
+#[derive(PartialEq, Debug, Clone)]
+pub struct TrustAnchor {
+    /// The zone the keys belong to.
+    pub zone: Name,
+
+    /// The DS records of the zone’s trusted keys.
+    pub ds: Vec<DS>,
+
+    /// The zone’s trusted keys, for anchors given as DNSKEY records.
+    pub keys: Vec<DNSKEY>,
+}
+
+impl TrustAnchor {
+    /// Returns the trust anchor built into dog, for the root zone.
+    pub fn builtin() -> Self {
+        Self::parse(BUILTIN_TRUST_ANCHOR).expect("Built-in trust anchor is invalid")
+    }
+
+    /// Parses a trust anchor file: a list of DS or DNSKEY records, in zone
+    /// file format with one record per line, that all belong to the same
+    /// zone. Comments start with a `;`.
+    pub fn parse(contents: &str) -> Result<Self, TrustAnchorError> {
+        let mut zone = None;
+        let mut ds = Vec::new();
+        let mut keys = Vec::new();
+
+        for (index, line) in contents.lines().enumerate() {
+            let line = line.split(';').next().unwrap_or_default();
+            let fields = line.split_whitespace().collect::<Vec<_>>();
+            if fields.is_empty() {
+                continue;
+            }
+
+            let invalid = || TrustAnchorError::InvalidLine(index + 1);
+            let type_index = fields
+                .iter()
+                .position(|f| f.eq_ignore_ascii_case("DS") || f.eq_ignore_ascii_case("DNSKEY"))
+                .filter(|&i| i > 0)
+                .ok_or_else(invalid)?;
+
+            let mut owner = Name::from_ascii(fields[0]).map_err(|_| invalid())?;
+            owner.set_fqdn(true);
+            match &zone {
+                Some(zone) if *zone != owner => {
+                    return Err(TrustAnchorError::DifferentZones(index + 1));
+                }
+                Some(_) => {}
+                None => zone = Some(owner),
+            }
+
+            let data = &fields[type_index + 1..];
+            if fields[type_index].eq_ignore_ascii_case("DS") {
+                match RData::try_from_str(RecordType::DS, &data.join(" ")) {
+                    Ok(RData::DNSSEC(DNSSECRData::DS(record))) => ds.push(record),
+                    _ => return Err(invalid()),
+                }
+            } else {
+                keys.push(parse_dnskey(data).ok_or_else(invalid)?);
+            }
+        }
+
+        match zone {
+            Some(zone) => Ok(Self { zone, ds, keys }),
+            None => Err(TrustAnchorError::NoRecords),
+        }
+    }
+}
+
+/// Parses the data of a DNSKEY record: its flags, protocol, algorithm, and
+/// the base64-encoded public key, which may be split into several fields.
+fn parse_dnskey(fields: &[&str]) -> Option<DNSKEY> {
+    let [flags, protocol, algorithm, key @ ..] = fields else {
+        return None;
+    };
+
+    if *protocol != "3" || key.is_empty() {
+        return None;
+    }
+
+    let algorithm = Algorithm::from_u8(algorithm.parse().ok()?);
+    let key = BASE64.decode(key.concat().as_bytes()).ok()?;
+    Some(DNSKEY::with_flags(
+        flags.parse().ok()?,
+        PublicKeyBuf::new(key, algorithm),
+    ))
+}
+
+/// Something wrong with a trust anchor file.
+#[derive(PartialEq, Debug)]
+pub enum TrustAnchorError {
+    /// A line isn’t a DS or DNSKEY record.
+    InvalidLine(usize),
+
+    /// A line is for a different zone than the lines before it.
+    DifferentZones(usize),
+
+    /// There are no records in the file.
+    NoRecords,
+}
+
+impl fmt::Display for TrustAnchorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidLine(number) => write!(f, "line {number} is not a DS or DNSKEY record"),
+            Self::DifferentZones(number) => {
+                write!(
+                    f,
+                    "line {number} is for a different zone than the lines before it"
+                )
+            }
+            Self::NoRecords => write!(f, "no DS or DNSKEY records"),
+        }
+    }
+}
+
+/// How far a set of records can be trusted, as defined in RFC 4033. These
+/// are ordered from best to worst, so the state of a whole chain is the
+/// worst state of any of its links.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Copy, Clone)]
+pub enum Security {
+    /// The records are signed, and the chain of trust from a trust anchor
+    /// is intact.
+    Secure,
+
+    /// The records are in a zone that is proven not to be signed, or is
+    /// signed using algorithms that aren’t supported.
+    Insecure,
+
+    /// The chain of trust couldn’t be followed, because a response didn’t
+    /// come back, or the records aren’t under the trust anchor.
+    Indeterminate,
+
+    /// The records should be signed, but the signatures are missing, have
+    /// expired, or don’t match.
+    Bogus,
+}
+
+impl Security {
+    /// The name of this state, for displaying to the user.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Secure => "secure",
+            Self::Insecure => "insecure",
+            Self::Indeterminate => "indeterminate",
+            Self::Bogus => "bogus",
+        }
+    }
+}
+
+impl fmt::Display for Security {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The reason a link in the chain of trust isn’t secure.
+#[derive(PartialEq, Debug, Clone)]
+pub enum Reason {
+    /// The records have no signatures.
+    MissingSignature,
+
+    /// Every signature on the records has expired.
+    SignatureExpired,
+
+    /// The signatures on the records aren’t valid yet.
+    SignatureNotYetValid,
+
+    /// The records are signed by a zone that they aren’t in.
+    WrongSigner(Name),
+
+    /// None of the zone’s keys produce the signature on the records.
+    InvalidSignature,
+
+    /// The zone has no DNSKEY records.
+    MissingKeys,
+
+    /// None of the zone’s keys match its DS records or the trust anchor.
+    NoMatchingKey,
+
+    /// The parent zone proves that there is no DS record for the zone, so
+    /// the zone is not signed.
+    NoDs,
+
+    /// There is no DS record for the zone, and no signed proof that there
+    /// shouldn’t be one.
+    MissingDs,
+
+    /// The records are signed using an algorithm that isn’t supported.
+    UnsupportedAlgorithm(Algorithm),
+
+    /// The DS records use a digest type that isn’t supported.
+    UnsupportedDigest(DigestType),
+
+    /// No response came back when fetching the records.
+    NoResponse(String),
+
+    /// The zone isn’t under the trust anchor.
+    OutsideTrustAnchor(Name),
+
+    /// The zone the records belong to couldn’t be found.
+    UnknownZone,
+
+    /// The records were expanded from a wildcard, and there is no signed
+    /// proof that the name itself doesn’t exist.
+    UnprovenWildcard,
+}
+
+impl Reason {
+    /// How far records with this problem can be trusted.
+    pub fn security(&self) -> Security {
+        match self {
+            Self::NoDs | Self::UnsupportedAlgorithm(_) | Self::UnsupportedDigest(_) => {
+                Security::Insecure
+            }
+            Self::NoResponse(_) | Self::OutsideTrustAnchor(_) | Self::UnknownZone => {
+                Security::Indeterminate
+            }
+            _ => Security::Bogus,
+        }
+    }
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingSignature => write!(f, "missing signature"),
+            Self::SignatureExpired => write!(f, "expired signature"),
+            Self::SignatureNotYetValid => write!(f, "signature not yet valid"),
+            Self::WrongSigner(signer) => write!(f, "signed by {signer}, which isn’t their zone"),
+            Self::InvalidSignature => write!(f, "invalid signature"),
+            Self::MissingKeys => write!(f, "missing DNSKEY"),
+            Self::NoMatchingKey => write!(f, "no DNSKEY matches the DS records"),
+            Self::NoDs => write!(f, "no DS, so the zone is unsigned"),
+            Self::MissingDs => write!(f, "missing DS, with no proof that it doesn’t exist"),
+            Self::UnsupportedAlgorithm(algorithm) => {
+                write!(f, "algorithm {algorithm} unsupported")
+            }
+            Self::UnsupportedDigest(digest_type) => {
+                write!(f, "digest type {} unsupported", u8::from(*digest_type))
+            }
+            Self::NoResponse(why) => write!(f, "no response: {why}"),
+            Self::OutsideTrustAnchor(zone) => write!(f, "not under the trust anchor for {zone}"),
+            Self::UnknownZone => write!(f, "could not find the zone"),
+            Self::UnprovenWildcard => {
+                write!(
+                    f,
+                    "expanded from a wildcard, with no proof the name doesn’t exist"
+                )
+            }
+        }
+    }
+}
+
+/// One set of records in the chain of trust, and how far it can be
+/// trusted.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Link {
+    /// The owner of the records.
+    pub name: Name,
+
+    /// The type of the records.
+    pub record_type: RecordType,
+
+    /// How far the records can be trusted.
+    pub security: Security,
+
+    /// Why the records aren’t secure, unless it’s because of a link
+    /// further up the chain.
+    pub reason: Option<Reason>,
+}
+
+impl Link {
+    /// Creates a link from the result of checking a set of records.
+    fn new(name: &Name, record_type: RecordType, result: Result<(), Reason>) -> Self {
+        let (security, reason) = match result {
+            Ok(()) => (Security::Secure, None),
+            Err(reason) => (reason.security(), Some(reason)),
+        };
+
+        Self {
+            name: name.clone(),
+            record_type,
+            security,
+            reason,
+        }
+    }
+}
+
+/// The result of validating one response: every link of the chains of
+/// trust, from the trust anchor down to the records in the response.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Validation {
+    /// The links, with those nearest the trust anchor first.
+    pub links: Vec<Link>,
+}
+
+impl Validation {
+    /// How far the response can be trusted, which is the worst of any of
+    /// its links. A response with nothing to validate is indeterminate.
+    pub fn security(&self) -> Security {
+        self.links
+            .iter()
+            .map(|link| link.security)
+            .max()
+            .unwrap_or(Security::Indeterminate)
+    }
+
+    /// The link responsible for the state of the response, if it isn’t
+    /// secure.
+    pub fn cause(&self) -> Option<&Link> {
+        let security = self.security();
+        self.links
+            .iter()
+            .find(|link| link.security == security && link.reason.is_some())
+    }
+}
+
+/// The way the validator fetches the DNSKEY and DS records it needs. This
+/// is a trait so the validation can be tested against stand-in responses.
+pub trait Fetch {
+    /// Queries for the records of the given name and type, returning the
+    /// response with its signatures, or the error that occurred.
+    fn fetch(
+        &self,
+        name: &Name,
+        qtype: RecordType,
+    ) -> impl Future<Output = Result<Message, NetError>>;
+}
+
+/// Fetches records from the same nameservers the query was sent to,
+/// re-sending truncated UDP responses over TCP if `retry_truncated` is set.
+pub struct Network {
+    /// The nameservers to try, in order.
+    pub transports: Vec<Transport>,
+
+    /// Whether to re-send truncated UDP responses over TCP.
+    pub retry_truncated: bool,
+}
+
+impl Fetch for Network {
+    async fn fetch(&self, name: &Name, qtype: RecordType) -> Result<Message, NetError> {
+        // Checking is disabled so that the nameserver passes along records
+        // that fail its own validation, letting dog say why they failed
+        let mut request = Message::query();
+        request.metadata.recursion_desired = true;
+        request.metadata.checking_disabled = true;
+        request.add_query(Query::query(name.clone(), qtype));
+
+        let mut edns = Edns::new();
+        edns.set_max_payload(DEFAULT_MAX_PAYLOAD_LEN);
+        edns.set_dnssec_ok(true);
+        request.set_edns(edns);
+
+        let mut last_error = NetError::NoConnections;
+        for transport in &self.transports {
+            let mut result = transport.send(request.clone()).await;
+            if self.retry_truncated
+                && transport.is_udp()
+                && matches!(&result, Ok(r) if r.metadata.truncation)
+            {
+                debug!("Response was truncated, re-sending over TCP");
+                result = transport.with_tcp().send(request.clone()).await;
+            }
+
+            match result {
+                Ok(response) => return Ok(response.into_message()),
+                Err(e) => last_error = e,
+            }
+        }
+
+        Err(last_error)
+    }
+}
+
+/// The links from the trust anchor down to one zone, and the zone’s keys
+/// if the chain is secure, or how far it can be trusted if it isn’t.
+#[derive(Debug, Clone)]
+struct Chain {
+    links: Vec<Link>,
+    keys: Result<Vec<DNSKEY>, Security>,
+}
+
+impl Chain {
+    /// A chain that ends with one link that isn’t secure.
+    fn broken(link: Link) -> Self {
+        let security = link.security;
+        Self {
+            links: vec![link],
+            keys: Err(security),
+        }
+    }
+}
+
+/// A future that resolves to the chain of trust of a zone.
+type ChainFuture<'a> = Pin<Box<dyn Future<Output = Chain> + 'a>>;
+
+/// Follows the chain of trust for the records in responses. The chains of
+/// the zones it has seen are kept, so validating several responses from
+/// the same zone only fetches its keys once.
+pub struct Validator<'a, F> {
+    /// The keys to trust.
+    anchor: &'a TrustAnchor,
+
+    /// How to fetch the DNSKEY and DS records.
+    fetcher: F,
+
+    /// The current time, as a UNIX timestamp, to check the signatures’
+    /// validity periods against.
+    now: u32,
+
+    /// The chains of the zones seen so far.
+    zones: RefCell<HashMap<Name, Chain>>,
+}
+
+impl<'a, F: Fetch> Validator<'a, F> {
+    /// Creates a validator that trusts the given anchor.
+    pub fn new(anchor: &'a TrustAnchor, fetcher: F) -> Self {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| u32::try_from(d.as_secs()).unwrap_or(u32::MAX));
+
+        Self {
+            anchor,
+            fetcher,
+            now,
+            zones: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Validates every set of records in the answer section of a response,
+    /// or in the authority section if the answer section is empty, so that
+    /// negative responses have their SOA and NSEC records checked.
+    pub async fn validate(&self, response: &Message) -> Validation {
+        let section = if response.answers.is_empty() {
+            &response.authorities
+        } else {
+            &response.answers
+        };
+
+        let mut links = Vec::new();
+        for (name, record_type) in rrset_keys(section) {
+            let records = rrset(section, &name, record_type);
+            let signatures = signatures(section, &name, record_type);
+
+            // A zone can only sign records at or below its own name, so a
+            // signature from anywhere else can’t vouch for them
+            let signer = signatures
+                .iter()
+                .map(|signature| &signature.input().signer_name)
+                .find(|signer| signer.zone_of(&name));
+
+            let zone = match (signer, signatures.first()) {
+                (Some(signer), _) => Some(signer.clone()),
+                (None, Some(signature)) => {
+                    let reason = Reason::WrongSigner(signature.input().signer_name.clone());
+                    links.push(Link::new(&name, record_type, Err(reason)));
+                    continue;
+                }
+                (None, None) => self.enclosing_zone(&name).await,
+            };
+
+            let Some(zone) = zone else {
+                links.push(Link::new(&name, record_type, Err(Reason::UnknownZone)));
+                continue;
+            };
+
+            let chain = self.zone_chain(zone.clone(), 0).await;
+            for link in chain.links {
+                if !links.contains(&link) {
+                    links.push(link);
+                }
+            }
+
+            links.push(match &chain.keys {
+                Ok(keys) => Link::new(
+                    &name,
+                    record_type,
+                    self.verify_rrset(&zone, &records, &signatures, keys)
+                        .and_then(|signature| {
+                            self.verify_expansion(&zone, &name, signature, response, keys)
+                        }),
+                ),
+                Err(security) => Link {
+                    name,
+                    record_type,
+                    security: *security,
+                    reason: None,
+                },
+            });
+        }
+
+        Validation { links }
+    }
+
+    /// Finds the zone that a name belongs to, by asking for its SOA record
+    /// and seeing which zone answers.
+    async fn enclosing_zone(&self, name: &Name) -> Option<Name> {
+        match self.fetcher.fetch(name, RecordType::SOA).await {
+            Ok(response) => zone_of_response(&response, name),
+            Err(e) => {
+                debug!("Failed to find the zone of {name}: {e}");
+                None
+            }
+        }
+    }
+
+    /// Returns the chain of trust from the trust anchor down to a zone,
+    /// following it upwards through each zone’s DS records.
+    fn zone_chain(&self, zone: Name, depth: usize) -> ChainFuture<'_> {
+        Box::pin(async move {
+            if let Some(chain) = self.zones.borrow().get(&zone) {
+                return chain.clone();
+            }
+
+            let chain = if zone == self.anchor.zone {
+                self.anchor_chain().await
+            } else if !self.anchor.zone.zone_of(&zone) || depth >= MAX_DEPTH {
+                let reason = Reason::OutsideTrustAnchor(self.anchor.zone.clone());
+                Chain::broken(Link::new(&zone, RecordType::DNSKEY, Err(reason)))
+            } else {
+                self.delegated_chain(&zone, depth).await
+            };
+
+            self.zones.borrow_mut().insert(zone, chain.clone());
+            chain
+        })
+    }
+
+    /// Returns the chain of trust for the trust anchor’s zone, which has
+    /// only the one link: its keys, vouched for by the anchor.
+    async fn anchor_chain(&self) -> Chain {
+        let zone = &self.anchor.zone;
+        let result = self
+            .fetch_keys(zone, &self.anchor.ds, &self.anchor.keys)
+            .await;
+        let link = Link::new(
+            zone,
+            RecordType::DNSKEY,
+            result.as_ref().map(|_| ()).map_err(Clone::clone),
+        );
+
+        Chain {
+            links: vec![link],
+            keys: result.map_err(|reason| reason.security()),
+        }
+    }
+
+    /// Returns the chain of trust for a zone below the trust anchor: the
+    /// chain of its parent, then its DS records in the parent zone, then
+    /// its own keys.
+    async fn delegated_chain(&self, zone: &Name, depth: usize) -> Chain {
+        let response = match self.fetcher.fetch(zone, RecordType::DS).await {
+            Ok(response) => response,
+            Err(e) => {
+                let reason = Reason::NoResponse(e.to_string());
+                return Chain::broken(Link::new(zone, RecordType::DS, Err(reason)));
+            }
+        };
+
+        // The DS records live in the parent zone, so the parent is whichever
+        // zone above this one signed the response
+        let parent =
+            zone_of_response(&response, &zone.base_name()).unwrap_or_else(|| zone.base_name());
+
+        let mut chain = self.zone_chain(parent.clone(), depth + 1).await;
+        let parent_keys = match &chain.keys {
+            Ok(keys) => keys.clone(),
+            Err(_) => return chain,
+        };
+
+        let ds_records = rrset(&response.answers, zone, RecordType::DS);
+        if ds_records.is_empty() {
+            let result = self.verify_denial(zone, &parent, &response, &parent_keys);
+            let link = Link::new(
+                zone,
+                RecordType::DS,
+                Err(result.err().unwrap_or(Reason::NoDs)),
+            );
+            chain.keys = Err(link.security);
+            chain.links.push(link);
+            return chain;
+        }
+
+        let signatures = signatures(&response.answers, zone, RecordType::DS);
+        if let Err(reason) = self.verify_rrset(&parent, &ds_records, &signatures, &parent_keys) {
+            chain.keys = Err(reason.security());
+            chain
+                .links
+                .push(Link::new(zone, RecordType::DS, Err(reason)));
+            return chain;
+        }
+
+        chain.links.push(Link::new(zone, RecordType::DS, Ok(())));
+
+        let ds = ds_records
+            .iter()
+            .filter_map(|record| match &record.data {
+                RData::DNSSEC(DNSSECRData::DS(ds)) => Some(ds.clone()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        let result = self.fetch_keys(zone, &ds, &[]).await;
+        chain.links.push(Link::new(
+            zone,
+            RecordType::DNSKEY,
+            result.as_ref().map(|_| ()).map_err(Clone::clone),
+        ));
+        chain.keys = result.map_err(|reason| reason.security());
+        chain
+    }
+
+    /// Fetches the keys of a zone, and checks that they are signed by one of
+    /// the keys matching its DS records or the trust anchor’s keys.
+    async fn fetch_keys(
+        &self,
+        zone: &Name,
+        ds: &[DS],
+        trusted: &[DNSKEY],
+    ) -> Result<Vec<DNSKEY>, Reason> {
+        // DS records that can’t be checked are treated as if they weren’t
+        // there, which makes the zone insecure if there are none left
+        let usable = ds
+            .iter()
+            .filter(|d| d.algorithm().is_supported() && d.digest_type().is_supported())
+            .collect::<Vec<_>>();
+
+        if usable.is_empty() && trusted.is_empty() {
+            return Err(match ds.first() {
+                Some(d) if !d.algorithm().is_supported() => {
+                    Reason::UnsupportedAlgorithm(d.algorithm())
+                }
+                Some(d) => Reason::UnsupportedDigest(d.digest_type()),
+                None => Reason::NoMatchingKey,
+            });
+        }
+
+        let response = self
+            .fetcher
+            .fetch(zone, RecordType::DNSKEY)
+            .await
+            .map_err(|e| Reason::NoResponse(e.to_string()))?;
+
+        let records = rrset(&response.answers, zone, RecordType::DNSKEY);
+        let keys = records
+            .iter()
+            .filter_map(|record| match &record.data {
+                RData::DNSSEC(DNSSECRData::DNSKEY(key)) if key.zone_key() && !key.revoke() => {
+                    Some(key.clone())
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        if keys.is_empty() {
+            return Err(Reason::MissingKeys);
+        }
+
+        let entry_points = keys
+            .iter()
+            .filter(|key| {
+                let tag = key.calculate_key_tag().ok();
+                usable
+                    .iter()
+                    .any(|d| Some(d.key_tag()) == tag && d.covers(zone, key).unwrap_or(false))
+                    || trusted.iter().any(|t| t.public_key() == key.public_key())
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+
+        if entry_points.is_empty() {
+            return Err(Reason::NoMatchingKey);
+        }
+
+        let signatures = signatures(&response.answers, zone, RecordType::DNSKEY);
+        self.verify_rrset(zone, &records, &signatures, &entry_points)?;
+        Ok(keys)
+    }
+
+    /// Checks that the NSEC or NSEC3 records in a response with no DS
+    /// records are signed by the parent zone, and that they prove that the
+    /// zone has no DS record.
+    fn verify_denial(
+        &self,
+        zone: &Name,
+        parent: &Name,
+        response: &Message,
+        keys: &[DNSKEY],
+    ) -> Result<(), Reason> {
+        if self.verify_nsec(parent, &response.authorities, keys)?
+            && denies_ds(&response.authorities, zone, parent)
+        {
+            Ok(())
+        } else {
+            Err(Reason::MissingDs)
+        }
+    }
+
+    /// Checks that a set of records whose signature says it was expanded
+    /// from a wildcard comes with NSEC or NSEC3 records, signed by the
+    /// zone, proving that there is no closer match for its name, as
+    /// RFC 4035 section 5.3.4 requires. Without them, the records could
+    /// have been expanded for a name that has records of its own.
+    fn verify_expansion(
+        &self,
+        zone: &Name,
+        name: &Name,
+        signature: &RRSIG,
+        response: &Message,
+        keys: &[DNSKEY],
+    ) -> Result<(), Reason> {
+        let labels = signature.input().num_labels;
+        if labels >= name.num_labels() {
+            return Ok(());
+        }
+
+        // The wildcard stands in for the name one label below it, so that
+        // is the name that has to be proven not to exist
+        let next_closer = name.trim_to(usize::from(labels) + 1);
+        if self.verify_nsec(zone, &response.authorities, keys)?
+            && denies_name(&response.authorities, name, &next_closer, zone)
+        {
+            Ok(())
+        } else {
+            Err(Reason::UnprovenWildcard)
+        }
+    }
+
+    /// Checks that every set of NSEC and NSEC3 records in a section is
+    /// signed by the given zone, returning whether there were any.
+    fn verify_nsec(
+        &self,
+        zone: &Name,
+        section: &[Record],
+        keys: &[DNSKEY],
+    ) -> Result<bool, Reason> {
+        let denials = rrset_keys(section)
+            .into_iter()
+            .filter(|(_, t)| *t == RecordType::NSEC || *t == RecordType::NSEC3)
+            .collect::<Vec<_>>();
+
+        for (name, record_type) in &denials {
+            let records = rrset(section, name, *record_type);
+            let signatures = signatures(section, name, *record_type);
+            self.verify_rrset(zone, &records, &signatures, keys)?;
+        }
+
+        Ok(!denials.is_empty())
+    }
+
+    /// Checks that one of the signatures on a set of records is valid at
+    /// the current time and was made by one of the given keys, which belong
+    /// to the given zone, returning the signature.
+    fn verify_rrset<'s>(
+        &self,
+        zone: &Name,
+        records: &[Record],
+        signatures: &'s [RRSIG],
+        keys: &[DNSKEY],
+    ) -> Result<&'s RRSIG, Reason> {
+        let Some(first) = records.first() else {
+            return Err(Reason::MissingSignature);
+        };
+
+        let mut failure = Reason::MissingSignature;
+        for signature in signatures {
+            let input = signature.input();
+            if input.signer_name != *zone || !zone.zone_of(&first.name) {
+                failure = Reason::WrongSigner(input.signer_name.clone());
+                continue;
+            }
+
+            if !input.algorithm.is_supported() {
+                failure = Reason::UnsupportedAlgorithm(input.algorithm);
+                continue;
+            }
+
+            if self.now > input.sig_expiration.get() {
+                failure = Reason::SignatureExpired;
+                continue;
+            }
+
+            if self.now < input.sig_inception.get() {
+                failure = Reason::SignatureNotYetValid;
+                continue;
+            }
+
+            failure = Reason::InvalidSignature;
+            let matching_keys = keys.iter().filter(|key| {
+                key.algorithm() == input.algorithm
+                    && key.calculate_key_tag().ok() == Some(input.key_tag)
+            });
+
+            for key in matching_keys {
+                if key
+                    .verify_rrsig(&first.name, first.dns_class, signature, records.iter())
+                    .is_ok()
+                {
+                    return Ok(signature);
+                }
+            }
+        }
+
+        Err(failure)
+    }
+}
+
+/// Returns the name and type of each set of records in a section, in the
+/// order they first appear, leaving out the signatures themselves.
+fn rrset_keys(section: &[Record]) -> Vec<(Name, RecordType)> {
+    let mut keys = Vec::new();
+    for record in section {
+        let key = (record.name.clone(), record.record_type());
+        if key.1 != RecordType::RRSIG && !keys.contains(&key) {
+            keys.push(key);
+        }
+    }
+
+    keys
+}
+
+/// Returns the records in a section with the given name and type.
+fn rrset(section: &[Record], name: &Name, record_type: RecordType) -> Vec<Record> {
+    section
+        .iter()
+        .filter(|record| record.name == *name && record.record_type() == record_type)
+        .cloned()
+        .collect()
+}
+
+/// Returns the signatures in a section that cover the records with the
+/// given name and type.
+fn signatures(section: &[Record], name: &Name, record_type: RecordType) -> Vec<RRSIG> {
+    section
+        .iter()
+        .filter(|record| record.name == *name)
+        .filter_map(|record| match &record.data {
+            RData::DNSSEC(DNSSECRData::RRSIG(rrsig))
+                if rrsig.input().type_covered == record_type =>
+            {
+                Some(rrsig.clone())
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Works out which zone a response about the given name came from: the
+/// closest zone above the name that signed records in it, or the owner of
+/// the SOA record if they aren’t signed. Signatures from zones that the
+/// name isn’t in, or on records outside the signer’s zone, can’t say
+/// anything about the name, so they’re ignored.
+fn zone_of_response(response: &Message, name: &Name) -> Option<Name> {
+    let records = response.answers.iter().chain(&response.authorities);
+    let mut signer: Option<Name> = None;
+    let mut soa_owner = None;
+
+    for record in records {
+        match &record.data {
+            RData::DNSSEC(DNSSECRData::RRSIG(rrsig)) => {
+                let signer_name = &rrsig.input().signer_name;
+                let closer = signer
+                    .as_ref()
+                    .is_none_or(|s| s.num_labels() < signer_name.num_labels());
+                if closer && signer_name.zone_of(&record.name) && signer_name.zone_of(name) {
+                    signer = Some(signer_name.clone());
+                }
+            }
+            RData::SOA(_) if soa_owner.is_none() && record.name.zone_of(name) => {
+                soa_owner = Some(record.name.clone());
+            }
+            _ => {}
+        }
+    }
+
+    signer.or(soa_owner)
+}
+
+/// Whether the NSEC or NSEC3 records in a section prove that a delegated
+/// zone has no DS record. Either a record for the zone itself says that
+/// there is a delegation there but no DS record, or NSEC3 records make up
+/// the closest encloser proof from RFC 5155 section 8.6: one matches the
+/// closest ancestor of the zone that exists, and an opt-out one covers the
+/// name one label below it, which says that any delegation there is
+/// unsigned.
+fn denies_ds(section: &[Record], zone: &Name, parent: &Name) -> bool {
+    let nsec = section.iter().any(|record| match &record.data {
+        RData::DNSSEC(DNSSECRData::NSEC(nsec)) => {
+            record.name == *zone && unsigned_delegation(nsec.type_bit_maps())
+        }
+        _ => false,
+    });
+    if nsec {
+        return true;
+    }
+
+    let nsec3s = nsec3_records(section, parent);
+    let matching = |name: &Name| {
+        nsec3s
+            .iter()
+            .find(|(owner, nsec3)| nsec3_hash(nsec3, name).as_ref() == Some(owner))
+    };
+
+    if let Some((_, nsec3)) = matching(zone) {
+        return unsigned_delegation(nsec3.type_bit_maps());
+    }
+
+    let mut next_closer = zone.clone();
+    while next_closer.num_labels() > parent.num_labels() {
+        let encloser = next_closer.base_name();
+        if matching(&encloser).is_some() {
+            return nsec3s
+                .iter()
+                .any(|(owner, nsec3)| nsec3.opt_out() && nsec3_covers(owner, nsec3, &next_closer));
+        }
+
+        next_closer = encloser;
+    }
+
+    false
+}
+
+/// Whether the NSEC or NSEC3 records in a section prove that a name doesn’t
+/// exist in a zone: either an NSEC record’s owner and next name fall either
+/// side of it, or an NSEC3 record covers the hash of the next closer name.
+fn denies_name(section: &[Record], name: &Name, next_closer: &Name, zone: &Name) -> bool {
+    let nsec = section.iter().any(|record| match &record.data {
+        RData::DNSSEC(DNSSECRData::NSEC(nsec)) => {
+            // The last record in the zone wraps around to the start
+            let next = nsec.next_domain_name();
+            record.name < *name && (name < next || *next <= record.name)
+        }
+        _ => false,
+    });
+
+    nsec || nsec3_records(section, zone)
+        .iter()
+        .any(|(owner, nsec3)| nsec3_covers(owner, nsec3, next_closer))
+}
+
+/// Returns the NSEC3 records in a section that belong to a zone, along with
+/// the hashes in the first labels of their owner names.
+fn nsec3_records<'a>(section: &'a [Record], zone: &Name) -> Vec<(Vec<u8>, &'a NSEC3)> {
+    section
+        .iter()
+        .filter_map(|record| match &record.data {
+            RData::DNSSEC(DNSSECRData::NSEC3(nsec3)) if record.name.base_name() == *zone => {
+                let label = record.name.iter().next()?;
+                let owner = BASE32HEX_NOPAD.decode(&label.to_ascii_uppercase()).ok()?;
+                Some((owner, nsec3))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Hashes a name the way an NSEC3 record says to.
+fn nsec3_hash(nsec3: &NSEC3, name: &Name) -> Option<Vec<u8>> {
+    let hash = nsec3
+        .hash_algorithm()
+        .hash(nsec3.salt(), name, nsec3.iterations())
+        .ok()?;
+    Some(hash.as_ref().to_vec())
+}
+
+/// Whether an NSEC3 record with the given owner hash covers a name, proving
+/// that it doesn’t exist.
+fn nsec3_covers(owner: &[u8], nsec3: &NSEC3, name: &Name) -> bool {
+    nsec3_hash(nsec3, name).is_some_and(|hash| covers(owner, nsec3.next_hashed_owner_name(), &hash))
+}
+
+/// Whether the types in an NSEC or NSEC3 record are those of a delegation
+/// without a DS record: there are NS records, but no DS or SOA records,
+/// as a SOA record would mean the record came from the child zone.
+fn unsigned_delegation(types: impl Iterator<Item = RecordType>) -> bool {
+    let types = types.collect::<Vec<_>>();
+    types.contains(&RecordType::NS)
+        && !types.contains(&RecordType::DS)
+        && !types.contains(&RecordType::SOA)
+}
+
+/// Whether a hash falls strictly between the owner and next hashes of an
+/// NSEC3 record, wrapping around at the end of the zone.
+fn covers(owner: &[u8], next: &[u8], hash: &[u8]) -> bool {
+    if owner < next {
+        owner < hash && hash < next
+    } else {
+        owner < hash || hash < next
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    use hickory_resolver::proto::dnssec::crypto::EcdsaSigningKey;
+    use hickory_resolver::proto::dnssec::rdata::{SigInput, NSEC, NSEC3};
+    use hickory_resolver::proto::dnssec::{Nsec3HashAlgorithm, SigningKey, TBS};
+    use hickory_resolver::proto::op::OpCode;
+    use hickory_resolver::proto::rr::rdata::{A, SOA};
+    use hickory_resolver::proto::rr::{DNSClass, SerialNumber};
+
+    /// The time the tests pretend it is.
+    const NOW: u32 = 1_700_000_000;
+
+    fn name(input: &str) -> Name {
+        Name::from_ascii(input).unwrap()
+    }
+
+    /// A zone with a freshly-generated key.
+    struct Zone {
+        name: Name,
+        key: EcdsaSigningKey,
+    }
+
+    impl Zone {
+        fn new(zone: &str) -> Self {
+            let algorithm = Algorithm::ECDSAP256SHA256;
+            let pkcs8 = EcdsaSigningKey::generate_pkcs8(algorithm).unwrap();
+            let key = EcdsaSigningKey::from_pkcs8(&pkcs8, algorithm).unwrap();
+            Self {
+                name: name(zone),
+                key,
+            }
+        }
+
+        fn dnskey(&self) -> DNSKEY {
+            DNSKEY::with_flags(257, self.key.to_public_key().unwrap())
+        }
+
+        fn ds(&self) -> DS {
+            let dnskey = self.dnskey();
+            let digest = dnskey.to_digest(&self.name, DigestType::SHA256).unwrap();
+            DS::new(
+                dnskey.calculate_key_tag().unwrap(),
+                dnskey.algorithm(),
+                DigestType::SHA256,
+                digest.as_ref().to_vec(),
+            )
+        }
+
+        /// Signs a set of records, with a signature that expires at the
+        /// given time.
+        fn sign(&self, records: &[Record], expiration: u32) -> Record {
+            self.sign_labels(records, expiration, records[0].name.num_labels())
+        }
+
+        /// Signs a set of records, with a signature that covers the given
+        /// number of labels of their name.
+        fn sign_labels(&self, records: &[Record], expiration: u32, num_labels: u8) -> Record {
+            let first = &records[0];
+            let input = SigInput {
+                type_covered: first.record_type(),
+                algorithm: Algorithm::ECDSAP256SHA256,
+                num_labels,
+                original_ttl: first.ttl,
+                sig_expiration: SerialNumber::new(expiration),
+                sig_inception: SerialNumber::new(NOW - 3600),
+                key_tag: self.dnskey().calculate_key_tag().unwrap(),
+                signer_name: self.name.clone(),
+            };
+
+            let tbs = TBS::from_input(&first.name, DNSClass::IN, &input, records.iter()).unwrap();
+            let signature = self.key.sign(&tbs).unwrap();
+            let rrsig = RRSIG::from_sig(input, signature);
+            Record::from_rdata(
+                first.name.clone(),
+                first.ttl,
+                RData::DNSSEC(DNSSECRData::RRSIG(rrsig)),
+            )
+        }
+
+        /// Returns the records, followed by their signature.
+        fn signed(&self, records: Vec<Record>) -> Vec<Record> {
+            let signature = self.sign(&records, NOW + 3600);
+            records.into_iter().chain([signature]).collect()
+        }
+
+        /// Returns the records, followed by a signature saying that they
+        /// were expanded from the wildcard with the given number of labels.
+        fn expanded(&self, records: Vec<Record>, num_labels: u8) -> Vec<Record> {
+            let signature = self.sign_labels(&records, NOW + 3600, num_labels);
+            records.into_iter().chain([signature]).collect()
+        }
+    }
+
+    fn record(owner: &Name, data: DNSSECRData) -> Record {
+        Record::from_rdata(owner.clone(), 3600, RData::DNSSEC(data))
+    }
+
+    fn a_record(owner: &str) -> Record {
+        Record::from_rdata(name(owner), 300, RData::A(A::new(192, 0, 2, 1)))
+    }
+
+    fn soa_record(owner: &str) -> Record {
+        let soa = SOA::new(name(owner), name(owner), 1, 3600, 600, 86400, 300);
+        Record::from_rdata(name(owner), 300, RData::SOA(soa))
+    }
+
+    fn response(answers: Vec<Record>, authorities: Vec<Record>) -> Message {
+        let mut message = Message::response(0, OpCode::Query);
+        for record in answers {
+            message.add_answer(record);
+        }
+        for record in authorities {
+            message.add_authority(record);
+        }
+        message
+    }
+
+    /// Canned responses for each name and type that gets fetched.
+    #[derive(Default)]
+    struct StandIns {
+        responses: HashMap<(Name, RecordType), Message>,
+    }
+
+    impl StandIns {
+        fn add(&mut self, owner: &Name, qtype: RecordType, response: Message) {
+            self.responses.insert((owner.clone(), qtype), response);
+        }
+    }
+
+    impl Fetch for StandIns {
+        async fn fetch(&self, name: &Name, qtype: RecordType) -> Result<Message, NetError> {
+            self.responses
+                .get(&(name.clone(), qtype))
+                .cloned()
+                .ok_or(NetError::from("timed out"))
+        }
+    }
+
+    /// A signed root zone that delegates the signed `dog.` zone, along with
+    /// a trust anchor for the root zone’s key.
+    fn signed_tree() -> (TrustAnchor, Zone, StandIns) {
+        let root = Zone::new(".");
+        let dog = Zone::new("dog.");
+        let mut stand_ins = StandIns::default();
+
+        let root_keys = vec![record(&root.name, DNSSECRData::DNSKEY(root.dnskey()))];
+        stand_ins.add(
+            &root.name,
+            RecordType::DNSKEY,
+            response(root.signed(root_keys), vec![]),
+        );
+
+        let dog_ds = vec![record(&dog.name, DNSSECRData::DS(dog.ds()))];
+        stand_ins.add(
+            &dog.name,
+            RecordType::DS,
+            response(root.signed(dog_ds), vec![]),
+        );
+
+        let dog_keys = vec![record(&dog.name, DNSSECRData::DNSKEY(dog.dnskey()))];
+        stand_ins.add(
+            &dog.name,
+            RecordType::DNSKEY,
+            response(dog.signed(dog_keys), vec![]),
+        );
+
+        let anchor = TrustAnchor {
+            zone: Name::root(),
+            ds: vec![root.ds()],
+            keys: vec![],
+        };
+
+        (anchor, dog, stand_ins)
+    }
+
+    fn validator(anchor: &TrustAnchor, stand_ins: StandIns) -> Validator<'_, StandIns> {
+        let mut validator = Validator::new(anchor, stand_ins);
+        validator.now = NOW;
+        validator
+    }
+
+    fn summary(validation: &Validation) -> Vec<String> {
+        validation
+            .links
+            .iter()
+            .map(|link| {
+                let reason = link
+                    .reason
+                    .as_ref()
+                    .map(ToString::to_string)
+                    .unwrap_or_default();
+                format!(
+                    "{} {} {} {reason}",
+                    link.name, link.record_type, link.security
+                )
+                .trim_end()
+                .to_string()
+            })
+            .collect()
+    }
+
+    // trust anchor tests
+
+    #[test]
+    fn builtin_trust_anchor() {
+        let anchor = TrustAnchor::builtin();
+        assert_eq!(anchor.zone, Name::root());
+        assert_eq!(
+            anchor.ds.iter().map(DS::key_tag).collect::<Vec<_>>(),
+            vec![20326, 38696]
+        );
+    }
+
+    #[test]
+    fn trust_anchor_dnskey() {
+        let dog = Zone::new("dog.");
+        let line = format!("dog. 3600 IN DNSKEY {} ; the dog key", dog.dnskey());
+        assert_eq!(
+            TrustAnchor::parse(&line),
+            Ok(TrustAnchor {
+                zone: name("dog."),
+                ds: vec![],
+                keys: vec![dog.dnskey()],
+            })
+        );
+    }
+
+    #[test]
+    fn trust_anchor_invalid_line() {
+        let contents = "; comment\n. IN DS 20326 8 2 nothex\n";
+        assert_eq!(
+            TrustAnchor::parse(contents),
+            Err(TrustAnchorError::InvalidLine(2))
+        );
+    }
+
+    #[test]
+    fn trust_anchor_different_zones() {
+        let contents = "\
+            . IN DS 20326 8 2 E06D44B80B8F1D39A95C0B0D7C65D08458E880409BBC683457104237C7F8EC8D
+            dog. IN DS 20326 8 2 E06D44B80B8F1D39A95C0B0D7C65D08458E880409BBC683457104237C7F8EC8D";
+        assert_eq!(
+            TrustAnchor::parse(contents),
+            Err(TrustAnchorError::DifferentZones(2))
+        );
+    }
+
+    #[test]
+    fn trust_anchor_empty() {
+        assert_eq!(
+            TrustAnchor::parse("; nothing here\n"),
+            Err(TrustAnchorError::NoRecords)
+        );
+    }
+
+    // validation tests
+
+    #[tokio::test]
+    async fn secure_answer() {
+        let (anchor, dog, stand_ins) = signed_tree();
+        let answer = response(dog.signed(vec![a_record("www.dog.")]), vec![]);
+
+        let validation = validator(&anchor, stand_ins).validate(&answer).await;
+        assert_eq!(validation.security(), Security::Secure);
+        assert_eq!(
+            summary(&validation),
+            vec![
+                ". DNSKEY secure",
+                "dog. DS secure",
+                "dog. DNSKEY secure",
+                "www.dog. A secure",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn expired_signature() {
+        let (anchor, dog, stand_ins) = signed_tree();
+        let records = vec![a_record("www.dog.")];
+        let signature = dog.sign(&records, NOW - 60);
+        let answer = response(vec![records[0].clone(), signature], vec![]);
+
+        let validation = validator(&anchor, stand_ins).validate(&answer).await;
+        assert_eq!(validation.security(), Security::Bogus);
+        assert_eq!(
+            validation.cause().and_then(|link| link.reason.clone()),
+            Some(Reason::SignatureExpired)
+        );
+    }
+
+    #[tokio::test]
+    async fn tampered_answer() {
+        let (anchor, dog, stand_ins) = signed_tree();
+        let mut records = dog.signed(vec![a_record("www.dog.")]);
+        records[0].data = RData::A(A::new(192, 0, 2, 66));
+
+        let validation = validator(&anchor, stand_ins)
+            .validate(&response(records, vec![]))
+            .await;
+        assert_eq!(
+            summary(&validation).last().unwrap(),
+            "www.dog. A bogus invalid signature"
+        );
+    }
+
+    #[tokio::test]
+    async fn unsigned_answer_in_signed_zone() {
+        let (anchor, dog, mut stand_ins) = signed_tree();
+        let soa = response(vec![], dog.signed(vec![soa_record("dog.")]));
+        stand_ins.add(&name("www.dog."), RecordType::SOA, soa);
+
+        let validation = validator(&anchor, stand_ins)
+            .validate(&response(vec![a_record("www.dog.")], vec![]))
+            .await;
+        assert_eq!(
+            summary(&validation).last().unwrap(),
+            "www.dog. A bogus missing signature"
+        );
+    }
+
+    #[tokio::test]
+    async fn unsigned_answer_with_unrelated_signer() {
+        let (anchor, dog, mut stand_ins) = signed_tree();
+        let cat = Zone::new("cat.");
+        let authorities = cat
+            .signed(vec![soa_record("cat.")])
+            .into_iter()
+            .chain(dog.signed(vec![soa_record("dog.")]))
+            .collect();
+        stand_ins.add(
+            &name("www.dog."),
+            RecordType::SOA,
+            response(vec![], authorities),
+        );
+
+        let validation = validator(&anchor, stand_ins)
+            .validate(&response(vec![a_record("www.dog.")], vec![]))
+            .await;
+        assert_eq!(
+            summary(&validation).last().unwrap(),
+            "www.dog. A bogus missing signature"
+        );
+    }
+
+    #[test]
+    fn zone_of_response_closest_signer() {
+        // The first signer isn’t the zone of the record it signs, the
+        // second has nothing to do with the name, and the third is above
+        // the zone that the last one is for
+        let authorities = [
+            Zone::new("www.dog.").signed(vec![soa_record("dog.")]),
+            Zone::new("cat.").signed(vec![soa_record("cat.")]),
+            Zone::new(".").signed(vec![soa_record("dog.")]),
+            Zone::new("dog.").signed(vec![soa_record("dog.")]),
+        ];
+        let response = response(vec![], authorities.concat());
+
+        assert_eq!(
+            zone_of_response(&response, &name("www.dog.")),
+            Some(name("dog."))
+        );
+    }
+
+    #[tokio::test]
+    async fn wildcard_answer() {
+        let (anchor, dog, stand_ins) = signed_tree();
+        let nsec = NSEC::new(name("zzz.dog."), [RecordType::A, RecordType::NSEC]);
+        let answer = response(
+            dog.expanded(vec![a_record("www.dog.")], 1),
+            dog.signed(vec![record(&name("dog."), DNSSECRData::NSEC(nsec))]),
+        );
+
+        let validation = validator(&anchor, stand_ins).validate(&answer).await;
+        assert_eq!(validation.security(), Security::Secure);
+    }
+
+    #[tokio::test]
+    async fn wildcard_answer_with_nsec3() {
+        let (anchor, dog, stand_ins) = signed_tree();
+        let answer = response(
+            dog.expanded(vec![a_record("www.dog.")], 1),
+            dog.signed(vec![nsec3(&[0; 20], &[0xff; 20], false)]),
+        );
+
+        let validation = validator(&anchor, stand_ins).validate(&answer).await;
+        assert_eq!(validation.security(), Security::Secure);
+    }
+
+    #[tokio::test]
+    async fn wildcard_answer_without_proof() {
+        let (anchor, dog, stand_ins) = signed_tree();
+        let answer = response(dog.expanded(vec![a_record("www.dog.")], 1), vec![]);
+
+        let validation = validator(&anchor, stand_ins).validate(&answer).await;
+        assert_eq!(
+            summary(&validation).last().unwrap(),
+            "www.dog. A bogus expanded from a wildcard, with no proof the name doesn’t exist"
+        );
+    }
+
+    #[tokio::test]
+    async fn wildcard_answer_for_existing_name() {
+        // The NSEC record starts at the name, so it proves the name exists
+        let (anchor, dog, stand_ins) = signed_tree();
+        let nsec = NSEC::new(name("zzz.dog."), [RecordType::TXT, RecordType::NSEC]);
+        let answer = response(
+            dog.expanded(vec![a_record("www.dog.")], 1),
+            dog.signed(vec![record(&name("www.dog."), DNSSECRData::NSEC(nsec))]),
+        );
+
+        let validation = validator(&anchor, stand_ins).validate(&answer).await;
+        assert_eq!(
+            validation.cause().and_then(|link| link.reason.clone()),
+            Some(Reason::UnprovenWildcard)
+        );
+    }
+
+    #[tokio::test]
+    async fn insecure_delegation() {
+        let (anchor, dog, mut stand_ins) = signed_tree();
+        let plain = name("plain.dog.");
+        stand_ins.add(
+            &name("www.plain.dog."),
+            RecordType::SOA,
+            response(vec![], vec![soa_record("plain.dog.")]),
+        );
+
+        let nsec = NSEC::new(name("zzz.dog."), [RecordType::NS, RecordType::NSEC]);
+        let denial = dog.signed(vec![record(&plain, DNSSECRData::NSEC(nsec))]);
+        stand_ins.add(&plain, RecordType::DS, response(vec![], denial));
+
+        let validation = validator(&anchor, stand_ins)
+            .validate(&response(vec![a_record("www.plain.dog.")], vec![]))
+            .await;
+        assert_eq!(validation.security(), Security::Insecure);
+        assert_eq!(
+            summary(&validation),
+            vec![
+                ". DNSKEY secure",
+                "dog. DS secure",
+                "dog. DNSKEY secure",
+                "plain.dog. DS insecure no DS, so the zone is unsigned",
+                "www.plain.dog. A insecure",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn missing_ds_without_proof() {
+        let (anchor, dog, mut stand_ins) = signed_tree();
+        let plain = name("plain.dog.");
+        stand_ins.add(
+            &name("www.plain.dog."),
+            RecordType::SOA,
+            response(vec![], vec![soa_record("plain.dog.")]),
+        );
+        stand_ins.add(
+            &plain,
+            RecordType::DS,
+            response(vec![], dog.signed(vec![soa_record("dog.")])),
+        );
+
+        let validation = validator(&anchor, stand_ins)
+            .validate(&response(vec![a_record("www.plain.dog.")], vec![]))
+            .await;
+        assert_eq!(validation.security(), Security::Bogus);
+        assert_eq!(
+            validation.cause().and_then(|link| link.reason.clone()),
+            Some(Reason::MissingDs)
+        );
+    }
+
+    /// A tree where `plain.dog.` is delegated without a DS record, and
+    /// the parent answers queries for its DS records with the given
+    /// authority records, each signed by `dog.`.
+    fn denied_tree(denial: Vec<Record>) -> (TrustAnchor, StandIns) {
+        let (anchor, dog, mut stand_ins) = signed_tree();
+        stand_ins.add(
+            &name("www.plain.dog."),
+            RecordType::SOA,
+            response(vec![], vec![soa_record("plain.dog.")]),
+        );
+        stand_ins.add(
+            &name("plain.dog."),
+            RecordType::DS,
+            response(
+                vec![],
+                denial
+                    .into_iter()
+                    .flat_map(|record| dog.signed(vec![record]))
+                    .collect(),
+            ),
+        );
+        (anchor, stand_ins)
+    }
+
+    fn nsec3(owner: &[u8], next: &[u8], opt_out: bool) -> Record {
+        let nsec3 = NSEC3::new(
+            Nsec3HashAlgorithm::SHA1,
+            opt_out,
+            0,
+            vec![],
+            next.to_vec(),
+            [RecordType::NS],
+        );
+        let owner = format!("{}.dog.", BASE32HEX_NOPAD.encode(owner).to_lowercase());
+        record(&name(&owner), DNSSECRData::NSEC3(nsec3))
+    }
+
+    #[tokio::test]
+    async fn unrelated_denial() {
+        let nsec = NSEC::new(name("zzz.dog."), [RecordType::NS, RecordType::NSEC]);
+        let (anchor, stand_ins) =
+            denied_tree(vec![record(&name("other.dog."), DNSSECRData::NSEC(nsec))]);
+
+        let validation = validator(&anchor, stand_ins)
+            .validate(&response(vec![a_record("www.plain.dog.")], vec![]))
+            .await;
+        assert_eq!(
+            validation.cause().and_then(|link| link.reason.clone()),
+            Some(Reason::MissingDs)
+        );
+    }
+
+    #[tokio::test]
+    async fn denial_from_child_zone() {
+        let types = [RecordType::NS, RecordType::SOA, RecordType::NSEC];
+        let nsec = NSEC::new(name("zzz.dog."), types);
+        let (anchor, stand_ins) =
+            denied_tree(vec![record(&name("plain.dog."), DNSSECRData::NSEC(nsec))]);
+
+        let validation = validator(&anchor, stand_ins)
+            .validate(&response(vec![a_record("www.plain.dog.")], vec![]))
+            .await;
+        assert_eq!(validation.security(), Security::Bogus);
+    }
+
+    /// The hash of a name, using the parameters of the test NSEC3 records.
+    fn hash(input: &str) -> Vec<u8> {
+        let hash = Nsec3HashAlgorithm::SHA1.hash(&[], &name(input), 0).unwrap();
+        hash.as_ref().to_vec()
+    }
+
+    #[tokio::test]
+    async fn opt_out_denial() {
+        let (anchor, stand_ins) = denied_tree(vec![
+            nsec3(&hash("dog."), &[0xff; 20], false),
+            nsec3(&[0; 20], &[0xff; 20], true),
+        ]);
+
+        let validation = validator(&anchor, stand_ins)
+            .validate(&response(vec![a_record("www.plain.dog.")], vec![]))
+            .await;
+        assert_eq!(validation.security(), Security::Insecure);
+    }
+
+    #[tokio::test]
+    async fn opt_out_denial_without_closest_encloser() {
+        let (anchor, stand_ins) = denied_tree(vec![nsec3(&[0; 20], &[0xff; 20], true)]);
+
+        let validation = validator(&anchor, stand_ins)
+            .validate(&response(vec![a_record("www.plain.dog.")], vec![]))
+            .await;
+        assert_eq!(
+            validation.cause().and_then(|link| link.reason.clone()),
+            Some(Reason::MissingDs)
+        );
+    }
+
+    #[tokio::test]
+    async fn covering_denial_without_opt_out() {
+        let (anchor, stand_ins) = denied_tree(vec![nsec3(&[0; 20], &[0xff; 20], false)]);
+
+        let validation = validator(&anchor, stand_ins)
+            .validate(&response(vec![a_record("www.plain.dog.")], vec![]))
+            .await;
+        assert_eq!(
+            validation.cause().and_then(|link| link.reason.clone()),
+            Some(Reason::MissingDs)
+        );
+    }
+
+    #[tokio::test]
+    async fn signer_outside_zone() {
+        // The owner of evil.dog. has a perfectly good chain of trust, but
+        // their keys can’t vouch for records in the rest of dog.
+        let (anchor, dog, mut stand_ins) = signed_tree();
+        let evil = Zone::new("evil.dog.");
+
+        let evil_ds = vec![record(&evil.name, DNSSECRData::DS(evil.ds()))];
+        stand_ins.add(
+            &evil.name,
+            RecordType::DS,
+            response(dog.signed(evil_ds), vec![]),
+        );
+        let evil_keys = vec![record(&evil.name, DNSSECRData::DNSKEY(evil.dnskey()))];
+        stand_ins.add(
+            &evil.name,
+            RecordType::DNSKEY,
+            response(evil.signed(evil_keys), vec![]),
+        );
+
+        let answer = response(evil.signed(vec![a_record("www.dog.")]), vec![]);
+        let validation = validator(&anchor, stand_ins).validate(&answer).await;
+        assert_eq!(validation.security(), Security::Bogus);
+        assert_eq!(
+            summary(&validation),
+            vec!["www.dog. A bogus signed by evil.dog., which isn’t their zone"]
+        );
+    }
+
+    #[tokio::test]
+    async fn unsupported_algorithm() {
+        let (anchor, dog, mut stand_ins) = signed_tree();
+        let odd = name("odd.dog.");
+        stand_ins.add(
+            &name("www.odd.dog."),
+            RecordType::SOA,
+            response(vec![], vec![soa_record("odd.dog.")]),
+        );
+
+        let ds = DS::new(
+            1234,
+            Algorithm::Unknown(253),
+            DigestType::SHA256,
+            vec![0; 32],
+        );
+        let ds_records = dog.signed(vec![record(&odd, DNSSECRData::DS(ds))]);
+        stand_ins.add(&odd, RecordType::DS, response(ds_records, vec![]));
+
+        let validation = validator(&anchor, stand_ins)
+            .validate(&response(vec![a_record("www.odd.dog.")], vec![]))
+            .await;
+        assert_eq!(validation.security(), Security::Insecure);
+        assert_eq!(
+            validation.cause().and_then(|link| link.reason.clone()),
+            Some(Reason::UnsupportedAlgorithm(Algorithm::Unknown(253)))
+        );
+    }
+
+    #[tokio::test]
+    async fn no_response() {
+        let (anchor, dog, _) = signed_tree();
+        let answer = response(dog.signed(vec![a_record("www.dog.")]), vec![]);
+
+        let validation = validator(&anchor, StandIns::default())
+            .validate(&answer)
+            .await;
+        assert_eq!(validation.security(), Security::Indeterminate);
+        assert_eq!(
+            summary(&validation),
+            vec![
+                "dog. DS indeterminate no response: timed out",
+                "www.dog. A indeterminate"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn outside_trust_anchor() {
+        let (_, dog, stand_ins) = signed_tree();
+        let anchor = TrustAnchor {
+            zone: name("cat."),
+            ds: vec![],
+            keys: vec![],
+        };
+
+        let answer = response(dog.signed(vec![a_record("www.dog.")]), vec![]);
+        let validation = validator(&anchor, stand_ins).validate(&answer).await;
+        assert_eq!(
+            summary(&validation),
+            vec![
+                "dog. DNSKEY indeterminate not under the trust anchor for cat.",
+                "www.dog. A indeterminate",
+            ]
+        );
+    }
+}